@@ -1,22 +1,31 @@
+// Code that's only reachable when compiling.
+#![cfg_attr(not(feature = "compiler"), allow(dead_code, unused_imports))]
+
 mod cache;
+#[cfg(feature = "compiler")]
 mod func_env;
 mod libcalls;
 mod module;
+#[cfg(feature = "compiler")]
 mod module_env;
 mod relocation;
 mod resolver;
 mod signal;
 mod trampoline;
 
+#[cfg(feature = "compiler")]
 use cranelift_codegen::{
     isa,
     settings::{self, Configurable},
 };
-use target_lexicon::Triple;
+#[cfg(feature = "compiler")]
+use std::str::FromStr;
+#[cfg(feature = "compiler")]
+use target_lexicon::{Architecture, Triple};
 
 use wasmer_runtime_core::cache::{Artifact, Error as CacheError};
 use wasmer_runtime_core::{
-    backend::{Compiler, CompilerConfig, Token},
+    backend::{Backend, Compiler, CompilerConfig, CpuFeature, OptLevel, Token},
     error::{CompileError, CompileResult},
     module::ModuleInner,
 };
@@ -24,9 +33,11 @@ use wasmer_runtime_core::{
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "compiler")]
 extern crate rayon;
 extern crate serde;
 
+#[cfg(feature = "compiler")]
 use wasmparser::{self, WasmDecoder};
 
 pub struct CraneliftCompiler {}
@@ -39,6 +50,7 @@ impl CraneliftCompiler {
 
 impl Compiler for CraneliftCompiler {
     /// Compiles wasm binary to a wasmer module.
+    #[cfg(feature = "compiler")]
     fn compile(
         &self,
         wasm: &[u8],
@@ -47,22 +59,49 @@ impl Compiler for CraneliftCompiler {
     ) -> CompileResult<ModuleInner> {
         validate(wasm)?;
 
-        let isa = get_isa();
+        let isa = get_isa(&compiler_config)?;
 
         let mut module = module::Module::new(&compiler_config);
         let module_env = module_env::ModuleEnv::new(&mut module, &*isa);
 
         let func_bodies = module_env.translate(wasm)?;
 
-        module.compile(&*isa, func_bodies)
+        // Function names and DWARF come from custom sections, which
+        // are otherwise only read once the module is compiled.
+        if compiler_config.generate_debug_info || compiler_config.profiling.is_some() {
+            module
+                .info
+                .import_custom_sections(wasm)
+                .map_err(|e| CompileError::InternalError {
+                    msg: format!("{:?}", e),
+                })?;
+        }
+
+        module.compile(&*isa, func_bodies, wasm, &compiler_config)
+    }
+
+    /// Without the `compiler` feature, modules can only be loaded
+    /// from artifacts compiled ahead of time.
+    #[cfg(not(feature = "compiler"))]
+    fn compile(&self, _: &[u8], _: CompilerConfig, _: Token) -> CompileResult<ModuleInner> {
+        Err(CompileError::InternalError {
+            msg: "wasmer-clif-backend was built without the `compiler` feature".to_string(),
+        })
     }
 
     /// Create a wasmer Module from an already-compiled cache.
 
     unsafe fn from_cache(&self, cache: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+        if cache.info().backend != Backend::Cranelift {
+            return Err(CacheError::IncompatibleBackend(cache.info().backend));
+        }
         module::Module::from_cache(cache)
     }
 
+    fn backend(&self) -> Backend {
+        Backend::Cranelift
+    }
+
     //
     // fn compile_to_backend_cache_data(
     //     &self,
@@ -95,22 +134,104 @@ impl Compiler for CraneliftCompiler {
     // }
 }
 
-fn get_isa() -> Box<isa::TargetIsa> {
+#[cfg(feature = "compiler")]
+fn get_isa(compiler_config: &CompilerConfig) -> CompileResult<Box<isa::TargetIsa>> {
     let flags = {
         let mut builder = settings::builder();
-        builder.set("opt_level", "best").unwrap();
-
-        if cfg!(not(test)) {
+        let opt_level = match compiler_config.opt_level {
+            OptLevel::None => "fastest",
+            OptLevel::Speed => "default",
+            OptLevel::SpeedAndSize => "best",
+        };
+        builder.set("opt_level", opt_level).unwrap();
+
+        // The verifier always runs in tests.
+        if !compiler_config.enable_verifier && cfg!(not(test)) {
             builder.set("enable_verifier", "false").unwrap();
         }
 
-        let flags = settings::Flags::new(builder);
-        debug_assert_eq!(flags.opt_level(), settings::OptLevel::Best);
-        flags
+        settings::Flags::new(builder)
+    };
+
+    let triple = match &compiler_config.triple {
+        Some(triple) => Triple::from_str(triple).map_err(|e| CompileError::InternalError {
+            msg: format!("invalid target triple {}: {:?}", triple, e),
+        })?,
+        None => Triple::host(),
+    };
+    let is_x86 = match triple.architecture {
+        Architecture::I386 | Architecture::I586 | Architecture::I686 | Architecture::X86_64 => true,
+        _ => false,
     };
-    isa::lookup(Triple::host()).unwrap().finish(flags)
+
+    let mut isa_builder = isa::lookup(triple).map_err(|e| CompileError::InternalError {
+        msg: format!("unsupported target: {:?}", e),
+    })?;
+
+    // Features found on the host are only used if Cranelift can, but
+    // asking for one it can't use is an error.
+    let unsupported: Vec<_> = compiler_config
+        .enable_cpu_features
+        .iter()
+        .filter(|&&feature| isa_setting(feature).is_none())
+        .map(|feature| feature.name())
+        .collect();
+    if !unsupported.is_empty() {
+        return Err(CompileError::InternalError {
+            msg: format!(
+                "the cranelift backend doesn't support these CPU features: {}",
+                unsupported.join(", ")
+            ),
+        });
+    }
+
+    let cpu_features = used_cpu_features(compiler_config);
+    if is_x86 {
+        for &feature in CpuFeature::all() {
+            if let Some(setting) = isa_setting(feature) {
+                let value = if cpu_features.contains(&feature) {
+                    "true"
+                } else {
+                    "false"
+                };
+                isa_builder.set(setting, value).unwrap();
+            }
+        }
+    } else if !cpu_features.is_empty() {
+        return Err(CompileError::InternalError {
+            msg: "CPU features can only be selected for x86 targets".to_string(),
+        });
+    }
+
+    Ok(isa_builder.finish(flags))
+}
+
+/// The Cranelift setting of a CPU feature, if Cranelift makes use of it.
+fn isa_setting(feature: CpuFeature) -> Option<&'static str> {
+    match feature {
+        CpuFeature::SSE3 => Some("has_sse3"),
+        CpuFeature::SSSE3 => Some("has_ssse3"),
+        CpuFeature::SSE41 => Some("has_sse41"),
+        CpuFeature::SSE42 => Some("has_sse42"),
+        CpuFeature::POPCNT => Some("has_popcnt"),
+        CpuFeature::BMI1 => Some("has_bmi1"),
+        CpuFeature::BMI2 => Some("has_bmi2"),
+        CpuFeature::LZCNT => Some("has_lzcnt"),
+        CpuFeature::AVX => Some("has_avx"),
+        CpuFeature::AVX2 => None,
+    }
+}
+
+/// The CPU features allowed by `compiler_config` that compiled code may use.
+fn used_cpu_features(compiler_config: &CompilerConfig) -> Vec<CpuFeature> {
+    compiler_config
+        .cpu_features()
+        .into_iter()
+        .filter(|&feature| isa_setting(feature).is_some())
+        .collect()
 }
 
+#[cfg(feature = "compiler")]
 fn validate(bytes: &[u8]) -> CompileResult<()> {
     let mut parser = wasmparser::ValidatingParser::new(bytes, None);
     loop {
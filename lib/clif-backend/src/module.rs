@@ -1,9 +1,10 @@
 use crate::cache::{BackendCache, CacheGenerator};
 use crate::{resolver::FuncResolverBuilder, signal::Caller, trampoline::Trampolines};
 
+#[cfg(feature = "compiler")]
 use cranelift_codegen::{ir, isa};
+#[cfg(feature = "compiler")]
 use cranelift_entity::EntityRef;
-use cranelift_wasm;
 use hashbrown::HashMap;
 use std::sync::Arc;
 
@@ -12,7 +13,9 @@ use wasmer_runtime_core::cache::{Artifact, Error as CacheError};
 use wasmer_runtime_core::{
     backend::{Backend, CompilerConfig},
     error::CompileResult,
+    jit_debug,
     module::{ModuleInfo, ModuleInner, StringTable},
+    profiling,
     structures::{Map, TypedIndex},
     types::{
         FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, MemoryIndex, SigIndex, TableIndex, Type,
@@ -24,6 +27,7 @@ pub struct Module {
     pub info: ModuleInfo,
 }
 
+#[cfg(feature = "compiler")]
 impl Module {
     pub fn new(compiler_config: &CompilerConfig) -> Self {
         Self {
@@ -47,12 +51,16 @@ impl Module {
                 func_assoc: Map::new(),
                 signatures: Map::new(),
                 backend: Backend::Cranelift,
+                triple: compiler_config.triple.clone(),
+                cpu_features: crate::used_cpu_features(compiler_config),
 
                 namespace_table: StringTable::new(),
                 name_table: StringTable::new(),
                 em_symbol_map: compiler_config.symbol_map.clone(),
 
                 custom_sections: HashMap::new(),
+
+                names: Default::default(),
             },
         }
     }
@@ -61,9 +69,15 @@ impl Module {
         self,
         isa: &isa::TargetIsa,
         functions: Map<LocalFuncIndex, ir::Function>,
+        wasm: &[u8],
+        compiler_config: &CompilerConfig,
     ) -> CompileResult<ModuleInner> {
-        let (func_resolver_builder, handler_data) =
-            FuncResolverBuilder::new(isa, functions, &self.info)?;
+        let (func_resolver_builder, handler_data, debug_functions) = FuncResolverBuilder::new(
+            isa,
+            functions,
+            &self.info,
+            compiler_config.generate_debug_info,
+        )?;
 
         let trampolines = Arc::new(Trampolines::new(isa, &self.info));
 
@@ -78,7 +92,19 @@ impl Module {
             Arc::clone(&func_resolver.memory),
         ));
 
-        let runnable_module = Caller::new(handler_data, trampolines, func_resolver);
+        if let Some(strategy) = compiler_config.profiling {
+            // Failing to write profiling data shouldn't fail compilation.
+            let _ = profiling::register(strategy, &self.info, &debug_functions);
+        }
+
+        let debug_registration = if compiler_config.generate_debug_info {
+            jit_debug::register(wasm, &self.info, &debug_functions)
+        } else {
+            None
+        };
+
+        let runnable_module =
+            Caller::new(handler_data, trampolines, func_resolver, debug_registration);
 
         Ok(ModuleInner {
             runnable_module: Box::new(runnable_module),
@@ -87,7 +113,9 @@ impl Module {
             info: self.info,
         })
     }
+}
 
+impl Module {
     pub fn from_cache(cache: Artifact) -> Result<ModuleInner, CacheError> {
         let (info, compiled_code, backend_cache) = BackendCache::from_cache(cache)?;
 
@@ -107,7 +135,7 @@ impl Module {
             Arc::clone(&func_resolver.memory),
         ));
 
-        let runnable_module = Caller::new(handler_data, trampolines, func_resolver);
+        let runnable_module = Caller::new(handler_data, trampolines, func_resolver, None);
 
         Ok(ModuleInner {
             runnable_module: Box::new(runnable_module),
@@ -118,8 +146,10 @@ impl Module {
     }
 }
 
+#[cfg(feature = "compiler")]
 pub struct Converter<T>(pub T);
 
+#[cfg(feature = "compiler")]
 macro_rules! convert_clif_to_runtime_index {
     ($clif_index:ident, $runtime_index:ident) => {
         impl From<Converter<cranelift_wasm::$clif_index>> for $runtime_index {
@@ -141,6 +171,7 @@ macro_rules! convert_clif_to_runtime_index {
     };
 }
 
+#[cfg(feature = "compiler")]
 convert_clif_to_runtime_index![
     (FuncIndex: FuncIndex),
     (MemoryIndex: MemoryIndex),
@@ -149,6 +180,7 @@ convert_clif_to_runtime_index![
     (SignatureIndex: SigIndex),
 ];
 
+#[cfg(feature = "compiler")]
 impl From<Converter<ir::Signature>> for FuncSig {
     fn from(signature: Converter<ir::Signature>) -> Self {
         FuncSig::new(
@@ -168,6 +200,7 @@ impl From<Converter<ir::Signature>> for FuncSig {
     }
 }
 
+#[cfg(feature = "compiler")]
 impl From<Converter<ir::Type>> for Type {
     fn from(ty: Converter<ir::Type>) -> Self {
         match ty.0 {
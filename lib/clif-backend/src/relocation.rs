@@ -2,7 +2,9 @@
 //! This structures are used by Cranelift when compiling functions to mark
 //! any other calls that this function is doing, so we can "patch" the
 //! function addrs in runtime with the functions we need.
+#[cfg(feature = "compiler")]
 use cranelift_codegen::binemit;
+#[cfg(feature = "compiler")]
 use cranelift_codegen::ir::{self, ExternalName, SourceLoc};
 use wasmer_runtime_core::{
     structures::TypedIndex,
@@ -47,18 +49,18 @@ pub struct ExternalRelocation {
     /// The relocation code.
     pub reloc: Reloc,
     /// The offset where to apply the relocation.
-    pub offset: binemit::CodeOffset,
+    pub offset: u32,
     /// The addend to add to the relocation value.
-    pub addend: binemit::Addend,
+    pub addend: i64,
     /// Relocation type.
     pub target: RelocationType,
 }
 
 pub struct LocalRelocation {
     /// The offset where to apply the relocation.
-    pub offset: binemit::CodeOffset,
+    pub offset: u32,
     /// The addend to add to the relocation value.
-    pub addend: binemit::Addend,
+    pub addend: i64,
     /// Relocation type.
     pub target: FuncIndex,
 }
@@ -97,6 +99,7 @@ pub struct RelocSink {
     pub local_relocs: Vec<LocalRelocation>,
 }
 
+#[cfg(feature = "compiler")]
 impl binemit::RelocSink for RelocSink {
     fn reloc_ebb(
         &mut self,
@@ -285,6 +288,7 @@ impl LocalTrapSink {
     }
 }
 
+#[cfg(feature = "compiler")]
 impl binemit::TrapSink for LocalTrapSink {
     fn trap(&mut self, offset: u32, srcloc: SourceLoc, trapcode: ir::TrapCode) {
         let trapcode = match trapcode {
@@ -7,10 +7,13 @@ use crate::{
     },
     signal::HandlerData,
 };
+#[cfg(feature = "compiler")]
 use rayon::prelude::*;
 
 use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "compiler")]
 use cranelift_codegen::{ir, isa, Context};
+use libc::c_void;
 use std::{
     mem,
     ptr::{write_unaligned, NonNull},
@@ -25,6 +28,7 @@ use wasmer_runtime_core::{
         SigRegistry,
     },
     error::{CompileError, CompileResult},
+    jit_debug::FunctionDebugInfo,
     module::ModuleInfo,
     structures::{Map, SliceMap, TypedIndex},
     types::{FuncSig, LocalFuncIndex, SigIndex},
@@ -87,48 +91,66 @@ impl FuncResolverBuilder {
         ))
     }
 
+    #[cfg(feature = "compiler")]
     pub fn new(
         isa: &isa::TargetIsa,
         function_bodies: Map<LocalFuncIndex, ir::Function>,
         info: &ModuleInfo,
-    ) -> CompileResult<(Self, HandlerData)> {
+        generate_debug_info: bool,
+    ) -> CompileResult<(Self, HandlerData, Vec<FunctionDebugInfo>)> {
         let num_func_bodies = function_bodies.len();
         let mut local_relocs = Map::with_capacity(num_func_bodies);
         let mut external_relocs = Map::with_capacity(num_func_bodies);
 
         let mut trap_sink = TrapSink::new();
 
-        let compiled_functions: Result<Vec<(Vec<u8>, (RelocSink, LocalTrapSink))>, CompileError> =
-            function_bodies
-                .into_vec()
-                .par_iter()
-                .map_init(
-                    || Context::new(),
-                    |ctx, func| {
-                        let mut code_buf = Vec::new();
-                        ctx.func = func.to_owned();
-                        let mut reloc_sink = RelocSink::new();
-                        let mut local_trap_sink = LocalTrapSink::new();
-
-                        ctx.compile_and_emit(
-                            isa,
-                            &mut code_buf,
-                            &mut reloc_sink,
-                            &mut local_trap_sink,
-                        )
+        let compiled_functions: Result<
+            Vec<(Vec<u8>, (RelocSink, LocalTrapSink, Vec<(usize, usize)>))>,
+            CompileError,
+        > = function_bodies
+            .into_vec()
+            .par_iter()
+            .map_init(
+                || Context::new(),
+                |ctx, func| {
+                    let mut code_buf = Vec::new();
+                    ctx.func = func.to_owned();
+                    let mut reloc_sink = RelocSink::new();
+                    let mut local_trap_sink = LocalTrapSink::new();
+
+                    ctx.compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut local_trap_sink)
                         .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
-                        ctx.clear();
-                        Ok((code_buf, (reloc_sink, local_trap_sink)))
-                    },
-                )
-                .collect();
+
+                    // Native offsets and the wasm offsets they were translated from.
+                    let mut srclocs = Vec::new();
+                    if generate_debug_info {
+                        let encinfo = isa.encoding_info();
+                        for ebb in ctx.func.layout.ebbs() {
+                            for (offset, inst, _) in ctx.func.inst_offsets(ebb, &encinfo) {
+                                let srcloc = ctx.func.srclocs[inst];
+                                if !srcloc.is_default() {
+                                    srclocs.push((offset as usize, srcloc.bits() as usize));
+                                }
+                            }
+                        }
+                    }
+
+                    ctx.clear();
+                    Ok((code_buf, (reloc_sink, local_trap_sink, srclocs)))
+                },
+            )
+            .collect();
 
         let compiled_functions = compiled_functions?;
         let mut total_size = 0;
         // We separate into two iterators, one iterable and one into iterable
-        let (code_bufs, sinks): (Vec<Vec<u8>>, Vec<(RelocSink, LocalTrapSink)>) =
-            compiled_functions.into_iter().unzip();
-        for (code_buf, (reloc_sink, mut local_trap_sink)) in code_bufs.iter().zip(sinks.into_iter())
+        let (code_bufs, sinks): (
+            Vec<Vec<u8>>,
+            Vec<(RelocSink, LocalTrapSink, Vec<(usize, usize)>)>,
+        ) = compiled_functions.into_iter().unzip();
+        let mut func_srclocs = Vec::with_capacity(num_func_bodies);
+        for (code_buf, (reloc_sink, mut local_trap_sink, srclocs)) in
+            code_bufs.iter().zip(sinks.into_iter())
         {
             // Clear the local trap sink and consolidate all trap info
             // into a single location.
@@ -139,6 +161,7 @@ impl FuncResolverBuilder {
 
             local_relocs.push(reloc_sink.local_relocs.into_boxed_slice());
             external_relocs.push(reloc_sink.external_relocs.into_boxed_slice());
+            func_srclocs.push(srclocs);
         }
 
         let mut memory = Memory::with_size(total_size)
@@ -163,7 +186,7 @@ impl FuncResolverBuilder {
             *i = 0xCC;
         }
 
-        let mut map = Map::with_capacity(num_func_bodies);
+        let mut map = Map::<LocalFuncIndex, _>::with_capacity(num_func_bodies);
 
         let mut previous_end = 0;
         for compiled in code_bufs.iter() {
@@ -176,6 +199,24 @@ impl FuncResolverBuilder {
             previous_end = new_end;
         }
 
+        let base = memory.as_ptr() as usize;
+        let debug_functions = code_bufs
+            .iter()
+            .zip(map.iter())
+            .zip(func_srclocs.into_iter())
+            .map(
+                |((code_buf, (local_func_index, &offset)), srclocs)| FunctionDebugInfo {
+                    func_index: local_func_index.convert_up(info),
+                    address: base + offset,
+                    size: code_buf.len(),
+                    instructions: srclocs
+                        .into_iter()
+                        .map(|(inst_offset, srcloc)| (base + offset + inst_offset, srcloc))
+                        .collect(),
+                },
+            )
+            .collect();
+
         let handler_data =
             HandlerData::new(Arc::new(trap_sink), memory.as_ptr() as _, memory.size());
 
@@ -189,7 +230,7 @@ impl FuncResolverBuilder {
 
         func_resolver_builder.relocate_locals();
 
-        Ok((func_resolver_builder, handler_data))
+        Ok((func_resolver_builder, handler_data, debug_functions))
     }
 
     fn relocate_locals(&mut self) {
@@ -360,6 +401,23 @@ impl FuncResolver {
     pub fn lookup(&self, index: LocalFuncIndex) -> Option<NonNull<vm::Func>> {
         lookup_func(&self.map, &self.memory, index)
     }
+
+    /// Find the local function whose code contains `ip`.
+    pub fn lookup_ip(&self, ip: *const c_void) -> Option<LocalFuncIndex> {
+        let start = self.memory.as_ptr() as usize;
+        let ip = ip as usize;
+
+        if ip < start || ip >= start + self.memory.size() {
+            return None;
+        }
+
+        let offset = ip - start;
+        self.map
+            .iter()
+            .filter(|(_, &func_offset)| func_offset <= offset)
+            .max_by_key(|(_, &func_offset)| func_offset)
+            .map(|(index, _)| index)
+    }
 }
 
 #[inline]
@@ -380,11 +438,9 @@ extern "C" fn f64_print(_ctx: &mut vm::Ctx, n: f64) {
     eprint!(" f64: {},", n);
 }
 extern "C" fn start_debug(ctx: &mut vm::Ctx, func_index: u32) {
-    if let Some(symbol_map) = unsafe { ctx.borrow_symbol_map() } {
-        if let Some(fn_name) = symbol_map.get(&func_index) {
-            eprint!("func ({} ({})), args: [", fn_name, func_index);
-            return;
-        }
+    if let Some(fn_name) = unsafe { ctx.borrow_function_name(func_index) } {
+        eprint!("func ({} ({})), args: [", fn_name, func_index);
+        return;
     }
     eprint!("func ({}), args: [", func_index);
 }
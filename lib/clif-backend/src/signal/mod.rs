@@ -5,6 +5,7 @@ use libc::c_void;
 use std::{any::Any, cell::Cell, ptr::NonNull, sync::Arc};
 use wasmer_runtime_core::{
     backend::RunnableModule,
+    jit_debug::JitDebugRegistration,
     module::ModuleInfo,
     typed_func::{Wasm, WasmTrapInfo},
     types::{LocalFuncIndex, SigIndex},
@@ -28,6 +29,9 @@ thread_local! {
 }
 
 pub struct Caller {
+    // Declared first so that it's dropped, and the code
+    // unregistered from debuggers, before the code is freed.
+    _debug_registration: Option<JitDebugRegistration>,
     handler_data: HandlerData,
     trampolines: Arc<Trampolines>,
     resolver: FuncResolver,
@@ -38,8 +42,10 @@ impl Caller {
         handler_data: HandlerData,
         trampolines: Arc<Trampolines>,
         resolver: FuncResolver,
+        debug_registration: Option<JitDebugRegistration>,
     ) -> Self {
         Self {
+            _debug_registration: debug_registration,
             handler_data,
             trampolines,
             resolver,
@@ -96,6 +102,12 @@ impl RunnableModule for Caller {
         TRAP_EARLY_DATA.with(|cell| cell.set(Some(data)));
         trigger_trap()
     }
+
+    #[cfg(unix)]
+    fn get_trap_func(&self, _: &ModuleInfo) -> Option<LocalFuncIndex> {
+        let (_, inst_ptr) = CAUGHT_ADDRESSES.with(|cell| cell.get());
+        self.resolver.lookup_ip(inst_ptr)
+    }
 }
 
 unsafe impl Send for HandlerData {}
@@ -71,6 +71,8 @@ pub fn call_protected<T>(handler_data: &HandlerData, f: impl FnOnce() -> T) -> R
             install_sighandler();
         });
 
+        CAUGHT_ADDRESSES.with(|cell| cell.set((ptr::null(), ptr::null())));
+
         let signum = setjmp(jmp_buf as *mut _);
         if signum != 0 {
             *jmp_buf = prev_jmp_buf;
@@ -1,4 +1,5 @@
 use crate::cache::TrampolineCache;
+#[cfg(feature = "compiler")]
 use cranelift_codegen::{
     binemit::{NullTrapSink, Reloc, RelocSink},
     cursor::{Cursor, FuncCursor},
@@ -14,8 +15,10 @@ use wasmer_runtime_core::{
     vm,
 };
 
+#[cfg(feature = "compiler")]
 struct NullRelocSink {}
 
+#[cfg(feature = "compiler")]
 impl RelocSink for NullRelocSink {
     fn reloc_ebb(&mut self, _: u32, _: Reloc, _: u32) {}
     fn reloc_external(&mut self, _: u32, _: Reloc, _: &ir::ExternalName, _: i64) {}
@@ -66,6 +69,7 @@ impl Trampolines {
         }
     }
 
+    #[cfg(feature = "compiler")]
     pub fn new(isa: &isa::TargetIsa, module: &ModuleInfo) -> Self {
         let func_index_iter = module
             .exports
@@ -84,7 +88,7 @@ impl Trampolines {
             let sig_index = module.func_assoc[*exported_func_index];
             let func_sig = &module.signatures[sig_index];
 
-            let trampoline_func = generate_func(&func_sig);
+            let trampoline_func = generate_func(isa, &func_sig);
 
             ctx.func = trampoline_func;
 
@@ -147,13 +151,14 @@ impl Trampolines {
 
 /// This function generates a trampoline for the specific signature
 /// passed into it.
-fn generate_func(func_sig: &FuncSig) -> ir::Function {
-    let trampoline_sig = generate_trampoline_signature();
+#[cfg(feature = "compiler")]
+fn generate_func(isa: &isa::TargetIsa, func_sig: &FuncSig) -> ir::Function {
+    let trampoline_sig = generate_trampoline_signature(isa);
 
     let mut func =
         ir::Function::with_name_signature(ir::ExternalName::testcase("trampln"), trampoline_sig);
 
-    let export_sig_ref = func.import_signature(generate_export_signature(func_sig));
+    let export_sig_ref = func.import_signature(generate_export_signature(isa, func_sig));
 
     let entry_ebb = func.dfg.make_ebb();
     let vmctx_ptr = func.dfg.append_ebb_param(entry_ebb, ir::types::I64);
@@ -198,6 +203,7 @@ fn generate_func(func_sig: &FuncSig) -> ir::Function {
     func
 }
 
+#[cfg(feature = "compiler")]
 fn wasm_ty_to_clif(ty: Type) -> ir::types::Type {
     match ty {
         Type::I32 => ir::types::I32,
@@ -207,8 +213,8 @@ fn wasm_ty_to_clif(ty: Type) -> ir::types::Type {
     }
 }
 
-fn generate_trampoline_signature() -> ir::Signature {
-    let isa = super::get_isa();
+#[cfg(feature = "compiler")]
+fn generate_trampoline_signature(isa: &isa::TargetIsa) -> ir::Signature {
     let call_convention = isa.default_call_conv();
     let mut sig = ir::Signature::new(call_convention);
 
@@ -224,8 +230,8 @@ fn generate_trampoline_signature() -> ir::Signature {
     sig
 }
 
-fn generate_export_signature(func_sig: &FuncSig) -> ir::Signature {
-    let isa = super::get_isa();
+#[cfg(feature = "compiler")]
+fn generate_export_signature(isa: &isa::TargetIsa, func_sig: &FuncSig) -> ir::Signature {
     let call_convention = isa.default_call_conv();
     let mut export_clif_sig = ir::Signature::new(call_convention);
 
@@ -259,6 +265,7 @@ fn generate_export_signature(func_sig: &FuncSig) -> ir::Signature {
     export_clif_sig
 }
 
+#[cfg(feature = "compiler")]
 #[inline]
 fn round_up(n: usize, multiple: usize) -> usize {
     (n + multiple - 1) & !(multiple - 1)
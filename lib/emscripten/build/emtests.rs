@@ -66,6 +66,9 @@ pub fn compile(file: &str, ignores: &Vec<String>) -> Option<String> {
             .arg(file)
             .arg("-s")
             .arg("WASM=1")
+            // emscripten drops `catch` clauses by default.
+            .arg("-s")
+            .arg("DISABLE_EXCEPTION_CATCHING=0")
             .arg("-o")
             .arg(output_str)
             .output()
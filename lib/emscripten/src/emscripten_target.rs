@@ -1,9 +1,11 @@
 #![allow(non_snake_case)]
 
 use crate::env::get_emscripten_data;
+use crate::Unwind;
 #[cfg(target_os = "linux")]
 use libc::getdtablesize;
-use wasmer_runtime_core::vm::Ctx;
+use libc::{EAGAIN, EINVAL, ESRCH};
+use wasmer_runtime_core::{error::RuntimeError, vm::Ctx};
 
 pub fn setTempRet0(ctx: &mut Ctx, val: i32) {
     debug!("emscripten::setTempRet0: {}", val);
@@ -27,20 +29,6 @@ pub fn __Unwind_GetIPInfo(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
     debug!("emscripten::__Unwind_GetIPInfo");
     0
 }
-pub fn ___cxa_find_matching_catch_2(_ctx: &mut Ctx) -> i32 {
-    debug!("emscripten::___cxa_find_matching_catch_2");
-    0
-}
-pub fn ___cxa_find_matching_catch_3(_ctx: &mut Ctx, _a: i32) -> i32 {
-    debug!("emscripten::___cxa_find_matching_catch_3");
-    0
-}
-pub fn ___cxa_free_exception(_ctx: &mut Ctx, _a: i32) {
-    debug!("emscripten::___cxa_free_exception");
-}
-pub fn ___resumeException(_ctx: &mut Ctx, _a: i32) {
-    debug!("emscripten::___resumeException");
-}
 pub fn _dladdr(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
     debug!("emscripten::_dladdr");
     0
@@ -49,29 +37,72 @@ pub fn _pthread_cond_destroy(_ctx: &mut Ctx, _a: i32) -> i32 {
     debug!("emscripten::_pthread_cond_destroy");
     0
 }
-pub fn _pthread_getspecific(_ctx: &mut Ctx, _a: i32) -> i32 {
+pub fn _pthread_getspecific(ctx: &mut Ctx, key: i32) -> i32 {
     debug!("emscripten::_pthread_getspecific");
-    0
+    get_emscripten_data(ctx)
+        .pthread_specific
+        .get(&key)
+        .cloned()
+        .unwrap_or(0)
 }
-pub fn _pthread_setspecific(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
+pub fn _pthread_setspecific(ctx: &mut Ctx, key: i32, value: i32) -> i32 {
     debug!("emscripten::_pthread_setspecific");
-    0
+    match get_emscripten_data(ctx).pthread_specific.get_mut(&key) {
+        Some(slot) => {
+            *slot = value;
+            0
+        }
+        None => EINVAL,
+    }
 }
-pub fn _pthread_once(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
+/// `once_control` is a `pthread_once_t`, an int that starts out as 0
+/// (`PTHREAD_ONCE_INIT`) and is set once `init_routine` has been called.
+pub fn _pthread_once(ctx: &mut Ctx, once_control: u32, init_routine: i32) -> Result<i32, String> {
     debug!("emscripten::_pthread_once");
-    0
-}
-pub fn _pthread_key_create(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
+    let control = &ctx.memory(0).view::<i32>()[once_control as usize / 4];
+    if control.get() != 0 {
+        return Ok(0);
+    }
+    control.set(1);
+    get_emscripten_data(ctx)
+        .dyn_call_v
+        .as_ref()
+        .expect("dyn_call_v is None")
+        .call(init_routine)
+        .map_err(|e| e.to_string())?;
+    Ok(0)
+}
+// Keys don't have destructors, as no thread ever exits but the main one.
+pub fn _pthread_key_create(ctx: &mut Ctx, key_ptr: u32, _destructor: i32) -> i32 {
     debug!("emscripten::_pthread_key_create");
+    if key_ptr == 0 {
+        return EINVAL;
+    }
+    let keys = &mut get_emscripten_data(ctx).pthread_specific;
+    let key = keys.keys().next_back().map_or(1, |key| key + 1);
+    keys.insert(key, 0);
+    ctx.memory(0).view::<i32>()[key_ptr as usize / 4].set(key);
     0
 }
-pub fn _pthread_create(_ctx: &mut Ctx, _a: i32, _b: i32, _c: i32, _d: i32) -> i32 {
+pub fn _pthread_key_delete(ctx: &mut Ctx, key: i32) -> i32 {
+    debug!("emscripten::_pthread_key_delete");
+    match get_emscripten_data(ctx).pthread_specific.remove(&key) {
+        Some(_) => 0,
+        None => EINVAL,
+    }
+}
+// Threads can't be created: wasmer has no shared memories or atomics yet,
+// so a thread couldn't run alongside the one that created it. Like emscripten
+// without USE_PTHREADS, `_pthread_create` fails with EAGAIN, for programs to
+// fall back to doing the work themselves or to fail visibly, and there is
+// never a thread to join.
+pub fn _pthread_create(_ctx: &mut Ctx, _thread: i32, _attr: i32, _start: i32, _arg: i32) -> i32 {
     debug!("emscripten::_pthread_create");
-    0
+    EAGAIN
 }
-pub fn _pthread_join(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
+pub fn _pthread_join(_ctx: &mut Ctx, _thread: i32, _retval: i32) -> i32 {
     debug!("emscripten::_pthread_join");
-    0
+    ESRCH
 }
 pub fn _pthread_cond_init(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
     debug!("emscripten::_pthread_cond_init");
@@ -176,20 +207,39 @@ pub fn _getloadavg(_ctx: &mut Ctx, _loadavg: i32, _nelem: i32) -> i32 {
 // Invoke functions
 // They save the stack to allow unwinding
 
+/// What an `invoke_*` function does when the call it made trapped, like
+/// emscripten's JS wrappers: restore the stack and, if it was a `longjmp`
+/// or a C++ exception, set `__THREW__` and return for the guest to handle
+/// it. Any other trap is passed on to the caller.
+fn recover(ctx: &mut Ctx, sp: i32, error: RuntimeError) -> Result<(), String> {
+    get_emscripten_data(ctx)
+        .stack_restore
+        .as_ref()
+        .expect("stack_restore is None")
+        .call(sp)
+        .expect("stack_restore call failed");
+    match get_emscripten_data(ctx).unwinding.take() {
+        Some(Unwind::Longjmp) | Some(Unwind::Exception) => {
+            get_emscripten_data(ctx)
+                .set_threw
+                .as_ref()
+                .expect("set_threw is None")
+                .call(1, 0)
+                .expect("set_threw call failed");
+            Ok(())
+        }
+        None => Err(error.to_string()),
+    }
+}
+
 // Macro definitions
 macro_rules! invoke {
     ($ctx: ident, $name:ident, $( $arg:ident ),*) => {{
         let sp = get_emscripten_data($ctx).stack_save.as_ref().expect("stack_save is None").call().expect("stack_save call failed");
         let result = get_emscripten_data($ctx).$name.as_ref().expect(concat!("Dynamic call is None: ", stringify!($name))).call($($arg),*);
         match result {
-            Ok(v) => v,
-            Err(_e) => {
-                get_emscripten_data($ctx).stack_restore.as_ref().expect("stack_restore is None").call(sp).expect("stack_restore call failed");
-                // TODO: We should check if _e != "longjmp" and if that's the case, re-throw the error
-                // JS version is: if (e !== e+0 && e !== 'longjmp') throw e;
-                get_emscripten_data($ctx).set_threw.as_ref().expect("set_threw is None").call(1, 0).expect("set_threw call failed");
-                0 as _
-            }
+            Ok(v) => Ok(v),
+            Err(e) => recover($ctx, sp, e).map(|()| 0 as _),
         }
     }};
 }
@@ -198,68 +248,84 @@ macro_rules! invoke_no_return {
         let sp = get_emscripten_data($ctx).stack_save.as_ref().expect("stack_save is None").call().expect("stack_save call failed");
         let result = get_emscripten_data($ctx).$name.as_ref().expect(concat!("Dynamic call is None: ", stringify!($name))).call($($arg),*);
         match result {
-            Ok(v) => v,
-            Err(_e) => {
-                get_emscripten_data($ctx).stack_restore.as_ref().expect("stack_restore is None").call(sp).expect("stack_restore call failed");
-                // TODO: We should check if _e != "longjmp" and if that's the case, re-throw the error
-                // JS version is: if (e !== e+0 && e !== 'longjmp') throw e;
-                get_emscripten_data($ctx).set_threw.as_ref().expect("set_threw is None").call(1, 0).expect("set_threw call failed");
-            }
+            Ok(()) => Ok(()),
+            Err(e) => recover($ctx, sp, e),
         }
     }};
 }
 
 // Invoke functions
-pub fn invoke_i(ctx: &mut Ctx, index: i32) -> i32 {
+pub fn invoke_i(ctx: &mut Ctx, index: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_i");
     invoke!(ctx, dyn_call_i, index)
 }
-pub fn invoke_ii(ctx: &mut Ctx, index: i32, a1: i32) -> i32 {
+pub fn invoke_ii(ctx: &mut Ctx, index: i32, a1: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_ii");
     invoke!(ctx, dyn_call_ii, index, a1)
 }
-pub fn invoke_iii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> i32 {
+pub fn invoke_iii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_iii");
     invoke!(ctx, dyn_call_iii, index, a1, a2)
 }
-pub fn invoke_iiii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_iiii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_iiii");
     invoke!(ctx, dyn_call_iiii, index, a1, a2, a3)
 }
-pub fn invoke_iifi(ctx: &mut Ctx, index: i32, a1: i32, a2: f64, a3: i32) -> i32 {
+pub fn invoke_iifi(ctx: &mut Ctx, index: i32, a1: i32, a2: f64, a3: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_iifi");
     invoke!(ctx, dyn_call_iifi, index, a1, a2, a3)
 }
-pub fn invoke_v(ctx: &mut Ctx, index: i32) {
+pub fn invoke_v(ctx: &mut Ctx, index: i32) -> Result<(), String> {
     debug!("emscripten::invoke_v");
-    invoke_no_return!(ctx, dyn_call_v, index);
+    invoke_no_return!(ctx, dyn_call_v, index)
 }
-pub fn invoke_vi(ctx: &mut Ctx, index: i32, a1: i32) {
+pub fn invoke_vi(ctx: &mut Ctx, index: i32, a1: i32) -> Result<(), String> {
     debug!("emscripten::invoke_vi");
-    invoke_no_return!(ctx, dyn_call_vi, index, a1);
+    invoke_no_return!(ctx, dyn_call_vi, index, a1)
 }
-pub fn invoke_vii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) {
+pub fn invoke_vii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> Result<(), String> {
     debug!("emscripten::invoke_vii");
-    invoke_no_return!(ctx, dyn_call_vii, index, a1, a2);
+    invoke_no_return!(ctx, dyn_call_vii, index, a1, a2)
 }
 
-pub fn invoke_viii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) {
+pub fn invoke_viii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> Result<(), String> {
     debug!("emscripten::invoke_viii");
-    invoke_no_return!(ctx, dyn_call_viii, index, a1, a2, a3);
+    invoke_no_return!(ctx, dyn_call_viii, index, a1, a2, a3)
 }
-pub fn invoke_viiii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
+pub fn invoke_viiii(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiii");
-    invoke_no_return!(ctx, dyn_call_viiii, index, a1, a2, a3, a4);
+    invoke_no_return!(ctx, dyn_call_viiii, index, a1, a2, a3, a4)
 }
-pub fn invoke_dii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> f64 {
+pub fn invoke_dii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> Result<f64, String> {
     debug!("emscripten::invoke_dii");
     invoke!(ctx, dyn_call_dii, index, a1, a2)
 }
-pub fn invoke_diiii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) -> f64 {
+pub fn invoke_diiii(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<f64, String> {
     debug!("emscripten::invoke_diiii");
     invoke!(ctx, dyn_call_diiii, index, a1, a2, a3, a4)
 }
-pub fn invoke_iiiii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) -> i32 {
+pub fn invoke_iiiii(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiii");
     invoke!(ctx, dyn_call_iiiii, index, a1, a2, a3, a4)
 }
@@ -271,7 +337,7 @@ pub fn invoke_iiiiii(
     a3: i32,
     a4: i32,
     a5: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiiii");
     invoke!(ctx, dyn_call_iiiiii, index, a1, a2, a3, a4, a5)
 }
@@ -284,7 +350,7 @@ pub fn invoke_iiiiiii(
     a4: i32,
     a5: i32,
     a6: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiiiii");
     invoke!(ctx, dyn_call_iiiiiii, index, a1, a2, a3, a4, a5, a6)
 }
@@ -298,7 +364,7 @@ pub fn invoke_iiiiiiii(
     a5: i32,
     a6: i32,
     a7: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiiiiii");
     invoke!(ctx, dyn_call_iiiiiiii, index, a1, a2, a3, a4, a5, a6, a7)
 }
@@ -313,7 +379,7 @@ pub fn invoke_iiiiiiiii(
     a6: i32,
     a7: i32,
     a8: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiiiiiii");
     invoke!(
         ctx,
@@ -341,7 +407,7 @@ pub fn invoke_iiiiiiiiii(
     a7: i32,
     a8: i32,
     a9: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiiiiiiii");
     invoke!(
         ctx,
@@ -371,7 +437,7 @@ pub fn invoke_iiiiiiiiiii(
     a8: i32,
     a9: i32,
     a10: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiiiiiiiiii");
     invoke!(
         ctx,
@@ -389,11 +455,19 @@ pub fn invoke_iiiiiiiiiii(
         a10
     )
 }
-pub fn invoke_vd(ctx: &mut Ctx, index: i32, a1: f64) {
+pub fn invoke_vd(ctx: &mut Ctx, index: i32, a1: f64) -> Result<(), String> {
     debug!("emscripten::invoke_vd");
     invoke_no_return!(ctx, dyn_call_vd, index, a1)
 }
-pub fn invoke_viiiii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
+pub fn invoke_viiiii(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+    a5: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiiii");
     invoke_no_return!(ctx, dyn_call_viiiii, index, a1, a2, a3, a4, a5)
 }
@@ -406,7 +480,7 @@ pub fn invoke_viiiiii(
     a4: i32,
     a5: i32,
     a6: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiiiii");
     invoke_no_return!(ctx, dyn_call_viiiiii, index, a1, a2, a3, a4, a5, a6)
 }
@@ -420,7 +494,7 @@ pub fn invoke_viiiiiii(
     a5: i32,
     a6: i32,
     a7: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiiiiii");
     invoke_no_return!(ctx, dyn_call_viiiiiii, index, a1, a2, a3, a4, a5, a6, a7)
 }
@@ -435,7 +509,7 @@ pub fn invoke_viiiiiiii(
     a6: i32,
     a7: i32,
     a8: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiiiiiii");
     invoke_no_return!(
         ctx,
@@ -463,7 +537,7 @@ pub fn invoke_viiiiiiiii(
     a7: i32,
     a8: i32,
     a9: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiiiiiiii");
     invoke_no_return!(
         ctx,
@@ -493,7 +567,7 @@ pub fn invoke_viiiiiiiiii(
     a8: i32,
     a9: i32,
     a10: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiiiiiiiii");
     invoke_no_return!(
         ctx,
@@ -512,12 +586,19 @@ pub fn invoke_viiiiiiiiii(
     )
 }
 
-pub fn invoke_iij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_iij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_iij");
     invoke!(ctx, dyn_call_iij, index, a1, a2, a3)
 }
 
-pub fn invoke_iiji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) -> i32 {
+pub fn invoke_iiji(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiji");
     invoke!(ctx, dyn_call_iiji, index, a1, a2, a3, a4)
 }
@@ -531,58 +612,49 @@ pub fn invoke_iiijj(
     a4: i32,
     a5: i32,
     a6: i32,
-) -> i32 {
+) -> Result<i32, String> {
     debug!("emscripten::invoke_iiijj");
     invoke!(ctx, dyn_call_iiijj, index, a1, a2, a3, a4, a5, a6)
 }
-pub fn invoke_j(ctx: &mut Ctx, index: i32) -> i32 {
+pub fn invoke_j(ctx: &mut Ctx, index: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_j");
-    if let Some(dyn_call_j) = &get_emscripten_data(ctx).dyn_call_j {
-        dyn_call_j.call(index).unwrap()
-    } else {
-        panic!("dyn_call_j is set to None");
-    }
+    invoke!(ctx, dyn_call_j, index)
 }
-pub fn invoke_ji(ctx: &mut Ctx, index: i32, a1: i32) -> i32 {
+pub fn invoke_ji(ctx: &mut Ctx, index: i32, a1: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_ji");
-    if let Some(dyn_call_ji) = &get_emscripten_data(ctx).dyn_call_ji {
-        dyn_call_ji.call(index, a1).unwrap()
-    } else {
-        panic!("dyn_call_ji is set to None");
-    }
+    invoke!(ctx, dyn_call_ji, index, a1)
 }
-pub fn invoke_jii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> i32 {
+pub fn invoke_jii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_jii");
-    if let Some(dyn_call_jii) = &get_emscripten_data(ctx).dyn_call_jii {
-        dyn_call_jii.call(index, a1, a2).unwrap()
-    } else {
-        panic!("dyn_call_jii is set to None");
-    }
+    invoke!(ctx, dyn_call_jii, index, a1, a2)
 }
 
-pub fn invoke_jij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_jij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> Result<i32, String> {
     debug!("emscripten::invoke_jij");
-    if let Some(dyn_call_jij) = &get_emscripten_data(ctx).dyn_call_jij {
-        dyn_call_jij.call(index, a1, a2, a3).unwrap()
-    } else {
-        panic!("dyn_call_jij is set to None");
-    }
+    invoke!(ctx, dyn_call_jij, index, a1, a2, a3)
 }
-pub fn invoke_jjj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) -> i32 {
+pub fn invoke_jjj(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<i32, String> {
     debug!("emscripten::invoke_jjj");
-    if let Some(dyn_call_jjj) = &get_emscripten_data(ctx).dyn_call_jjj {
-        dyn_call_jjj.call(index, a1, a2, a3, a4).unwrap()
-    } else {
-        panic!("dyn_call_jjj is set to None");
-    }
+    invoke!(ctx, dyn_call_jjj, index, a1, a2, a3, a4)
 }
-pub fn invoke_viiij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
+pub fn invoke_viiij(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+    a5: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiij");
-    if let Some(dyn_call_viiij) = &get_emscripten_data(ctx).dyn_call_viiij {
-        dyn_call_viiij.call(index, a1, a2, a3, a4, a5).unwrap();
-    } else {
-        panic!("dyn_call_viiij is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viiij, index, a1, a2, a3, a4, a5)
 }
 pub fn invoke_viiijiiii(
     ctx: &mut Ctx,
@@ -596,15 +668,22 @@ pub fn invoke_viiijiiii(
     a7: i32,
     a8: i32,
     a9: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiijiiii");
-    if let Some(dyn_call_viiijiiii) = &get_emscripten_data(ctx).dyn_call_viiijiiii {
-        dyn_call_viiijiiii
-            .call(index, a1, a2, a3, a4, a5, a6, a7, a8, a9)
-            .unwrap();
-    } else {
-        panic!("dyn_call_viiijiiii is set to None");
-    }
+    invoke_no_return!(
+        ctx,
+        dyn_call_viiijiiii,
+        index,
+        a1,
+        a2,
+        a3,
+        a4,
+        a5,
+        a6,
+        a7,
+        a8,
+        a9
+    )
 }
 pub fn invoke_viiijiiiiii(
     ctx: &mut Ctx,
@@ -620,31 +699,47 @@ pub fn invoke_viiijiiiiii(
     a9: i32,
     a10: i32,
     a11: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiijiiiiii");
-    if let Some(dyn_call_viiijiiiiii) = &get_emscripten_data(ctx).dyn_call_viiijiiiiii {
-        dyn_call_viiijiiiiii
-            .call(index, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11)
-            .unwrap();
-    } else {
-        panic!("dyn_call_viiijiiiiii is set to None");
-    }
+    invoke_no_return!(
+        ctx,
+        dyn_call_viiijiiiiii,
+        index,
+        a1,
+        a2,
+        a3,
+        a4,
+        a5,
+        a6,
+        a7,
+        a8,
+        a9,
+        a10,
+        a11
+    )
 }
-pub fn invoke_viij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
+pub fn invoke_viij(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viij");
-    if let Some(dyn_call_viij) = &get_emscripten_data(ctx).dyn_call_viij {
-        dyn_call_viij.call(index, a1, a2, a3, a4).unwrap();
-    } else {
-        panic!("dyn_call_viij is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viij, index, a1, a2, a3, a4)
 }
-pub fn invoke_viiji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
+pub fn invoke_viiji(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+    a5: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viiji");
-    if let Some(dyn_call_viiji) = &get_emscripten_data(ctx).dyn_call_viiji {
-        dyn_call_viiji.call(index, a1, a2, a3, a4, a5).unwrap();
-    } else {
-        panic!("dyn_call_viiji is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viiji, index, a1, a2, a3, a4, a5)
 }
 pub fn invoke_viijiii(
     ctx: &mut Ctx,
@@ -656,15 +751,9 @@ pub fn invoke_viijiii(
     a5: i32,
     a6: i32,
     a7: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viijiii");
-    if let Some(dyn_call_viijiii) = &get_emscripten_data(ctx).dyn_call_viijiii {
-        dyn_call_viijiii
-            .call(index, a1, a2, a3, a4, a5, a6, a7)
-            .unwrap();
-    } else {
-        panic!("dyn_call_viijiii is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viijiii, index, a1, a2, a3, a4, a5, a6, a7)
 }
 pub fn invoke_viijj(
     ctx: &mut Ctx,
@@ -675,37 +764,28 @@ pub fn invoke_viijj(
     a4: i32,
     a5: i32,
     a6: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viijj");
-    if let Some(dyn_call_viijj) = &get_emscripten_data(ctx).dyn_call_viijj {
-        dyn_call_viijj.call(index, a1, a2, a3, a4, a5, a6).unwrap();
-    } else {
-        panic!("dyn_call_viijj is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viijj, index, a1, a2, a3, a4, a5, a6)
 }
-pub fn invoke_vj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) {
+pub fn invoke_vj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> Result<(), String> {
     debug!("emscripten::invoke_vj");
-    if let Some(dyn_call_vj) = &get_emscripten_data(ctx).dyn_call_vj {
-        dyn_call_vj.call(index, a1, a2).unwrap();
-    } else {
-        panic!("dyn_call_vj is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vj, index, a1, a2)
 }
-pub fn invoke_vij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) {
+pub fn invoke_vij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> Result<(), String> {
     debug!("emscripten::invoke_vij");
-    if let Some(dyn_call_vij) = &get_emscripten_data(ctx).dyn_call_vij {
-        dyn_call_vij.call(index, a1, a2, a3).unwrap();
-    } else {
-        panic!("dyn_call_vij is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vij, index, a1, a2, a3)
 }
-pub fn invoke_viji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
+pub fn invoke_viji(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viji");
-    if let Some(dyn_call_viji) = &get_emscripten_data(ctx).dyn_call_viji {
-        dyn_call_viji.call(index, a1, a2, a3, a4).unwrap()
-    } else {
-        panic!("dyn_call_viji is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viji, index, a1, a2, a3, a4)
 }
 pub fn invoke_vijiii(
     ctx: &mut Ctx,
@@ -716,29 +796,37 @@ pub fn invoke_vijiii(
     a4: i32,
     a5: i32,
     a6: i32,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_vijiii");
-    if let Some(dyn_call_vijiii) = &get_emscripten_data(ctx).dyn_call_vijiii {
-        dyn_call_vijiii.call(index, a1, a2, a3, a4, a5, a6).unwrap()
-    } else {
-        panic!("dyn_call_vijiii is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vijiii, index, a1, a2, a3, a4, a5, a6)
 }
-pub fn invoke_vijj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
+pub fn invoke_vijj(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+    a5: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_vijj");
-    if let Some(dyn_call_vijj) = &get_emscripten_data(ctx).dyn_call_vijj {
-        dyn_call_vijj.call(index, a1, a2, a3, a4, a5).unwrap()
-    } else {
-        panic!("dyn_call_vijj is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vijj, index, a1, a2, a3, a4, a5)
 }
-pub fn invoke_viid(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: f64) {
+pub fn invoke_viid(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: f64) -> Result<(), String> {
     debug!("emscripten::invoke_viid");
-    invoke_no_return!(ctx, dyn_call_viid, index, a1, a2, a3);
+    invoke_no_return!(ctx, dyn_call_viid, index, a1, a2, a3)
 }
-pub fn invoke_viidii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: f64, a4: i32, a5: i32) {
+pub fn invoke_viidii(
+    ctx: &mut Ctx,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: f64,
+    a4: i32,
+    a5: i32,
+) -> Result<(), String> {
     debug!("emscripten::invoke_viidii");
-    invoke_no_return!(ctx, dyn_call_viidii, index, a1, a2, a3, a4, a5);
+    invoke_no_return!(ctx, dyn_call_viidii, index, a1, a2, a3, a4, a5)
 }
 pub fn invoke_viidddddddd(
     ctx: &mut Ctx,
@@ -753,7 +841,7 @@ pub fn invoke_viidddddddd(
     a8: f64,
     a9: f64,
     a10: f64,
-) {
+) -> Result<(), String> {
     debug!("emscripten::invoke_viidddddddd");
     invoke_no_return!(
         ctx,
@@ -769,5 +857,80 @@ pub fn invoke_viidddddddd(
         a8,
         a9,
         a10
-    );
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        _pthread_create, _pthread_getspecific, _pthread_join, _pthread_key_create,
+        _pthread_key_delete, _pthread_once, _pthread_setspecific,
+    };
+    use crate::utils::tests::with_runtime;
+    use libc::{EAGAIN, EINVAL, ESRCH};
+    use wasmer_runtime_core::vm::Ctx;
+
+    const ONCE: u32 = 16;
+    const KEYS: u32 = 32;
+    const CALLS: usize = 65532;
+
+    fn calls(ctx: &mut Ctx) -> i32 {
+        ctx.memory(0).view::<i32>()[CALLS / 4].get()
+    }
+
+    #[test]
+    fn pthread_once_runs_the_init_routine_once_per_control() {
+        with_runtime(|ctx| {
+            assert_eq!(_pthread_once(ctx, ONCE, 1), Ok(0));
+            assert_eq!(_pthread_once(ctx, ONCE, 1), Ok(0));
+            assert_eq!(calls(ctx), 1);
+            assert_eq!(ctx.memory(0).view::<i32>()[ONCE as usize / 4].get(), 1);
+
+            // A control at another address hasn't run yet, and neither has
+            // one put back to `PTHREAD_ONCE_INIT`.
+            assert_eq!(_pthread_once(ctx, ONCE + 4, 1), Ok(0));
+            assert_eq!(calls(ctx), 2);
+            ctx.memory(0).view::<i32>()[ONCE as usize / 4].set(0);
+            assert_eq!(_pthread_once(ctx, ONCE, 1), Ok(0));
+            assert_eq!(calls(ctx), 3);
+        });
+    }
+
+    #[test]
+    fn pthread_keys_hold_values_until_deleted() {
+        with_runtime(|ctx| {
+            assert_eq!(_pthread_key_create(ctx, KEYS, 0), 0);
+            assert_eq!(_pthread_key_create(ctx, KEYS + 4, 0), 0);
+            let (first, second) = {
+                let view = ctx.memory(0).view::<i32>();
+                (
+                    view[KEYS as usize / 4].get(),
+                    view[KEYS as usize / 4 + 1].get(),
+                )
+            };
+            assert_ne!(first, second);
+            assert_eq!(_pthread_key_create(ctx, 0, 0), EINVAL);
+
+            assert_eq!(_pthread_getspecific(ctx, first), 0);
+            assert_eq!(_pthread_setspecific(ctx, first, 42), 0);
+            assert_eq!(_pthread_setspecific(ctx, second, 7), 0);
+            assert_eq!(_pthread_getspecific(ctx, first), 42);
+            assert_eq!(_pthread_getspecific(ctx, second), 7);
+
+            assert_eq!(_pthread_key_delete(ctx, first), 0);
+            assert_eq!(_pthread_key_delete(ctx, first), EINVAL);
+            assert_eq!(_pthread_setspecific(ctx, first, 1), EINVAL);
+            assert_eq!(_pthread_getspecific(ctx, first), 0);
+            assert_eq!(_pthread_getspecific(ctx, second), 7);
+        });
+    }
+
+    #[test]
+    fn pthread_create_fails_and_there_is_nothing_to_join() {
+        with_runtime(|ctx| {
+            assert_eq!(_pthread_create(ctx, 0, 0, 1, 0), EAGAIN);
+            assert_eq!(calls(ctx), 0);
+            assert_eq!(_pthread_join(ctx, 0, 0), ESRCH);
+        });
+    }
 }
@@ -14,9 +14,26 @@ use crate::{allocate_on_stack, EmscriptenData};
 use std::os::raw::c_int;
 use wasmer_runtime_core::vm::Ctx;
 
-pub fn _getaddrinfo(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32, _four: i32) -> i32 {
-    debug!("emscripten::_getaddrinfo");
-    -1
+/// emscripten: freeaddrinfo(res: *mut addrinfo)
+pub fn _freeaddrinfo(ctx: &mut Ctx, res: u32) {
+    debug!("emscripten::_freeaddrinfo");
+    // Each entry, its address and its name are allocations of their own.
+    let mut entry = res;
+    while entry != 0 {
+        let view = ctx.memory(0).view::<u32>();
+        let (addr, canonname, next) = (
+            view[entry as usize / 4 + 5].get(),
+            view[entry as usize / 4 + 6].get(),
+            view[entry as usize / 4 + 7].get(),
+        );
+        let free = &get_emscripten_data(ctx).free;
+        for &ptr in &[addr, canonname, entry] {
+            if ptr != 0 {
+                free.call(ptr).expect("free call failed");
+            }
+        }
+        entry = next;
+    }
 }
 
 pub fn call_malloc(ctx: &mut Ctx, size: u32) -> u32 {
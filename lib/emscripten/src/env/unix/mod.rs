@@ -1,14 +1,17 @@
 /// NOTE: These syscalls only support wasm_32 for now because they take u32 offset
 use libc::{
-    c_int, getenv, getgrnam as libc_getgrnam, getpwnam as libc_getpwnam, putenv, setenv, sysconf,
-    unsetenv,
+    c_int, getenv, getgrnam as libc_getgrnam, getpwnam as libc_getpwnam, putenv, setenv,
+    sockaddr_storage, socklen_t, sysconf, unsetenv,
 };
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::mem;
+use std::net::SocketAddr;
 use std::os::raw::c_char;
+use std::ptr;
 
 use crate::env::call_malloc;
-use crate::utils::{copy_cstr_into_wasm, copy_terminated_array_of_cstrs};
+use crate::net;
+use crate::utils::{copy_cstr_into_wasm, copy_terminated_array_of_cstrs, read_string_from_wasm};
 use wasmer_runtime_core::vm::Ctx;
 
 // #[no_mangle]
@@ -140,3 +143,343 @@ pub fn _sysconf(_ctx: &mut Ctx, name: c_int) -> i32 {
     // TODO: Implement like emscripten expects regarding memory/page size
     unsafe { sysconf(name) as i32 } // TODO review i64
 }
+
+/// The guest's `struct addrinfo`.
+#[repr(C)]
+struct GuestAddrinfo {
+    ai_flags: i32,
+    ai_family: i32,
+    ai_socktype: i32,
+    ai_protocol: i32,
+    ai_addrlen: u32,
+    ai_addr: u32,
+    ai_canonname: u32,
+    ai_next: u32,
+}
+
+// The `getaddrinfo` flags of the guest, with their host numbers.
+const AI_FLAGS: &[(c_int, c_int)] = &[
+    (0x1, libc::AI_PASSIVE),
+    (0x2, libc::AI_CANONNAME),
+    (0x4, libc::AI_NUMERICHOST),
+    (0x8, libc::AI_V4MAPPED),
+    (0x10, libc::AI_ALL),
+    (0x20, libc::AI_ADDRCONFIG),
+    (0x400, libc::AI_NUMERICSERV),
+];
+
+// The guest's `getaddrinfo` errors.
+const EAI_BADFLAGS: c_int = -1;
+const EAI_NONAME: c_int = -2;
+const EAI_AGAIN: c_int = -3;
+const EAI_FAIL: c_int = -4;
+const EAI_FAMILY: c_int = -6;
+const EAI_SOCKTYPE: c_int = -7;
+const EAI_SERVICE: c_int = -8;
+const EAI_MEMORY: c_int = -10;
+const EAI_SYSTEM: c_int = -11;
+const EAI_OVERFLOW: c_int = -12;
+
+// The guest's `getnameinfo` flags.
+const NI_NUMERICHOST: c_int = 1;
+const NI_NUMERICSERV: c_int = 2;
+const NI_NAMEREQD: c_int = 8;
+
+/// The guest's number for host `getaddrinfo` error `error`.
+fn guest_eai(error: c_int) -> c_int {
+    match error {
+        libc::EAI_BADFLAGS => EAI_BADFLAGS,
+        libc::EAI_NONAME => EAI_NONAME,
+        libc::EAI_AGAIN => EAI_AGAIN,
+        libc::EAI_FAMILY => EAI_FAMILY,
+        libc::EAI_SOCKTYPE => EAI_SOCKTYPE,
+        libc::EAI_SERVICE => EAI_SERVICE,
+        libc::EAI_MEMORY => EAI_MEMORY,
+        libc::EAI_SYSTEM => EAI_SYSTEM,
+        _ => EAI_FAIL,
+    }
+}
+
+/// The optional guest string at `ptr`, for the host.
+fn read_cstring(ctx: &Ctx, ptr: u32) -> Option<CString> {
+    if ptr == 0 {
+        return None;
+    }
+    let string = read_string_from_wasm(ctx.memory(0), ptr);
+    Some(CString::new(string).unwrap_or_default())
+}
+
+/// Copy `bytes` into a new guest allocation.
+fn copy_bytes_into_wasm(ctx: &mut Ctx, bytes: &[u8]) -> u32 {
+    let ptr = call_malloc(ctx, bytes.len() as u32);
+    for (cell, &byte) in ctx.memory(0).view::<u8>()[ptr as usize..].iter().zip(bytes) {
+        cell.set(byte);
+    }
+    ptr
+}
+
+/// emscripten: getaddrinfo(node: *const c_char, service: *const c_char,
+/// hints: *const addrinfo, res: *mut *mut addrinfo) -> c_int
+///
+/// The host resolves the names; the guest gets a list of its own,
+/// allocated in its memory and released with `freeaddrinfo`.
+#[allow(clippy::cast_ptr_alignment)]
+pub fn _getaddrinfo(ctx: &mut Ctx, node: u32, service: u32, hints: u32, res: u32) -> c_int {
+    debug!("emscripten::_getaddrinfo");
+    let node = read_cstring(ctx, node);
+    let service = read_cstring(ctx, service);
+    if node.is_none() && service.is_none() {
+        return EAI_NONAME;
+    }
+
+    let mut host_hints: libc::addrinfo = unsafe { mem::zeroed() };
+    let mut flags = 0;
+    if hints != 0 {
+        let hints =
+            unsafe { &*(emscripten_memory_pointer!(ctx.memory(0), hints) as *const GuestAddrinfo) };
+        flags = hints.ai_flags;
+        if AI_FLAGS.iter().fold(flags, |left, (flag, _)| left & !flag) != 0 {
+            return EAI_BADFLAGS;
+        }
+        host_hints.ai_flags = AI_FLAGS
+            .iter()
+            .filter(|(flag, _)| flags & flag != 0)
+            .fold(0, |host, (_, flag)| host | flag);
+        host_hints.ai_family = match hints.ai_family {
+            net::AF_UNSPEC => libc::AF_UNSPEC,
+            net::AF_INET => libc::AF_INET,
+            net::AF_INET6 => libc::AF_INET6,
+            _ => return EAI_FAMILY,
+        };
+        host_hints.ai_socktype = match hints.ai_socktype {
+            0 => 0,
+            net::SOCK_STREAM => libc::SOCK_STREAM,
+            net::SOCK_DGRAM => libc::SOCK_DGRAM,
+            net::SOCK_RAW => libc::SOCK_RAW,
+            _ => return EAI_SOCKTYPE,
+        };
+        host_hints.ai_protocol = hints.ai_protocol;
+    }
+
+    let mut list: *mut libc::addrinfo = ptr::null_mut();
+    let error = unsafe {
+        libc::getaddrinfo(
+            node.as_ref().map_or(ptr::null(), |node| node.as_ptr()),
+            service
+                .as_ref()
+                .map_or(ptr::null(), |service| service.as_ptr()),
+            &host_hints,
+            &mut list,
+        )
+    };
+    if error != 0 {
+        return guest_eai(error);
+    }
+
+    // Built back to front, for each entry to point at the next.
+    let mut entries = vec![];
+    let mut entry = list;
+    while !entry.is_null() {
+        entries.push(unsafe { &*entry });
+        entry = unsafe { (*entry).ai_next };
+    }
+    let mut next = 0;
+    for (i, entry) in entries.iter().enumerate().rev() {
+        let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+        let len = (entry.ai_addrlen as usize).min(mem::size_of::<sockaddr_storage>());
+        unsafe {
+            ptr::copy_nonoverlapping(
+                entry.ai_addr as *const u8,
+                &mut storage as *mut sockaddr_storage as *mut u8,
+                len,
+            )
+        };
+        let (address, family) = match net::guest_address(&storage) {
+            Some(net::Address::Inet(addr @ SocketAddr::V4(_))) => (addr, net::AF_INET),
+            Some(net::Address::Inet(addr @ SocketAddr::V6(_))) => (addr, net::AF_INET6),
+            _ => continue,
+        };
+        let socktype = match entry.ai_socktype {
+            libc::SOCK_STREAM => net::SOCK_STREAM,
+            libc::SOCK_DGRAM => net::SOCK_DGRAM,
+            libc::SOCK_RAW => net::SOCK_RAW,
+            _ => continue,
+        };
+        let bytes = net::address_bytes(&net::Address::Inet(address));
+        let addr = copy_bytes_into_wasm(ctx, &bytes);
+        let canonname = if i == 0 && !entry.ai_canonname.is_null() {
+            unsafe { copy_cstr_into_wasm(ctx, entry.ai_canonname) }
+        } else {
+            0
+        };
+        let guest = call_malloc(ctx, mem::size_of::<GuestAddrinfo>() as u32);
+        unsafe {
+            *(emscripten_memory_pointer!(ctx.memory(0), guest) as *mut GuestAddrinfo) =
+                GuestAddrinfo {
+                    ai_flags: flags,
+                    ai_family: family,
+                    ai_socktype: socktype,
+                    ai_protocol: entry.ai_protocol,
+                    ai_addrlen: bytes.len() as u32,
+                    ai_addr: addr,
+                    ai_canonname: canonname,
+                    ai_next: next,
+                };
+        }
+        next = guest;
+    }
+    unsafe { libc::freeaddrinfo(list) };
+
+    if next == 0 {
+        return EAI_NONAME;
+    }
+    ctx.memory(0).view::<u32>()[res as usize / 4].set(next);
+    0
+}
+
+/// emscripten: getnameinfo(addr: *const sockaddr, addrlen: socklen_t, host: *mut c_char,
+/// hostlen: socklen_t, serv: *mut c_char, servlen: socklen_t, flags: c_int) -> c_int
+#[allow(clippy::too_many_arguments)]
+pub fn _getnameinfo(
+    ctx: &mut Ctx,
+    addr: u32,
+    addrlen: u32,
+    host: u32,
+    hostlen: u32,
+    serv: u32,
+    servlen: u32,
+    flags: c_int,
+) -> c_int {
+    debug!("emscripten::_getnameinfo");
+    let addr = match net::read_address(ctx, addr, addrlen) {
+        Ok(net::Address::Inet(addr)) => addr,
+        _ => return EAI_FAMILY,
+    };
+    let (storage, len) = net::host_socket_address(&addr);
+
+    if host != 0 && hostlen != 0 {
+        let numeric = addr.ip().to_string();
+        let name = if flags & NI_NUMERICHOST != 0 {
+            numeric
+        } else {
+            match host_name_info(&storage, len, true) {
+                // Without a name, the host falls back to the number.
+                Ok(name) if name == numeric && flags & NI_NAMEREQD != 0 => return EAI_NONAME,
+                Ok(name) => name,
+                Err(error) => return error,
+            }
+        };
+        if !write_name(ctx, host, hostlen, &name) {
+            return EAI_OVERFLOW;
+        }
+    }
+
+    if serv != 0 && servlen != 0 {
+        let service = if flags & NI_NUMERICSERV != 0 {
+            addr.port().to_string()
+        } else {
+            match host_name_info(&storage, len, false) {
+                Ok(service) => service,
+                Err(error) => return error,
+            }
+        };
+        if !write_name(ctx, serv, servlen, &service) {
+            return EAI_OVERFLOW;
+        }
+    }
+    0
+}
+
+/// The host's name for the address in `storage`, or for its port.
+fn host_name_info(storage: &sockaddr_storage, len: socklen_t, host: bool) -> Result<String, c_int> {
+    let mut buffer = [0 as c_char; 1025];
+    let (host_buf, serv_buf) = if host {
+        (buffer.as_mut_ptr(), ptr::null_mut())
+    } else {
+        (ptr::null_mut(), buffer.as_mut_ptr())
+    };
+    let error = unsafe {
+        libc::getnameinfo(
+            storage as *const sockaddr_storage as *const libc::sockaddr,
+            len,
+            host_buf,
+            if host { buffer.len() as socklen_t } else { 0 },
+            serv_buf,
+            if host { 0 } else { buffer.len() as socklen_t },
+            0,
+        )
+    };
+    if error != 0 {
+        return Err(guest_eai(error));
+    }
+    Ok(unsafe { CStr::from_ptr(buffer.as_ptr()) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Write `name` and a null byte to the guest buffer of `len` bytes
+/// at `ptr`. Returns whether it fit.
+fn write_name(ctx: &Ctx, ptr: u32, len: u32, name: &str) -> bool {
+    if name.len() >= len as usize {
+        return false;
+    }
+    let view = ctx.memory(0).view::<u8>();
+    for (cell, byte) in view[ptr as usize..].iter().zip(name.bytes().chain(Some(0))) {
+        cell.set(byte);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{_getaddrinfo, EAI_FAMILY, EAI_NONAME};
+    use crate::env::_freeaddrinfo;
+    use crate::utils::tests::with_runtime;
+    use wasmer_runtime_core::vm::Ctx;
+
+    fn write(ctx: &Ctx, ptr: u32, bytes: &[u8]) {
+        for (cell, &byte) in ctx.memory(0).view::<u8>()[ptr as usize..].iter().zip(bytes) {
+            cell.set(byte);
+        }
+    }
+
+    fn read_u32(ctx: &Ctx, ptr: u32) -> u32 {
+        ctx.memory(0).view::<u32>()[ptr as usize / 4].get()
+    }
+
+    #[test]
+    fn loopback_addresses_are_resolved_into_guest_lists() {
+        with_runtime(|ctx| {
+            write(ctx, 1024, b"127.0.0.1\0");
+            write(ctx, 1040, b"8080\0");
+            // struct addrinfo hints: AI_NUMERICHOST | AI_NUMERICSERV,
+            // AF_INET and SOCK_STREAM.
+            write(ctx, 1056, &[4, 4, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0]);
+            assert_eq!(_getaddrinfo(ctx, 1024, 1040, 1056, 1100), 0);
+
+            let entry = read_u32(ctx, 1100);
+            assert_eq!(read_u32(ctx, entry + 4), 2);
+            assert_eq!(read_u32(ctx, entry + 8), 1);
+            assert_eq!(read_u32(ctx, entry + 16), 16);
+            let addr = read_u32(ctx, entry + 20) as usize;
+            let bytes: Vec<u8> = ctx.memory(0).view::<u8>()[addr..addr + 8]
+                .iter()
+                .map(|cell| cell.get())
+                .collect();
+            assert_eq!(bytes, [2, 0, 0x1f, 0x90, 127, 0, 0, 1]);
+            assert_eq!(read_u32(ctx, entry + 28), 0);
+            _freeaddrinfo(ctx, entry);
+        });
+    }
+
+    #[test]
+    fn bad_requests_fail_with_guest_errors() {
+        with_runtime(|ctx| {
+            assert_eq!(_getaddrinfo(ctx, 0, 0, 0, 1100), EAI_NONAME);
+            write(ctx, 1024, b"127.0.0.1\0");
+            // An address family the guest doesn't have.
+            write(ctx, 1056, &[0, 0, 0, 0, 99, 0, 0, 0]);
+            assert_eq!(_getaddrinfo(ctx, 1024, 0, 1056, 1100), EAI_FAMILY);
+        });
+    }
+}
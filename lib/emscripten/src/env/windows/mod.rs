@@ -131,3 +131,28 @@ pub fn _sysconf(_ctx: &mut Ctx, name: c_int) -> c_long {
     // stub because sysconf is not valid on windows
     0
 }
+
+/// emscripten: getaddrinfo(node: *const c_char, service: *const c_char,
+/// hints: *const addrinfo, res: *mut *mut addrinfo) -> c_int
+pub fn _getaddrinfo(_ctx: &mut Ctx, _node: u32, _service: u32, _hints: u32, _res: u32) -> c_int {
+    debug!("emscripten::_getaddrinfo");
+    // EAI_FAIL
+    -4
+}
+
+/// emscripten: getnameinfo
+#[allow(clippy::too_many_arguments)]
+pub fn _getnameinfo(
+    _ctx: &mut Ctx,
+    _addr: u32,
+    _addrlen: u32,
+    _host: u32,
+    _hostlen: u32,
+    _serv: u32,
+    _servlen: u32,
+    _flags: c_int,
+) -> c_int {
+    debug!("emscripten::_getnameinfo");
+    // EAI_FAIL
+    -4
+}
@@ -1,11 +1,21 @@
-// use std::collections::HashMap;
+use crate::env::get_emscripten_data;
 use wasmer_runtime_core::vm::Ctx;
 
-pub fn ___seterrno(_ctx: &mut Ctx, _value: i32) {
-    debug!("emscripten::___seterrno {}", _value);
-    // TODO: Incomplete impl
-    eprintln!("failed to set errno!");
-    // value
+/// Set the guest's `errno`, like emscripten's `___setErrNo`. Modules
+/// that don't export `___errno_location` have no `errno` to set.
+pub(crate) fn set_errno(ctx: &mut Ctx, value: i32) {
+    let location = match &get_emscripten_data(ctx).errno_location {
+        Some(errno_location) => errno_location.call(),
+        None => return,
+    };
+    if let Ok(ptr) = location {
+        ctx.memory(0).view::<i32>()[ptr as usize / 4].set(value);
+    }
+}
+
+pub fn ___seterrno(ctx: &mut Ctx, value: i32) {
+    debug!("emscripten::___seterrno {}", value);
+    set_errno(ctx, value);
 }
 
 // pub enum ErrnoCodes {
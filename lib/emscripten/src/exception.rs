@@ -1,30 +1,396 @@
+//! C++ exceptions, the way emscripten's JS runtime handles them.
+//!
+//! `__cxa_throw` records the exception and traps; the nearest `invoke_*`
+//! catches it and returns, and the landing pad the guest jumps to asks
+//! `__cxa_find_matching_catch_*` which of its catch clauses, if any, takes
+//! the exception, using `__cxa_can_catch` from the module's own libc++abi.
+
 use super::env;
-use super::process::_abort;
+use super::env::get_emscripten_data;
+use crate::Unwind;
+use std::collections::HashMap;
 use wasmer_runtime_core::vm::Ctx;
 
+/// What's known about an exception thrown by the guest.
+#[derive(Debug)]
+struct ExceptionInfo {
+    /// The type_info of the thrown object.
+    ty: u32,
+    /// The destructor of the thrown object, a table index.
+    destructor: u32,
+    /// Pointers to the object as the catch clauses that took it saw it,
+    /// e.g. to one of its base classes.
+    adjusted: Vec<u32>,
+    refcount: u32,
+    caught: bool,
+    rethrown: bool,
+}
+
+/// The exceptions in flight in an instance.
+#[derive(Debug, Default)]
+pub(crate) struct Exceptions {
+    infos: HashMap<u32, ExceptionInfo>,
+    /// The exception that was thrown last.
+    last: u32,
+    /// The exceptions being handled, innermost last.
+    caught: Vec<u32>,
+    /// How many exceptions are thrown and not caught yet.
+    uncaught: u32,
+    /// A word of guest memory for `__cxa_can_catch` to adjust pointers in.
+    buffer: u32,
+}
+
+impl Exceptions {
+    /// The exception that `ptr`, maybe adjusted by a catch clause, points to.
+    fn de_adjust(&self, ptr: u32) -> u32 {
+        if ptr == 0 || self.infos.contains_key(&ptr) {
+            return ptr;
+        }
+        self.infos
+            .iter()
+            .find(|(_, info)| info.adjusted.contains(&ptr))
+            .map_or(ptr, |(&thrown, _)| thrown)
+    }
+
+    fn add_ref(&mut self, ptr: u32) {
+        if let Some(info) = self.infos.get_mut(&ptr) {
+            info.refcount += 1;
+        }
+    }
+
+    /// Drop a reference to `ptr`, with its info if that was the last one.
+    fn dec_ref(&mut self, ptr: u32) -> Option<ExceptionInfo> {
+        let info = self.infos.get_mut(&ptr)?;
+        info.refcount = info.refcount.saturating_sub(1);
+        if info.refcount == 0 && !info.rethrown {
+            self.infos.remove(&ptr)
+        } else {
+            None
+        }
+    }
+
+    fn throw(&mut self, ptr: u32, ty: u32, destructor: u32) {
+        self.infos.insert(
+            ptr,
+            ExceptionInfo {
+                ty,
+                destructor,
+                adjusted: vec![ptr],
+                refcount: 0,
+                caught: false,
+                rethrown: false,
+            },
+        );
+        self.last = ptr;
+        self.uncaught += 1;
+    }
+
+    /// Throw the exception being handled again. Only the first rethrow
+    /// keeps it on the stack of caught exceptions: the handler that
+    /// catches it next is the one to end the catch.
+    fn rethrow(&mut self) -> Result<(), &'static str> {
+        let ptr = self.caught.pop().ok_or("rethrow with no exception")?;
+        let ptr = self.de_adjust(ptr);
+        if let Some(info) = self.infos.get_mut(&ptr) {
+            if !info.rethrown {
+                info.rethrown = true;
+                self.caught.push(ptr);
+            }
+        }
+        self.last = ptr;
+        Ok(())
+    }
+
+    fn begin_catch(&mut self, ptr: u32) {
+        if let Some(info) = self.infos.get_mut(&ptr) {
+            if !info.caught {
+                info.caught = true;
+                self.uncaught = self.uncaught.saturating_sub(1);
+            }
+            info.rethrown = false;
+        }
+        self.caught.push(ptr);
+        let thrown = self.de_adjust(ptr);
+        self.add_ref(thrown);
+    }
+
+    /// End the innermost catch, with the exception it handled
+    /// and its info if nothing refers to it any more.
+    fn end_catch(&mut self) -> Option<(u32, ExceptionInfo)> {
+        match self.caught.pop() {
+            Some(ptr) if ptr != 0 => {
+                let thrown = self.de_adjust(ptr);
+                self.last = 0;
+                self.dec_ref(thrown).map(|info| (thrown, info))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Destroy the exception at `ptr`, now that nothing refers to it any more.
+fn destroy(ctx: &mut Ctx, ptr: u32, info: ExceptionInfo) -> Result<(), String> {
+    if info.destructor != 0 {
+        get_emscripten_data(ctx)
+            .dyn_call_vi
+            .as_ref()
+            .expect("dyn_call_vi is None")
+            .call(info.destructor as i32, ptr as i32)
+            .map_err(|e| e.to_string())?;
+    }
+    ___cxa_free_exception(ctx, ptr);
+    Ok(())
+}
+
 /// emscripten: ___cxa_allocate_exception
 pub fn ___cxa_allocate_exception(ctx: &mut Ctx, size: u32) -> u32 {
     debug!("emscripten::___cxa_allocate_exception");
     env::call_malloc(ctx, size as _)
 }
 
+/// emscripten: ___cxa_free_exception
+pub fn ___cxa_free_exception(ctx: &mut Ctx, ptr: u32) {
+    debug!("emscripten::___cxa_free_exception");
+    get_emscripten_data(ctx)
+        .free
+        .call(ptr)
+        .expect("free call failed");
+}
+
 /// emscripten: ___cxa_throw
-/// TODO: We don't have support for exceptions yet
-pub fn ___cxa_throw(ctx: &mut Ctx, _ptr: u32, _ty: u32, _destructor: u32) {
+pub fn ___cxa_throw(ctx: &mut Ctx, ptr: u32, ty: u32, destructor: u32) -> Result<(), &'static str> {
     debug!("emscripten::___cxa_throw");
-    _abort(ctx);
+    let data = get_emscripten_data(ctx);
+    data.exceptions.throw(ptr, ty, destructor);
+    data.unwinding = Some(Unwind::Exception);
+    Err("uncaught C++ exception")
 }
 
-pub fn ___cxa_begin_catch(_ctx: &mut Ctx, _exception_object_ptr: u32) -> i32 {
+/// emscripten: ___cxa_rethrow
+pub fn ___cxa_rethrow(ctx: &mut Ctx) -> Result<(), &'static str> {
+    debug!("emscripten::___cxa_rethrow");
+    let data = get_emscripten_data(ctx);
+    data.exceptions.rethrow()?;
+    data.unwinding = Some(Unwind::Exception);
+    Err("uncaught C++ exception")
+}
+
+/// emscripten: ___resumeException
+pub fn ___resumeException(ctx: &mut Ctx, ptr: u32) -> Result<(), &'static str> {
+    debug!("emscripten::___resumeException");
+    let data = get_emscripten_data(ctx);
+    if data.exceptions.last == 0 {
+        data.exceptions.last = ptr;
+    }
+    data.unwinding = Some(Unwind::Exception);
+    Err("uncaught C++ exception")
+}
+
+/// The catch clause that takes the last exception thrown, out of the
+/// type_infos in `types`. Returns the pointer to the exception as that
+/// clause sees it, with the type_info of the clause in tempRet0, or the
+/// type_info of the exception if none takes it.
+fn find_matching_catch(ctx: &mut Ctx, types: &[u32]) -> Result<u32, String> {
+    let thrown = get_emscripten_data(ctx).exceptions.last;
+    let ty = match get_emscripten_data(ctx).exceptions.infos.get(&thrown) {
+        Some(info) => info.ty,
+        None => 0,
+    };
+    if thrown == 0 || ty == 0 {
+        get_emscripten_data(ctx).temp_ret_0 = 0;
+        return Ok(thrown);
+    }
+
+    if get_emscripten_data(ctx).exceptions.buffer == 0 {
+        let buffer = env::call_malloc(ctx, 4);
+        get_emscripten_data(ctx).exceptions.buffer = buffer;
+    }
+    let buffer = get_emscripten_data(ctx).exceptions.buffer;
+    let slot = buffer as usize / 4;
+    for &catch_ty in types {
+        if catch_ty == 0 {
+            continue;
+        }
+        ctx.memory(0).view::<u32>()[slot].set(thrown);
+        let can_catch = get_emscripten_data(ctx)
+            .cxa_can_catch
+            .as_ref()
+            .ok_or("the module doesn't export ___cxa_can_catch")?
+            .call(catch_ty, ty, buffer)
+            .map_err(|e| format!("___cxa_can_catch failed: {}", e))?;
+        if can_catch != 0 {
+            let adjusted = ctx.memory(0).view::<u32>()[slot].get();
+            let data = get_emscripten_data(ctx);
+            if let Some(info) = data.exceptions.infos.get_mut(&thrown) {
+                info.adjusted.push(adjusted);
+            }
+            data.temp_ret_0 = catch_ty as i32;
+            return Ok(adjusted);
+        }
+    }
+    get_emscripten_data(ctx).temp_ret_0 = ty as i32;
+    Ok(thrown)
+}
+
+/// emscripten: ___cxa_find_matching_catch_2
+pub fn ___cxa_find_matching_catch_2(ctx: &mut Ctx) -> Result<u32, String> {
+    debug!("emscripten::___cxa_find_matching_catch_2");
+    find_matching_catch(ctx, &[])
+}
+
+/// emscripten: ___cxa_find_matching_catch_3
+pub fn ___cxa_find_matching_catch_3(ctx: &mut Ctx, a: u32) -> Result<u32, String> {
+    debug!("emscripten::___cxa_find_matching_catch_3");
+    find_matching_catch(ctx, &[a])
+}
+
+/// emscripten: ___cxa_find_matching_catch_4
+pub fn ___cxa_find_matching_catch_4(ctx: &mut Ctx, a: u32, b: u32) -> Result<u32, String> {
+    debug!("emscripten::___cxa_find_matching_catch_4");
+    find_matching_catch(ctx, &[a, b])
+}
+
+/// emscripten: ___cxa_find_matching_catch_5
+pub fn ___cxa_find_matching_catch_5(ctx: &mut Ctx, a: u32, b: u32, c: u32) -> Result<u32, String> {
+    debug!("emscripten::___cxa_find_matching_catch_5");
+    find_matching_catch(ctx, &[a, b, c])
+}
+
+/// emscripten: ___cxa_begin_catch
+pub fn ___cxa_begin_catch(ctx: &mut Ctx, ptr: u32) -> u32 {
     debug!("emscripten::___cxa_begin_catch");
-    -1
+    get_emscripten_data(ctx).exceptions.begin_catch(ptr);
+    ptr
 }
 
-pub fn ___cxa_end_catch(_ctx: &mut Ctx) {
+/// emscripten: ___cxa_end_catch
+pub fn ___cxa_end_catch(ctx: &mut Ctx) -> Result<(), String> {
     debug!("emscripten::___cxa_end_catch");
+    if let Some(set_threw) = &get_emscripten_data(ctx).set_threw {
+        set_threw.call(0, 0).map_err(|e| e.to_string())?;
+    }
+    match get_emscripten_data(ctx).exceptions.end_catch() {
+        Some((ptr, info)) => destroy(ctx, ptr, info),
+        None => Ok(()),
+    }
 }
 
-pub fn ___cxa_uncaught_exception(_ctx: &mut Ctx) -> i32 {
+/// emscripten: ___cxa_uncaught_exception
+pub fn ___cxa_uncaught_exception(ctx: &mut Ctx) -> i32 {
     debug!("emscripten::___cxa_uncaught_exception");
-    -1
+    (get_emscripten_data(ctx).exceptions.uncaught > 0) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Exceptions;
+    use crate::utils::tests::get_compiler;
+    use crate::{
+        generate_emscripten_env, invoke_emscripten_function_with_config, EmscriptenConfig,
+        EmscriptenGlobals,
+    };
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{compile_with, types::Value};
+
+    const THROWN: u32 = 1000;
+    const BASE: u32 = 1008;
+
+    #[test]
+    fn adjusted_pointers_lead_back_to_the_exception() {
+        let mut exceptions = Exceptions::default();
+        exceptions.throw(THROWN, 1, 0);
+        exceptions
+            .infos
+            .get_mut(&THROWN)
+            .unwrap()
+            .adjusted
+            .push(BASE);
+        assert_eq!(exceptions.de_adjust(THROWN), THROWN);
+        assert_eq!(exceptions.de_adjust(BASE), THROWN);
+        assert_eq!(exceptions.de_adjust(2000), 2000);
+        assert_eq!(exceptions.de_adjust(0), 0);
+    }
+
+    #[test]
+    fn exceptions_live_until_the_last_catch_ends() {
+        let mut exceptions = Exceptions::default();
+        exceptions.throw(THROWN, 1, 0);
+        exceptions
+            .infos
+            .get_mut(&THROWN)
+            .unwrap()
+            .adjusted
+            .push(BASE);
+        assert_eq!(exceptions.uncaught, 1);
+
+        // Caught once through a base class, then again in a nested handler.
+        exceptions.begin_catch(BASE);
+        exceptions.begin_catch(THROWN);
+        assert_eq!(exceptions.uncaught, 0);
+        assert_eq!(exceptions.infos[&THROWN].refcount, 2);
+
+        assert!(exceptions.end_catch().is_none());
+        assert_eq!(exceptions.infos[&THROWN].refcount, 1);
+        let (ptr, info) = exceptions.end_catch().unwrap();
+        assert_eq!(ptr, THROWN);
+        assert_eq!(info.refcount, 0);
+        assert!(exceptions.infos.is_empty());
+        assert!(exceptions.end_catch().is_none());
+    }
+
+    #[test]
+    fn rethrown_exceptions_outlive_their_catch() {
+        let mut exceptions = Exceptions::default();
+        assert!(exceptions.rethrow().is_err());
+
+        exceptions.throw(THROWN, 1, 0);
+        exceptions.begin_catch(THROWN);
+        exceptions.last = 0;
+        exceptions.rethrow().unwrap();
+        assert_eq!(exceptions.last, THROWN);
+        assert!(exceptions.infos[&THROWN].rethrown);
+
+        // The rethrowing handler's end doesn't destroy the exception.
+        assert!(exceptions.end_catch().is_none());
+        assert!(exceptions.infos.contains_key(&THROWN));
+
+        // The handler that catches it again does.
+        exceptions.begin_catch(THROWN);
+        assert!(!exceptions.infos[&THROWN].rethrown);
+        assert_eq!(exceptions.end_catch().map(|(ptr, _)| ptr), Some(THROWN));
+        assert!(exceptions.infos.is_empty());
+    }
+
+    #[test]
+    fn exceptions_are_caught_through_invoke() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/exceptions.wast");
+        let wasm_binary = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        let module =
+            compile_with(&wasm_binary[..], &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        let mut call = |name, params: &[Value]| {
+            invoke_emscripten_function_with_config(
+                &mut instance,
+                name,
+                params,
+                EmscriptenConfig::default(),
+            )
+            .unwrap()
+        };
+        // Caught, and freed once the catch ends.
+        assert_eq!(
+            call("_catch", &[Value::I32(42), Value::I32(100)]),
+            vec![Value::I32(42)]
+        );
+        assert_eq!(call("_frees", &[]), vec![Value::I32(1)]);
+        // A clause for another type doesn't take it.
+        assert_eq!(
+            call("_catch", &[Value::I32(42), Value::I32(200)]),
+            vec![Value::I32(-2)]
+        );
+        assert_eq!(call("_frees", &[]), vec![Value::I32(1)]);
+    }
 }
@@ -1,53 +1,120 @@
+//! The `exec` family.
+//!
+//! The program replaces the whole host process, instance and all, like it
+//! would replace a native one, and gets the guest's stdio as its own. Only
+//! the programs the embedder allows with `Commands` can be run; for the
+//! others, these fail. Hosts other than Unix can't replace a process, so
+//! there the program runs as a child instead, and its exit status becomes
+//! that of the whole process.
+
+use crate::process;
+use crate::utils::read_string_from_wasm;
 use crate::varargs::VarArgs;
-use libc::execvp as libc_execvp;
-use std::cell::Cell;
-use std::ffi::CString;
+#[cfg(not(unix))]
+use std::process::exit;
+use std::process::Command;
 use wasmer_runtime_core::vm::Ctx;
 
+/// The strings in the null-terminated array of pointers at `ptr`.
+fn read_strings(ctx: &mut Ctx, ptr: u32) -> Vec<String> {
+    let mut strings = vec![];
+    if ptr == 0 {
+        return strings;
+    }
+    let mut index = ptr as usize / 4;
+    loop {
+        let string = ctx.memory(0).view::<u32>()[index].get();
+        if string == 0 {
+            return strings;
+        }
+        strings.push(read_string_from_wasm(ctx.memory(0), string));
+        index += 1;
+    }
+}
+
+/// The strings passed as varargs up to a null pointer.
+fn read_vararg_strings(ctx: &mut Ctx, first: u32, varargs: &mut VarArgs) -> Vec<String> {
+    let mut strings = vec![];
+    let mut string = first;
+    while string != 0 {
+        strings.push(read_string_from_wasm(ctx.memory(0), string));
+        string = varargs.get(ctx);
+    }
+    strings
+}
+
+/// Replace the process with `path`. Only returns, with -1, if it can't
+/// be run. `env` replaces the environment, if given.
+fn exec(ctx: &mut Ctx, path: u32, argv: Vec<String>, env: Option<Vec<String>>) -> i32 {
+    let program = read_string_from_wasm(ctx.memory(0), path);
+    let args = argv.get(1..).unwrap_or_default();
+    let mut command = match process::command(ctx, &program, args) {
+        Ok(command) => command,
+        Err(_) => return -1,
+    };
+    if let Some(env) = env {
+        command.env_clear();
+        for variable in env {
+            let mut parts = variable.splitn(2, '=');
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                command.env(name, value);
+            }
+        }
+    }
+    replace_with(command)
+}
+
+#[cfg(unix)]
+fn replace_with(mut command: Command) -> i32 {
+    use std::os::unix::process::CommandExt;
+    // Only returns if it fails.
+    let _ = command.exec();
+    -1
+}
+
+#[cfg(not(unix))]
+fn replace_with(mut command: Command) -> i32 {
+    match command.status() {
+        Ok(status) => exit(status.code().unwrap_or(1)),
+        Err(_) => -1,
+    }
+}
+
+/// emscripten: execvp(file: *const c_char, argv: *const *const c_char) -> c_int
 pub fn execvp(ctx: &mut Ctx, command_name_offset: u32, argv_offset: u32) -> i32 {
-    // a single reference to re-use
-    let emscripten_memory = ctx.memory(0);
-
-    // read command name as string
-    let command_name_string_vec: Vec<u8> = emscripten_memory.view()
-        [(command_name_offset as usize)..]
-        .iter()
-        .map(|cell| cell.get())
-        .take_while(|&byte| byte != 0)
-        .collect();
-    let command_name_string = CString::new(command_name_string_vec).unwrap();
-
-    // get the array of args
-    let mut argv: Vec<*const i8> = emscripten_memory.view()[((argv_offset / 4) as usize)..]
-        .iter()
-        .map(|cell: &Cell<u32>| cell.get())
-        .take_while(|&byte| byte != 0)
-        .map(|offset| {
-            let p: *const i8 = (emscripten_memory.view::<u8>()[(offset as usize)..])
-                .iter()
-                .map(|cell| cell.as_ptr() as *const i8)
-                .collect::<Vec<*const i8>>()[0];
-            p
-        })
-        .collect();
-
-    // push a nullptr on to the end of the args array
-    argv.push(std::ptr::null());
-
-    // construct raw pointers and hand them to `execvp`
-    let command_pointer = command_name_string.as_ptr() as *const i8;
-    let args_pointer = argv.as_ptr();
-    unsafe { libc_execvp(command_pointer, args_pointer) }
+    debug!("emscripten::execvp");
+    let argv = read_strings(ctx, argv_offset);
+    exec(ctx, command_name_offset, argv, None)
+}
+
+/// emscripten: execv(path: *const c_char, argv: *const *const c_char) -> c_int
+pub fn execv(ctx: &mut Ctx, path_ptr: u32, argv_offset: u32) -> i32 {
+    debug!("emscripten::execv");
+    let argv = read_strings(ctx, argv_offset);
+    exec(ctx, path_ptr, argv, None)
+}
+
+/// emscripten: execve(path: *const c_char, argv: *const *const c_char, envp: *const *const c_char) -> c_int
+pub fn execve(ctx: &mut Ctx, path_ptr: u32, argv_offset: u32, envp_offset: u32) -> i32 {
+    debug!("emscripten::execve");
+    let argv = read_strings(ctx, argv_offset);
+    let env = read_strings(ctx, envp_offset);
+    exec(ctx, path_ptr, argv, Some(env))
 }
 
 /// execl
-pub fn execl(_ctx: &mut Ctx, _path_ptr: i32, _arg0_ptr: i32, _varargs: VarArgs) -> i32 {
+pub fn execl(ctx: &mut Ctx, path_ptr: u32, arg0_ptr: u32, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::execl");
-    -1
+    let argv = read_vararg_strings(ctx, arg0_ptr, &mut varargs);
+    exec(ctx, path_ptr, argv, None)
 }
 
 /// execle
-pub fn execle(_ctx: &mut Ctx, _path_ptr: i32, _arg0_ptr: i32, _varargs: VarArgs) -> i32 {
+pub fn execle(ctx: &mut Ctx, path_ptr: u32, arg0_ptr: u32, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::execle");
-    -1
+    let argv = read_vararg_strings(ctx, arg0_ptr, &mut varargs);
+    // The environment follows the null pointer ending the arguments.
+    let envp_offset: u32 = varargs.get(ctx);
+    let env = read_strings(ctx, envp_offset);
+    exec(ctx, path_ptr, argv, Some(env))
 }
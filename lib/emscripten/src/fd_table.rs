@@ -0,0 +1,188 @@
+//! The file descriptors emscripten modules see.
+//!
+//! Guests get their own numbers for the files, pipes and sockets they
+//! open, each leading to a host fd the table owns. They can't reach host
+//! fds they weren't given, and closing one of theirs, even stdout, only
+//! closes their own copy. Host programs the guest runs only get its stdio.
+
+use crate::env::get_emscripten_data;
+use libc::{c_int, close, dup, EBADF, EMFILE};
+use std::collections::BTreeMap;
+use wasmer_runtime_core::vm::Ctx;
+
+/// One more than the highest guest fd, the `FD_SETSIZE` of emscripten's libc.
+pub const MAX_FDS: c_int = 1024;
+
+/// Guest fds and the host fds they lead to.
+#[derive(Debug)]
+pub struct FdTable {
+    fds: BTreeMap<c_int, c_int>,
+}
+
+impl FdTable {
+    /// A table where guest fds 0, 1 and 2 lead to copies of
+    /// the host fds in `stdio`. The originals stay the caller's.
+    pub fn new(stdio: [c_int; 3]) -> Self {
+        let mut table = Self {
+            fds: BTreeMap::new(),
+        };
+        for (guest, &host) in stdio.iter().enumerate() {
+            let host = unsafe { dup(host) };
+            // The host may well run without a stdin.
+            if host >= 0 {
+                set_cloexec(host);
+                table.fds.insert(guest as c_int, host);
+            }
+        }
+        table
+    }
+
+    /// The host fd that guest fd `guest` leads to.
+    pub fn get(&self, guest: c_int) -> Option<c_int> {
+        self.fds.get(&guest).cloned()
+    }
+
+    /// Give `host` to the guest, under the lowest free number like `open`
+    /// does. Fails if the guest has run out of fds; `host` is closed then.
+    pub fn insert(&mut self, host: c_int) -> Option<c_int> {
+        match (0..MAX_FDS).find(|guest| !self.fds.contains_key(guest)) {
+            Some(guest) => {
+                set_cloexec(host);
+                self.fds.insert(guest, host);
+                Some(guest)
+            }
+            None => {
+                unsafe { close(host) };
+                None
+            }
+        }
+    }
+
+    /// Make guest fd `guest` lead to `host`, like `dup2` does,
+    /// closing whatever it led to before.
+    pub fn insert_at(&mut self, guest: c_int, host: c_int) {
+        set_cloexec(host);
+        if let Some(old) = self.fds.insert(guest, host) {
+            unsafe { close(old) };
+        }
+    }
+
+    /// Close guest fd `guest`, with what `close` returned on the host.
+    pub fn close(&mut self, guest: c_int) -> Option<c_int> {
+        self.fds.remove(&guest).map(|host| unsafe { close(host) })
+    }
+}
+
+impl Drop for FdTable {
+    fn drop(&mut self) {
+        for &host in self.fds.values() {
+            unsafe { close(host) };
+        }
+    }
+}
+
+/// Keep `host` from the host programs the guest runs.
+#[cfg(unix)]
+fn set_cloexec(host: c_int) {
+    unsafe { libc::fcntl(host, libc::F_SETFD, libc::FD_CLOEXEC) };
+}
+
+#[cfg(not(unix))]
+fn set_cloexec(_host: c_int) {}
+
+/// The host fd that guest fd `fd` leads to, or `-EBADF`
+/// to return to the guest if there's none.
+pub(crate) fn host_fd(ctx: &mut Ctx, fd: c_int) -> Result<c_int, c_int> {
+    get_emscripten_data(ctx).fds.get(fd).ok_or(-EBADF)
+}
+
+/// Give the guest the host fd a syscall just returned.
+/// Errors are passed through as they are.
+pub(crate) fn guest_fd(ctx: &mut Ctx, host: c_int) -> c_int {
+    if host < 0 {
+        return host;
+    }
+    get_emscripten_data(ctx).fds.insert(host).unwrap_or(-EMFILE)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::FdTable;
+    use libc::{c_int, c_void};
+
+    /// A pipe whose read end doesn't block.
+    fn pipe() -> (c_int, c_int) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { libc::fcntl(fds[0], libc::F_SETFL, libc::O_NONBLOCK) };
+        (fds[0], fds[1])
+    }
+
+    /// Whether every copy of the write end of the pipe `read` is
+    /// the read end of has been closed.
+    fn write_end_closed(read: c_int) -> bool {
+        let mut byte = 0u8;
+        unsafe { libc::read(read, &mut byte as *mut u8 as *mut c_void, 1) == 0 }
+    }
+
+    #[test]
+    fn fds_are_allocated_lowest_first() {
+        let mut table = FdTable::new([-1, -1, -1]);
+        let (read, write) = pipe();
+        assert_eq!(table.insert(read), Some(0));
+        assert_eq!(table.insert(write), Some(1));
+        assert_eq!(table.insert(unsafe { libc::dup(write) }), Some(2));
+
+        assert_eq!(table.close(0), Some(0));
+        assert_eq!(table.get(0), None);
+        assert_eq!(table.insert(unsafe { libc::dup(write) }), Some(0));
+        assert_eq!(table.insert(unsafe { libc::dup(write) }), Some(3));
+        assert_eq!(table.close(7), None);
+    }
+
+    #[test]
+    fn insert_at_closes_what_the_fd_led_to() {
+        let mut table = FdTable::new([-1, -1, -1]);
+        let (read, write) = pipe();
+        let (other_read, other_write) = pipe();
+        table.insert_at(5, write);
+        assert!(!write_end_closed(read));
+
+        table.insert_at(5, other_write);
+        assert!(write_end_closed(read));
+        assert_eq!(table.get(5), Some(other_write));
+
+        drop(table);
+        assert!(write_end_closed(other_read));
+        unsafe {
+            libc::close(read);
+            libc::close(other_read);
+        }
+    }
+
+    #[test]
+    fn stdio_is_duplicated() {
+        let (read, write) = pipe();
+        let table = FdTable::new([-1, write, write]);
+        assert_eq!(table.get(0), None);
+        let stdout = table.get(1).unwrap();
+        let stderr = table.get(2).unwrap();
+        assert!(stdout != write && stderr != write && stdout != stderr);
+
+        // The table's copies keep the pipe open after the caller's is closed.
+        unsafe { libc::close(write) };
+        assert!(!write_end_closed(read));
+        drop(table);
+        assert!(write_end_closed(read));
+        unsafe { libc::close(read) };
+    }
+
+    #[test]
+    fn closing_stdout_keeps_the_hosts_open() {
+        let stdout = libc::STDOUT_FILENO;
+        let mut table = FdTable::new([libc::STDIN_FILENO, stdout, libc::STDERR_FILENO]);
+        assert_eq!(table.close(1), Some(0));
+        assert_eq!(table.get(1), None);
+        assert!(unsafe { libc::fcntl(stdout, libc::F_GETFD) } != -1);
+    }
+}
@@ -0,0 +1,241 @@
+//! The filesystem emscripten modules see.
+//!
+//! Guests don't reach the host filesystem directly: their paths go
+//! through a table of mounts, each making a host directory (or file)
+//! available at some guest path. Paths that aren't under any mount
+//! don't exist, and paths that would lead out of a mount, with `..`
+//! or through a symlink, are refused.
+//!
+//! Symlinks are checked when a path is resolved, and the host path is
+//! opened afterwards, so this doesn't hold against other processes
+//! changing the mounted directories in between: a symlink one of them
+//! swaps in is followed wherever it leads. Only mount directories
+//! nothing else writes to.
+
+use crate::env::get_emscripten_data;
+use libc::{c_char, c_int, EACCES, EINVAL, ENOENT, ENOTDIR};
+use std::ffi::{CStr, CString};
+use std::io;
+use std::path::{Path, PathBuf};
+use wasmer_runtime_core::vm::Ctx;
+
+/// Devices that are always available, since so much of libc expects them.
+#[cfg(unix)]
+const DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+#[cfg(not(unix))]
+const DEVICES: &[&str] = &[];
+
+/// Where the paths of a guest lead on the host.
+#[derive(Clone, Debug)]
+pub struct Mounts {
+    /// Guest paths and the canonical host paths they stand for.
+    mounts: Vec<(String, PathBuf)>,
+    /// The working directory of the guest, as a normalized guest path.
+    cwd: String,
+    /// Whether guest paths are used on the host as is.
+    unrestricted: bool,
+}
+
+impl Mounts {
+    /// Nothing but the usual devices, like `/dev/null` and `/dev/urandom`.
+    pub fn new() -> Self {
+        let mut mounts = Self {
+            mounts: vec![],
+            cwd: "/".to_string(),
+            unrestricted: false,
+        };
+        for device in DEVICES {
+            // Not every host has all of them.
+            let _ = mounts.mount(device, device);
+        }
+        mounts
+    }
+
+    /// The whole host filesystem, with guest paths used as is.
+    /// Modules are not confined at all.
+    pub fn host() -> Self {
+        Self {
+            unrestricted: true,
+            ..Self::new()
+        }
+    }
+
+    /// Make `host` available to the guest at the absolute path `guest`.
+    /// A later mount shadows what earlier ones have at the same path.
+    pub fn mount<P: AsRef<Path>>(&mut self, guest: &str, host: P) -> io::Result<()> {
+        if !guest.starts_with('/') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not an absolute path", guest),
+            ));
+        }
+        let host = host.as_ref().canonicalize()?;
+        let guest = normalize("/", guest);
+        self.mounts.retain(|(path, _)| *path != guest);
+        self.mounts.push((guest, host));
+        Ok(())
+    }
+
+    /// The host path that `guest` leads to, or the errno
+    /// to fail with if there's none the guest may use.
+    pub fn resolve(&self, guest: &str) -> Result<PathBuf, c_int> {
+        if self.unrestricted {
+            return Ok(PathBuf::from(guest));
+        }
+
+        let guest = normalize(&self.cwd, guest);
+        let (mount, root) = self
+            .mounts
+            .iter()
+            .filter(|(mount, _)| is_under(&guest, mount))
+            .max_by_key(|(mount, _)| mount.len())
+            .ok_or(ENOENT)?;
+
+        let rest = guest[mount.len()..].trim_start_matches('/');
+        let host = if rest.is_empty() {
+            root.clone()
+        } else {
+            root.join(rest)
+        };
+        if stays_within(root, &host) {
+            Ok(host)
+        } else {
+            Err(EACCES)
+        }
+    }
+
+    /// The working directory of the guest, unless it's the host's.
+    pub fn cwd(&self) -> Option<&str> {
+        if self.unrestricted {
+            None
+        } else {
+            Some(&self.cwd)
+        }
+    }
+
+    /// Change the working directory of a confined guest.
+    pub(crate) fn chdir(&mut self, guest: &str) -> Result<(), c_int> {
+        if !self.resolve(guest)?.is_dir() {
+            return Err(ENOTDIR);
+        }
+        self.cwd = normalize(&self.cwd, guest);
+        Ok(())
+    }
+}
+
+/// The current directory of the host, at `/`.
+impl Default for Mounts {
+    fn default() -> Self {
+        let mut mounts = Self::new();
+        if let Ok(cwd) = std::env::current_dir() {
+            let _ = mounts.mount("/", cwd);
+        }
+        mounts
+    }
+}
+
+/// Make `path` absolute, relative to `cwd`, and get rid of `.` and `..`.
+/// Like on a real root directory, `/..` is `/`.
+fn normalize(cwd: &str, path: &str) -> String {
+    let mut components: Vec<&str> = vec![];
+    let base = if path.starts_with('/') { "" } else { cwd };
+    for component in base.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    format!("/{}", components.join("/"))
+}
+
+fn is_under(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path == dir
+        || (path.starts_with(dir) && path.as_bytes().get(dir.len()) == Some(&b'/'))
+}
+
+/// Whether `path`, with symlinks followed, is still under `root`.
+///
+/// Only the part of `path` that exists can be checked; the rest will be
+/// created under it. A dangling symlink could lead anywhere, so it's refused.
+/// The answer only holds until the directories change: see the module docs.
+fn stays_within(root: &Path, path: &Path) -> bool {
+    for ancestor in path.ancestors() {
+        if ancestor.symlink_metadata().is_ok() {
+            return match ancestor.canonicalize() {
+                Ok(real) => real.starts_with(root),
+                Err(_) => false,
+            };
+        }
+    }
+    false
+}
+
+/// Read the guest path at `path_ptr` and find the host path it leads to.
+/// Errors are negated errnos, ready to be returned to the guest.
+pub(crate) fn host_path(ctx: &mut Ctx, path_ptr: u32) -> Result<CString, c_int> {
+    let guest = unsafe {
+        let addr = emscripten_memory_pointer!(ctx.memory(0), path_ptr) as *const c_char;
+        CStr::from_ptr(addr)
+    };
+    let guest = guest.to_str().map_err(|_| -EINVAL)?;
+    let host = get_emscripten_data(ctx)
+        .mounts
+        .resolve(guest)
+        .map_err(|errno| -errno)?;
+    debug!("=> guest path: {}, host path: {:?}", guest, host);
+    host.to_str()
+        .and_then(|host| CString::new(host).ok())
+        .ok_or(-EINVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, Mounts};
+    use libc::{EACCES, ENOENT};
+    use std::fs;
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize("/", "a/./b/../c"), "/a/c");
+        assert_eq!(normalize("/x/y", "../z"), "/x/z");
+        assert_eq!(normalize("/x", "/../../etc/passwd"), "/etc/passwd");
+        assert_eq!(normalize("/", ""), "/");
+    }
+
+    #[test]
+    fn paths_resolve_under_their_mount() {
+        let dir = std::env::temp_dir().join(format!("wasmer-mounts-{}", std::process::id()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+        let root = dir.canonicalize().unwrap();
+
+        let mut mounts = Mounts::new();
+        mounts.mount("/app", &dir).unwrap();
+        mounts.mount("/app/cache", dir.join("data")).unwrap();
+
+        assert_eq!(mounts.resolve("/app/file"), Ok(root.join("file")));
+        assert_eq!(mounts.resolve("/app/cache/x"), Ok(root.join("data/x")));
+        assert_eq!(mounts.resolve("/app/../app/y"), Ok(root.join("y")));
+        // `..` can't climb out of the guest's root, let alone a mount.
+        assert_eq!(mounts.resolve("/app/../../etc/passwd"), Err(ENOENT));
+        assert_eq!(mounts.resolve("/application"), Err(ENOENT));
+        assert_eq!(mounts.resolve("relative"), Err(ENOENT));
+
+        mounts.chdir("/app/cache").unwrap();
+        assert_eq!(mounts.cwd(), Some("/app/cache"));
+        assert_eq!(mounts.resolve("../z"), Ok(root.join("z")));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc", dir.join("escape")).unwrap();
+            assert_eq!(mounts.resolve("/app/escape/passwd"), Err(EACCES));
+        }
+        #[cfg(not(unix))]
+        let _ = EACCES;
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -10,8 +10,19 @@ pub use self::unix::*;
 #[cfg(windows)]
 pub use self::windows::*;
 
+use crate::env::get_emscripten_data;
+use libc::c_void;
 use wasmer_runtime_core::vm::Ctx;
 
+/// putchar
+pub fn putchar(ctx: &mut Ctx, chr: i32) {
+    // Straight to the guest's stdout, wherever that leads.
+    if let Some(fd) = get_emscripten_data(ctx).fds.get(1) {
+        let byte = chr as u8;
+        unsafe { libc::write(fd, &byte as *const u8 as *const c_void, 1) };
+    }
+}
+
 /// getprotobyname
 pub fn getprotobyname(_ctx: &mut Ctx, _name_ptr: i32) -> i32 {
     debug!("emscripten::getprotobyname");
@@ -50,12 +61,6 @@ pub fn sigfillset(ctx: &mut Ctx, set: i32) -> i32 {
     0
 }
 
-/// tzset
-pub fn tzset(_ctx: &mut Ctx) {
-    debug!("emscripten::tzset - stub");
-    //unimplemented!()
-}
-
 /// strptime
 pub fn strptime(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
     debug!("emscripten::strptime");
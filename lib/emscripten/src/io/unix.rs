@@ -1,12 +1,8 @@
-use libc::{chroot as _chroot, printf as _printf};
+use libc::{chroot as _chroot, printf as _printf, EPERM};
 
+use crate::env::get_emscripten_data;
 use wasmer_runtime_core::vm::Ctx;
 
-/// putchar
-pub fn putchar(_ctx: &mut Ctx, chr: i32) {
-    unsafe { libc::putchar(chr) };
-}
-
 /// printf
 pub fn printf(ctx: &mut Ctx, memory_offset: i32, extra: i32) -> i32 {
     debug!("emscripten::printf {}, {}", memory_offset, extra);
@@ -19,6 +15,10 @@ pub fn printf(ctx: &mut Ctx, memory_offset: i32, extra: i32) -> i32 {
 /// chroot
 pub fn chroot(ctx: &mut Ctx, name_ptr: i32) -> i32 {
     debug!("emscripten::chroot");
+    if get_emscripten_data(ctx).mounts.cwd().is_some() {
+        // That would change the root of the whole process, not just the guest's.
+        return -EPERM;
+    }
     let name = emscripten_memory_pointer!(ctx.memory(0), name_ptr) as *const i8;
     unsafe { _chroot(name) }
 }
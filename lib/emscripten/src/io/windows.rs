@@ -13,11 +13,6 @@ use wasmer_runtime_core::vm::Ctx;
 //    pub fn _printf(s: *const c_char, ...) -> c_int;
 //}
 
-/// putchar
-pub fn putchar(_ctx: &mut Ctx, chr: i32) {
-    unsafe { libc::putchar(chr) };
-}
-
 /// printf
 pub fn printf(_ctx: &mut Ctx, memory_offset: i32, extra: i32) -> i32 {
     debug!("emscripten::printf {}, {}", memory_offset, extra);
@@ -1,61 +1,74 @@
 use super::env::get_emscripten_data;
-use super::process::abort_with_message;
+use crate::Unwind;
 use libc::c_int;
-// use std::cell::UnsafeCell;
 use wasmer_runtime_core::vm::Ctx;
 
+// Emscripten lowers `setjmp` to `_saveSetjmp`, which the module itself
+// provides, and makes every call that could `longjmp` through an `invoke_*`
+// import. `longjmp` sets `__THREW__` and traps; the nearest `invoke_*`
+// catches it and returns, and the guest looks for the matching `setjmp`
+// with `_testSetjmp`, jumping again from there if it's not in this frame.
+
 /// setjmp
-pub fn __setjmp(ctx: &mut Ctx, _env_addr: u32) -> c_int {
+// Only reached when setjmp wasn't lowered, e.g. when it's called through
+// a pointer. It can't return a second time; a longjmp to it unwinds the
+// whole module instead.
+pub fn __setjmp(_ctx: &mut Ctx, _env_addr: u32) -> c_int {
     debug!("emscripten::__setjmp (setjmp)");
-    abort_with_message(ctx, "missing function: _longjmp");
-    unreachable!()
-    // unsafe {
-    //     // Rather than using the env as the holder of the jump buffer pointer,
-    //     // we use the environment address to store the index relative to jumps
-    //     // so the address of the jump it's outside the wasm memory itself.
-    //     let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-    //     // We create the jump buffer outside of the wasm memory
-    //     let jump_buf: UnsafeCell<[u32; 27]> = UnsafeCell::new([0; 27]);
-    //     let jumps = &mut get_emscripten_data(ctx).jumps;
-    //     let result = setjmp(jump_buf.get() as _);
-    //     // We set the jump index to be the last 3value of jumps
-    //     *jump_index = jumps.len() as _;
-    //     // We hold the reference of the jump buffer
-    //     jumps.push(jump_buf);
-    //     result
-    // }
+    0
 }
 
 /// longjmp
-#[allow(unreachable_code)]
-pub fn __longjmp(ctx: &mut Ctx, _env_addr: u32, _val: c_int) {
+pub fn __longjmp(ctx: &mut Ctx, env_addr: u32, val: c_int) -> Result<(), &'static str> {
     debug!("emscripten::__longjmp (longmp)");
-    abort_with_message(ctx, "missing function: _longjmp");
-    // unsafe {
-    //     // We retrieve the jump index from the env address
-    //     let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-    //     let jumps = &mut get_emscripten_data(ctx).jumps;
-    //     // We get the real jump buffer from the jumps vector, using the retrieved index
-    //     let jump_buf = &jumps[*jump_index as usize];
-    //     longjmp(jump_buf.get() as _, val)
-    // };
+    _longjmp(ctx, env_addr as i32, val)
 }
 
 /// _longjmp
-// This function differs from the js implementation, it should return Result<(), &'static str>
-pub fn _longjmp(ctx: &mut Ctx, env_addr: i32, val: c_int) -> Result<(), ()> {
+pub fn _longjmp(ctx: &mut Ctx, env_addr: i32, val: c_int) -> Result<(), &'static str> {
+    debug!("emscripten::_longjmp");
     let val = if val == 0 { 1 } else { val };
-    get_emscripten_data(ctx)
-        .set_threw
+    let data = get_emscripten_data(ctx);
+    data.set_threw
         .as_ref()
-        .expect("set_threw is None")
+        .ok_or("longjmp: the module doesn't export _setThrew")?
         .call(env_addr, val)
-        .expect("set_threw failed to call");
-    // TODO: return Err("longjmp")
-    Err(())
+        .map_err(|_| "longjmp: _setThrew failed")?;
+    data.unwinding = Some(Unwind::Longjmp);
+    Err("longjmp")
 }
 
-// extern "C" {
-//     fn setjmp(env: *mut c_void) -> c_int;
-//     fn longjmp(env: *mut c_void, val: c_int) -> !;
-// }
+#[cfg(test)]
+mod tests {
+    use crate::utils::tests::get_compiler;
+    use crate::{
+        generate_emscripten_env, invoke_emscripten_function_with_config, EmscriptenConfig,
+        EmscriptenGlobals,
+    };
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{compile_with, types::Value};
+
+    #[test]
+    fn longjmps_unwind_to_the_nearest_invoke() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/longjmp.wast");
+        let wasm_binary = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        let module =
+            compile_with(&wasm_binary[..], &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        let mut jump = |val| {
+            invoke_emscripten_function_with_config(
+                &mut instance,
+                "_jump",
+                &[Value::I32(val)],
+                EmscriptenConfig::default(),
+            )
+            .unwrap()
+        };
+        assert_eq!(jump(7), vec![Value::I32(7)]);
+        // `setjmp` never returns 0 the second time.
+        assert_eq!(jump(0), vec![Value::I32(1)]);
+    }
+}
@@ -2,9 +2,12 @@
 extern crate wasmer_runtime_core;
 
 use lazy_static::lazy_static;
-use std::cell::UnsafeCell;
-use std::{f64, ffi::c_void};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::{f64, ffi::c_void, fmt, os::raw::c_int};
+use wasmer_clif_backend::CraneliftCompiler;
 use wasmer_runtime_core::{
+    backend::Compiler,
     error::CallResult,
     export::Export,
     func,
@@ -34,12 +37,16 @@ mod errno;
 mod exception;
 mod exec;
 mod exit;
+mod fd_table;
+mod fs;
 mod io;
 mod jmp;
 mod linking;
 mod lock;
 mod math;
 mod memory;
+#[cfg(unix)]
+mod net;
 mod process;
 mod signal;
 mod storage;
@@ -48,10 +55,19 @@ mod time;
 mod utils;
 mod varargs;
 
+use self::exception::Exceptions;
+pub use self::fd_table::FdTable;
+pub use self::fs::Mounts;
+use self::linking::Libraries;
+use self::process::Children;
+pub use self::process::Commands;
+use self::signal::Signals;
 pub use self::storage::{align_memory, static_alloc};
+pub use self::time::Clock;
+use self::time::Clocks;
 pub use self::utils::{
     allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size,
-    get_emscripten_table_size, is_emscripten_module,
+    get_emscripten_table_size, is_emscripten_import, is_emscripten_module,
 };
 
 // TODO: Magic number - how is this calculated?
@@ -77,7 +93,6 @@ pub struct EmscriptenData<'a> {
     pub memalign: Option<Func<'a, (u32, u32), u32>>,
     pub memset: Func<'a, (u32, u32, u32), u32>,
     pub stack_alloc: Func<'a, u32, u32>,
-    pub jumps: Vec<UnsafeCell<[u32; 27]>>,
 
     pub dyn_call_i: Option<Func<'a, i32, i32>>,
     pub dyn_call_ii: Option<Func<'a, (i32, i32), i32>>,
@@ -136,14 +151,48 @@ pub struct EmscriptenData<'a> {
     pub dyn_call_viidddddddd:
         Option<Func<'a, (i32, i32, i32, f64, f64, f64, f64, f64, f64, f64, f64)>>,
     pub temp_ret_0: i32,
+    pub(crate) pthread_specific: BTreeMap<i32, i32>,
 
     pub stack_save: Option<Func<'a, (), i32>>,
     pub stack_restore: Option<Func<'a, (i32)>>,
     pub set_threw: Option<Func<'a, (i32, i32)>>,
+    pub cxa_can_catch: Option<Func<'a, (u32, u32, u32), i32>>,
+    pub fdopen: Option<Func<'a, (i32, u32), u32>>,
+    pub fclose: Option<Func<'a, u32, i32>>,
+    pub get_timezone: Option<Func<'a, (), u32>>,
+    pub get_daylight: Option<Func<'a, (), u32>>,
+    pub get_tzname: Option<Func<'a, (), u32>>,
+    pub errno_location: Option<Func<'a, (), u32>>,
+    pub(crate) unwinding: Option<Unwind>,
+    pub(crate) exceptions: Exceptions,
+    pub(crate) imports: ImportObject,
+    pub(crate) main_exports: Vec<(String, Export)>,
+    pub(crate) libraries: Libraries,
+    pub(crate) signals: Signals,
+    pub(crate) children: Children,
+    pub(crate) commands: Commands,
+    pub(crate) clocks: Clocks,
+
+    pub mounts: Mounts,
+    pub fds: FdTable,
+}
+
+/// Why an import trapped on purpose, to unwind the guest stack
+/// up to the `invoke_*` call that can handle it.
+#[derive(Debug)]
+pub(crate) enum Unwind {
+    /// A `longjmp`, with `__THREW__` already set for the guest to find its `setjmp`.
+    Longjmp,
+    /// A C++ exception, for the guest to find a catch clause for.
+    Exception,
 }
 
 impl<'a> EmscriptenData<'a> {
-    pub fn new(instance: &'a mut Instance) -> EmscriptenData<'a> {
+    pub fn new(instance: &'a mut Instance, config: EmscriptenConfig) -> EmscriptenData<'a> {
+        // What side modules get linked against.
+        let imports = instance.import_object().clone_ref();
+        let main_exports = instance.exports().collect();
+
         let malloc = instance.func("_malloc").unwrap();
         let free = instance.func("_free").unwrap();
         let memalign = if let Ok(func) = instance.func("_memalign") {
@@ -209,6 +258,13 @@ impl<'a> EmscriptenData<'a> {
         let stack_save = instance.func("stackSave").ok();
         let stack_restore = instance.func("stackRestore").ok();
         let set_threw = instance.func("_setThrew").ok();
+        let cxa_can_catch = instance.func("___cxa_can_catch").ok();
+        let fdopen = instance.func("_fdopen").ok();
+        let fclose = instance.func("_fclose").ok();
+        let get_timezone = instance.func("__get_timezone").ok();
+        let get_daylight = instance.func("__get_daylight").ok();
+        let get_tzname = instance.func("__get_tzname").ok();
+        let errno_location = instance.func("___errno_location").ok();
 
         EmscriptenData {
             malloc,
@@ -216,7 +272,6 @@ impl<'a> EmscriptenData<'a> {
             memalign,
             memset,
             stack_alloc,
-            jumps: Vec::new(),
             dyn_call_i,
             dyn_call_ii,
             dyn_call_iii,
@@ -269,33 +324,103 @@ impl<'a> EmscriptenData<'a> {
             dyn_call_viidii,
             dyn_call_viidddddddd,
             temp_ret_0: 0,
+            pthread_specific: BTreeMap::new(),
 
             stack_save,
             stack_restore,
             set_threw,
+            cxa_can_catch,
+            fdopen,
+            fclose,
+            get_timezone,
+            get_daylight,
+            get_tzname,
+            errno_location,
+            unwinding: None,
+            exceptions: Exceptions::default(),
+            imports,
+            main_exports,
+            libraries: Libraries::new(config.compiler),
+            signals: Signals::default(),
+            children: Children::default(),
+            commands: config.commands,
+            clocks: Clocks::new(config.clock),
+
+            mounts: config.mounts,
+            fds: FdTable::new([config.stdin, config.stdout, config.stderr]),
+        }
+    }
+}
+
+/// How to run an emscripten module.
+#[derive(Clone)]
+pub struct EmscriptenConfig {
+    /// The filesystem the module can reach. By default, that's only the
+    /// current directory, which the module sees as `/`.
+    pub mounts: Mounts,
+    /// The host fds the module gets as its stdin, stdout and stderr.
+    /// It gets copies, so these stay open whatever it does with them.
+    pub stdin: c_int,
+    pub stdout: c_int,
+    pub stderr: c_int,
+    /// The host programs the module may run. By default, none.
+    pub commands: Commands,
+    /// Where the module gets the time from. By default, the host.
+    pub clock: Clock,
+    /// What the side modules the module loads with `dlopen` are compiled
+    /// with. By default, Cranelift; it should be what compiled the module.
+    pub compiler: Arc<dyn Compiler>,
+}
+
+impl fmt::Debug for EmscriptenConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EmscriptenConfig")
+            .field("mounts", &self.mounts)
+            .field("stdin", &self.stdin)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .field("commands", &self.commands)
+            .field("clock", &self.clock)
+            .field("compiler", &self.compiler.backend())
+            .finish()
+    }
+}
+
+impl Default for EmscriptenConfig {
+    fn default() -> Self {
+        Self {
+            mounts: Mounts::default(),
+            stdin: 0,
+            stdout: 1,
+            stderr: 2,
+            commands: Commands::default(),
+            clock: Clock::default(),
+            compiler: Arc::new(CraneliftCompiler::new()),
         }
     }
 }
 
 pub fn run_emscripten_instance(
+    module: &Module,
+    instance: &mut Instance,
+    path: &str,
+    args: Vec<&str>,
+) -> CallResult<()> {
+    run_emscripten_instance_with_config(module, instance, path, args, EmscriptenConfig::default())
+}
+
+pub fn run_emscripten_instance_with_config(
     _module: &Module,
     instance: &mut Instance,
     path: &str,
     args: Vec<&str>,
+    config: EmscriptenConfig,
 ) -> CallResult<()> {
-    let mut data = EmscriptenData::new(instance);
+    let mut data = EmscriptenData::new(instance, config);
     let data_ptr = &mut data as *mut _ as *mut c_void;
     instance.context_mut().data = data_ptr;
 
-    // ATINIT
-    // (used by C++)
-    if let Ok(_func) = instance.dyn_func("globalCtors") {
-        instance.call("globalCtors", &[])?;
-    }
-
-    if let Ok(_func) = instance.dyn_func("___emscripten_environ_constructor") {
-        instance.call("___emscripten_environ_constructor", &[])?;
-    }
+    run_constructors(instance)?;
 
     // println!("running emscripten instance");
 
@@ -320,6 +445,37 @@ pub fn run_emscripten_instance(
     Ok(())
 }
 
+/// Call the exported function `name` of an emscripten instance instead of
+/// its `_main`, once the runtime is set up as `run_emscripten_instance`
+/// sets it up.
+pub fn invoke_emscripten_function_with_config(
+    instance: &mut Instance,
+    name: &str,
+    params: &[Value],
+    config: EmscriptenConfig,
+) -> CallResult<Vec<Value>> {
+    let mut data = EmscriptenData::new(instance, config);
+    let data_ptr = &mut data as *mut _ as *mut c_void;
+    instance.context_mut().data = data_ptr;
+
+    run_constructors(instance)?;
+
+    instance.call(name, params)
+}
+
+fn run_constructors(instance: &mut Instance) -> CallResult<()> {
+    // ATINIT
+    // (used by C++)
+    if let Ok(_func) = instance.dyn_func("globalCtors") {
+        instance.call("globalCtors", &[])?;
+    }
+
+    if let Ok(_func) = instance.dyn_func("___emscripten_environ_constructor") {
+        instance.call("___emscripten_environ_constructor", &[])?;
+    }
+    Ok(())
+}
+
 fn store_module_arguments(ctx: &mut Ctx, path: &str, args: Vec<&str>) -> (u32, u32) {
     let argc = args.len() + 1;
 
@@ -505,13 +661,15 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_getpwuid" => func!(crate::io::getpwuid),
         "_sigdelset" => func!(crate::io::sigdelset),
         "_sigfillset" => func!(crate::io::sigfillset),
-        "_tzset" => func!(crate::io::tzset),
+        "_tzset" => func!(crate::time::_tzset),
         "_strptime" => func!(crate::io::strptime),
 
         // exec
         "_execvp" => func!(crate::exec::execvp),
         "_execl" => func!(crate::exec::execl),
         "_execle" => func!(crate::exec::execle),
+        "_execv" => func!(crate::exec::execv),
+        "_execve" => func!(crate::exec::execve),
 
         // exit
         "__exit" => func!(crate::exit::exit),
@@ -529,6 +687,8 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_getpagesize" => func!(crate::env::_getpagesize),
         "_sysconf" => func!(crate::env::_sysconf),
         "_getaddrinfo" => func!(crate::env::_getaddrinfo),
+        "_freeaddrinfo" => func!(crate::env::_freeaddrinfo),
+        "_getnameinfo" => func!(crate::env::_getnameinfo),
         "_times" => func!(crate::env::_times),
 
         // Syscalls
@@ -611,13 +771,13 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_exit" => func!(crate::process::_exit),
         "_system" => func!(crate::process::_system),
         "_popen" => func!(crate::process::_popen),
+        "_pclose" => func!(crate::process::_pclose),
         "_endgrent" => func!(crate::process::_endgrent),
-        "_execve" => func!(crate::process::_execve),
-        "_kill" => func!(crate::process::_kill),
+        "_kill" => func!(crate::signal::_kill),
         "_llvm_stackrestore" => func!(crate::process::_llvm_stackrestore),
         "_llvm_stacksave" => func!(crate::process::_llvm_stacksave),
         "_llvm_eh_typeid_for" => func!(crate::process::_llvm_eh_typeid_for),
-        "_raise" => func!(crate::process::_raise),
+        "_raise" => func!(crate::signal::_raise),
         "_sem_init" => func!(crate::process::_sem_init),
         "_sem_post" => func!(crate::process::_sem_post),
         "_sem_wait" => func!(crate::process::_sem_wait),
@@ -625,7 +785,10 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_sched_yield" => func!(crate::process::_sched_yield),
         "_setgrent" => func!(crate::process::_setgrent),
         "_setgroups" => func!(crate::process::_setgroups),
-        "_setitimer" => func!(crate::process::_setitimer),
+        "_setitimer" => func!(crate::signal::_setitimer),
+        "_getitimer" => func!(crate::signal::_getitimer),
+        "_alarm" => func!(crate::signal::_alarm),
+        "_pause" => func!(crate::signal::_pause),
         "_usleep" => func!(crate::process::_usleep),
         "_nanosleep" => func!(crate::process::_nanosleep),
         "_utimes" => func!(crate::process::_utimes),
@@ -652,6 +815,7 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         // Exception
         "___cxa_allocate_exception" => func!(crate::exception::___cxa_allocate_exception),
         "___cxa_throw" => func!(crate::exception::___cxa_throw),
+        "___cxa_rethrow" => func!(crate::exception::___cxa_rethrow),
         "___cxa_begin_catch" => func!(crate::exception::___cxa_begin_catch),
         "___cxa_end_catch" => func!(crate::exception::___cxa_end_catch),
         "___cxa_uncaught_exception" => func!(crate::exception::___cxa_uncaught_exception),
@@ -659,11 +823,15 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         // Time
         "_gettimeofday" => func!(crate::time::_gettimeofday),
         "_clock_gettime" => func!(crate::time::_clock_gettime),
-        "___clock_gettime" => func!(crate::time::_clock_gettime),
+        "___clock_gettime" => func!(crate::time::___clock_gettime),
+        "_clock_getres" => func!(crate::time::_clock_getres),
+        "_clock_settime" => func!(crate::time::_clock_settime),
         "_clock" => func!(crate::time::_clock),
         "_difftime" => func!(crate::time::_difftime),
         "_asctime" => func!(crate::time::_asctime),
         "_asctime_r" => func!(crate::time::_asctime_r),
+        "_ctime" => func!(crate::time::_ctime),
+        "_ctime_r" => func!(crate::time::_ctime_r),
         "_localtime" => func!(crate::time::_localtime),
         "_time" => func!(crate::time::_time),
         "_strftime" => func!(crate::time::_strftime),
@@ -672,6 +840,7 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_gmtime_r" => func!(crate::time::_gmtime_r),
         "_mktime" => func!(crate::time::_mktime),
         "_gmtime" => func!(crate::time::_gmtime),
+        "_timegm" => func!(crate::time::_timegm),
 
         // Math
         "f64-rem" => func!(crate::math::f64_rem),
@@ -689,6 +858,8 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "__setjmp" => func!(crate::jmp::__setjmp),
         "__longjmp" => func!(crate::jmp::__longjmp),
         "_longjmp" => func!(crate::jmp::_longjmp),
+        // No signal mask is saved by `sigsetjmp`, so there is none to restore.
+        "_siglongjmp" => func!(crate::jmp::_longjmp),
         "_emscripten_longjmp" => func!(crate::jmp::_longjmp),
 
         // Bitwise
@@ -717,10 +888,12 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "__Unwind_Backtrace" => func!(crate::emscripten_target::__Unwind_Backtrace),
         "__Unwind_FindEnclosingFunction" => func!(crate::emscripten_target::__Unwind_FindEnclosingFunction),
         "__Unwind_GetIPInfo" => func!(crate::emscripten_target::__Unwind_GetIPInfo),
-        "___cxa_find_matching_catch_2" => func!(crate::emscripten_target::___cxa_find_matching_catch_2),
-        "___cxa_find_matching_catch_3" => func!(crate::emscripten_target::___cxa_find_matching_catch_3),
-        "___cxa_free_exception" => func!(crate::emscripten_target::___cxa_free_exception),
-        "___resumeException" => func!(crate::emscripten_target::___resumeException),
+        "___cxa_find_matching_catch_2" => func!(crate::exception::___cxa_find_matching_catch_2),
+        "___cxa_find_matching_catch_3" => func!(crate::exception::___cxa_find_matching_catch_3),
+        "___cxa_find_matching_catch_4" => func!(crate::exception::___cxa_find_matching_catch_4),
+        "___cxa_find_matching_catch_5" => func!(crate::exception::___cxa_find_matching_catch_5),
+        "___cxa_free_exception" => func!(crate::exception::___cxa_free_exception),
+        "___resumeException" => func!(crate::exception::___resumeException),
         "_dladdr" => func!(crate::emscripten_target::_dladdr),
         "_pthread_create" => func!(crate::emscripten_target::_pthread_create),
         "_pthread_join" => func!(crate::emscripten_target::_pthread_join),
@@ -743,6 +916,7 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_pthread_setspecific" => func!(crate::emscripten_target::_pthread_setspecific),
         "_pthread_once" => func!(crate::emscripten_target::_pthread_once),
         "_pthread_key_create" => func!(crate::emscripten_target::_pthread_key_create),
+        "_pthread_key_delete" => func!(crate::emscripten_target::_pthread_key_delete),
         "___gxx_personality_v0" => func!(crate::emscripten_target::___gxx_personality_v0),
         "_getdtablesize" => func!(crate::emscripten_target::_getdtablesize),
         "_gethostbyaddr" => func!(crate::emscripten_target::_gethostbyaddr),
@@ -824,3 +998,34 @@ pub fn nullfunc(ctx: &mut Ctx, _x: u32) {
 
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate_emscripten_env, invoke_emscripten_function_with_config, EmscriptenConfig,
+        EmscriptenGlobals,
+    };
+    use crate::utils::tests::get_compiler;
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{compile_with, types::Value};
+
+    #[test]
+    fn invoked_functions_can_use_the_runtime() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/invoke.wast");
+        let wasm_binary = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        let module =
+            compile_with(&wasm_binary[..], &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        let results = invoke_emscripten_function_with_config(
+            &mut instance,
+            "_add_to_key",
+            &[Value::I32(41)],
+            EmscriptenConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(results, vec![Value::I32(42)]);
+    }
+}
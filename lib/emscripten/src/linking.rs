@@ -1,27 +1,400 @@
-use wasmer_runtime_core::vm::Ctx;
+//! Dynamic linking of side modules, built with `-s SIDE_MODULE=1`.
+//!
+//! Each side module gets an instance of its own that shares the memory and
+//! table of the main module and is linked against its exports and the
+//! emscripten environment. Its data goes in memory allocated with `malloc`
+//! and its functions in slots added at the end of the table. As in
+//! emscripten, libraries are never unloaded: the guest may still hold
+//! pointers into them.
 
-// TODO: Need to implement.
+use crate::env::{call_malloc, call_memset, get_emscripten_data};
+use crate::fs::host_path;
+use crate::utils::{copy_cstr_into_wasm, read_string_from_wasm};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::sync::Arc;
+use wasmer_runtime_core::{
+    backend::Compiler,
+    compile_with,
+    export::Export,
+    global::Global,
+    import::ImportObject,
+    module::Module,
+    table::{Anyfunc, Element, Table},
+    types::Value,
+    vm::Ctx,
+    Instance,
+};
+
+/// A side module loaded by `dlopen`.
+struct Library {
+    /// The path the guest opened it with.
+    name: String,
+    /// The host path it was loaded from.
+    path: String,
+    instance: Instance,
+    table: Table,
+    memory_base: u32,
+    /// The table indices of the functions looked up with `dlsym`.
+    functions: HashMap<String, u32>,
+}
+
+/// The side modules of an instance, by handle minus one.
+pub(crate) struct Libraries {
+    libraries: Vec<Library>,
+    /// What side modules are compiled with.
+    compiler: Arc<dyn Compiler>,
+    /// The message the next `dlerror` returns.
+    error: Option<String>,
+    /// The message the last `dlerror` returned, to free it.
+    error_ptr: u32,
+}
+
+impl Libraries {
+    pub(crate) fn new(compiler: Arc<dyn Compiler>) -> Self {
+        Self {
+            libraries: vec![],
+            compiler,
+            error: None,
+            error_ptr: 0,
+        }
+    }
+}
+
+/// The `dylink` section of a side module: the size and alignment of its
+/// memory and of its part of the table, alignments as powers of two.
+fn dylink_section(module: &Module) -> Option<[u32; 4]> {
+    let mut section = &module.info().custom_sections.get("dylink")?[0][..];
+    let mut fields = [0; 4];
+    for field in fields.iter_mut() {
+        *field = read_leb128(&mut section)?;
+    }
+    Some(fields)
+}
+
+fn read_leb128(bytes: &mut &[u8]) -> Option<u32> {
+    let mut value = 0u32;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= u32::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Load the side module at `path`, run its constructors and keep it.
+fn load(ctx: &mut Ctx, name: String, path: String) -> Result<i32, String> {
+    let wasm = std::fs::read(&path).map_err(|e| format!("{}: {}", name, e))?;
+    let compiler = Arc::clone(&get_emscripten_data(ctx).libraries.compiler);
+    let module = compile_with(&wasm, &*compiler).map_err(|e| format!("{}: {}", name, e))?;
+    let [memory_size, memory_align, table_size, _] =
+        dylink_section(&module).ok_or_else(|| format!("{}: not a side module", name))?;
+
+    let align = 1u32
+        .checked_shl(memory_align)
+        .ok_or_else(|| format!("{}: invalid dylink section", name))?;
+    let memory = call_malloc(ctx, memory_size + align);
+    if memory == 0 {
+        return Err(format!("{}: out of memory", name));
+    }
+    let memory_base = (memory + align - 1) & !(align - 1);
+    call_memset(ctx, memory_base, 0, memory_size);
+
+    let host_imports = get_emscripten_data(ctx).imports.clone_ref();
+    let table = match host_imports
+        .get_namespace("env")
+        .and_then(|env| env.get_export("table"))
+    {
+        Some(Export::Table(table)) => table,
+        _ => return Err(format!("{}: the main module has no table", name)),
+    };
+    let table_base = table
+        .grow(table_size)
+        .map_err(|e| format!("{}: {}", name, e))?;
+
+    // Exports of the main module take precedence over the environment,
+    // as they do in emscripten.
+    let mut imports = ImportObject::new();
+    imports.extend(host_imports);
+    imports.extend(
+        get_emscripten_data(ctx)
+            .main_exports
+            .iter()
+            .cloned()
+            .map(|(symbol, export)| ("env".to_string(), symbol, export)),
+    );
+    for &(symbol, base) in &[
+        ("__memory_base", memory_base),
+        ("memoryBase", memory_base),
+        ("__table_base", table_base),
+        ("tableBase", table_base),
+    ] {
+        let base = Export::Global(Global::new(Value::I32(base as i32)));
+        imports.extend(vec![("env".to_string(), symbol.to_string(), base)]);
+    }
+
+    let mut instance = module
+        .instantiate(&imports)
+        .map_err(|e| format!("{}: {}", name, e))?;
+    instance.context_mut().data = ctx.data;
+    if instance.dyn_func("__post_instantiate").is_ok() {
+        instance
+            .call("__post_instantiate", &[])
+            .map_err(|e| format!("{}: {}", name, e))?;
+    }
+
+    let libraries = &mut get_emscripten_data(ctx).libraries.libraries;
+    libraries.push(Library {
+        name,
+        path,
+        instance,
+        table,
+        memory_base,
+        functions: HashMap::new(),
+    });
+    Ok(libraries.len() as i32)
+}
+
+fn open(ctx: &mut Ctx, filename: u32) -> Result<i32, String> {
+    if filename == 0 {
+        return Err("dlopen of the main program is not supported".to_string());
+    }
+    let name = read_string_from_wasm(ctx.memory(0), filename);
+    let path = host_path(ctx, filename)
+        .map_err(|errno| format!("{}: {}", name, io::Error::from_raw_os_error(-errno)))?
+        .into_string()
+        .map_err(|_| format!("{}: invalid path", name))?;
+
+    let libraries = &get_emscripten_data(ctx).libraries.libraries;
+    match libraries.iter().position(|library| library.path == path) {
+        Some(index) => Ok(index as i32 + 1),
+        None => load(ctx, name, path),
+    }
+}
+
+/// The address of `symbol` in the library behind `handle`. Functions are
+/// given a table slot the first time they're looked up.
+fn lookup(ctx: &mut Ctx, handle: i32, symbol: &str) -> Result<u32, String> {
+    let libraries = &mut get_emscripten_data(ctx).libraries.libraries;
+    let library = match handle {
+        handle if handle > 0 => libraries.get_mut(handle as usize - 1),
+        _ => None,
+    }
+    .ok_or_else(|| format!("invalid handle {}", handle))?;
+    // C symbols are exported with a leading underscore.
+    let export_name = format!("_{}", symbol);
+    if let Some(&index) = library.functions.get(&export_name) {
+        return Ok(index);
+    }
+
+    let export = library
+        .instance
+        .exports()
+        .find(|(name, _)| *name == export_name)
+        .map(|(_, export)| export);
+    match export {
+        Some(Export::Function { .. }) => {
+            let func = library
+                .instance
+                .dyn_func(&export_name)
+                .map_err(|e| format!("{}: {}", library.name, e))?;
+            let index = library
+                .table
+                .grow(1)
+                .map_err(|e| format!("{}: {}", library.name, e))?;
+            library
+                .table
+                .set(index, Element::Anyfunc(Anyfunc::from(func)))
+                .map_err(|_| format!("{}: can't add {} to the table", library.name, symbol))?;
+            library.functions.insert(export_name, index);
+            Ok(index)
+        }
+        // Data symbols are exported relative to the memory of the library.
+        Some(Export::Global(global)) => match global.get() {
+            Value::I32(offset) => Ok(library.memory_base.wrapping_add(offset as u32)),
+            _ => Err(format!("{}: {} is not an address", library.name, symbol)),
+        },
+        _ => Err(format!("{}: undefined symbol: {}", library.name, symbol)),
+    }
+}
 
 /// emscripten: dlopen(filename: *const c_char, flag: c_int) -> *mut c_void
-pub fn _dlopen(_ctx: &mut Ctx, _filename: u32, _flag: u32) -> i32 {
+pub fn _dlopen(ctx: &mut Ctx, filename: u32, _flag: u32) -> i32 {
     debug!("emscripten::_dlopen");
-    -1
+    open(ctx, filename).unwrap_or_else(|message| {
+        get_emscripten_data(ctx).libraries.error = Some(message);
+        0
+    })
 }
 
 /// emscripten: dlclose(handle: *mut c_void) -> c_int
-pub fn _dlclose(_ctx: &mut Ctx, _filename: u32) -> i32 {
+pub fn _dlclose(ctx: &mut Ctx, handle: i32) -> i32 {
     debug!("emscripten::_dlclose");
-    -1
+    let libraries = &mut get_emscripten_data(ctx).libraries;
+    if handle > 0 && handle as usize <= libraries.libraries.len() {
+        0
+    } else {
+        libraries.error = Some(format!("invalid handle {}", handle));
+        1
+    }
 }
 
 /// emscripten: dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void
-pub fn _dlsym(_ctx: &mut Ctx, _filepath: u32, _symbol: u32) -> i32 {
+pub fn _dlsym(ctx: &mut Ctx, handle: i32, symbol: u32) -> i32 {
     debug!("emscripten::_dlsym");
-    -1
+    let symbol = read_string_from_wasm(ctx.memory(0), symbol);
+    match lookup(ctx, handle, &symbol) {
+        Ok(address) => address as i32,
+        Err(message) => {
+            get_emscripten_data(ctx).libraries.error = Some(message);
+            0
+        }
+    }
 }
 
 /// emscripten: dlerror() -> *mut c_char
-pub fn _dlerror(_ctx: &mut Ctx) -> i32 {
+pub fn _dlerror(ctx: &mut Ctx) -> i32 {
     debug!("emscripten::_dlerror");
-    -1
+    let libraries = &mut get_emscripten_data(ctx).libraries;
+    let message = match libraries.error.take() {
+        Some(message) => message,
+        None => return 0,
+    };
+    let previous = libraries.error_ptr;
+    if previous != 0 {
+        get_emscripten_data(ctx)
+            .free
+            .call(previous)
+            .expect("free call failed");
+    }
+    let message = CString::new(message).unwrap_or_default();
+    let ptr = unsafe { copy_cstr_into_wasm(ctx, message.as_ptr()) };
+    get_emscripten_data(ctx).libraries.error_ptr = ptr;
+    ptr as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dylink_section, read_leb128};
+    use crate::utils::tests::get_compiler;
+    use crate::{
+        generate_emscripten_env, invoke_emscripten_function_with_config, EmscriptenConfig,
+        EmscriptenGlobals, Mounts,
+    };
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{
+        backend::{Compiler, CompilerConfig, Token},
+        cache::{Artifact, Error as CacheError},
+        compile_with,
+        error::CompileResult,
+        module::ModuleInner,
+        types::Value,
+    };
+
+    /// The compiler of the tests, counting the modules it compiles.
+    struct CountingCompiler(AtomicUsize);
+
+    impl Compiler for CountingCompiler {
+        fn compile(
+            &self,
+            wasm: &[u8],
+            comp_conf: CompilerConfig,
+            token: Token,
+        ) -> CompileResult<ModuleInner> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            get_compiler().compile(wasm, comp_conf, token)
+        }
+
+        unsafe fn from_cache(
+            &self,
+            cache: Artifact,
+            token: Token,
+        ) -> Result<ModuleInner, CacheError> {
+            get_compiler().from_cache(cache, token)
+        }
+    }
+
+    /// `wasm` with a `dylink` custom section holding `payload` at the end.
+    fn with_dylink_section(mut wasm: Vec<u8>, payload: &[u8]) -> Vec<u8> {
+        let name = b"dylink";
+        wasm.push(0);
+        wasm.push((1 + name.len() + payload.len()) as u8);
+        wasm.push(name.len() as u8);
+        wasm.extend_from_slice(name);
+        wasm.extend_from_slice(payload);
+        wasm
+    }
+
+    fn side_module() -> Vec<u8> {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/dlopen_side.wast");
+        let wasm = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        // 8 bytes of memory aligned on 4, and no table slots.
+        with_dylink_section(wasm, &[8, 2, 0, 0])
+    }
+
+    #[test]
+    fn leb128_values_are_read() {
+        let mut bytes = &[0x2a, 0xe5, 0x8e, 0x26, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x80][..];
+        assert_eq!(read_leb128(&mut bytes), Some(42));
+        assert_eq!(read_leb128(&mut bytes), Some(624_485));
+        assert_eq!(read_leb128(&mut bytes), Some(u32::max_value()));
+        // The last value is cut short.
+        assert_eq!(read_leb128(&mut bytes), None);
+        assert!(bytes.is_empty());
+        // Too long for a u32.
+        let mut bytes = &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01][..];
+        assert_eq!(read_leb128(&mut bytes), None);
+    }
+
+    #[test]
+    fn dylink_sections_are_parsed() {
+        let module = compile_with(&side_module(), &get_compiler()).unwrap();
+        assert_eq!(dylink_section(&module), Some([8, 2, 0, 0]));
+
+        let wasm = wat2wasm(&include_bytes!("tests/dlopen_side.wast")[..]).unwrap();
+        let module = compile_with(&wasm, &get_compiler()).unwrap();
+        assert_eq!(dylink_section(&module), None);
+
+        let truncated = with_dylink_section(wasm, &[8, 2, 0]);
+        let module = compile_with(&truncated, &get_compiler()).unwrap();
+        assert_eq!(dylink_section(&module), None);
+    }
+
+    #[test]
+    fn side_modules_are_opened_and_their_symbols_found() {
+        let dir = std::env::temp_dir().join(format!("wasmer-dlopen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("side.wasm"), side_module()).unwrap();
+        let mut mounts = Mounts::new();
+        mounts.mount("/", &dir).unwrap();
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/dlopen_main.wast");
+        let wasm = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        let module = compile_with(&wasm, &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        let compiler = Arc::new(CountingCompiler(AtomicUsize::new(0)));
+        let config = EmscriptenConfig {
+            mounts,
+            compiler: compiler.clone(),
+            ..EmscriptenConfig::default()
+        };
+        let results =
+            invoke_emscripten_function_with_config(&mut instance, "_use_side_module", &[], config);
+        fs::remove_dir_all(&dir).unwrap();
+        // The function of the side module returns 7 and its data holds 42.
+        assert_eq!(results.unwrap(), vec![Value::I32(49)]);
+        // The side module was compiled with the compiler of the config.
+        assert_eq!(compiler.0.load(Ordering::SeqCst), 1);
+    }
 }
@@ -0,0 +1,674 @@
+//! The sockets of emscripten modules, in terms of the host's.
+//!
+//! Guests use the constants and struct layouts of emscripten's libc, the
+//! Linux ones on wasm32, which needn't be the host's. Addresses, options,
+//! flags and errnos are translated both ways here; anything the guest
+//! passes that has no host equivalent is refused rather than passed on.
+
+use crate::env::get_emscripten_data;
+use libc::{
+    c_int, c_void, sa_family_t, sockaddr, sockaddr_in, sockaddr_in6, sockaddr_storage, sockaddr_un,
+    socklen_t, EAFNOSUPPORT, EFAULT, EINVAL, ENAMETOOLONG, ENOPROTOOPT,
+};
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::ffi::OsStrExt;
+use std::slice;
+use wasmer_runtime_core::vm::Ctx;
+
+// Address families.
+pub const AF_UNSPEC: c_int = 0;
+pub const AF_UNIX: c_int = 1;
+pub const AF_INET: c_int = 2;
+pub const AF_INET6: c_int = 10;
+
+// Socket types, and the flags that can go with them.
+pub const SOCK_STREAM: c_int = 1;
+pub const SOCK_DGRAM: c_int = 2;
+pub const SOCK_RAW: c_int = 3;
+pub const SOCK_NONBLOCK: c_int = 0o4000;
+pub const SOCK_CLOEXEC: c_int = 0o2_000_000;
+
+/// The file status flag of non-blocking fds, for `fcntl` and `accept4`.
+pub const O_NONBLOCK: c_int = 0o4000;
+const O_APPEND: c_int = 0o2000;
+
+// Events of `poll`. These are the same on every host.
+pub const POLLIN: i16 = 0x1;
+pub const POLLPRI: i16 = 0x2;
+pub const POLLOUT: i16 = 0x4;
+pub const POLLERR: i16 = 0x8;
+pub const POLLHUP: i16 = 0x10;
+pub const POLLNVAL: i16 = 0x20;
+
+/// The errno the last host call failed with, as the guest knows it.
+pub fn last_errno() -> c_int {
+    guest_errno(io::Error::last_os_error().raw_os_error().unwrap_or(EINVAL))
+}
+
+/// The guest's number for host errno `errno`. The small ones are
+/// the same everywhere; the networking ones are not.
+pub fn guest_errno(errno: c_int) -> c_int {
+    match errno {
+        libc::EAGAIN => 11,
+        libc::EDEADLK => 35,
+        libc::ENAMETOOLONG => 36,
+        libc::ENOSYS => 38,
+        libc::ENOTEMPTY => 39,
+        libc::ELOOP => 40,
+        libc::ENOTSOCK => 88,
+        libc::EDESTADDRREQ => 89,
+        libc::EMSGSIZE => 90,
+        libc::EPROTOTYPE => 91,
+        libc::ENOPROTOOPT => 92,
+        libc::EPROTONOSUPPORT => 93,
+        libc::EOPNOTSUPP => 95,
+        libc::EAFNOSUPPORT => 97,
+        libc::EADDRINUSE => 98,
+        libc::EADDRNOTAVAIL => 99,
+        libc::ENETDOWN => 100,
+        libc::ENETUNREACH => 101,
+        libc::ECONNABORTED => 103,
+        libc::ECONNRESET => 104,
+        libc::ENOBUFS => 105,
+        libc::EISCONN => 106,
+        libc::ENOTCONN => 107,
+        libc::ETIMEDOUT => 110,
+        libc::ECONNREFUSED => 111,
+        libc::EHOSTUNREACH => 113,
+        libc::EALREADY => 114,
+        libc::EINPROGRESS => 115,
+        errno => errno,
+    }
+}
+
+/// What a host call returned, or the negated guest errno it failed with.
+pub fn guest_result(ret: isize) -> c_int {
+    if ret < 0 {
+        -last_errno()
+    } else {
+        ret as c_int
+    }
+}
+
+/// A host pointer to the `len` bytes of guest memory at `ptr`,
+/// or `EFAULT` if they're not all in it.
+pub fn guest_bytes(ctx: &Ctx, ptr: u32, len: u32) -> Result<*mut u8, c_int> {
+    let view = ctx.memory(0).view::<u8>();
+    if ptr as usize + len as usize > view.len() {
+        return Err(EFAULT);
+    }
+    Ok(unsafe { (view.as_ptr() as *mut u8).add(ptr as usize) })
+}
+
+/// A socket address of the guest.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    Inet(SocketAddr),
+    /// A Unix socket, by its path in the guest filesystem.
+    Unix(String),
+    /// A Unix socket without a name.
+    Unnamed,
+}
+
+/// The guest `struct sockaddr` of `len` bytes at `ptr`.
+pub fn read_address(ctx: &Ctx, ptr: u32, len: u32) -> Result<Address, c_int> {
+    if len < 2 || len as usize > mem::size_of::<sockaddr_storage>() {
+        return Err(EINVAL);
+    }
+    guest_bytes(ctx, ptr, len)?;
+    let bytes: Vec<u8> = ctx.memory(0).view::<u8>()[ptr as usize..(ptr + len) as usize]
+        .iter()
+        .map(|cell| cell.get())
+        .collect();
+    let word = |at: usize| {
+        u32::from(bytes[at])
+            | u32::from(bytes[at + 1]) << 8
+            | u32::from(bytes[at + 2]) << 16
+            | u32::from(bytes[at + 3]) << 24
+    };
+    let port = u16::from(bytes[2]) << 8 | u16::from(bytes[3]);
+
+    match c_int::from(u16::from(bytes[0]) | u16::from(bytes[1]) << 8) {
+        // struct sockaddr_in { sa_family_t; in_port_t; struct in_addr; char[8]; }
+        AF_INET if len >= 16 => {
+            let ip = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+            Ok(Address::Inet(SocketAddr::V4(SocketAddrV4::new(ip, port))))
+        }
+        // struct sockaddr_in6 { sa_family_t; in_port_t; uint32_t sin6_flowinfo;
+        // struct in6_addr; uint32_t sin6_scope_id; }
+        AF_INET6 if len >= 24 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&bytes[8..24]);
+            let scope_id = if len >= 28 { word(24) } else { 0 };
+            Ok(Address::Inet(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(octets),
+                port,
+                word(4),
+                scope_id,
+            ))))
+        }
+        // struct sockaddr_un { sa_family_t; char sun_path[108]; }
+        AF_UNIX if len == 2 => Ok(Address::Unnamed),
+        AF_UNIX => {
+            let path: Vec<u8> = bytes[2..].iter().cloned().take_while(|&b| b != 0).collect();
+            // Abstract sockets are Linux's own, and not in any filesystem.
+            if path.is_empty() {
+                return Err(EINVAL);
+            }
+            Ok(Address::Unix(String::from_utf8_lossy(&path).into_owned()))
+        }
+        AF_INET | AF_INET6 => Err(EINVAL),
+        _ => Err(EAFNOSUPPORT),
+    }
+}
+
+/// `address` laid out as a guest `struct sockaddr`.
+pub fn address_bytes(address: &Address) -> Vec<u8> {
+    let mut bytes = vec![];
+    match address {
+        Address::Inet(SocketAddr::V4(addr)) => {
+            bytes.extend_from_slice(&(AF_INET as u16).to_le_bytes());
+            bytes.extend_from_slice(&addr.port().to_be_bytes());
+            bytes.extend_from_slice(&addr.ip().octets());
+            bytes.extend_from_slice(&[0; 8]);
+        }
+        Address::Inet(SocketAddr::V6(addr)) => {
+            bytes.extend_from_slice(&(AF_INET6 as u16).to_le_bytes());
+            bytes.extend_from_slice(&addr.port().to_be_bytes());
+            bytes.extend_from_slice(&addr.flowinfo().to_le_bytes());
+            bytes.extend_from_slice(&addr.ip().octets());
+            bytes.extend_from_slice(&addr.scope_id().to_le_bytes());
+        }
+        Address::Unix(path) => {
+            bytes.extend_from_slice(&(AF_UNIX as u16).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.push(0);
+        }
+        Address::Unnamed => bytes.extend_from_slice(&(AF_UNIX as u16).to_le_bytes()),
+    }
+    bytes
+}
+
+/// Write `address` to the guest buffer at `ptr`, whose size is at
+/// `len_ptr`, like the kernel does: truncated if it doesn't fit, with
+/// its whole length written back to `len_ptr`. Null pointers are fine.
+pub fn write_address(ctx: &Ctx, ptr: u32, len_ptr: u32, address: &Address) -> Result<(), c_int> {
+    if ptr == 0 || len_ptr == 0 {
+        return Ok(());
+    }
+    guest_bytes(ctx, len_ptr, 4)?;
+    let view = ctx.memory(0).view::<u32>();
+    let room = view[len_ptr as usize / 4].get();
+    let bytes = address_bytes(address);
+    let len = bytes.len().min(room as usize);
+    guest_bytes(ctx, ptr, len as u32)?;
+    for (cell, &byte) in ctx.memory(0).view::<u8>()[ptr as usize..]
+        .iter()
+        .zip(&bytes[..len])
+    {
+        cell.set(byte);
+    }
+    view[len_ptr as usize / 4].set(bytes.len() as u32);
+    Ok(())
+}
+
+/// `address` as a host `struct sockaddr`. Unix socket paths are
+/// looked up in the guest filesystem like other paths are.
+pub fn host_address(
+    ctx: &mut Ctx,
+    address: &Address,
+) -> Result<(sockaddr_storage, socklen_t), c_int> {
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match address {
+        Address::Inet(addr) => return Ok(host_socket_address(addr)),
+        Address::Unix(path) => {
+            let path = get_emscripten_data(ctx).mounts.resolve(path)?;
+            let path = path.as_os_str().as_bytes();
+            let sun = unsafe { &mut *(&mut storage as *mut sockaddr_storage as *mut sockaddr_un) };
+            // Room is left for the null byte.
+            if path.len() >= sun.sun_path.len() {
+                return Err(ENAMETOOLONG);
+            }
+            sun.sun_family = libc::AF_UNIX as sa_family_t;
+            for (c, &byte) in sun.sun_path.iter_mut().zip(path) {
+                *c = byte as _;
+            }
+            mem::size_of::<sa_family_t>() + path.len() + 1
+        }
+        Address::Unnamed => {
+            storage.ss_family = libc::AF_UNIX as sa_family_t;
+            mem::size_of::<sa_family_t>()
+        }
+    };
+    set_sockaddr_len(&mut storage, len);
+    Ok((storage, len as socklen_t))
+}
+
+/// `addr` as a host `struct sockaddr`.
+pub fn host_socket_address(addr: &SocketAddr) -> (sockaddr_storage, socklen_t) {
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sin = unsafe { &mut *(&mut storage as *mut sockaddr_storage as *mut sockaddr_in) };
+            sin.sin_family = libc::AF_INET as sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            mem::size_of::<sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6 =
+                unsafe { &mut *(&mut storage as *mut sockaddr_storage as *mut sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr.s6_addr = addr.ip().octets();
+            sin6.sin6_scope_id = addr.scope_id();
+            mem::size_of::<sockaddr_in6>()
+        }
+    };
+    set_sockaddr_len(&mut storage, len);
+    (storage, len as socklen_t)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn set_sockaddr_len(storage: &mut sockaddr_storage, len: usize) {
+    storage.ss_len = len as u8;
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+fn set_sockaddr_len(_storage: &mut sockaddr_storage, _len: usize) {}
+
+/// The guest's view of the host address in `storage`. Unix socket
+/// paths are host paths, which the guest doesn't get to see.
+pub fn guest_address(storage: &sockaddr_storage) -> Option<Address> {
+    match c_int::from(storage.ss_family) {
+        libc::AF_INET => {
+            let sin = unsafe { &*(storage as *const sockaddr_storage as *const sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+            Some(Address::Inet(SocketAddr::V4(SocketAddrV4::new(
+                ip,
+                u16::from_be(sin.sin_port),
+            ))))
+        }
+        libc::AF_INET6 => {
+            let sin6 = unsafe { &*(storage as *const sockaddr_storage as *const sockaddr_in6) };
+            Some(Address::Inet(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(sin6.sin6_addr.s6_addr),
+                u16::from_be(sin6.sin6_port),
+                sin6.sin6_flowinfo,
+                sin6.sin6_scope_id,
+            ))))
+        }
+        libc::AF_UNIX => Some(Address::Unnamed),
+        _ => None,
+    }
+}
+
+/// Call `f` with a host buffer for an address, and write the address
+/// it got there back to the guest buffer at `ptr` with its size at `len_ptr`.
+pub fn with_address_buffer<F>(ctx: &mut Ctx, ptr: u32, len_ptr: u32, f: F) -> c_int
+where
+    F: FnOnce(*mut sockaddr, *mut socklen_t) -> isize,
+{
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<sockaddr_storage>() as socklen_t;
+    let ret = guest_result(f(
+        &mut storage as *mut sockaddr_storage as *mut sockaddr,
+        &mut len,
+    ));
+    if ret < 0 {
+        return ret;
+    }
+    if let Some(address) = guest_address(&storage) {
+        if let Err(errno) = write_address(ctx, ptr, len_ptr, &address) {
+            return -errno;
+        }
+    }
+    ret
+}
+
+/// The host's number for guest address family `domain`.
+pub fn host_domain(domain: c_int) -> Result<c_int, c_int> {
+    match domain {
+        AF_UNIX => Ok(libc::AF_UNIX),
+        AF_INET => Ok(libc::AF_INET),
+        AF_INET6 => Ok(libc::AF_INET6),
+        _ => Err(EAFNOSUPPORT),
+    }
+}
+
+/// The host's number for guest socket type `ty`, without its flags.
+pub fn host_type(ty: c_int) -> Result<c_int, c_int> {
+    match ty & !(SOCK_NONBLOCK | SOCK_CLOEXEC) {
+        SOCK_STREAM => Ok(libc::SOCK_STREAM),
+        SOCK_DGRAM => Ok(libc::SOCK_DGRAM),
+        SOCK_RAW => Ok(libc::SOCK_RAW),
+        _ => Err(EINVAL),
+    }
+}
+
+/// Make host fd `fd` blocking or not.
+/// Whether calls on host fd `fd` fail instead of blocking.
+pub fn is_nonblocking(fd: c_int) -> bool {
+    unsafe { libc::fcntl(fd, libc::F_GETFL) & libc::O_NONBLOCK != 0 }
+}
+
+pub fn set_nonblocking(fd: c_int, nonblocking: bool) -> Result<(), c_int> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(last_errno());
+    }
+    let flags = if nonblocking {
+        flags | libc::O_NONBLOCK
+    } else {
+        flags & !libc::O_NONBLOCK
+    };
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+        return Err(last_errno());
+    }
+    Ok(())
+}
+
+/// The file status flags of host fd `fd`, as `F_GETFL` returns them to the guest.
+pub fn get_status_flags(fd: c_int) -> c_int {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return -last_errno();
+    }
+    // The access mode is in the same two bits everywhere.
+    let mut guest = flags & libc::O_ACCMODE;
+    if flags & libc::O_NONBLOCK != 0 {
+        guest |= O_NONBLOCK;
+    }
+    if flags & libc::O_APPEND != 0 {
+        guest |= O_APPEND;
+    }
+    guest
+}
+
+/// Set the file status flags of host fd `fd` that the guest can change with `F_SETFL`.
+pub fn set_status_flags(fd: c_int, guest: c_int) -> c_int {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return -last_errno();
+    }
+    let mut flags = flags & !(libc::O_NONBLOCK | libc::O_APPEND);
+    if guest & O_NONBLOCK != 0 {
+        flags |= libc::O_NONBLOCK;
+    }
+    if guest & O_APPEND != 0 {
+        flags |= libc::O_APPEND;
+    }
+    guest_result(unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } as isize)
+}
+
+// Flags of `send` and `recv`, with their host numbers.
+const MSG_FLAGS: &[(c_int, c_int)] = &[
+    (0x1, libc::MSG_OOB),
+    (0x2, libc::MSG_PEEK),
+    (0x4, libc::MSG_DONTROUTE),
+    (0x8, libc::MSG_CTRUNC),
+    (0x20, libc::MSG_TRUNC),
+    (0x40, libc::MSG_DONTWAIT),
+    (0x80, libc::MSG_EOR),
+    (0x100, libc::MSG_WAITALL),
+];
+
+/// The host's `send` and `recv` flags for guest flags `flags`.
+/// Sending never raises `SIGPIPE` on the host, which would kill it.
+pub fn host_msg_flags(flags: c_int) -> c_int {
+    let host = MSG_FLAGS
+        .iter()
+        .filter(|(guest, _)| flags & guest != 0)
+        .fold(0, |host, (_, flag)| host | flag);
+    host | NO_SIGPIPE
+}
+
+/// The guest's `msg_flags` for host flags `flags`.
+pub fn guest_msg_flags(flags: c_int) -> c_int {
+    MSG_FLAGS
+        .iter()
+        .filter(|(_, host)| flags & host != 0)
+        .fold(0, |guest, (flag, _)| guest | flag)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+const NO_SIGPIPE: c_int = libc::MSG_NOSIGNAL;
+// Sockets are made with `SO_NOSIGPIPE` instead.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+const NO_SIGPIPE: c_int = 0;
+
+/// How the value of a socket option is laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptionValue {
+    /// An `int`.
+    Int,
+    /// A `struct timeval`, two 32-bit fields in the guest.
+    Timeval,
+    /// Bytes laid out the same in the guest and on the host.
+    Bytes,
+}
+
+/// The host level and name of a guest socket option, and its layout.
+pub fn host_option(level: c_int, name: c_int) -> Result<(c_int, c_int, OptionValue), c_int> {
+    use self::OptionValue::*;
+    let option = match (level, name) {
+        // SOL_SOCKET
+        (1, 1) => (libc::SOL_SOCKET, libc::SO_DEBUG, Int),
+        (1, 2) => (libc::SOL_SOCKET, libc::SO_REUSEADDR, Int),
+        (1, 3) => (libc::SOL_SOCKET, libc::SO_TYPE, Int),
+        (1, 4) => (libc::SOL_SOCKET, libc::SO_ERROR, Int),
+        (1, 5) => (libc::SOL_SOCKET, libc::SO_DONTROUTE, Int),
+        (1, 6) => (libc::SOL_SOCKET, libc::SO_BROADCAST, Int),
+        (1, 7) => (libc::SOL_SOCKET, libc::SO_SNDBUF, Int),
+        (1, 8) => (libc::SOL_SOCKET, libc::SO_RCVBUF, Int),
+        (1, 9) => (libc::SOL_SOCKET, libc::SO_KEEPALIVE, Int),
+        (1, 10) => (libc::SOL_SOCKET, libc::SO_OOBINLINE, Int),
+        (1, 13) => (libc::SOL_SOCKET, libc::SO_LINGER, Bytes),
+        (1, 15) => (libc::SOL_SOCKET, libc::SO_REUSEPORT, Int),
+        (1, 18) => (libc::SOL_SOCKET, libc::SO_RCVLOWAT, Int),
+        (1, 19) => (libc::SOL_SOCKET, libc::SO_SNDLOWAT, Int),
+        (1, 20) => (libc::SOL_SOCKET, libc::SO_RCVTIMEO, Timeval),
+        (1, 21) => (libc::SOL_SOCKET, libc::SO_SNDTIMEO, Timeval),
+        (1, 30) => (libc::SOL_SOCKET, libc::SO_ACCEPTCONN, Int),
+        // IPPROTO_IP
+        (0, 1) => (libc::IPPROTO_IP, libc::IP_TOS, Int),
+        (0, 2) => (libc::IPPROTO_IP, libc::IP_TTL, Int),
+        (0, 33) => (libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, Int),
+        (0, 34) => (libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP, Int),
+        (0, 35) => (libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, Bytes),
+        (0, 36) => (libc::IPPROTO_IP, libc::IP_DROP_MEMBERSHIP, Bytes),
+        // IPPROTO_TCP
+        (6, 1) => (libc::IPPROTO_TCP, libc::TCP_NODELAY, Int),
+        // IPPROTO_IPV6
+        (41, 16) => (libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, Int),
+        (41, 26) => (libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, Int),
+        _ => return Err(ENOPROTOOPT),
+    };
+    Ok(option)
+}
+
+/// setsockopt, with the guest option value of `len` bytes at `value`.
+pub fn set_option(ctx: &Ctx, fd: c_int, level: c_int, name: c_int, value: u32, len: u32) -> c_int {
+    let (host_level, host_name, layout) = match host_option(level, name) {
+        Ok(option) => option,
+        Err(errno) => return -errno,
+    };
+    let guest = match guest_bytes(ctx, value, len) {
+        Ok(guest) => guest,
+        Err(errno) => return -errno,
+    };
+    let view = ctx.memory(0).view::<i32>();
+    let ret = match layout {
+        OptionValue::Int => {
+            if len < 4 {
+                return -EINVAL;
+            }
+            let int: c_int = view[value as usize / 4].get();
+            unsafe {
+                libc::setsockopt(
+                    fd,
+                    host_level,
+                    host_name,
+                    &int as *const c_int as *const c_void,
+                    mem::size_of::<c_int>() as socklen_t,
+                )
+            }
+        }
+        OptionValue::Timeval => {
+            if len < 8 {
+                return -EINVAL;
+            }
+            let timeval = libc::timeval {
+                tv_sec: view[value as usize / 4].get() as _,
+                tv_usec: view[value as usize / 4 + 1].get() as _,
+            };
+            unsafe {
+                libc::setsockopt(
+                    fd,
+                    host_level,
+                    host_name,
+                    &timeval as *const libc::timeval as *const c_void,
+                    mem::size_of::<libc::timeval>() as socklen_t,
+                )
+            }
+        }
+        OptionValue::Bytes => unsafe {
+            libc::setsockopt(fd, host_level, host_name, guest as *const c_void, len)
+        },
+    };
+    guest_result(ret as isize)
+}
+
+/// getsockopt, into the guest buffer at `value` with its size at `len_ptr`.
+pub fn get_option(
+    ctx: &Ctx,
+    fd: c_int,
+    level: c_int,
+    name: c_int,
+    value: u32,
+    len_ptr: u32,
+) -> c_int {
+    let (host_level, host_name, layout) = match host_option(level, name) {
+        Ok(option) => option,
+        Err(errno) => return -errno,
+    };
+    if let Err(errno) = guest_bytes(ctx, len_ptr, 4) {
+        return -errno;
+    }
+    let room = ctx.memory(0).view::<u32>()[len_ptr as usize / 4].get();
+
+    // Aligned for any option value.
+    let mut buffer = [0u64; 8];
+    let mut host_len = mem::size_of_val(&buffer) as socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            host_level,
+            host_name,
+            buffer.as_mut_ptr() as *mut c_void,
+            &mut host_len,
+        )
+    };
+    if ret < 0 {
+        return -last_errno();
+    }
+    let buffer_bytes =
+        unsafe { slice::from_raw_parts(buffer.as_ptr() as *const u8, host_len as usize) };
+
+    let bytes: Vec<u8> = match layout {
+        OptionValue::Int => {
+            let mut int = [0u8; 4];
+            int.copy_from_slice(&buffer_bytes[..4]);
+            let mut int = c_int::from_ne_bytes(int);
+            if (host_level, host_name) == (libc::SOL_SOCKET, libc::SO_ERROR) {
+                int = guest_errno(int);
+            }
+            int.to_le_bytes().to_vec()
+        }
+        OptionValue::Timeval => {
+            let timeval = unsafe { &*(buffer.as_ptr() as *const libc::timeval) };
+            let mut bytes = (timeval.tv_sec as i32).to_le_bytes().to_vec();
+            bytes.extend_from_slice(&(timeval.tv_usec as i32).to_le_bytes());
+            bytes
+        }
+        OptionValue::Bytes => buffer_bytes.to_vec(),
+    };
+    let len = bytes.len().min(room as usize);
+    if let Err(errno) = guest_bytes(ctx, value, len as u32) {
+        return -errno;
+    }
+    for (cell, &byte) in ctx.memory(0).view::<u8>()[value as usize..]
+        .iter()
+        .zip(&bytes[..len])
+    {
+        cell.set(byte);
+    }
+    ctx.memory(0).view::<u32>()[len_ptr as usize / 4].set(len as u32);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        address_bytes, guest_address, guest_errno, host_option, host_socket_address, Address,
+        OptionValue,
+    };
+    use std::mem;
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn addresses_use_the_guest_layout() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        assert_eq!(
+            address_bytes(&Address::Inet(addr)),
+            [2, 0, 0x1f, 0x90, 127, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        let addr: SocketAddr = "[::1]:80".parse().unwrap();
+        let bytes = address_bytes(&Address::Inet(addr));
+        assert_eq!(bytes.len(), 28);
+        assert_eq!(&bytes[..4], &[10, 0, 0, 80]);
+        assert_eq!(bytes[23], 1);
+        assert_eq!(
+            address_bytes(&Address::Unix("/s".to_string())),
+            [1, 0, b'/', b's', 0]
+        );
+    }
+
+    #[test]
+    fn options_and_errnos_are_translated() {
+        assert_eq!(
+            host_option(1, 2),
+            Ok((libc::SOL_SOCKET, libc::SO_REUSEADDR, OptionValue::Int))
+        );
+        assert_eq!(host_option(1, 999), Err(libc::ENOPROTOOPT));
+        assert_eq!(guest_errno(libc::EINPROGRESS), 115);
+        assert_eq!(guest_errno(libc::EAGAIN), 11);
+        assert_eq!(guest_errno(libc::EBADF), 9);
+    }
+
+    #[test]
+    fn host_addresses_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (storage, len) = host_socket_address(&addr);
+        let stream = TcpStream::connect(addr).unwrap();
+
+        let mut peer: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut peer_len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getpeername(
+                stream.as_raw_fd(),
+                &mut peer as *mut libc::sockaddr_storage as *mut libc::sockaddr,
+                &mut peer_len,
+            )
+        };
+        assert_eq!(ret, 0);
+        assert_eq!(peer_len, len);
+        assert_eq!(guest_address(&peer), Some(Address::Inet(addr)));
+        assert_eq!(guest_address(&storage), Some(Address::Inet(addr)));
+    }
+}
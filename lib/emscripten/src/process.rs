@@ -1,13 +1,278 @@
-use libc::{abort, c_char, c_int, exit, EAGAIN};
+use libc::{abort, c_char, c_int, exit, EACCES, ECHILD, EINTR};
 
 #[cfg(not(target_os = "windows"))]
 type PidT = libc::pid_t;
 #[cfg(target_os = "windows")]
 type PidT = c_int;
 
+use crate::env::get_emscripten_data;
+use crate::fd_table;
+use crate::signal;
+use crate::utils::read_string_from_wasm;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::CStr;
+use std::io;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 use wasmer_runtime_core::vm::Ctx;
 
+/// What `system` and `popen` run commands with.
+const SHELL: &str = "sh";
+
+const WNOHANG: c_int = 1;
+
+/// The host programs an emscripten module may run with `system`, `popen`
+/// and the `exec` family. By default, there are none.
+///
+/// Programs are allowed by the name the guest runs them with, like `ls`,
+/// found in the host's `PATH`, or `/bin/ls`. They run on the host, outside
+/// of the module's sandbox: arguments that are absolute paths are resolved
+/// through the guest's mounts, and so is its working directory, but nothing
+/// stops a program from opening other host files itself.
+///
+/// `system` and `popen` run command lines made of plain words, like
+/// `ls -l '/my dir'`, as the program they name. Anything else, with
+/// pipes, redirections or variables, needs a shell, and allowing `sh`
+/// allows anything.
+#[derive(Clone, Debug, Default)]
+pub struct Commands {
+    allowed: Vec<String>,
+    all: bool,
+}
+
+impl Commands {
+    /// Any host program at all.
+    pub fn all() -> Self {
+        Self {
+            allowed: vec![],
+            all: true,
+        }
+    }
+
+    /// Let the module run `program`.
+    pub fn allow(&mut self, program: &str) {
+        self.allowed.push(program.to_string());
+    }
+
+    fn allows(&self, program: &str) -> bool {
+        self.all || self.allowed.iter().any(|allowed| allowed == program)
+    }
+}
+
+/// The host processes an instance started and hasn't waited for yet.
+#[derive(Debug, Default)]
+pub(crate) struct Children {
+    /// By pid. Those `fork` made have no `Child`.
+    children: BTreeMap<i32, Option<Child>>,
+    /// The children behind the streams `popen` returned, by `FILE *`.
+    streams: HashMap<u32, i32>,
+}
+
+impl Children {
+    /// Take the child `pid`, or any child if `pid` is -1 or 0, if it has exited.
+    fn reap(&mut self, pid: i32) -> Result<Option<(i32, ExitStatus)>, c_int> {
+        let mut found = false;
+        let mut exited = None;
+        for (&child_pid, child) in self.children.iter_mut() {
+            if pid > 0 && child_pid != pid {
+                continue;
+            }
+            found = true;
+            match try_wait(child_pid, child) {
+                Ok(Some(status)) => {
+                    exited = Some((child_pid, status));
+                    break;
+                }
+                Ok(None) => {}
+                Err(e) => return Err(e.raw_os_error().unwrap_or(ECHILD)),
+            }
+        }
+        if let Some((child_pid, _)) = exited {
+            self.children.remove(&child_pid);
+        }
+        if found {
+            Ok(exited)
+        } else {
+            Err(ECHILD)
+        }
+    }
+}
+
+/// Whether the child `pid` has exited, without waiting for it.
+#[cfg(unix)]
+fn try_wait(pid: i32, _child: &mut Option<Child>) -> io::Result<Option<ExitStatus>> {
+    use std::os::unix::process::ExitStatusExt;
+    let mut status = 0;
+    match unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(None),
+        _ => Ok(Some(ExitStatus::from_raw(status))),
+    }
+}
+
+#[cfg(not(unix))]
+fn try_wait(_pid: i32, child: &mut Option<Child>) -> io::Result<Option<ExitStatus>> {
+    match child {
+        Some(child) => child.try_wait(),
+        None => Ok(None),
+    }
+}
+
+/// A command running `program` for the guest, if it may, with the guest's
+/// stdio and, when it's confined, its working directory and the absolute
+/// paths among `args` resolved through its mounts.
+pub(crate) fn command(ctx: &mut Ctx, program: &str, args: &[String]) -> Result<Command, c_int> {
+    let data = get_emscripten_data(ctx);
+    if !data.commands.allows(program) {
+        return Err(EACCES);
+    }
+    let mut command = Command::new(program);
+    if let Some(cwd) = data.mounts.cwd() {
+        command.current_dir(data.mounts.resolve(cwd)?);
+        for arg in args {
+            if arg.starts_with('/') {
+                command.arg(data.mounts.resolve(arg)?);
+            } else {
+                command.arg(arg);
+            }
+        }
+    } else {
+        command.args(args);
+    }
+    command
+        .stdin(stdio(ctx, 0))
+        .stdout(stdio(ctx, 1))
+        .stderr(stdio(ctx, 2));
+    Ok(command)
+}
+
+/// A command running the command line `line` for the guest, like `sh -c`
+/// does. Unless the guest may run the shell, only lines made of plain words
+/// can run, as the program named by the first one.
+fn shell_command(ctx: &mut Ctx, line: &str) -> Result<Command, c_int> {
+    if !get_emscripten_data(ctx).commands.allows(SHELL) {
+        if let Some(words) = split_words(line) {
+            if let Some((program, args)) = words.split_first() {
+                return command(ctx, program, args);
+            }
+        }
+    }
+    command(ctx, SHELL, &["-c".to_string(), line.to_string()])
+}
+
+/// The words of the command line `line`, if the shell would run it as is:
+/// words can be quoted, but there must be nothing for the shell to expand,
+/// redirect or run separately, and no variable assignments.
+fn split_words(line: &str) -> Option<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '$' | '`' | '\\' => return None,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '=' if words.is_empty() => return None,
+            '\n' | '|' | '&' | ';' | '<' | '>' | '(' | ')' | '$' | '`' | '\\' | '*' | '?' | '['
+            | ']' | '#' | '~' | '{' | '}' | '!' => return None,
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Some(words)
+}
+
+/// What guest fd `fd` leads to, for a child to get as its own.
+#[cfg(unix)]
+fn stdio(ctx: &mut Ctx, fd: c_int) -> Stdio {
+    use std::os::unix::io::FromRawFd;
+    match fd_table::host_fd(ctx, fd) {
+        Ok(host) => match unsafe { libc::dup(host) } {
+            -1 => Stdio::null(),
+            host => unsafe { Stdio::from_raw_fd(host) },
+        },
+        Err(_) => Stdio::null(),
+    }
+}
+
+#[cfg(not(unix))]
+fn stdio(ctx: &mut Ctx, fd: c_int) -> Stdio {
+    match fd_table::host_fd(ctx, fd) {
+        Ok(_) => Stdio::inherit(),
+        Err(_) => Stdio::null(),
+    }
+}
+
+/// Start `command` as a child of the guest, returning its pid.
+fn spawn(ctx: &mut Ctx, command: &mut Command) -> io::Result<i32> {
+    let child = command.spawn()?;
+    let pid = child.id() as i32;
+    get_emscripten_data(ctx)
+        .children
+        .children
+        .insert(pid, Some(child));
+    Ok(pid)
+}
+
+/// `status` encoded the way the guest's `WIFEXITED` and friends expect.
+fn wait_status(status: ExitStatus) -> c_int {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return sig & 0x7f;
+        }
+    }
+    (status.code().unwrap_or(0) & 0xff) << 8
+}
+
+/// Wait for the child `pid`, or any child if it's -1 or 0, like `waitpid`
+/// does. Returns the pid of the child that exited and its wait status,
+/// a pid of 0 if none has yet and `options` has `WNOHANG`, or a negated
+/// errno. Signals for the guest are delivered while it waits, and cut
+/// the wait short.
+pub(crate) fn wait(ctx: &mut Ctx, pid: i32, options: c_int) -> Result<(i32, c_int), String> {
+    loop {
+        match get_emscripten_data(ctx).children.reap(pid) {
+            Ok(Some((pid, status))) => return Ok((pid, wait_status(status))),
+            Ok(None) if options & WNOHANG != 0 => return Ok((0, 0)),
+            Ok(None) => {}
+            Err(errno) => return Ok((-errno, 0)),
+        }
+        if !signal::sleep(ctx, Duration::from_millis(10))? {
+            return Ok((-EINTR, 0));
+        }
+    }
+}
+
+/// Wait for the child `pid` until it exits, whatever signals come.
+fn wait_for(ctx: &mut Ctx, pid: i32) -> Result<c_int, String> {
+    loop {
+        match wait(ctx, pid, 0)? {
+            (result, _) if result == -EINTR => {}
+            (result, _) if result < 0 => return Ok(-1),
+            (_, status) => return Ok(status),
+        }
+    }
+}
+
 pub fn abort_with_message(ctx: &mut Ctx, message: &str) {
     debug!("emscripten::abort_with_message");
     println!("{}", message);
@@ -21,11 +286,35 @@ pub fn _abort(_ctx: &mut Ctx) {
     }
 }
 
+/// Forks the host process, and the instance with it: the child goes on
+/// from this call with a copy of the guest's memory and fds and a return
+/// of 0, like a native process does, in the same sandbox as its parent.
+/// It starts out with no children and no pending signals or alarm.
+///
+/// Only the thread making the call is copied, as with any `fork`.
+#[cfg(unix)]
+pub fn _fork(ctx: &mut Ctx) -> PidT {
+    debug!("emscripten::_fork");
+    match unsafe { libc::fork() } {
+        -1 => -1,
+        0 => {
+            let data = get_emscripten_data(ctx);
+            data.children = Children::default();
+            data.signals.forked();
+            0
+        }
+        pid => {
+            get_emscripten_data(ctx).children.children.insert(pid, None);
+            pid
+        }
+    }
+}
+
+/// There's no way to copy a process, so this fails like it does in
+/// emscripten.
+#[cfg(not(unix))]
 pub fn _fork(_ctx: &mut Ctx) -> PidT {
     debug!("emscripten::_fork");
-    // unsafe {
-    //     fork()
-    // }
     -1
 }
 
@@ -33,11 +322,6 @@ pub fn _endgrent(_ctx: &mut Ctx) {
     debug!("emscripten::_endgrent");
 }
 
-pub fn _execve(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
-    debug!("emscripten::_execve");
-    -1
-}
-
 #[allow(unreachable_code)]
 pub fn _exit(_ctx: &mut Ctx, status: c_int) {
     // -> !
@@ -57,11 +341,6 @@ pub fn em_abort(ctx: &mut Ctx, message: u32) {
     }
 }
 
-pub fn _kill(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::_kill");
-    -1
-}
-
 pub fn _sched_yield(_ctx: &mut Ctx) -> i32 {
     debug!("emscripten::_sched_yield");
     -1
@@ -76,11 +355,6 @@ pub fn _llvm_stackrestore(_ctx: &mut Ctx, _one: i32) {
     debug!("emscripten::_llvm_stackrestore");
 }
 
-pub fn _raise(_ctx: &mut Ctx, _one: i32) -> i32 {
-    debug!("emscripten::_raise");
-    -1
-}
-
 pub fn _sem_init(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
     debug!("emscripten::_sem_init");
     -1
@@ -111,19 +385,39 @@ pub fn _setgroups(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn _setitimer(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
-    debug!("emscripten::_setitimer");
-    -1
-}
-
-pub fn _usleep(_ctx: &mut Ctx, _one: i32) -> i32 {
+pub fn _usleep(ctx: &mut Ctx, usec: u32) -> Result<i32, String> {
     debug!("emscripten::_usleep");
-    -1
+    let slept = signal::sleep(ctx, Duration::from_micros(usec.into()))?;
+    Ok(if slept { 0 } else { -1 })
 }
 
-pub fn _nanosleep(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+/// Like on the host, a signal cuts the sleep short and
+/// the time that was left is written to `rem`.
+pub fn _nanosleep(ctx: &mut Ctx, req: u32, rem: u32) -> Result<i32, String> {
     debug!("emscripten::_nanosleep");
-    -1
+    // struct timespec { time_t tv_sec; long tv_nsec; }, both 32 bits.
+    let (sec, nsec) = {
+        let view = ctx.memory(0).view::<u32>();
+        (
+            view[req as usize / 4].get(),
+            view[req as usize / 4 + 1].get(),
+        )
+    };
+    if nsec >= 1_000_000_000 {
+        return Ok(-1);
+    }
+    let duration = Duration::new(sec.into(), nsec);
+    let start = Instant::now();
+    if signal::sleep(ctx, duration)? {
+        return Ok(0);
+    }
+    if rem != 0 {
+        let left = duration.checked_sub(start.elapsed()).unwrap_or_default();
+        let view = ctx.memory(0).view::<u32>();
+        view[rem as usize / 4].set(left.as_secs() as u32);
+        view[rem as usize / 4 + 1].set(left.subsec_nanos());
+    }
+    Ok(-1)
 }
 
 pub fn _utimes(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
@@ -131,9 +425,15 @@ pub fn _utimes(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn _waitpid(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
+/// Only the children the module started with `fork`, `system` or
+/// `popen` can be waited for; it can't reach other host processes.
+pub fn _waitpid(ctx: &mut Ctx, pid: i32, status: u32, options: c_int) -> Result<i32, String> {
     debug!("emscripten::_waitpid");
-    -1
+    let (pid, wait_status) = wait(ctx, pid, options)?;
+    if pid > 0 && status != 0 {
+        ctx.memory(0).view::<i32>()[status as usize / 4].set(wait_status);
+    }
+    Ok(pid.max(-1))
 }
 
 pub fn abort_stack_overflow(ctx: &mut Ctx, _what: c_int) {
@@ -155,18 +455,278 @@ pub fn _llvm_eh_typeid_for(_ctx: &mut Ctx, _type_info_addr: u32) -> i32 {
     -1
 }
 
-pub fn _system(_ctx: &mut Ctx, _one: i32) -> c_int {
+/// emscripten: system(command: *const c_char) -> c_int
+pub fn _system(ctx: &mut Ctx, command: u32) -> Result<c_int, String> {
     debug!("emscripten::_system");
-    // TODO: May need to change this Em impl to a working version
-    eprintln!("Can't call external programs");
-    return EAGAIN;
+    // Whether there's a shell at all.
+    if command == 0 {
+        return Ok(get_emscripten_data(ctx).commands.allows(SHELL) as c_int);
+    }
+    let line = read_string_from_wasm(ctx.memory(0), command);
+    let mut command = match shell_command(ctx, &line) {
+        Ok(command) => command,
+        Err(_) => return Ok(-1),
+    };
+    let pid = match spawn(ctx, &mut command) {
+        Ok(pid) => pid,
+        Err(_) => return Ok(-1),
+    };
+    wait_for(ctx, pid)
 }
 
-pub fn _popen(_ctx: &mut Ctx, _one: i32, _two: i32) -> c_int {
+/// emscripten: popen(command: *const c_char, mode: *const c_char) -> *mut FILE
+///
+/// The stream is the guest libc's, so this needs the module to export
+/// `fdopen` and `fclose` to make one out of the pipe to the child.
+pub fn _popen(ctx: &mut Ctx, command: u32, mode: u32) -> Result<u32, String> {
     debug!("emscripten::_popen");
-    // TODO: May need to change this Em impl to a working version
-    eprintln!("Missing function: popen");
-    unsafe {
-        abort();
+    let data = get_emscripten_data(ctx);
+    if data.fdopen.is_none() || data.fclose.is_none() {
+        return Ok(0);
+    }
+    let reading = match ctx.memory(0).view::<u8>()[mode as usize].get() {
+        b'r' => true,
+        b'w' => false,
+        _ => return Ok(0),
+    };
+    let line = read_string_from_wasm(ctx.memory(0), command);
+    let mut command = match shell_command(ctx, &line) {
+        Ok(command) => command,
+        Err(_) => return Ok(0),
+    };
+    if reading {
+        command.stdout(Stdio::piped());
+    } else {
+        command.stdin(Stdio::piped());
+    }
+    let pid = match spawn(ctx, &mut command) {
+        Ok(pid) => pid,
+        Err(_) => return Ok(0),
+    };
+    let host = get_emscripten_data(ctx)
+        .children
+        .children
+        .get_mut(&pid)
+        .and_then(Option::as_mut)
+        .and_then(pipe_fd);
+    let fd = match host {
+        Some(host) => fd_table::guest_fd(ctx, host),
+        None => -1,
+    };
+    if fd < 0 {
+        return Ok(0);
+    }
+
+    let stream = get_emscripten_data(ctx)
+        .fdopen
+        .as_ref()
+        .expect("fdopen is None")
+        .call(fd, mode)
+        .map_err(|e| e.to_string())?;
+    if stream == 0 {
+        get_emscripten_data(ctx).fds.close(fd);
+    } else {
+        get_emscripten_data(ctx)
+            .children
+            .streams
+            .insert(stream, pid);
+    }
+    Ok(stream)
+}
+
+/// The host end of the pipe to a child `popen` started.
+#[cfg(unix)]
+fn pipe_fd(child: &mut Child) -> Option<c_int> {
+    use std::os::unix::io::IntoRawFd;
+    match (child.stdout.take(), child.stdin.take()) {
+        (Some(stdout), _) => Some(stdout.into_raw_fd()),
+        (_, Some(stdin)) => Some(stdin.into_raw_fd()),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn pipe_fd(_child: &mut Child) -> Option<c_int> {
+    None
+}
+
+/// emscripten: pclose(stream: *mut FILE) -> c_int
+pub fn _pclose(ctx: &mut Ctx, stream: u32) -> Result<c_int, String> {
+    debug!("emscripten::_pclose");
+    let pid = match get_emscripten_data(ctx).children.streams.remove(&stream) {
+        Some(pid) => pid,
+        None => return Ok(-1),
+    };
+    // The child sees the end of its input once the stream is closed.
+    get_emscripten_data(ctx)
+        .fclose
+        .as_ref()
+        .expect("fclose is None")
+        .call(stream)
+        .map_err(|e| e.to_string())?;
+    wait_for(ctx, pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        _fork, _pclose, _popen, _system, _waitpid, command, spawn, split_words, wait, Commands,
+        WNOHANG,
+    };
+    use crate::env::get_emscripten_data;
+    use crate::fd_table;
+    use crate::utils::tests::with_runtime;
+    use libc::ECHILD;
+    use wasmer_runtime_core::vm::Ctx;
+
+    const STATUS: u32 = 16;
+    const MODE: u32 = 32;
+    const LINE: u32 = 1024;
+
+    fn write_str(ctx: &mut Ctx, ptr: u32, s: &str) {
+        let view = ctx.memory(0).view::<u8>();
+        for (cell, byte) in view[ptr as usize..].iter().zip(s.bytes().chain(Some(0))) {
+            cell.set(byte);
+        }
+    }
+
+    fn system(ctx: &mut Ctx, line: &str) -> i32 {
+        write_str(ctx, LINE, line);
+        _system(ctx, LINE).unwrap()
+    }
+
+    fn popen(ctx: &mut Ctx, line: &str, mode: &str) -> u32 {
+        write_str(ctx, LINE, line);
+        write_str(ctx, MODE, mode);
+        _popen(ctx, LINE, MODE).unwrap()
+    }
+
+    fn status(ctx: &mut Ctx) -> i32 {
+        ctx.memory(0).view::<i32>()[STATUS as usize / 4].get()
+    }
+
+    #[test]
+    fn only_allowed_commands_run() {
+        let mut commands = Commands::default();
+        assert!(!commands.allows("sh"));
+        commands.allow("ls");
+        assert!(commands.allows("ls"));
+        assert!(!commands.allows("/bin/ls"));
+        assert!(Commands::all().allows("sh"));
+    }
+
+    #[test]
+    fn plain_command_lines_are_split_into_words() {
+        assert_eq!(
+            split_words(" ls  -l '/my dir' \"a b\"c ''"),
+            Some(vec![
+                "ls".to_string(),
+                "-l".to_string(),
+                "/my dir".to_string(),
+                "a bc".to_string(),
+                "".to_string(),
+            ])
+        );
+        assert_eq!(split_words("ls --color=auto").map(|w| w.len()), Some(2));
+        assert_eq!(split_words("'$HOME;'"), Some(vec!["$HOME;".to_string()]));
+        assert_eq!(split_words("ls; rm x"), None);
+        assert_eq!(split_words("cat < x"), None);
+        assert_eq!(split_words("echo $HOME"), None);
+        assert_eq!(split_words("echo \"$HOME\""), None);
+        assert_eq!(split_words("ls *"), None);
+        assert_eq!(split_words("A=1 ls"), None);
+        assert_eq!(split_words("echo 'unterminated"), None);
+    }
+
+    #[test]
+    fn system_runs_allowed_programs_and_reports_their_status() {
+        with_runtime(|ctx| {
+            // No shell, and nothing runs.
+            assert_eq!(_system(ctx, 0), Ok(0));
+            assert_eq!(system(ctx, "true"), -1);
+
+            get_emscripten_data(ctx).commands.allow("true");
+            get_emscripten_data(ctx).commands.allow("false");
+            assert_eq!(system(ctx, "true"), 0);
+            assert_eq!(system(ctx, "false"), 1 << 8);
+            // Running two needs the shell.
+            assert_eq!(system(ctx, "true; false"), -1);
+
+            get_emscripten_data(ctx).commands.allow("sh");
+            assert_eq!(_system(ctx, 0), Ok(1));
+            assert_eq!(system(ctx, "true; exit 3"), 3 << 8);
+            // Killed by SIGKILL.
+            assert_eq!(system(ctx, "kill -9 $$"), 9);
+        });
+    }
+
+    #[test]
+    fn popen_streams_lead_to_the_child() {
+        with_runtime(|ctx| {
+            assert_eq!(popen(ctx, "echo hello", "r"), 0);
+
+            get_emscripten_data(ctx).commands.allow("echo");
+            let stream = popen(ctx, "echo hello", "r");
+            assert_ne!(stream, 0);
+            let host = fd_table::host_fd(ctx, stream as i32 - 1000).unwrap();
+            let mut output = [0u8; 64];
+            let mut len = 0;
+            loop {
+                let read = unsafe {
+                    libc::read(
+                        host,
+                        output[len..].as_mut_ptr() as *mut _,
+                        output.len() - len,
+                    )
+                };
+                assert!(read >= 0);
+                if read == 0 {
+                    break;
+                }
+                len += read as usize;
+            }
+            assert_eq!(&output[..len], b"hello\n");
+            assert_eq!(_pclose(ctx, stream), Ok(0));
+            assert_eq!(_pclose(ctx, stream), Ok(-1));
+
+            // The child waits for the end of its input, which
+            // it gets once `pclose` closes the stream.
+            get_emscripten_data(ctx).commands = Commands::all();
+            let stream = popen(ctx, "cat > /dev/null; exit 4", "w");
+            assert_ne!(stream, 0);
+            let host = fd_table::host_fd(ctx, stream as i32 - 1000).unwrap();
+            assert_eq!(
+                unsafe { libc::write(host, b"data".as_ptr() as *const _, 4) },
+                4
+            );
+            assert_eq!(_pclose(ctx, stream), Ok(4 << 8));
+        });
+    }
+
+    #[test]
+    fn waitpid_only_waits_for_children() {
+        with_runtime(|ctx| {
+            assert_eq!(wait(ctx, -1, 0), Ok((-ECHILD, 0)));
+            assert_eq!(_waitpid(ctx, -1, STATUS, 0), Ok(-1));
+
+            // The forked child exits right away, before it
+            // could do anything a copy of a test shouldn't.
+            let pid = _fork(ctx);
+            if pid == 0 {
+                unsafe { libc::_exit(5) };
+            }
+            assert!(pid > 0);
+            assert_eq!(_waitpid(ctx, -1, STATUS, 0), Ok(pid));
+            assert_eq!(status(ctx), 5 << 8);
+            assert_eq!(wait(ctx, pid, 0), Ok((-ECHILD, 0)));
+
+            get_emscripten_data(ctx).commands.allow("sleep");
+            let mut sleep = command(ctx, "sleep", &["0.2".to_string()]).unwrap();
+            let pid = spawn(ctx, &mut sleep).unwrap();
+            assert_eq!(_waitpid(ctx, pid, STATUS, WNOHANG), Ok(0));
+            assert_eq!(_waitpid(ctx, pid, STATUS, 0), Ok(pid));
+            assert_eq!(status(ctx), 0);
+            assert_eq!(_waitpid(ctx, pid, STATUS, WNOHANG), Ok(-1));
+        });
     }
 }
@@ -1,6 +1,244 @@
+//! Signals, as emscripten modules see them.
+//!
+//! They never come from the host: the guest raises them itself, with
+//! `raise` and `kill`, or has `alarm` and `setitimer` raise `SIGALRM`.
+//! Pending signals are delivered by calling the guest's handlers from
+//! the table when it calls into the signal, sleep and time functions,
+//! or when its `read`, `write` and socket calls return. The alarm also
+//! interrupts those calls, and `poll` and `select`, while they block,
+//! unless the handler asks for `SA_RESTART`, as `signal` does.
+//!
+//! They aren't delivered when other imports return, and guest code
+//! can't be interrupted while it runs either. A `SIGALRM` that's due
+//! while the guest only computes, or only calls other imports, waits
+//! until it next calls one of the functions above.
+
 // use super::varargs::VarArgs;
+use crate::env::get_emscripten_data;
+use libc::c_int;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
 use wasmer_runtime_core::vm::Ctx;
 
+// The numbers and flags of emscripten's libc, whatever the host's are.
+const SIG_DFL: u32 = 0;
+const SIG_IGN: u32 = 1;
+const SIG_ERR: i32 = -1;
+const SIGKILL: c_int = 9;
+const SIGALRM: c_int = 14;
+const SIGCHLD: c_int = 17;
+const SIGCONT: c_int = 18;
+const SIGSTOP: c_int = 19;
+const SIGTSTP: c_int = 20;
+const SIGTTIN: c_int = 21;
+const SIGTTOU: c_int = 22;
+const SIGURG: c_int = 23;
+const SIGWINCH: c_int = 28;
+const NSIG: c_int = 65;
+const SA_SIGINFO: c_int = 4;
+const SA_RESTART: c_int = 0x1000_0000;
+const SA_NODEFER: c_int = 0x4000_0000;
+const SA_RESETHAND: c_int = 0x8000_0000u32 as c_int;
+const SIG_BLOCK: c_int = 0;
+const SIG_UNBLOCK: c_int = 1;
+const SIG_SETMASK: c_int = 2;
+const ITIMER_REAL: c_int = 0;
+
+/// The signals of an instance.
+#[derive(Debug, Default)]
+pub(crate) struct Signals {
+    /// The handler and `sa_flags` of each signal that has one. Handlers
+    /// are `SIG_DFL`, `SIG_IGN` or table indices.
+    actions: HashMap<c_int, (u32, c_int)>,
+    /// The signals raised and not delivered yet, one bit each.
+    pending: u64,
+    /// The signals blocked with `sigprocmask`.
+    blocked: u64,
+    /// When `SIGALRM` is due, and the interval to raise it again after.
+    alarm: Option<(Instant, Option<Duration>)>,
+}
+
+impl Signals {
+    fn raise(&mut self, signum: c_int) {
+        self.pending |= bit(signum);
+    }
+
+    /// Raise `SIGALRM` if it's due.
+    fn check_alarm(&mut self, now: Instant) {
+        if let Some((deadline, interval)) = self.alarm {
+            if now >= deadline {
+                self.raise(SIGALRM);
+                self.alarm = interval.map(|interval| (now + interval, Some(interval)));
+            }
+        }
+    }
+
+    /// Forget what only the parent of a fork has: its pending signals and
+    /// its alarm. Handlers and the signal mask are inherited.
+    pub(crate) fn forked(&mut self) {
+        self.pending = 0;
+        self.alarm = None;
+    }
+
+    /// Whether a signal will be delivered when the guest next gets the chance.
+    fn ready(&self) -> bool {
+        self.pending & !self.blocked != 0
+    }
+}
+
+fn bit(signum: c_int) -> u64 {
+    1 << (signum - 1)
+}
+
+fn is_valid(signum: c_int) -> bool {
+    signum > 0 && signum < NSIG
+}
+
+/// Signals that do nothing at all when they have no handler.
+fn ignored_by_default(signum: c_int) -> bool {
+    match signum {
+        SIGCHLD | SIGCONT | SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU | SIGURG | SIGWINCH => true,
+        _ => false,
+    }
+}
+
+/// Deliver the pending signals that aren't blocked, lowest first.
+/// Signals left to their default action end the program with a trap.
+/// Returns whether a handler without `SA_RESTART` ran, which interrupts
+/// the call the guest is blocked in.
+pub(crate) fn deliver(ctx: &mut Ctx) -> Result<bool, String> {
+    let mut interrupting = false;
+    loop {
+        let signals = &mut get_emscripten_data(ctx).signals;
+        signals.check_alarm(Instant::now());
+        let ready = signals.pending & !signals.blocked;
+        if ready == 0 {
+            return Ok(interrupting);
+        }
+        let signum = ready.trailing_zeros() as c_int + 1;
+        signals.pending &= !bit(signum);
+
+        let (handler, flags) = signals
+            .actions
+            .get(&signum)
+            .cloned()
+            .unwrap_or((SIG_DFL, 0));
+        match handler {
+            SIG_IGN => {}
+            SIG_DFL if ignored_by_default(signum) => {}
+            SIG_DFL => return Err(format!("terminated by signal {}", signum)),
+            handler => {
+                if flags & SA_RESETHAND != 0 {
+                    signals.actions.remove(&signum);
+                }
+                let blocked = signals.blocked;
+                interrupting |= flags & SA_RESTART == 0;
+                if flags & SA_NODEFER == 0 {
+                    signals.blocked |= bit(signum);
+                }
+                let data = get_emscripten_data(ctx);
+                let result = if flags & SA_SIGINFO != 0 {
+                    data.dyn_call_viii
+                        .as_ref()
+                        .expect("dyn_call_viii is None")
+                        .call(handler as i32, signum, 0, 0)
+                } else {
+                    data.dyn_call_vi
+                        .as_ref()
+                        .expect("dyn_call_vi is None")
+                        .call(handler as i32, signum)
+                };
+                get_emscripten_data(ctx).signals.blocked = blocked;
+                result.map_err(|e| e.to_string())?;
+            }
+        }
+    }
+}
+
+/// Sleep for `duration`, or until a signal is delivered.
+/// Returns whether the whole time went by.
+pub(crate) fn sleep(ctx: &mut Ctx, duration: Duration) -> Result<bool, String> {
+    let end = Instant::now() + duration;
+    loop {
+        deliver(ctx)?;
+        let now = Instant::now();
+        if now >= end {
+            return Ok(true);
+        }
+        let wake = match get_emscripten_data(ctx).signals.alarm {
+            Some((deadline, _)) if deadline < end => deadline,
+            _ => end,
+        };
+        if wake > now {
+            thread::sleep(wake - now);
+        }
+        let signals = &mut get_emscripten_data(ctx).signals;
+        signals.check_alarm(Instant::now());
+        if signals.ready() {
+            deliver(ctx)?;
+            return Ok(false);
+        }
+    }
+}
+
+/// When the alarm is next due, for calls that block on the host
+/// and have to wake up in time to raise it.
+pub(crate) fn next_alarm(ctx: &mut Ctx) -> Option<Instant> {
+    get_emscripten_data(ctx)
+        .signals
+        .alarm
+        .map(|(deadline, _)| deadline)
+}
+
+/// Deliver the signals raised while blocked on the host, if any.
+/// Returns whether there were some, for the call to fail with `EINTR`.
+/// If the call is one `SA_RESTART` restarts, as told by `restart`, only
+/// handlers without it count.
+pub(crate) fn interrupted(ctx: &mut Ctx, restart: bool) -> Result<bool, String> {
+    let signals = &mut get_emscripten_data(ctx).signals;
+    signals.check_alarm(Instant::now());
+    if !signals.ready() {
+        return Ok(false);
+    }
+    let interrupting = deliver(ctx)?;
+    Ok(interrupting || !restart)
+}
+
+/// Wait until a signal is delivered. Only the alarm can raise one
+/// meanwhile, so without it the guest would wait forever.
+fn wait(ctx: &mut Ctx) -> Result<(), String> {
+    loop {
+        let signals = &mut get_emscripten_data(ctx).signals;
+        signals.check_alarm(Instant::now());
+        if signals.ready() {
+            return deliver(ctx).map(|_| ());
+        }
+        match signals.alarm {
+            Some((deadline, _)) => {
+                let now = Instant::now();
+                if deadline > now {
+                    thread::sleep(deadline - now);
+                }
+            }
+            None => return Err("waiting for a signal that can never come".to_string()),
+        }
+    }
+}
+
+fn read_sigset(ctx: &Ctx, set: u32) -> u64 {
+    let view = ctx.memory(0).view::<u32>();
+    let index = set as usize / 4;
+    u64::from(view[index].get()) | u64::from(view[index + 1].get()) << 32
+}
+
+fn write_sigset(ctx: &Ctx, set: u32, signals: u64) {
+    let view = ctx.memory(0).view::<u32>();
+    let index = set as usize / 4;
+    view[index].set(signals as u32);
+    view[index + 1].set((signals >> 32) as u32);
+}
+
 #[allow(clippy::cast_ptr_alignment)]
 pub fn _sigemptyset(ctx: &mut Ctx, set: u32) -> i32 {
     debug!("emscripten::_sigemptyset");
@@ -11,8 +249,36 @@ pub fn _sigemptyset(ctx: &mut Ctx, set: u32) -> i32 {
     0
 }
 
-pub fn _sigaction(_ctx: &mut Ctx, _signum: u32, _act: u32, _oldact: u32) -> i32 {
-    debug!("emscripten::_sigaction {}, {}, {}", _signum, _act, _oldact);
+pub fn _sigaction(ctx: &mut Ctx, signum: c_int, act: u32, oldact: u32) -> i32 {
+    debug!("emscripten::_sigaction {}, {}, {}", signum, act, oldact);
+    if !is_valid(signum) || (act != 0 && (signum == SIGKILL || signum == SIGSTOP)) {
+        return -1;
+    }
+    // struct sigaction { handler; sigset_t sa_mask (128 bytes); int sa_flags; ... }
+    let (handler, flags) = get_emscripten_data(ctx)
+        .signals
+        .actions
+        .get(&signum)
+        .cloned()
+        .unwrap_or((SIG_DFL, 0));
+    if oldact != 0 {
+        let view = ctx.memory(0).view::<u32>();
+        let index = oldact as usize / 4;
+        view[index].set(handler);
+        for word in &view[index + 1..index + 33] {
+            word.set(0);
+        }
+        view[index + 33].set(flags as u32);
+    }
+    if act != 0 {
+        let view = ctx.memory(0).view::<u32>();
+        let index = act as usize / 4;
+        let action = (view[index].get(), view[index + 33].get() as c_int);
+        get_emscripten_data(ctx)
+            .signals
+            .actions
+            .insert(signum, action);
+    }
     0
 }
 
@@ -26,17 +292,238 @@ pub fn _sigaddset(ctx: &mut Ctx, set: u32, signum: u32) -> i32 {
     0
 }
 
-pub fn _sigsuspend(_ctx: &mut Ctx, _one: i32) -> i32 {
+pub fn _sigsuspend(ctx: &mut Ctx, mask: u32) -> Result<i32, String> {
     debug!("emscripten::_sigsuspend");
-    -1
+    let mask = read_sigset(ctx, mask);
+    let blocked = std::mem::replace(&mut get_emscripten_data(ctx).signals.blocked, mask);
+    let result = wait(ctx);
+    get_emscripten_data(ctx).signals.blocked = blocked;
+    result.map(|()| -1)
 }
 
-pub fn _sigprocmask(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
+pub fn _sigprocmask(ctx: &mut Ctx, how: c_int, set: u32, oldset: u32) -> Result<i32, String> {
     debug!("emscripten::_sigprocmask");
+    let blocked = get_emscripten_data(ctx).signals.blocked;
+    if oldset != 0 {
+        write_sigset(ctx, oldset, blocked);
+    }
+    if set != 0 {
+        let set = read_sigset(ctx, set);
+        let blocked = match how {
+            SIG_BLOCK => blocked | set,
+            SIG_UNBLOCK => blocked & !set,
+            SIG_SETMASK => set,
+            _ => return Ok(-1),
+        };
+        // SIGKILL and SIGSTOP can't be blocked.
+        get_emscripten_data(ctx).signals.blocked = blocked & !(bit(SIGKILL) | bit(SIGSTOP));
+    }
+    deliver(ctx)?;
+    Ok(0)
+}
+
+/// Like musl's, the handler restarts the calls it interrupts.
+pub fn _signal(ctx: &mut Ctx, sig: c_int, handler: u32) -> i32 {
+    debug!("emscripten::_signal ({})", sig);
+    if !is_valid(sig) || sig == SIGKILL || sig == SIGSTOP {
+        return SIG_ERR;
+    }
+    get_emscripten_data(ctx)
+        .signals
+        .actions
+        .insert(sig, (handler, SA_RESTART))
+        .map_or(SIG_DFL, |(previous, _)| previous) as i32
+}
+
+pub fn _raise(ctx: &mut Ctx, sig: c_int) -> Result<i32, String> {
+    debug!("emscripten::_raise");
+    if !is_valid(sig) {
+        return Ok(-1);
+    }
+    get_emscripten_data(ctx).signals.raise(sig);
+    deliver(ctx)?;
+    Ok(0)
+}
+
+/// Only the guest itself can be sent signals.
+pub fn _kill(ctx: &mut Ctx, pid: i32, sig: c_int) -> Result<i32, String> {
+    debug!("emscripten::_kill");
+    let own_pid = unsafe { libc::getpid() } as i32;
+    if pid != own_pid && pid != 0 && pid != -1 {
+        return Ok(-1);
+    }
+    if sig == 0 {
+        return Ok(0);
+    }
+    _raise(ctx, sig)
+}
+
+pub fn _pause(ctx: &mut Ctx) -> Result<i32, String> {
+    debug!("emscripten::_pause");
+    wait(ctx).map(|()| -1)
+}
+
+/// The time left until `deadline`.
+fn remaining(deadline: Instant) -> Duration {
+    let now = Instant::now();
+    if deadline > now {
+        deadline - now
+    } else {
+        Duration::from_secs(0)
+    }
+}
+
+pub fn _alarm(ctx: &mut Ctx, seconds: u32) -> u32 {
+    debug!("emscripten::_alarm {}", seconds);
+    let signals = &mut get_emscripten_data(ctx).signals;
+    let previous = signals.alarm.take().map_or(0, |(deadline, _)| {
+        let left = remaining(deadline);
+        // A pending alarm is never reported as 0 seconds away.
+        (left.as_secs() as u32 + (left.subsec_nanos() > 0) as u32).max(1)
+    });
+    if seconds > 0 {
+        signals.alarm = Some((Instant::now() + Duration::from_secs(seconds.into()), None));
+    }
+    previous
+}
+
+// struct itimerval { struct timeval it_interval, it_value; }, with
+// struct timeval { time_t tv_sec; suseconds_t tv_usec; }, all 32 bits.
+fn write_itimerval(ctx: &Ctx, ptr: u32, alarm: Option<(Instant, Option<Duration>)>) {
+    let (value, interval) = match alarm {
+        Some((deadline, interval)) => (remaining(deadline), interval.unwrap_or_default()),
+        None => (Duration::default(), Duration::default()),
+    };
+    let view = ctx.memory(0).view::<u32>();
+    let index = ptr as usize / 4;
+    view[index].set(interval.as_secs() as u32);
+    view[index + 1].set(interval.subsec_micros());
+    view[index + 2].set(value.as_secs() as u32);
+    view[index + 3].set(value.subsec_micros());
+}
+
+fn read_timeval(ctx: &Ctx, ptr: u32) -> Duration {
+    let view = ctx.memory(0).view::<u32>();
+    let index = ptr as usize / 4;
+    Duration::from_secs(view[index].get().into())
+        + Duration::from_micros(view[index + 1].get().into())
+}
+
+pub fn _getitimer(ctx: &mut Ctx, which: c_int, curr_value: u32) -> i32 {
+    debug!("emscripten::_getitimer");
+    if which != ITIMER_REAL {
+        return -1;
+    }
+    let alarm = get_emscripten_data(ctx).signals.alarm;
+    write_itimerval(ctx, curr_value, alarm);
     0
 }
 
-pub fn _signal(_ctx: &mut Ctx, _sig: u32, _two: i32) -> i32 {
-    debug!("emscripten::_signal ({})", _sig);
+/// Only `ITIMER_REAL` is supported, the others counting time
+/// the guest spends running, which isn't tracked.
+pub fn _setitimer(ctx: &mut Ctx, which: c_int, new_value: u32, old_value: u32) -> i32 {
+    debug!("emscripten::_setitimer");
+    if which != ITIMER_REAL {
+        return -1;
+    }
+    let alarm = get_emscripten_data(ctx).signals.alarm;
+    if old_value != 0 {
+        write_itimerval(ctx, old_value, alarm);
+    }
+    let interval = read_timeval(ctx, new_value);
+    let value = read_timeval(ctx, new_value + 8);
+    get_emscripten_data(ctx).signals.alarm = if value == Duration::default() {
+        None
+    } else if interval == Duration::default() {
+        Some((Instant::now() + value, None))
+    } else {
+        Some((Instant::now() + value, Some(interval)))
+    };
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::tests::get_compiler;
+    use crate::{
+        generate_emscripten_env, invoke_emscripten_function_with_config, EmscriptenConfig,
+        EmscriptenGlobals,
+    };
+    use std::time::{Duration, Instant};
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{
+        compile_with,
+        error::{CallError, CallResult},
+        types::Value,
+    };
+
+    /// Call `name` in a fresh instance of the signals fixture.
+    fn call(name: &str) -> CallResult<Vec<Value>> {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/signals.wast");
+        let wasm = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        let module = compile_with(&wasm, &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module.instantiate(&import_object).unwrap();
+        invoke_emscripten_function_with_config(
+            &mut instance,
+            name,
+            &[],
+            EmscriptenConfig::default(),
+        )
+    }
+
+    #[test]
+    fn raised_signals_reach_their_handler() {
+        assert_eq!(call("_raise_handled").unwrap(), vec![Value::I32(10)]);
+    }
+
+    #[test]
+    fn ignored_signals_are_dropped() {
+        // Nothing was caught, and sigaction reports SIG_IGN.
+        assert_eq!(call("_raise_ignored").unwrap(), vec![Value::I32(1)]);
+    }
+
+    #[test]
+    fn unhandled_signals_end_the_program() {
+        match call("_raise_unhandled") {
+            Err(CallError::Runtime(_)) => {}
+            result => panic!("expected a trap, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn timers_are_reported() {
+        // An interval of 1s, with between 1s and 2s left.
+        assert_eq!(call("_timer_is_reported").unwrap(), vec![Value::I32(11)]);
+    }
+
+    #[test]
+    fn timers_interrupt_sleep() {
+        let start = Instant::now();
+        assert_eq!(
+            call("_alarm_interrupts_sleep").unwrap(),
+            vec![Value::I32(141)]
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn timers_interrupt_reads() {
+        let start = Instant::now();
+        assert_eq!(
+            call("_alarm_interrupts_read").unwrap(),
+            vec![Value::I32(141)]
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn timers_restart_reads_with_sa_restart() {
+        // The handler wrote the `*` the restarted read got.
+        assert_eq!(
+            call("_alarm_restarts_read").unwrap(),
+            vec![Value::I32(14_000 + 100 + 42)]
+        );
+    }
+}
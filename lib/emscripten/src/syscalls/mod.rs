@@ -21,8 +21,7 @@ use libc::{
     c_void,
     chdir,
     // fcntl, setsockopt, getppid
-    close,
-    dup2,
+    dup,
     exit,
     fstat,
     getpid,
@@ -37,11 +36,17 @@ use libc::{
     stat,
     write,
     // sockaddr_in,
+    EBADF,
+    EINVAL,
+    EMFILE,
+    ERANGE,
 };
 use wasmer_runtime_core::vm::Ctx;
 
 use super::env;
-use std::cell::Cell;
+use super::fd_table;
+use super::fs;
+use std::ffi::CStr;
 #[allow(unused_imports)]
 use std::io::Error;
 use std::mem;
@@ -57,28 +62,40 @@ pub fn ___syscall1(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) {
 }
 
 /// read
-pub fn ___syscall3(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
+pub fn ___syscall3(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> Result<i32, String> {
     // -> ssize_t
     debug!("emscripten::___syscall3 (read) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return Ok(errno),
+    };
     let buf: u32 = varargs.get(ctx);
     let count: i32 = varargs.get(ctx);
     debug!("=> fd: {}, buf_offset: {}, count: {}", fd, buf, count);
-    let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut c_void;
-    let ret = unsafe { read(fd, buf_addr, count as _) };
-    debug!("=> ret: {}", ret);
-    ret as _
+    interruptible(ctx, fd, Some(POLLIN), |ctx| {
+        let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut c_void;
+        let ret = unsafe { read(fd, buf_addr, count as _) };
+        debug!("=> ret: {}", ret);
+        ret as _
+    })
 }
 
 /// write
-pub fn ___syscall4(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall4(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> Result<c_int, String> {
     debug!("emscripten::___syscall4 (write) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return Ok(errno),
+    };
     let buf: u32 = varargs.get(ctx);
     let count: i32 = varargs.get(ctx);
     debug!("=> fd: {}, buf: {}, count: {}", fd, buf, count);
-    let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *const c_void;
-    unsafe { write(fd, buf_addr, count as _) as i32 }
+    interruptible(ctx, fd, Some(POLLOUT), |ctx| {
+        let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *const c_void;
+        unsafe { write(fd, buf_addr, count as _) as i32 }
+    })
 }
 
 /// close
@@ -86,20 +103,37 @@ pub fn ___syscall6(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall6 (close) {}", _which);
     let fd: i32 = varargs.get(ctx);
     debug!("fd: {}", fd);
-    unsafe { close(fd) }
+    match env::get_emscripten_data(ctx).fds.close(fd) {
+        Some(ret) => ret,
+        None => -EBADF,
+    }
 }
 
 // chdir
 pub fn ___syscall12(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall12 (chdir) {}", _which);
-    let path_addr: i32 = varargs.get(ctx);
-    unsafe {
+    let path_addr: u32 = varargs.get(ctx);
+    if env::get_emscripten_data(ctx).mounts.cwd().is_some() {
+        // A confined guest only has a working directory of its own.
         let path_ptr = emscripten_memory_pointer!(ctx.memory(0), path_addr) as *const i8;
-        let _path = std::ffi::CStr::from_ptr(path_ptr);
-        let ret = chdir(path_ptr);
-        debug!("=> path: {:?}, ret: {}", _path, ret);
-        ret
+        let path = match unsafe { CStr::from_ptr(path_ptr) }.to_str() {
+            Ok(path) => path.to_string(),
+            Err(_) => return -EINVAL,
+        };
+        let ret = match env::get_emscripten_data(ctx).mounts.chdir(&path) {
+            Ok(()) => 0,
+            Err(errno) => -errno,
+        };
+        debug!("=> path: {}, ret: {}", path, ret);
+        return ret;
     }
+    let path = match fs::host_path(ctx, path_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let ret = unsafe { chdir(path.as_ptr()) };
+    debug!("=> path: {:?}, ret: {}", path, ret);
+    ret
 }
 
 pub fn ___syscall10(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
@@ -127,8 +161,11 @@ pub fn ___syscall38(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
 pub fn ___syscall40(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall40 (rmdir)");
     let pathname: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    unsafe { rmdir(pathname_addr) }
+    let path = match fs::host_path(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    unsafe { rmdir(path.as_ptr()) }
 }
 
 // pipe
@@ -137,24 +174,35 @@ pub fn ___syscall42(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     // offset to a file descriptor, which contains a read end and write end, 2 integers
     let fd_offset: u32 = varargs.get(ctx);
 
-    let emscripten_memory = ctx.memory(0);
-
-    // convert the file descriptor into a vec with two slots
-    let mut fd_vec: Vec<c_int> = emscripten_memory.view()[((fd_offset / 4) as usize)..]
-        .iter()
-        .map(|pipe_end: &Cell<c_int>| pipe_end.get())
-        .take(2)
-        .collect();
+    let mut host_fds: [c_int; 2] = [0; 2];
 
-    // get it as a mutable pointer
-    let fd_ptr = fd_vec.as_mut_ptr();
-
-    // call pipe and store the pointers in this array
+    // call pipe and store the host ends in this array
     #[cfg(target_os = "windows")]
-    let result: c_int = unsafe { libc::pipe(fd_ptr, 2048, 0) };
+    let result: c_int = unsafe { libc::pipe(host_fds.as_mut_ptr(), 2048, 0) };
     #[cfg(not(target_os = "windows"))]
-    let result: c_int = unsafe { libc::pipe(fd_ptr) };
-    result
+    let result: c_int = unsafe { libc::pipe(host_fds.as_mut_ptr()) };
+    if result != 0 {
+        return result;
+    }
+
+    let read_end = fd_table::guest_fd(ctx, host_fds[0]);
+    let write_end = fd_table::guest_fd(ctx, host_fds[1]);
+    if read_end < 0 || write_end < 0 {
+        let fds = &mut env::get_emscripten_data(ctx).fds;
+        if read_end >= 0 {
+            fds.close(read_end);
+        }
+        if write_end >= 0 {
+            fds.close(write_end);
+        }
+        return -EMFILE;
+    }
+
+    let view = ctx.memory(0).view::<c_int>();
+    let index = (fd_offset / 4) as usize;
+    view[index].set(read_end);
+    view[index + 1].set(write_end);
+    0
 }
 
 pub fn ___syscall60(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
@@ -169,7 +217,22 @@ pub fn ___syscall63(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     let src: i32 = varargs.get(ctx);
     let dst: i32 = varargs.get(ctx);
 
-    unsafe { dup2(src, dst) }
+    let host_src = match fd_table::host_fd(ctx, src) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
+    if dst < 0 || dst >= fd_table::MAX_FDS {
+        return -EBADF;
+    }
+    if src == dst {
+        return dst;
+    }
+    let host_dst = unsafe { dup(host_src) };
+    if host_dst < 0 {
+        return host_dst;
+    }
+    env::get_emscripten_data(ctx).fds.insert_at(dst, host_dst);
+    dst
 }
 
 // getppid
@@ -211,12 +274,16 @@ pub fn ___syscall110(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
 // getcwd
 pub fn ___syscall183(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::___syscall183");
-    use std::env;
     let buf_offset: c_int = varargs.get(ctx);
-    let _size: c_int = varargs.get(ctx);
-    let path = env::current_dir();
-    let path_string = path.unwrap().display().to_string();
+    let size: c_int = varargs.get(ctx);
+    let path_string = match env::get_emscripten_data(ctx).mounts.cwd() {
+        Some(cwd) => cwd.to_string(),
+        None => std::env::current_dir().unwrap().display().to_string(),
+    };
     let len = path_string.len();
+    if len >= size as usize {
+        return -ERANGE;
+    }
     unsafe {
         let pointer_to_buffer =
             emscripten_memory_pointer!(ctx.memory(0), buf_offset) as *mut libc::c_char;
@@ -260,6 +327,10 @@ pub fn ___syscall140(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
     // -> c_int
     debug!("emscripten::___syscall140 (lseek) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let _ = varargs.get::<i32>(ctx); // ignore high offset
     let offset_low: i32 = varargs.get(ctx);
     let result_ptr_value = varargs.get::<i32>(ctx);
@@ -286,11 +357,15 @@ pub fn ___syscall140(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
 
 /// readv
 #[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall145(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32 {
+pub fn ___syscall145(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> Result<i32, String> {
     // -> ssize_t
     debug!("emscripten::___syscall145 (readv) {}", _which);
 
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return Ok(errno),
+    };
     let iov: i32 = varargs.get(ctx);
     let iovcnt: i32 = varargs.get(ctx);
 
@@ -301,32 +376,38 @@ pub fn ___syscall145(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32
     }
 
     debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
-    let mut ret = 0;
-    unsafe {
-        for i in 0..iovcnt {
-            let guest_iov_addr =
-                emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
-            let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
-                as *mut c_void;
-            let iov_len = (*guest_iov_addr).iov_len as _;
-            // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = read(fd, iov_base, iov_len);
-            if curr < 0 {
-                return -1;
+    interruptible(ctx, fd, Some(POLLIN), |ctx| {
+        let mut ret = 0;
+        unsafe {
+            for i in 0..iovcnt {
+                let guest_iov_addr =
+                    emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
+                let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
+                    as *mut c_void;
+                let iov_len = (*guest_iov_addr).iov_len as _;
+                // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
+                let curr = read(fd, iov_base, iov_len);
+                if curr < 0 {
+                    return -1;
+                }
+                ret += curr;
             }
-            ret += curr;
+            // debug!(" => ret: {}", ret);
+            ret as _
         }
-        // debug!(" => ret: {}", ret);
-        ret as _
-    }
+    })
 }
 
 // writev
 #[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall146(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
+pub fn ___syscall146(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> Result<i32, String> {
     // -> ssize_t
     debug!("emscripten::___syscall146 (writev) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return Ok(errno),
+    };
     let iov: i32 = varargs.get(ctx);
     let iovcnt: i32 = varargs.get(ctx);
 
@@ -337,29 +418,26 @@ pub fn ___syscall146(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
     }
 
     debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
-    let mut ret = 0;
-    unsafe {
-        for i in 0..iovcnt {
-            let guest_iov_addr =
-                emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
-            let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
-                as *const c_void;
-            let iov_len = (*guest_iov_addr).iov_len as _;
-            // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = write(fd, iov_base, iov_len);
-            if curr < 0 {
-                return -1;
+    interruptible(ctx, fd, Some(POLLOUT), |ctx| {
+        let mut ret = 0;
+        unsafe {
+            for i in 0..iovcnt {
+                let guest_iov_addr =
+                    emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
+                let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
+                    as *const c_void;
+                let iov_len = (*guest_iov_addr).iov_len as _;
+                // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
+                let curr = write(fd, iov_base, iov_len);
+                if curr < 0 {
+                    return -1;
+                }
+                ret += curr;
             }
-            ret += curr;
+            // debug!(" => ret: {}", ret);
+            ret as _
         }
-        // debug!(" => ret: {}", ret);
-        ret as _
-    }
-}
-
-pub fn ___syscall168(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall168");
-    -1
+    })
 }
 
 pub fn ___syscall191(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
@@ -378,16 +456,19 @@ pub fn ___syscall195(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     let pathname: u32 = varargs.get(ctx);
     let buf: u32 = varargs.get(ctx);
 
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
+    let path = match fs::host_path(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
 
     unsafe {
         let mut _stat: stat = std::mem::zeroed();
-        let ret = stat(pathname_addr, &mut _stat);
+        let ret = stat(path.as_ptr(), &mut _stat);
         debug!(
-            "=> pathname: {}, buf: {}, path: {} = {}\nlast os error: {}",
+            "=> pathname: {}, buf: {}, path: {:?} = {}\nlast os error: {}",
             pathname,
             buf,
-            std::ffi::CStr::from_ptr(pathname_addr).to_str().unwrap(),
+            path,
             ret,
             Error::last_os_error()
         );
@@ -403,6 +484,10 @@ pub fn ___syscall195(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 pub fn ___syscall197(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall197 (fstat64) {}", _which);
     let fd: c_int = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let buf: u32 = varargs.get(ctx);
 
     unsafe {
@@ -429,6 +514,10 @@ pub fn ___syscall221(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     // fcntl64
     let _fd: i32 = varargs.get(ctx);
     let cmd: u32 = varargs.get(ctx);
+    let _host_fd = match fd_table::host_fd(ctx, _fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     // (FAPPEND   - 0x08
     // |FASYNC    - 0x40
     // |FFSYNC    - 0x80
@@ -436,6 +525,13 @@ pub fn ___syscall221(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     debug!("=> fd: {}, cmd: {}", _fd, cmd);
     match cmd {
         2 => 0,
+        #[cfg(unix)]
+        3 => crate::net::get_status_flags(_host_fd),
+        #[cfg(unix)]
+        4 => {
+            let flags: c_int = varargs.get(ctx);
+            crate::net::set_status_flags(_host_fd, flags)
+        }
         13 | 14 => 0, // pretend file locking worked
         _ => -1,
     }
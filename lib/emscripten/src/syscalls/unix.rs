@@ -2,17 +2,12 @@ use crate::varargs::VarArgs;
 /// NOTE: TODO: These syscalls only support wasm_32 for now because they assume offsets are u32
 /// Syscall list: https://www.cs.utexas.edu/~bismith/test/syscalls/syscalls32.html
 use libc::{
-    accept,
     access,
-    bind,
-    c_char,
     c_int,
     c_void,
     chown,
-    // fcntl, setsockopt, getppid
-    connect,
+    // fcntl, getppid
     dup,
-    dup2,
     fchmod,
     fchown,
     fcntl,
@@ -20,85 +15,69 @@ use libc::{
     fsync,
     getgid,
     getgroups,
-    getpeername,
     getrusage,
-    getsockname,
-    getsockopt,
     gid_t,
-    in_addr_t,
-    in_port_t,
     ioctl,
+    iovec,
     lchown,
     link,
-    // iovec,
-    listen,
     mkdir,
     mode_t,
     msghdr,
+    nfds_t,
     nice,
     off_t,
     open,
     pid_t,
+    poll,
+    pollfd,
     pread,
     pwrite,
     // readv,
-    recvfrom,
-    recvmsg,
     // ENOTTY,
     rusage,
-    sa_family_t,
     // writev,
-    select,
-    sendmsg,
-    sendto,
     setpgid,
-    setsockopt,
     size_t,
     sockaddr,
-    socket,
-    socklen_t,
+    sockaddr_storage,
     stat,
     symlink,
     uid_t,
     uname,
     utsname,
+    EBADF,
+    EINTR,
     EINVAL,
-    // sockaddr_in,
-    FIOCLEX,
     FIONBIO,
     F_GETFD,
     F_SETFD,
-    SOL_SOCKET,
-    SO_REUSEADDR,
     TIOCGWINSZ,
 };
 use wasmer_runtime_core::vm::Ctx;
 
-use crate::utils;
+use crate::env::get_emscripten_data;
+use crate::net::{self, POLLERR, POLLHUP, POLLNVAL, POLLPRI};
+pub use crate::net::{POLLIN, POLLOUT};
+use crate::{fd_table, fs, process, signal, utils};
+use std::ffi::CStr;
 #[allow(unused_imports)]
 use std::io::Error;
 use std::mem;
+use std::ptr;
+use std::time::{Duration, Instant};
 
 // Linking to functions that are not provided by rust libc
 #[cfg(target_os = "macos")]
 #[link(name = "c")]
 extern "C" {
-    pub fn wait4(pid: pid_t, status: *mut c_int, options: c_int, rusage: *mut rusage) -> pid_t;
     pub fn madvise(addr: *mut c_void, len: size_t, advice: c_int) -> c_int;
     pub fn fdatasync(fd: c_int) -> c_int;
     pub fn lstat64(path: *const c_char, buf: *mut c_void) -> c_int;
 }
 
 #[cfg(not(target_os = "macos"))]
-use libc::{fallocate, fdatasync, ftruncate64, lstat, madvise, wait4};
-
-// Another conditional constant for name resolution: Macos et iOS use
-// SO_NOSIGPIPE as a setsockopt flag to disable SIGPIPE emission on socket.
-// Other platforms do otherwise.
-#[cfg(target_os = "darwin")]
-use libc::SO_NOSIGPIPE;
-#[cfg(not(target_os = "darwin"))]
-const SO_NOSIGPIPE: c_int = 0;
+use libc::{fallocate, fdatasync, ftruncate64, lstat, madvise};
 
 /// open
 pub fn ___syscall5(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
@@ -106,16 +85,17 @@ pub fn ___syscall5(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     let pathname: u32 = varargs.get(ctx);
     let flags: i32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    let _path_str = unsafe { std::ffi::CStr::from_ptr(pathname_addr).to_str().unwrap() };
-    let fd = unsafe { open(pathname_addr, flags, mode) };
+    let path = match fs::host_path(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let fd = fd_table::guest_fd(ctx, unsafe { open(path.as_ptr(), flags, mode) });
     debug!(
-        "=> pathname: {}, flags: {}, mode: {} = fd: {}\npath: {}\nlast os error: {}",
+        "=> pathname: {}, flags: {}, mode: {} = fd: {}\nlast os error: {}",
         pathname,
         flags,
         mode,
         fd,
-        _path_str,
         Error::last_os_error(),
     );
     fd
@@ -125,16 +105,16 @@ pub fn ___syscall5(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 pub fn ___syscall9(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall9 (link) {}", _which);
 
-    let oldname: c_int = varargs.get(ctx);
-    let newname: c_int = varargs.get(ctx);
-    let oldname_ptr = emscripten_memory_pointer!(ctx.memory(0), oldname) as *const i8;
-    let newname_ptr = emscripten_memory_pointer!(ctx.memory(0), newname) as *const i8;
-    let result = unsafe { link(oldname_ptr, newname_ptr) };
+    let oldname: u32 = varargs.get(ctx);
+    let newname: u32 = varargs.get(ctx);
+    let (oldname, newname) = match (fs::host_path(ctx, oldname), fs::host_path(ctx, newname)) {
+        (Ok(oldname), Ok(newname)) => (oldname, newname),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+    let result = unsafe { link(oldname.as_ptr(), newname.as_ptr()) };
     debug!(
-        "=> oldname: {}, newname: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(oldname_ptr).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(newname_ptr).to_str().unwrap() },
-        result,
+        "=> oldname: {:?}, newname: {:?}, result: {}",
+        oldname, newname, result,
     );
     result
 }
@@ -155,16 +135,28 @@ pub fn ___syscall77(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 pub fn ___syscall83(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall83 (symlink) {}", _which);
 
-    let path1_ptr: c_int = varargs.get(ctx);
-    let path2_ptr: c_int = varargs.get(ctx);
-    let path1 = emscripten_memory_pointer!(ctx.memory(0), path1_ptr) as *mut i8;
-    let path2 = emscripten_memory_pointer!(ctx.memory(0), path2_ptr) as *mut i8;
-    let result = unsafe { symlink(path1, path2) };
+    let path1_ptr: u32 = varargs.get(ctx);
+    let path2_ptr: u32 = varargs.get(ctx);
+    // The target is only looked up when the link is followed, and then
+    // from the host. Absolute targets must lead where they would in the guest.
+    let path1 = emscripten_memory_pointer!(ctx.memory(0), path1_ptr) as *const i8;
+    let path1 = unsafe { CStr::from_ptr(path1) }.to_owned();
+    let path1 = if path1.as_bytes().starts_with(b"/") {
+        match fs::host_path(ctx, path1_ptr) {
+            Ok(path) => path,
+            Err(errno) => return errno,
+        }
+    } else {
+        path1
+    };
+    let path2 = match fs::host_path(ctx, path2_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let result = unsafe { symlink(path1.as_ptr(), path2.as_ptr()) };
     debug!(
-        "=> path1: {}, path2: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(path1).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(path2).to_str().unwrap() },
-        result,
+        "=> path1: {:?}, path2: {:?}, result: {}",
+        path1, path2, result,
     );
     result
 }
@@ -173,6 +165,10 @@ pub fn ___syscall83(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 pub fn ___syscall194(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall194 (ftruncate64) {}", _which);
     let _fd: c_int = varargs.get(ctx);
+    let _fd = match fd_table::host_fd(ctx, _fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let _length: i64 = varargs.get(ctx);
     #[cfg(not(target_os = "macos"))]
     unsafe {
@@ -185,17 +181,17 @@ pub fn ___syscall194(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 /// lchown
 pub fn ___syscall198(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall198 (lchown) {}", _which);
-    let path: c_int = varargs.get(ctx);
+    let path: u32 = varargs.get(ctx);
     let uid: uid_t = varargs.get(ctx);
     let gid: gid_t = varargs.get(ctx);
-    let path_ptr = emscripten_memory_pointer!(ctx.memory(0), path) as *const i8;
-    let result = unsafe { lchown(path_ptr, uid, gid) };
+    let path = match fs::host_path(ctx, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let result = unsafe { lchown(path.as_ptr(), uid, gid) };
     debug!(
-        "=> path: {}, uid: {}, gid: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(path_ptr).to_str().unwrap() },
-        uid,
-        gid,
-        result,
+        "=> path: {:?}, uid: {}, gid: {}, result: {}",
+        path, uid, gid, result,
     );
     result
 }
@@ -225,9 +221,12 @@ pub fn ___syscall212(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     let owner: u32 = varargs.get(ctx);
     let group: u32 = varargs.get(ctx);
 
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
+    let path = match fs::host_path(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
 
-    unsafe { chown(pathname_addr, owner, group) }
+    unsafe { chown(path.as_ptr(), owner, group) }
 }
 
 /// madvise
@@ -246,15 +245,14 @@ pub fn ___syscall219(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 /// access
 pub fn ___syscall33(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall33 (access) {}", _which);
-    let path_ptr: c_int = varargs.get(ctx);
+    let path_ptr: u32 = varargs.get(ctx);
     let amode: c_int = varargs.get(ctx);
-    let path = emscripten_memory_pointer!(ctx.memory(0), path_ptr) as *const i8;
-    let result = unsafe { access(path, amode) };
-    debug!(
-        "=> path: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(path).to_str().unwrap() },
-        result
-    );
+    let path = match fs::host_path(ctx, path_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let result = unsafe { access(path.as_ptr(), amode) };
+    debug!("=> path: {:?}, result: {}", path, result);
     result
 }
 
@@ -270,15 +268,22 @@ pub fn ___syscall39(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall39 (mkdir) {}", _which);
     let pathname: u32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    unsafe { mkdir(pathname_addr, mode as _) }
+    let path = match fs::host_path(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    unsafe { mkdir(path.as_ptr(), mode as _) }
 }
 
 /// dup
 pub fn ___syscall41(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall41 (dup) {}", _which);
     let fd: c_int = varargs.get(ctx);
-    unsafe { dup(fd) }
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
+    fd_table::guest_fd(ctx, unsafe { dup(fd) })
 }
 
 /// getgid
@@ -310,6 +315,10 @@ pub fn ___syscall202(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
 pub fn ___syscall207(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall207 (fchown) {}", _which);
     let fd: c_int = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let owner: uid_t = varargs.get(ctx);
     let group: gid_t = varargs.get(ctx);
     unsafe { fchown(fd, owner, group) }
@@ -324,13 +333,23 @@ pub fn ___syscall330(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_
     let flags: c_int = varargs.get(ctx);
 
     if oldfd == newfd {
-        return EINVAL;
+        return -EINVAL;
+    }
+    let host_oldfd = match fd_table::host_fd(ctx, oldfd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
+    if newfd < 0 || newfd >= fd_table::MAX_FDS {
+        return -EBADF;
     }
 
-    let res = unsafe { dup2(oldfd, newfd) };
+    let host_newfd = unsafe { dup(host_oldfd) };
+    if host_newfd < 0 {
+        return host_newfd;
+    }
 
     // Set flags on newfd (https://www.gnu.org/software/libc/manual/html_node/Descriptor-Flags.html)
-    let mut old_flags = unsafe { fcntl(newfd, F_GETFD, 0) };
+    let mut old_flags = unsafe { fcntl(host_newfd, F_GETFD, 0) };
 
     if old_flags > 0 {
         old_flags |= flags;
@@ -339,20 +358,26 @@ pub fn ___syscall330(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_
     }
 
     unsafe {
-        fcntl(newfd, F_SETFD, old_flags);
+        fcntl(host_newfd, F_SETFD, old_flags);
     }
 
+    get_emscripten_data(ctx).fds.insert_at(newfd, host_newfd);
+
     debug!(
-        "=> oldfd: {}, newfd: {}, flags: {} = pid: {}",
-        oldfd, newfd, flags, res
+        "=> oldfd: {}, newfd: {}, flags: {} = host fd: {}",
+        oldfd, newfd, flags, host_newfd
     );
-    res
+    newfd
 }
 
 /// ioctl
 pub fn ___syscall54(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall54 (ioctl) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let request: u32 = varargs.get(ctx);
     debug!("fd: {}, op: {}", fd, request);
     // Got the equivalents here: https://code.woboq.org/linux/linux/include/uapi/asm-generic/ioctls.h.html
@@ -360,11 +385,13 @@ pub fn ___syscall54(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
         21537 => {
             // FIONBIO
             let argp: u32 = varargs.get(ctx);
-            let argp_ptr = emscripten_memory_pointer!(ctx.memory(0), argp) as *mut c_void;
-            let ret = unsafe { ioctl(fd, FIONBIO, argp_ptr) };
+            if let Err(errno) = net::guest_bytes(ctx, argp, 4) {
+                return -errno;
+            }
+            let mut nonblocking: c_int = ctx.memory(0).view::<c_int>()[argp as usize / 4].get();
+            let ret = unsafe { ioctl(fd, FIONBIO, &mut nonblocking as *mut c_int) };
             debug!("ret(FIONBIO): {}", ret);
-            ret
-            // 0
+            net::guest_result(ret as isize)
         }
         21523 => {
             // TIOCGWINSZ
@@ -392,33 +419,57 @@ pub fn ___syscall54(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 }
 
 // socketcall
-#[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> Result<c_int, String> {
     debug!("emscripten::___syscall102 (socketcall) {}", _which);
     let call: u32 = varargs.get(ctx);
     let mut socket_varargs: VarArgs = varargs.get(ctx);
 
-    #[repr(C)]
-    pub struct GuestSockaddrIn {
-        pub sin_family: sa_family_t, // u16
-        pub sin_port: in_port_t,     // u16
-        pub sin_addr: GuestInAddr,   // u32
-        pub sin_zero: [u8; 8],       // u8 * 8
-                                     // 2 + 2 + 4 + 8 = 16
-    }
-
-    #[repr(C)]
-    pub struct GuestInAddr {
-        pub s_addr: in_addr_t, // u32
-    }
+    // Every call but `socket` and `socketpair` starts with the socket to act on.
+    let socket: c_int = if call == 1 || call == 8 {
+        -1
+    } else {
+        let fd: c_int = socket_varargs.get(ctx);
+        match fd_table::host_fd(ctx, fd) {
+            Ok(fd) => fd,
+            Err(errno) => return Ok(errno),
+        }
+    };
 
-    // debug!("GuestSockaddrIn = {}", size_of::<GuestSockaddrIn>());
+    let events = blocking_events(ctx, call, socket_varargs);
+    interruptible(ctx, socket, events, |ctx| {
+        socketcall(ctx, call, socket, socket_varargs)
+    })
+}
 
-    pub struct LinuxSockAddr {
-        pub sa_family: u16,
-        pub sa_data: [c_char; 14],
+/// What socket call `call` waits for its socket to be ready for, if it
+/// can block, given its arguments after the socket.
+fn blocking_events(ctx: &mut Ctx, call: u32, mut socket_varargs: VarArgs) -> Option<i16> {
+    // The events, and how many arguments come before the flags.
+    let (events, before_flags) = match call {
+        5 | 18 => return Some(POLLIN),
+        // send and sendto (socket, buf, len, flags, ..)
+        9 | 11 => (POLLOUT, 2),
+        // recv and recvfrom (socket, buf, len, flags, ..)
+        10 | 12 => (POLLIN, 2),
+        // sendmsg and recvmsg (socket, msg, flags)
+        16 => (POLLOUT, 1),
+        17 => (POLLIN, 1),
+        _ => return None,
+    };
+    for _ in 0..before_flags {
+        let _: u32 = socket_varargs.get(ctx);
     }
+    let flags: c_int = socket_varargs.get(ctx);
+    if net::host_msg_flags(flags) & libc::MSG_DONTWAIT != 0 {
+        None
+    } else {
+        Some(events)
+    }
+}
 
+/// Socket call `call` on host socket `socket`, or on none for `socket`
+/// and `socketpair`.
+fn socketcall(ctx: &mut Ctx, call: u32, socket: c_int, mut socket_varargs: VarArgs) -> c_int {
     match call {
         1 => {
             debug!("socket: socket");
@@ -426,226 +477,394 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let domain: i32 = socket_varargs.get(ctx);
             let ty: i32 = socket_varargs.get(ctx);
             let protocol: i32 = socket_varargs.get(ctx);
-            let fd = unsafe { socket(domain, ty, protocol) };
-            // set_cloexec
-            unsafe {
-                ioctl(fd, FIOCLEX);
+            let (host_domain, host_type) = match (net::host_domain(domain), net::host_type(ty)) {
+                (Ok(domain), Ok(ty)) => (domain, ty),
+                (Err(errno), _) | (_, Err(errno)) => return -errno,
             };
-
-            type T = u32;
-            let payload = 1 as *const T as _;
-            unsafe {
-                setsockopt(
-                    fd,
-                    SOL_SOCKET,
-                    SO_NOSIGPIPE,
-                    payload,
-                    mem::size_of::<T>() as socklen_t,
-                );
-            };
-
+            let fd = unsafe { libc::socket(host_domain, host_type, protocol) };
             debug!(
-                "=> domain: {} (AF_INET/2), type: {} (SOCK_STREAM/1), protocol: {} = fd: {}",
+                "=> domain: {}, type: {}, protocol: {} = fd: {}",
                 domain, ty, protocol, fd
             );
-            fd as _
+            if fd < 0 {
+                return -net::last_errno();
+            }
+            new_socket(ctx, fd, ty & net::SOCK_NONBLOCK != 0)
         }
         2 => {
             debug!("socket: bind");
             // bind (socket: c_int, address: *const sockaddr, address_len: socklen_t) -> c_int
-            // TODO: Emscripten has a different signature.
-            let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-
-            // Debug received address
-            let _proper_address = address as *const GuestSockaddrIn;
-            debug!(
-                    "=> address.sin_family: {:?}, address.sin_port: {:?}, address.sin_addr.s_addr: {:?}",
-                unsafe { (*_proper_address).sin_family }, unsafe { (*_proper_address).sin_port }, unsafe { (*_proper_address).sin_addr.s_addr }
-                );
-
-            let status = unsafe { bind(socket, address, address_len) };
-            // debug!("=> status: {}", status);
-            debug!(
-                "=> socketfd: {}, address: {:?}, address_len: {} = status: {}",
-                socket, address, address_len, status
-            );
-            status
-            // -1
+            let address_len: u32 = socket_varargs.get(ctx);
+            let (address, address_len) = match net::read_address(ctx, address, address_len)
+                .and_then(|address| net::host_address(ctx, &address))
+            {
+                Ok(address) => address,
+                Err(errno) => return -errno,
+            };
+            let status = unsafe {
+                libc::bind(
+                    socket,
+                    &address as *const sockaddr_storage as *const sockaddr,
+                    address_len,
+                )
+            };
+            debug!("=> socketfd: {} = status: {}", socket, status);
+            net::guest_result(status as isize)
         }
         3 => {
             debug!("socket: connect");
             // connect (socket: c_int, address: *const sockaddr, len: socklen_t) -> c_int
-            // TODO: Emscripten has a different signature.
-            let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { connect(socket, address, address_len) }
+            let address_len: u32 = socket_varargs.get(ctx);
+            let (address, address_len) = match net::read_address(ctx, address, address_len)
+                .and_then(|address| net::host_address(ctx, &address))
+            {
+                Ok(address) => address,
+                Err(errno) => return -errno,
+            };
+            net::guest_result(unsafe {
+                libc::connect(
+                    socket,
+                    &address as *const sockaddr_storage as *const sockaddr,
+                    address_len,
+                )
+            } as isize)
         }
         4 => {
             debug!("socket: listen");
             // listen (socket: c_int, backlog: c_int) -> c_int
-            let socket = socket_varargs.get(ctx);
             let backlog: i32 = socket_varargs.get(ctx);
-            let status = unsafe { listen(socket, backlog) };
+            let status = unsafe { libc::listen(socket, backlog) };
             debug!(
                 "=> socketfd: {}, backlog: {} = status: {}",
                 socket, backlog, status
             );
-            status
+            net::guest_result(status as isize)
         }
-        5 => {
+        5 | 18 => {
             debug!("socket: accept");
             // accept (socket: c_int, address: *mut sockaddr, address_len: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
-            let address_addr: u32 = socket_varargs.get(ctx);
+            // accept4 (.., flags: c_int) -> c_int
+            let address: u32 = socket_varargs.get(ctx);
             let address_len: u32 = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address_addr) as *mut sockaddr;
-
-            debug!(
-                "=> socket: {}, address: {:?}, address_len: {}",
-                socket, address, address_len
-            );
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            // let mut address_len_addr: socklen_t = 0;
-
-            let fd = unsafe { accept(socket, address, address_len_addr) };
-
-            unsafe {
-                let address_linux =
-                    emscripten_memory_pointer!(ctx.memory(0), address_addr) as *mut LinuxSockAddr;
-                (*address_linux).sa_family = (*address).sa_family as u16;
-                (*address_linux).sa_data = (*address).sa_data;
-            };
-
-            // set_cloexec
-            unsafe {
-                ioctl(fd, FIOCLEX);
+            let flags: c_int = if call == 18 {
+                socket_varargs.get(ctx)
+            } else {
+                0
             };
-
+            if flags & !(net::SOCK_NONBLOCK | net::SOCK_CLOEXEC) != 0 {
+                return -EINVAL;
+            }
+            let fd = net::with_address_buffer(ctx, address, address_len, |address, len| unsafe {
+                libc::accept(socket, address, len) as isize
+            });
             debug!("fd: {}", fd);
-
-            fd as _
+            if fd < 0 {
+                return fd;
+            }
+            new_socket(ctx, fd, flags & net::SOCK_NONBLOCK != 0)
         }
         6 => {
             debug!("socket: getsockname");
             // getsockname (socket: c_int, address: *mut sockaddr, address_len: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
             let address_len: u32 = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            unsafe { getsockname(socket, address, address_len_addr) }
+            net::with_address_buffer(ctx, address, address_len, |address, len| unsafe {
+                libc::getsockname(socket, address, len) as isize
+            })
         }
         7 => {
             debug!("socket: getpeername");
             // getpeername (socket: c_int, address: *mut sockaddr, address_len: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
             let address_len: u32 = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            unsafe { getpeername(socket, address, address_len_addr) }
+            net::with_address_buffer(ctx, address, address_len, |address, len| unsafe {
+                libc::getpeername(socket, address, len) as isize
+            })
+        }
+        8 => {
+            debug!("socket: socketpair");
+            // socketpair (domain: c_int, ty: c_int, protocol: c_int, sv: *mut c_int) -> c_int
+            let domain: i32 = socket_varargs.get(ctx);
+            let ty: i32 = socket_varargs.get(ctx);
+            let protocol: i32 = socket_varargs.get(ctx);
+            let sv: u32 = socket_varargs.get(ctx);
+            let (host_domain, host_type) = match (net::host_domain(domain), net::host_type(ty)) {
+                (Ok(domain), Ok(ty)) => (domain, ty),
+                (Err(errno), _) | (_, Err(errno)) => return -errno,
+            };
+            if let Err(errno) = net::guest_bytes(ctx, sv, 8) {
+                return -errno;
+            }
+            let mut fds = [0; 2];
+            if unsafe { libc::socketpair(host_domain, host_type, protocol, fds.as_mut_ptr()) } < 0 {
+                return -net::last_errno();
+            }
+            let nonblocking = ty & net::SOCK_NONBLOCK != 0;
+            let first = new_socket(ctx, fds[0], nonblocking);
+            if first < 0 {
+                unsafe { libc::close(fds[1]) };
+                return first;
+            }
+            let second = new_socket(ctx, fds[1], nonblocking);
+            if second < 0 {
+                get_emscripten_data(ctx).fds.close(first);
+                unsafe { libc::close(fds[0]) };
+                return second;
+            }
+            let view = ctx.memory(0).view::<c_int>();
+            view[sv as usize / 4].set(first);
+            view[sv as usize / 4 + 1].set(second);
+            0
         }
-        11 => {
+        9 | 11 => {
             debug!("socket: sendto");
-            // sendto (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
-            let socket = socket_varargs.get(ctx);
+            // send (socket: c_int, buf: *const c_void, len: size_t, flags: c_int) -> ssize_t
+            // sendto (.., addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
             let buf: u32 = socket_varargs.get(ctx);
-            let flags = socket_varargs.get(ctx);
-            let len: i32 = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { sendto(socket, buf_addr, flags, len, address, address_len) as i32 }
+            let len: u32 = socket_varargs.get(ctx);
+            let flags: c_int = socket_varargs.get(ctx);
+            let (address, address_len): (u32, u32) = if call == 11 {
+                (socket_varargs.get(ctx), socket_varargs.get(ctx))
+            } else {
+                (0, 0)
+            };
+            let buf = match net::guest_bytes(ctx, buf, len) {
+                Ok(buf) => buf,
+                Err(errno) => return -errno,
+            };
+            let address = if address == 0 {
+                None
+            } else {
+                match net::read_address(ctx, address, address_len)
+                    .and_then(|address| net::host_address(ctx, &address))
+                {
+                    Ok(address) => Some(address),
+                    Err(errno) => return -errno,
+                }
+            };
+            let (address_ptr, address_len) = match &address {
+                Some((address, len)) => {
+                    (address as *const sockaddr_storage as *const sockaddr, *len)
+                }
+                None => (ptr::null(), 0),
+            };
+            net::guest_result(unsafe {
+                libc::sendto(
+                    socket,
+                    buf as *const c_void,
+                    len as size_t,
+                    net::host_msg_flags(flags),
+                    address_ptr,
+                    address_len,
+                )
+            })
         }
-        12 => {
+        10 | 12 => {
             debug!("socket: recvfrom");
-            // recvfrom (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
-            let socket = socket_varargs.get(ctx);
+            // recv (socket: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t
+            // recvfrom (.., addr: *mut sockaddr, addrlen: *mut socklen_t) -> ssize_t
             let buf: u32 = socket_varargs.get(ctx);
-            let flags = socket_varargs.get(ctx);
-            let len: i32 = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len: u32 = socket_varargs.get(ctx);
-            let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            unsafe { recvfrom(socket, buf_addr, flags, len, address, address_len_addr) as i32 }
+            let len: u32 = socket_varargs.get(ctx);
+            let flags: c_int = socket_varargs.get(ctx);
+            let (address, address_len): (u32, u32) = if call == 12 {
+                (socket_varargs.get(ctx), socket_varargs.get(ctx))
+            } else {
+                (0, 0)
+            };
+            let buf = match net::guest_bytes(ctx, buf, len) {
+                Ok(buf) => buf,
+                Err(errno) => return -errno,
+            };
+            net::with_address_buffer(ctx, address, address_len, |address, address_len| unsafe {
+                libc::recvfrom(
+                    socket,
+                    buf as *mut c_void,
+                    len as size_t,
+                    net::host_msg_flags(flags),
+                    address,
+                    address_len,
+                )
+            })
+        }
+        13 => {
+            debug!("socket: shutdown");
+            // shutdown (socket: c_int, how: c_int) -> c_int
+            let how: c_int = socket_varargs.get(ctx);
+            net::guest_result(unsafe { libc::shutdown(socket, how) } as isize)
         }
         14 => {
             debug!("socket: setsockopt");
-            // NOTE: Emscripten seems to be passing the wrong values to this syscall
-            //      level: Em passes 1 as SOL_SOCKET; SOL_SOCKET is 0xffff in BSD
-            //      name: Em passes SO_ACCEPTCONN, but Nginx complains about REUSEADDR
-            //      https://github.com/openbsd/src/blob/master/sys/sys/socket.h#L156
             // setsockopt (socket: c_int, level: c_int, name: c_int, value: *const c_void, option_len: socklen_t) -> c_int
-
-            let socket = socket_varargs.get(ctx);
-            // SOL_SOCKET = 0xffff (BSD, Linux)
-            let level: i32 = SOL_SOCKET;
-            let _: u32 = socket_varargs.get(ctx);
-            // SO_REUSEADDR = 0x4 (BSD, Linux)
-            let name: i32 = SO_REUSEADDR;
-            let _: u32 = socket_varargs.get(ctx);
+            let level: i32 = socket_varargs.get(ctx);
+            let name: i32 = socket_varargs.get(ctx);
             let value: u32 = socket_varargs.get(ctx);
-            let option_len = socket_varargs.get(ctx);
-            let value_addr = emscripten_memory_pointer!(ctx.memory(0), value) as _; // Endian problem
-            let ret = unsafe { setsockopt(socket, level, name, value_addr, option_len) };
-
-            debug!("=> socketfd: {}, level: {} (SOL_SOCKET/0xffff), name: {} (SO_REUSEADDR/4), value_addr: {:?}, option_len: {} = status: {}", socket, level, name, value_addr, option_len, ret);
+            let option_len: u32 = socket_varargs.get(ctx);
+            let ret = net::set_option(ctx, socket, level, name, value, option_len);
+            debug!(
+                "=> socketfd: {}, level: {}, name: {}, option_len: {} = status: {}",
+                socket, level, name, option_len, ret
+            );
             ret
         }
         15 => {
             debug!("socket: getsockopt");
             // getsockopt (sockfd: c_int, level: c_int, optname: c_int, optval: *mut c_void, optlen: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
             let level: i32 = socket_varargs.get(ctx);
             let name: i32 = socket_varargs.get(ctx);
             let value: u32 = socket_varargs.get(ctx);
             let option_len: u32 = socket_varargs.get(ctx);
-            let value_addr = emscripten_memory_pointer!(ctx.memory(0), value) as _;
-            let option_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), option_len) as *mut socklen_t;
-            unsafe { getsockopt(socket, level, name, value_addr, option_len_addr) }
+            net::get_option(ctx, socket, level, name, value, option_len)
         }
         16 => {
             debug!("socket: sendmsg");
             // sendmsg (fd: c_int, msg: *const msghdr, flags: c_int) -> ssize_t
-            let socket: i32 = socket_varargs.get(ctx);
             let msg: u32 = socket_varargs.get(ctx);
             let flags: i32 = socket_varargs.get(ctx);
-            let msg_addr = emscripten_memory_pointer!(ctx.memory(0), msg) as *const msghdr;
-            unsafe { sendmsg(socket, msg_addr, flags) as i32 }
+            let message = match read_msghdr(ctx, msg) {
+                Ok(message) => message,
+                Err(errno) => return -errno,
+            };
+            // Ancillary data could pass fds, which don't mean the same on the host.
+            if message.control_len != 0 {
+                return -EINVAL;
+            }
+            let address = if message.name == 0 {
+                None
+            } else {
+                match net::read_address(ctx, message.name, message.name_len)
+                    .and_then(|address| net::host_address(ctx, &address))
+                {
+                    Ok(address) => Some(address),
+                    Err(errno) => return -errno,
+                }
+            };
+            let mut iovecs = message.iovecs;
+            let mut host_msg: msghdr = unsafe { mem::zeroed() };
+            if let Some((address, len)) = &address {
+                host_msg.msg_name = address as *const sockaddr_storage as *mut c_void;
+                host_msg.msg_namelen = *len;
+            }
+            host_msg.msg_iov = iovecs.as_mut_ptr();
+            host_msg.msg_iovlen = iovecs.len() as _;
+            net::guest_result(unsafe {
+                libc::sendmsg(socket, &host_msg, net::host_msg_flags(flags))
+            })
         }
         17 => {
             debug!("socket: recvmsg");
             // recvmsg (fd: c_int, msg: *mut msghdr, flags: c_int) -> ssize_t
-            let socket: i32 = socket_varargs.get(ctx);
             let msg: u32 = socket_varargs.get(ctx);
             let flags: i32 = socket_varargs.get(ctx);
-            let msg_addr = emscripten_memory_pointer!(ctx.memory(0), msg) as *mut msghdr;
-            unsafe { recvmsg(socket, msg_addr, flags) as i32 }
+            let message = match read_msghdr(ctx, msg) {
+                Ok(message) => message,
+                Err(errno) => return -errno,
+            };
+            let mut iovecs = message.iovecs;
+            let mut host_flags = 0;
+            // `msg_namelen` is where the address' size is written back.
+            let name_len = if message.name == 0 { 0 } else { msg + 4 };
+            let ret = net::with_address_buffer(ctx, message.name, name_len, |address, len| {
+                let mut host_msg: msghdr = unsafe { mem::zeroed() };
+                host_msg.msg_name = address as *mut c_void;
+                host_msg.msg_namelen = unsafe { *len };
+                host_msg.msg_iov = iovecs.as_mut_ptr();
+                host_msg.msg_iovlen = iovecs.len() as _;
+                let ret =
+                    unsafe { libc::recvmsg(socket, &mut host_msg, net::host_msg_flags(flags)) };
+                unsafe { *len = host_msg.msg_namelen };
+                host_flags = host_msg.msg_flags;
+                ret
+            });
+            if ret >= 0 {
+                let view = ctx.memory(0).view::<u32>();
+                if message.name == 0 {
+                    view[msg as usize / 4 + 1].set(0);
+                }
+                view[msg as usize / 4 + 5].set(0);
+                view[msg as usize / 4 + 6].set(net::guest_msg_flags(host_flags) as u32);
+            }
+            ret
         }
         _ => {
-            // others
-            -1
+            debug!("emscripten::___syscall102 -> unknown call {}", call);
+            -EINVAL
         }
     }
 }
 
+/// Give the guest a socket the host just made, as an fd of its own.
+fn new_socket(ctx: &mut Ctx, fd: c_int, nonblocking: bool) -> c_int {
+    if nonblocking {
+        if let Err(errno) = net::set_nonblocking(fd, true) {
+            unsafe { libc::close(fd) };
+            return -errno;
+        }
+    }
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    unsafe {
+        // There's no `MSG_NOSIGNAL`, so the socket itself mustn't raise `SIGPIPE`.
+        let on: c_int = 1;
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_NOSIGPIPE,
+            &on as *const c_int as *const c_void,
+            mem::size_of::<c_int>() as socklen_t,
+        );
+    }
+    let guest = fd_table::guest_fd(ctx, fd);
+    if guest < 0 {
+        unsafe { libc::close(fd) };
+    }
+    guest
+}
+
+/// A guest `struct msghdr`, with its buffers as host iovecs.
+struct Message {
+    name: u32,
+    name_len: u32,
+    iovecs: Vec<iovec>,
+    control_len: u32,
+}
+
+/// Read the guest `struct msghdr` at `msg`: seven 32-bit fields, name,
+/// namelen, iov, iovlen, control, controllen and flags.
+fn read_msghdr(ctx: &Ctx, msg: u32) -> Result<Message, c_int> {
+    net::guest_bytes(ctx, msg, 28)?;
+    let view = ctx.memory(0).view::<u32>();
+    let field = |index: usize| view[msg as usize / 4 + index].get();
+    let (iov, iov_len) = (field(2), field(3));
+    if iov_len > 1024 {
+        return Err(EINVAL);
+    }
+    net::guest_bytes(ctx, iov, iov_len * 8)?;
+    let mut iovecs = vec![];
+    for i in 0..iov_len as usize {
+        let base = view[iov as usize / 4 + i * 2].get();
+        let len = view[iov as usize / 4 + i * 2 + 1].get();
+        iovecs.push(iovec {
+            iov_base: net::guest_bytes(ctx, base, len)? as *mut c_void,
+            iov_len: len as size_t,
+        });
+    }
+    Ok(Message {
+        name: field(0),
+        name_len: field(1),
+        iovecs,
+        control_len: field(5),
+    })
+}
+
 // pread
 pub fn ___syscall180(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall180 (pread) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let buf: u32 = varargs.get(ctx);
     let count: u32 = varargs.get(ctx);
     {
@@ -663,6 +882,10 @@ pub fn ___syscall180(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 pub fn ___syscall181(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall181 (pwrite) {}", _which);
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let buf: u32 = varargs.get(ctx);
     let count: u32 = varargs.get(ctx);
     {
@@ -684,54 +907,297 @@ pub fn ___syscall181(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 pub fn ___syscall94(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall118 (fchmod) {}", _which);
     let fd: c_int = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let mode: mode_t = varargs.get(ctx);
     unsafe { fchmod(fd, mode) }
 }
 
 /// wait4
-#[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall114(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_t {
+///
+/// Like `waitpid`, only for the children the module started itself.
+/// Their resource usage isn't known, and is reported as all zeros.
+pub fn ___syscall114(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> Result<pid_t, String> {
     debug!("emscripten::___syscall114 (wait4)");
     let pid: pid_t = varargs.get(ctx);
     let status: u32 = varargs.get(ctx);
     let options: c_int = varargs.get(ctx);
     let rusage: u32 = varargs.get(ctx);
-    let status_addr = emscripten_memory_pointer!(ctx.memory(0), status) as *mut c_int;
 
-    let rusage_addr = emscripten_memory_pointer!(ctx.memory(0), rusage) as *mut rusage;
-    let res = unsafe { wait4(pid, status_addr, options, rusage_addr) };
+    let (res, wait_status) = process::wait(ctx, pid, options)?;
+    if res > 0 {
+        let view = ctx.memory(0).view::<u32>();
+        if status != 0 {
+            view[status as usize / 4].set(wait_status as u32);
+        }
+        if rusage != 0 {
+            // struct rusage: two struct timevals and 14 longs.
+            for word in &view[rusage as usize / 4..rusage as usize / 4 + 18] {
+                word.set(0);
+            }
+        }
+    }
     debug!(
-        "=> pid: {}, status: {:?}, options: {}, rusage: {:?} = pid: {}",
-        pid, status_addr, options, rusage_addr, res
+        "=> pid: {}, status: {}, options: {} = pid: {}",
+        pid, wait_status, options, res
     );
-    res
+    Ok(res)
 }
 
 /// fsync
 pub fn ___syscall118(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall118 (fsync) {}", _which);
     let fd: c_int = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     unsafe { fsync(fd) }
 }
 
 // select
-#[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall142(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall142(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> Result<c_int, String> {
     debug!("emscripten::___syscall142 (newselect) {}", _which);
 
     let nfds: i32 = varargs.get(ctx);
     let readfds: u32 = varargs.get(ctx);
     let writefds: u32 = varargs.get(ctx);
     let exceptfds: u32 = varargs.get(ctx);
-    let _timeout: i32 = varargs.get(ctx);
+    let timeout: u32 = varargs.get(ctx);
 
-    assert!(nfds <= 64, "`nfds` must be less than or equal to 64");
-    assert!(exceptfds == 0, "`exceptfds` is not supporrted");
+    if nfds < 0 || nfds > fd_table::MAX_FDS {
+        return Ok(-EINVAL);
+    }
+    let timeout = if timeout == 0 {
+        None
+    } else {
+        let view = ctx.memory(0).view::<i32>();
+        let (seconds, micros) = (
+            view[timeout as usize / 4].get(),
+            view[timeout as usize / 4 + 1].get(),
+        );
+        if seconds < 0 || micros < 0 || micros >= 1_000_000 {
+            return Ok(-EINVAL);
+        }
+        Some(Duration::new(seconds as u64, micros as u32 * 1000))
+    };
+
+    // The guest's sets hold guest fds, which are polled for the events
+    // each set is about, and the sets are then rewritten with the results.
+    let guest_sets = [readfds, writefds, exceptfds];
+    let set_events = [POLLIN, POLLOUT, POLLPRI];
+    let mut fds = vec![];
+    for guest in 0..nfds {
+        let events = (0..3)
+            .filter(|&i| guest_sets[i] != 0 && guest_fd_isset(ctx, guest_sets[i], guest))
+            .fold(0, |events, i| events | set_events[i]);
+        if events == 0 {
+            continue;
+        }
+        let host = match fd_table::host_fd(ctx, guest) {
+            Ok(fd) => fd,
+            Err(errno) => return Ok(errno),
+        };
+        fds.push((
+            guest,
+            pollfd {
+                fd: host,
+                events,
+                revents: 0,
+            },
+        ));
+    }
 
-    let readfds_ptr = emscripten_memory_pointer!(ctx.memory(0), readfds) as _;
-    let writefds_ptr = emscripten_memory_pointer!(ctx.memory(0), writefds) as _;
+    let mut host_fds: Vec<pollfd> = fds.iter().map(|&(_, fd)| fd).collect();
+    let ret = poll_fds(ctx, &mut host_fds, timeout, false)?;
+    if ret < 0 {
+        return Ok(ret);
+    }
 
-    unsafe { select(nfds, readfds_ptr, writefds_ptr, 0 as _, 0 as _) }
+    let ready_events = [POLLIN | POLLHUP | POLLERR, POLLOUT | POLLERR, POLLPRI];
+    let mut ready = 0;
+    for (&(guest, _), fd) in fds.iter().zip(&host_fds) {
+        let sets = guest_sets.iter().zip(&set_events).zip(&ready_events);
+        for ((&set, &events), &ready_events) in sets {
+            if set == 0 {
+                continue;
+            }
+            let is_ready = fd.events & events != 0 && fd.revents & ready_events != 0;
+            set_guest_fd(ctx, set, guest, is_ready);
+            if is_ready {
+                ready += 1;
+            }
+        }
+    }
+    Ok(ready)
+}
+
+/// poll
+pub fn ___syscall168(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> Result<c_int, String> {
+    debug!("emscripten::___syscall168 (poll) {}", _which);
+    let fds: u32 = varargs.get(ctx);
+    let nfds: u32 = varargs.get(ctx);
+    let timeout: c_int = varargs.get(ctx);
+
+    if nfds > fd_table::MAX_FDS as u32 {
+        return Ok(-EINVAL);
+    }
+    if let Err(errno) = net::guest_bytes(ctx, fds, nfds * 8) {
+        return Ok(-errno);
+    }
+
+    // struct pollfd { int fd; short events; short revents; }
+    let entries: Vec<(c_int, i16)> = {
+        let view = ctx.memory(0).view::<i16>();
+        (0..nfds as usize)
+            .map(|i| {
+                let entry = fds as usize / 2 + i * 4;
+                let guest = c_int::from(view[entry].get() as u16)
+                    | c_int::from(view[entry + 1].get()) << 16;
+                (guest, view[entry + 2].get() & (POLLIN | POLLPRI | POLLOUT))
+            })
+            .collect()
+    };
+    let mut host_fds = vec![];
+    for (guest, events) in entries {
+        // Negative fds are left out; closed ones are reported as such.
+        let fd = if guest < 0 {
+            -1
+        } else {
+            get_emscripten_data(ctx).fds.get(guest).unwrap_or(-1)
+        };
+        host_fds.push(pollfd {
+            fd,
+            events,
+            revents: 0,
+        });
+    }
+
+    let ret = poll_fds(
+        ctx,
+        &mut host_fds,
+        if timeout < 0 {
+            None
+        } else {
+            Some(Duration::from_millis(timeout as u64))
+        },
+        false,
+    )?;
+    if ret < 0 {
+        return Ok(ret);
+    }
+
+    let view = ctx.memory(0).view::<i16>();
+    let mut ready = 0;
+    for (i, fd) in host_fds.iter().enumerate() {
+        let entry = fds as usize / 2 + i * 4;
+        let guest =
+            c_int::from(view[entry].get() as u16) | c_int::from(view[entry + 1].get()) << 16;
+        let revents = if guest >= 0 && fd.fd < 0 {
+            POLLNVAL
+        } else {
+            fd.revents & (POLLIN | POLLPRI | POLLOUT | POLLERR | POLLHUP | POLLNVAL)
+        };
+        view[entry + 3].set(revents);
+        if revents != 0 {
+            ready += 1;
+        }
+    }
+    Ok(ready)
+}
+
+/// Poll host fds `fds` for up to `timeout`, or for as long as it takes
+/// if there's none. Signals interrupt it like they do on the host,
+/// and it returns `-EINTR` once they've been delivered. With `restart`,
+/// signals whose handlers ask for `SA_RESTART` don't.
+fn poll_fds(
+    ctx: &mut Ctx,
+    fds: &mut [pollfd],
+    timeout: Option<Duration>,
+    restart: bool,
+) -> Result<c_int, String> {
+    let end = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        signal::deliver(ctx)?;
+        // Poll until the alarm is due at the latest, to raise it on time.
+        let now = Instant::now();
+        let wake = match (end, signal::next_alarm(ctx)) {
+            (Some(end), Some(alarm)) => Some(end.min(alarm)),
+            (end, alarm) => end.or(alarm),
+        };
+        let millis = match wake {
+            Some(wake) if wake > now => {
+                let wait = wake - now;
+                // Rounded up, not to come back a little early over and over.
+                (wait.as_secs() * 1000 + u64::from(wait.subsec_nanos() + 999_999) / 1_000_000)
+                    .min(c_int::max_value() as u64) as c_int
+            }
+            Some(_) => 0,
+            None => -1,
+        };
+        let ret = unsafe { poll(fds.as_mut_ptr(), fds.len() as nfds_t, millis) };
+        if ret < 0 && Error::last_os_error().raw_os_error() != Some(EINTR) {
+            return Ok(-net::last_errno());
+        }
+        if ret > 0 {
+            return Ok(ret);
+        }
+        if signal::interrupted(ctx, restart)? {
+            return Ok(-EINTR);
+        }
+        if let Some(end) = end {
+            if Instant::now() >= end {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// Make `call` on host fd `fd`, which blocks until `fd` is ready for
+/// `events` unless it's `None`, and deliver pending signals after it.
+/// While the alarm is set, the fd is polled first, for the alarm to
+/// interrupt the wait with `-EINTR` as it would interrupt the call.
+pub fn interruptible<F: FnOnce(&mut Ctx) -> c_int>(
+    ctx: &mut Ctx,
+    fd: c_int,
+    events: Option<i16>,
+    call: F,
+) -> Result<c_int, String> {
+    if let Some(events) = events {
+        // Only the alarm can raise a signal while the guest waits.
+        if signal::next_alarm(ctx).is_some() && !net::is_nonblocking(fd) {
+            let mut fds = [pollfd {
+                fd,
+                events,
+                revents: 0,
+            }];
+            if poll_fds(ctx, &mut fds, None, true)? == -EINTR {
+                return Ok(-EINTR);
+            }
+        }
+    }
+    let ret = call(ctx);
+    signal::deliver(ctx)?;
+    Ok(ret)
+}
+
+/// Whether guest fd `fd` is in the guest `fd_set` at `set`.
+fn guest_fd_isset(ctx: &Ctx, set: u32, fd: c_int) -> bool {
+    let byte = ctx.memory(0).view::<u8>()[set as usize + fd as usize / 8].get();
+    byte & (1 << (fd % 8)) != 0
+}
+
+/// Add guest fd `fd` to the guest `fd_set` at `set`, or remove it.
+fn set_guest_fd(ctx: &Ctx, set: u32, fd: c_int, present: bool) {
+    let view = ctx.memory(0).view::<u8>();
+    let cell = &view[set as usize + fd as usize / 8];
+    if present {
+        cell.set(cell.get() | 1 << (fd % 8));
+    } else {
+        cell.set(cell.get() & !(1 << (fd % 8)));
+    }
 }
 
 /// fdatasync
@@ -739,6 +1205,10 @@ pub fn ___syscall148(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     debug!("emscripten::___syscall148 (fdatasync) {}", _which);
 
     let fd: i32 = varargs.get(ctx);
+    let fd = match fd_table::host_fd(ctx, fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
 
     unsafe { fdatasync(fd) }
 }
@@ -764,9 +1234,13 @@ pub fn ___syscall122(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 /// lstat64
 pub fn ___syscall196(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::___syscall196 (lstat64) {}", _which);
-    let path_ptr: c_int = varargs.get(ctx);
+    let path_ptr: u32 = varargs.get(ctx);
     let buf_ptr: u32 = varargs.get(ctx);
-    let path = emscripten_memory_pointer!(ctx.memory(0), path_ptr) as *const i8;
+    let path = match fs::host_path(ctx, path_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let path = path.as_ptr();
     unsafe {
         let mut stat: stat = std::mem::zeroed();
 
@@ -793,6 +1267,10 @@ pub fn ___syscall196(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
 pub fn ___syscall324(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall324 (fallocate) {}", _which);
     let _fd: c_int = varargs.get(ctx);
+    let _fd = match fd_table::host_fd(ctx, _fd) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
     let _mode: c_int = varargs.get(ctx);
     let _offset: off_t = varargs.get(ctx);
     let _len: off_t = varargs.get(ctx);
@@ -805,3 +1283,89 @@ pub fn ___syscall324(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{___syscall102, ___syscall168};
+    use crate::env::get_emscripten_data;
+    use crate::net::{POLLIN, POLLNVAL, POLLOUT};
+    use crate::utils::tests::with_runtime;
+    use crate::varargs::VarArgs;
+    use std::net::UdpSocket;
+    use std::os::unix::io::AsRawFd;
+    use wasmer_runtime_core::vm::Ctx;
+
+    fn write_u32s(ctx: &Ctx, ptr: u32, values: &[u32]) {
+        let view = ctx.memory(0).view::<u32>();
+        for (cell, &value) in view[ptr as usize / 4..].iter().zip(values) {
+            cell.set(value);
+        }
+    }
+
+    fn read_u32(ctx: &Ctx, ptr: u32) -> u32 {
+        ctx.memory(0).view::<u32>()[ptr as usize / 4].get()
+    }
+
+    /// Make socketcall `call` with `args`, laid out in guest memory.
+    fn socketcall(ctx: &mut Ctx, call: u32, args: &[u32]) -> i32 {
+        write_u32s(ctx, 2048, args);
+        write_u32s(ctx, 1024, &[call, 2048]);
+        ___syscall102(ctx, 102, VarArgs { pointer: 1024 }).unwrap()
+    }
+
+    #[test]
+    fn socket_options_round_trip() {
+        with_runtime(|ctx| {
+            // socket(AF_INET, SOCK_DGRAM, 0)
+            let fd = socketcall(ctx, 1, &[2, 2, 0]);
+            assert!(fd >= 0);
+            // setsockopt(fd, SOL_SOCKET, SO_REUSEADDR, &1, 4)
+            write_u32s(ctx, 3000, &[1]);
+            assert_eq!(socketcall(ctx, 14, &[fd as u32, 1, 2, 3000, 4]), 0);
+            // getsockopt(fd, SOL_SOCKET, SO_REUSEADDR, &value, &len)
+            write_u32s(ctx, 3000, &[0, 16]);
+            assert_eq!(socketcall(ctx, 15, &[fd as u32, 1, 2, 3000, 3004]), 0);
+            assert_eq!(read_u32(ctx, 3000), 1);
+            assert_eq!(read_u32(ctx, 3004), 4);
+            // SO_TYPE is SOCK_DGRAM, in the guest's numbers.
+            write_u32s(ctx, 3004, &[4]);
+            assert_eq!(socketcall(ctx, 15, &[fd as u32, 1, 3, 3000, 3004]), 0);
+            assert_eq!(read_u32(ctx, 3000), 2);
+            // Options the guest has no name for are refused: ENOPROTOOPT.
+            assert_eq!(socketcall(ctx, 14, &[fd as u32, 1, 999, 3000, 4]), -92);
+        });
+    }
+
+    #[test]
+    fn poll_reports_loopback_datagrams() {
+        with_runtime(|ctx| {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let host = unsafe { libc::dup(socket.as_raw_fd()) };
+            let fd = get_emscripten_data(ctx).fds.insert(host).unwrap() as u32;
+
+            // struct pollfd { int fd; short events; short revents; }, and
+            // another one for a guest fd that isn't open.
+            let poll = |ctx: &mut Ctx, events: u32, timeout: u32| {
+                write_u32s(ctx, 3000, &[fd, events, 500, u32::from(POLLIN as u16)]);
+                write_u32s(ctx, 1024, &[3000, 2, timeout]);
+                let ready = ___syscall168(ctx, 168, VarArgs { pointer: 1024 }).unwrap();
+                let revents = |entry: u32| (read_u32(ctx, 3000 + entry * 8 + 4) >> 16) as i16;
+                (ready, revents(0), revents(1))
+            };
+
+            assert_eq!(poll(ctx, u32::from(POLLIN as u16), 0), (1, 0, POLLNVAL));
+            assert_eq!(
+                poll(ctx, u32::from(POLLOUT as u16), 0),
+                (2, POLLOUT, POLLNVAL)
+            );
+            sender
+                .send_to(b"ping", socket.local_addr().unwrap())
+                .unwrap();
+            assert_eq!(
+                poll(ctx, u32::from(POLLIN as u16), 1000),
+                (2, POLLIN, POLLNVAL)
+            );
+        });
+    }
+}
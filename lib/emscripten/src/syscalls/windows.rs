@@ -1,5 +1,6 @@
 use crate::utils::copy_cstr_into_wasm;
 use crate::varargs::VarArgs;
+use crate::{fd_table, fs, signal};
 use libc::mkdir;
 use libc::open;
 use rand::Rng;
@@ -13,6 +14,23 @@ use wasmer_runtime_core::vm::Ctx;
 #[allow(non_camel_case_types)]
 type pid_t = c_int;
 
+// The guest's `poll` events, which `interruptible` ignores here.
+pub const POLLIN: i16 = 0x1;
+pub const POLLOUT: i16 = 0x4;
+
+/// Make `call` on host fd `fd`, and deliver pending signals after it.
+/// Unlike on unix, the alarm can't interrupt it while it blocks.
+pub fn interruptible<F: FnOnce(&mut Ctx) -> c_int>(
+    ctx: &mut Ctx,
+    _fd: c_int,
+    _events: Option<i16>,
+    call: F,
+) -> Result<c_int, String> {
+    let ret = call(ctx);
+    signal::deliver(ctx)?;
+    Ok(ret)
+}
+
 /// open
 pub fn ___syscall5(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall5 (open) {}", which);
@@ -40,7 +58,9 @@ pub fn ___syscall5(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
             let urandom_file_offset = unsafe { copy_cstr_into_wasm(ctx, ptr) };
             let raw_pointer_to_urandom_file =
                 emscripten_memory_pointer!(ctx.memory(0), urandom_file_offset) as *const i8;
-            let fd = unsafe { open(raw_pointer_to_urandom_file, flags, mode) };
+            let fd = fd_table::guest_fd(ctx, unsafe {
+                open(raw_pointer_to_urandom_file, flags, mode)
+            });
             debug!(
                 "=> pathname: {}, flags: {}, mode: {} = fd: {}",
                 pathname, flags, mode, fd
@@ -48,10 +68,14 @@ pub fn ___syscall5(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
             fd
         }
         _ => {
-            let fd = unsafe { open(pathname_addr, flags, mode) };
+            let path = match fs::host_path(ctx, pathname) {
+                Ok(path) => path,
+                Err(errno) => return errno,
+            };
+            let fd = fd_table::guest_fd(ctx, unsafe { open(path.as_ptr(), flags, mode) });
             debug!(
-                "=> pathname: {}, flags: {}, mode: {} = fd: {}\npath: {}",
-                pathname, flags, mode, fd, path_str
+                "=> pathname: {}, flags: {}, mode: {} = fd: {}\npath: {:?}",
+                pathname, flags, mode, fd, path
             );
             fd
         }
@@ -96,8 +120,11 @@ pub fn ___syscall39(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     #[cfg(not(feature = "debug"))]
     let _ = which;
     let pathname: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    unsafe { mkdir(pathname_addr) }
+    let path = match fs::host_path(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    unsafe { mkdir(path.as_ptr()) }
 }
 
 /// dup
@@ -222,6 +249,14 @@ pub fn ___syscall142(_ctx: &mut Ctx, which: c_int, mut _varargs: VarArgs) -> c_i
     -1
 }
 
+/// poll
+pub fn ___syscall168(_ctx: &mut Ctx, which: c_int, mut _varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall168 (poll) {}", which);
+    #[cfg(not(feature = "debug"))]
+    let _ = which;
+    -1
+}
+
 /// fdatasync
 pub fn ___syscall148(_ctx: &mut Ctx, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall148 (fdatasync) {}", _which);
@@ -0,0 +1,44 @@
+(module
+ (type $i (func (result i32)))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "_dlopen" (func $_dlopen (param i32 i32) (result i32)))
+ (import "env" "_dlsym" (func $_dlsym (param i32 i32) (result i32)))
+ (global $heap (mut i32) (i32.const 4096))
+ (export "_malloc" (func $_malloc))
+ (export "_free" (func $_free))
+ (export "_memset" (func $_memset))
+ (export "stackAlloc" (func $stackAlloc))
+ (export "_use_side_module" (func $_use_side_module))
+ (data (i32.const 16) "/side.wasm\00")
+ (data (i32.const 32) "answer\00")
+ (data (i32.const 48) "value\00")
+ (data (i32.const 64) "missing\00")
+ ;; A bump allocator, enough for the side module's memory.
+ (func $_malloc (param $size i32) (result i32)
+  (local $ptr i32)
+  (set_local $ptr (get_global $heap))
+  (set_global $heap (i32.add (get_global $heap) (get_local $size)))
+  (get_local $ptr)
+ )
+ (func $_free (param $ptr i32))
+ (func $_memset (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+  (get_local $ptr)
+ )
+ (func $stackAlloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ ;; Open /side.wasm and return what its `answer` function returns
+ ;; plus its `value`, or -1 if `missing` was found.
+ (func $_use_side_module (result i32)
+  (local $handle i32)
+  (set_local $handle (call $_dlopen (i32.const 16) (i32.const 0)))
+  (if (call $_dlsym (get_local $handle) (i32.const 64))
+   (then (return (i32.const -1)))
+  )
+  (i32.add
+   (call_indirect (type $i) (call $_dlsym (get_local $handle) (i32.const 32)))
+   (i32.load (call $_dlsym (get_local $handle) (i32.const 48)))
+  )
+ )
+)
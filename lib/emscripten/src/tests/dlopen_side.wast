@@ -0,0 +1,14 @@
+(module
+ (import "env" "memory" (memory 0))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "__memory_base" (global $__memory_base i32))
+ (import "env" "__table_base" (global $__table_base i32))
+ (global $value i32 (i32.const 4))
+ (export "_answer" (func $_answer))
+ (export "_value" (global $value))
+ (data (get_global $__memory_base) "\07\00\00\00\2a\00\00\00")
+ ;; The first word of the module's memory.
+ (func $_answer (result i32)
+  (i32.load (get_global $__memory_base))
+ )
+)
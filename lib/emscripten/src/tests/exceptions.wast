@@ -0,0 +1,97 @@
+(module
+ (type $vi (func (param i32)))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 2 anyfunc))
+ (import "env" "invoke_vi" (func $invoke_vi (param i32 i32)))
+ (import "env" "___cxa_allocate_exception" (func $___cxa_allocate_exception (param i32) (result i32)))
+ (import "env" "___cxa_throw" (func $___cxa_throw (param i32 i32 i32)))
+ (import "env" "___cxa_find_matching_catch_3" (func $___cxa_find_matching_catch_3 (param i32) (result i32)))
+ (import "env" "___cxa_begin_catch" (func $___cxa_begin_catch (param i32) (result i32)))
+ (import "env" "___cxa_end_catch" (func $___cxa_end_catch))
+ (import "env" "getTempRet0" (func $getTempRet0 (result i32)))
+ (global $heap (mut i32) (i32.const 65536))
+ (global $frees (mut i32) (i32.const 0))
+ (global $sp (mut i32) (i32.const 4096))
+ (global $threw (mut i32) (i32.const 0))
+ (global $threwValue (mut i32) (i32.const 0))
+ (export "_malloc" (func $_malloc))
+ (export "_free" (func $_free))
+ (export "_memset" (func $_memset))
+ (export "stackAlloc" (func $stackAlloc))
+ (export "stackSave" (func $stackSave))
+ (export "stackRestore" (func $stackRestore))
+ (export "_setThrew" (func $_setThrew))
+ (export "___cxa_can_catch" (func $___cxa_can_catch))
+ (export "dynCall_vi" (func $dynCall_vi))
+ (export "_catch" (func $_catch))
+ (export "_frees" (func $_frees))
+ (elem (i32.const 1) $throws)
+ ;; A bump allocator, from the second page on.
+ (func $_malloc (param $size i32) (result i32)
+  (local $ptr i32)
+  (set_local $ptr (get_global $heap))
+  (set_global $heap
+   (i32.and (i32.add (i32.add (get_global $heap) (get_local $size)) (i32.const 7)) (i32.const -8))
+  )
+  (get_local $ptr)
+ )
+ (func $_free (param $ptr i32)
+  (set_global $frees (i32.add (get_global $frees) (i32.const 1)))
+ )
+ (func $_frees (result i32)
+  (get_global $frees)
+ )
+ (func $_memset (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+  (get_local $ptr)
+ )
+ (func $stackAlloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $stackSave (result i32)
+  (get_global $sp)
+ )
+ (func $stackRestore (param $sp i32)
+  (set_global $sp (get_local $sp))
+ )
+ (func $_setThrew (param $threw i32) (param $value i32)
+  (if (i32.eqz (get_global $threw))
+   (then
+    (set_global $threw (get_local $threw))
+    (set_global $threwValue (get_local $value))
+   )
+  )
+ )
+ ;; Type infos are plain numbers here, and only catch themselves.
+ (func $___cxa_can_catch (param $catch_ty i32) (param $ty i32) (param $ptr i32) (result i32)
+  (i32.eq (get_local $catch_ty) (get_local $ty))
+ )
+ (func $dynCall_vi (param $f i32) (param $a i32)
+  (call_indirect (type $vi) (get_local $a) (get_local $f))
+ )
+ ;; Table function 1: throws an int holding $x, of type 100.
+ (func $throws (param $x i32)
+  (local $ptr i32)
+  (set_local $ptr (call $___cxa_allocate_exception (i32.const 4)))
+  (i32.store (get_local $ptr) (get_local $x))
+  (call $___cxa_throw (get_local $ptr) (i32.const 100) (i32.const 0))
+ )
+ ;; try { throws(x); } catch (catch_ty e) { return e; }, lowered the way
+ ;; emscripten lowers it. Returns -1 if nothing was thrown and -2 if the
+ ;; clause doesn't take the exception.
+ (func $_catch (param $x i32) (param $catch_ty i32) (result i32)
+  (local $ptr i32)
+  (local $value i32)
+  (set_global $threw (i32.const 0))
+  (call $invoke_vi (i32.const 1) (get_local $x))
+  (if (i32.eqz (get_global $threw))
+   (then (return (i32.const -1)))
+  )
+  (set_local $ptr (call $___cxa_find_matching_catch_3 (get_local $catch_ty)))
+  (if (i32.ne (call $getTempRet0) (get_local $catch_ty))
+   (then (return (i32.const -2)))
+  )
+  (set_local $value (i32.load (call $___cxa_begin_catch (get_local $ptr))))
+  (call $___cxa_end_catch)
+  (get_local $value)
+ )
+)
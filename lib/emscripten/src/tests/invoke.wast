@@ -0,0 +1,42 @@
+(module
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "_emscripten_memcpy_big" (func $_emscripten_memcpy_big (param i32 i32 i32) (result i32)))
+ (import "env" "_pthread_key_create" (func $_pthread_key_create (param i32 i32) (result i32)))
+ (import "env" "_pthread_setspecific" (func $_pthread_setspecific (param i32 i32) (result i32)))
+ (import "env" "_pthread_getspecific" (func $_pthread_getspecific (param i32) (result i32)))
+ (global $constructed (mut i32) (i32.const 0))
+ (export "_malloc" (func $_malloc))
+ (export "_free" (func $_free))
+ (export "_memset" (func $_memset))
+ (export "stackAlloc" (func $stackAlloc))
+ (export "globalCtors" (func $globalCtors))
+ (export "_add_to_key" (func $_add_to_key))
+ (func $_malloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $_free (param $ptr i32))
+ (func $_memset (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+  (get_local $ptr)
+ )
+ (func $stackAlloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $globalCtors
+  (set_global $constructed (i32.const 1))
+ )
+ ;; Store $x under a new thread-specific key, which lives in the runtime's
+ ;; data, and read it back. Adds 1 if the constructors ran.
+ (func $_add_to_key (param $x i32) (result i32)
+  (drop
+   (call $_pthread_key_create (i32.const 1024) (i32.const 0))
+  )
+  (drop
+   (call $_pthread_setspecific (i32.load (i32.const 1024)) (get_local $x))
+  )
+  (i32.add
+   (call $_pthread_getspecific (i32.load (i32.const 1024)))
+   (get_global $constructed)
+  )
+ )
+)
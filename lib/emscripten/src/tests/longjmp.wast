@@ -0,0 +1,69 @@
+(module
+ (type $vii (func (param i32 i32)))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 2 anyfunc))
+ (import "env" "invoke_vii" (func $invoke_vii (param i32 i32 i32)))
+ (import "env" "_longjmp" (func $_longjmp (param i32 i32)))
+ (global $sp (mut i32) (i32.const 4096))
+ (global $threw (mut i32) (i32.const 0))
+ (global $threwValue (mut i32) (i32.const 0))
+ (export "_malloc" (func $_malloc))
+ (export "_free" (func $_free))
+ (export "_memset" (func $_memset))
+ (export "stackAlloc" (func $stackAlloc))
+ (export "stackSave" (func $stackSave))
+ (export "stackRestore" (func $stackRestore))
+ (export "_setThrew" (func $_setThrew))
+ (export "dynCall_vii" (func $dynCall_vii))
+ (export "_jump" (func $_jump))
+ (elem (i32.const 1) $jumps)
+ (func $_malloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $_free (param $ptr i32))
+ (func $_memset (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+  (get_local $ptr)
+ )
+ (func $stackAlloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $stackSave (result i32)
+  (get_global $sp)
+ )
+ (func $stackRestore (param $sp i32)
+  (set_global $sp (get_local $sp))
+ )
+ ;; Like emscripten's, only the first throw is recorded.
+ (func $_setThrew (param $threw i32) (param $value i32)
+  (if (i32.eqz (get_global $threw))
+   (then
+    (set_global $threw (get_local $threw))
+    (set_global $threwValue (get_local $value))
+   )
+  )
+ )
+ (func $dynCall_vii (param $f i32) (param $a i32) (param $b i32)
+  (call_indirect (type $vii) (get_local $a) (get_local $b) (get_local $f))
+ )
+ ;; Table function 1: grows the stack, as a frame would, and jumps out.
+ (func $jumps (param $env i32) (param $val i32)
+  (set_global $sp (i32.add (get_global $sp) (i32.const 64)))
+  (call $_longjmp (get_local $env) (get_local $val))
+ )
+ ;; Call $jumps through `invoke_vii`, as emscripten does for calls that may
+ ;; longjmp, with a `jmp_buf` at 1024. Returns the value `setjmp` would
+ ;; return the second time, or -1 if `__THREW__` doesn't lead back to the
+ ;; `jmp_buf` or the stack wasn't restored.
+ (func $_jump (param $val i32) (result i32)
+  (set_global $threw (i32.const 0))
+  (call $invoke_vii (i32.const 1) (i32.const 1024) (get_local $val))
+  (if (result i32)
+   (i32.and
+    (i32.eq (get_global $threw) (i32.const 1024))
+    (i32.eq (get_global $sp) (i32.const 4096))
+   )
+   (then (get_global $threwValue))
+   (else (i32.const -1))
+  )
+ )
+)
@@ -0,0 +1,47 @@
+(module
+ (type $v (func))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 2 anyfunc))
+ (import "env" "___syscall6" (func $close (param i32 i32) (result i32)))
+ (global $heap (mut i32) (i32.const 65536))
+ (export "_malloc" (func $_malloc))
+ (export "_free" (func $_free))
+ (export "_memset" (func $_memset))
+ (export "stackAlloc" (func $stackAlloc))
+ (export "dynCall_v" (func $dynCall_v))
+ (export "_fdopen" (func $_fdopen))
+ (export "_fclose" (func $_fclose))
+ (elem (i32.const 1) $count)
+ ;; A bump allocator, from the second page on.
+ (func $_malloc (param $size i32) (result i32)
+  (local $ptr i32)
+  (set_local $ptr (get_global $heap))
+  (set_global $heap
+   (i32.and (i32.add (i32.add (get_global $heap) (get_local $size)) (i32.const 7)) (i32.const -8))
+  )
+  (get_local $ptr)
+ )
+ (func $_free (param $ptr i32))
+ (func $_memset (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+  (get_local $ptr)
+ )
+ (func $stackAlloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $dynCall_v (param $f i32)
+  (call_indirect (type $v) (get_local $f))
+ )
+ ;; Streams are their fd plus 1000.
+ (func $_fdopen (param $fd i32) (param $mode i32) (result i32)
+  (i32.add (get_local $fd) (i32.const 1000))
+ )
+ ;; Closes the fd with the word before the counter below as varargs.
+ (func $_fclose (param $stream i32) (result i32)
+  (i32.store (i32.const 65528) (i32.sub (get_local $stream) (i32.const 1000)))
+  (call $close (i32.const 6) (i32.const 65528))
+ )
+ ;; Table function 1: counts its calls in the last word of the first page.
+ (func $count
+  (i32.store (i32.const 65532) (i32.add (i32.load (i32.const 65532)) (i32.const 1)))
+ )
+)
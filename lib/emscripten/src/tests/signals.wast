@@ -0,0 +1,147 @@
+(module
+ (type $vi (func (param i32)))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "_sigaction" (func $_sigaction (param i32 i32 i32) (result i32)))
+ (import "env" "_raise" (func $_raise (param i32) (result i32)))
+ (import "env" "_setitimer" (func $_setitimer (param i32 i32 i32) (result i32)))
+ (import "env" "_getitimer" (func $_getitimer (param i32 i32) (result i32)))
+ (import "env" "_usleep" (func $_usleep (param i32) (result i32)))
+ (import "env" "___syscall3" (func $read (param i32 i32) (result i32)))
+ (import "env" "___syscall4" (func $write (param i32 i32) (result i32)))
+ (import "env" "___syscall42" (func $pipe (param i32 i32) (result i32)))
+ (global $caught (mut i32) (i32.const 0))
+ (export "_malloc" (func $_malloc))
+ (export "_free" (func $_free))
+ (export "_memset" (func $_memset))
+ (export "stackAlloc" (func $stackAlloc))
+ (export "dynCall_vi" (func $dynCall_vi))
+ (export "_raise_handled" (func $_raise_handled))
+ (export "_raise_ignored" (func $_raise_ignored))
+ (export "_raise_unhandled" (func $_raise_unhandled))
+ (export "_timer_is_reported" (func $_timer_is_reported))
+ (export "_alarm_interrupts_sleep" (func $_alarm_interrupts_sleep))
+ (export "_alarm_interrupts_read" (func $_alarm_interrupts_read))
+ (export "_alarm_restarts_read" (func $_alarm_restarts_read))
+ ;; Table indices 0 and 1 would be SIG_DFL and SIG_IGN.
+ (elem (i32.const 2) $handler $writing_handler)
+ ;; struct sigaction with the handler, and with SIG_IGN.
+ (data (i32.const 1024) "\02")
+ (data (i32.const 1200) "\01")
+ ;; struct sigaction with the writing handler and SA_RESTART.
+ (data (i32.const 1700) "\03")
+ (data (i32.const 1832) "\00\00\00\10")
+ ;; The pipe's fds go to 3000. Reads of a byte into 3200 are made
+ ;; from the first, and writes of the byte at 3400 to the second.
+ (data (i32.const 3050) "\b8\0b\00\00")
+ (data (i32.const 3104) "\80\0c\00\00\01\00\00\00")
+ (data (i32.const 3304) "\48\0d\00\00\01\00\00\00")
+ (data (i32.const 3400) "*")
+ ;; struct itimerval of no interval and 1ms, and of 1s and 2s.
+ (data (i32.const 1400) "\00\00\00\00\00\00\00\00\00\00\00\00\e8\03\00\00")
+ (data (i32.const 1500) "\01\00\00\00\00\00\00\00\02\00\00\00\00\00\00\00")
+ (func $_malloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $_free (param $ptr i32))
+ (func $_memset (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+  (get_local $ptr)
+ )
+ (func $stackAlloc (param $size i32) (result i32)
+  (i32.const 0)
+ )
+ (func $dynCall_vi (param $f i32) (param $a i32)
+  (call_indirect (type $vi) (get_local $a) (get_local $f))
+ )
+ (func $handler (param $signum i32)
+  (set_global $caught (get_local $signum))
+ )
+ ;; Writes the byte at 3400 to the pipe.
+ (func $writing_handler (param $signum i32)
+  (set_global $caught (get_local $signum))
+  (i32.store (i32.const 3300) (i32.load (i32.const 3004)))
+  (drop (call $write (i32.const 4) (i32.const 3300)))
+ )
+ ;; Opens the pipe and sets SIGALRM to be raised in 1ms, with the
+ ;; sigaction at `act`, then reads from the pipe and returns the result.
+ (func $read_with_alarm (param $act i32) (result i32)
+  (drop (call $pipe (i32.const 42) (i32.const 3050)))
+  (i32.store (i32.const 3100) (i32.load (i32.const 3000)))
+  (drop (call $_sigaction (i32.const 14) (get_local $act) (i32.const 0)))
+  (drop (call $_setitimer (i32.const 0) (i32.const 1400) (i32.const 0)))
+  (call $read (i32.const 3) (i32.const 3100))
+ )
+ ;; SIGALRM with a handler, due in 1ms during a read from an empty
+ ;; pipe: returns the signal caught times 10, plus 1 if the read
+ ;; failed with EINTR.
+ (func $_alarm_interrupts_read (result i32)
+  (local $interrupted i32)
+  (set_local $interrupted
+   (i32.eq (call $read_with_alarm (i32.const 1024)) (i32.const -4))
+  )
+  (i32.add
+   (i32.mul (get_global $caught) (i32.const 10))
+   (get_local $interrupted)
+  )
+ )
+ ;; SIGALRM with a handler that writes to the pipe and asks for
+ ;; SA_RESTART, due in 1ms during a read from the pipe: returns the
+ ;; signal caught times 1000, plus the bytes read times 100, plus
+ ;; the byte read.
+ (func $_alarm_restarts_read (result i32)
+  (local $read i32)
+  (set_local $read (call $read_with_alarm (i32.const 1700)))
+  (i32.add
+   (i32.add
+    (i32.mul (get_global $caught) (i32.const 1000))
+    (i32.mul (get_local $read) (i32.const 100))
+   )
+   (i32.load8_u (i32.const 3200))
+  )
+ )
+ ;; SIGUSR1 with a handler: returns the signal it caught.
+ (func $_raise_handled (result i32)
+  (drop (call $_sigaction (i32.const 10) (i32.const 1024) (i32.const 0)))
+  (drop (call $_raise (i32.const 10)))
+  (get_global $caught)
+ )
+ ;; SIGUSR1 ignored: returns the signal caught times 10
+ ;; plus the handler sigaction reports.
+ (func $_raise_ignored (result i32)
+  (drop (call $_sigaction (i32.const 10) (i32.const 1200) (i32.const 0)))
+  (drop (call $_raise (i32.const 10)))
+  (drop (call $_sigaction (i32.const 10) (i32.const 0) (i32.const 2048)))
+  (i32.add
+   (i32.mul (get_global $caught) (i32.const 10))
+   (i32.load (i32.const 2048))
+  )
+ )
+ ;; SIGUSR2 without a handler ends the program.
+ (func $_raise_unhandled (result i32)
+  (call $_raise (i32.const 12))
+ )
+ ;; Returns the whole seconds of the interval and of the time left,
+ ;; as tens and units.
+ (func $_timer_is_reported (result i32)
+  (drop (call $_setitimer (i32.const 0) (i32.const 1500) (i32.const 0)))
+  (drop (call $_getitimer (i32.const 0) (i32.const 1600)))
+  (i32.add
+   (i32.mul (i32.load (i32.const 1600)) (i32.const 10))
+   (i32.load (i32.const 1608))
+  )
+ )
+ ;; SIGALRM with a handler, due in 1ms during a 10s sleep: returns the
+ ;; signal caught times 10, plus 1 if the sleep was cut short.
+ (func $_alarm_interrupts_sleep (result i32)
+  (local $interrupted i32)
+  (drop (call $_sigaction (i32.const 14) (i32.const 1024) (i32.const 0)))
+  (drop (call $_setitimer (i32.const 0) (i32.const 1400) (i32.const 0)))
+  (set_local $interrupted
+   (i32.eq (call $_usleep (i32.const 10000000)) (i32.const -1))
+  )
+  (i32.add
+   (i32.mul (get_global $caught) (i32.const 10))
+   (get_local $interrupted)
+  )
+ )
+)
@@ -1,374 +1,1011 @@
-use super::utils::{copy_cstr_into_wasm, write_to_buf};
-use libc::{c_char, c_int};
-use std::mem;
-use std::time::SystemTime;
+//! Clocks, time zones and the conversions between `time_t` and `struct tm`.
+//!
+//! The conversions are done here rather than on the host, so they work
+//! with the guest's 32-bit `time_t` and the layouts of its libc; only the
+//! offset and name of the local time zone come from the host.
 
-#[cfg(not(target_os = "windows"))]
-use libc::{clockid_t, time as libc_time};
+use super::env;
+use super::signal;
+use super::utils::{copy_cstr_into_wasm, read_string_from_wasm, write_to_buf};
+use crate::env::get_emscripten_data;
+use crate::errno::set_errno;
+use libc::{c_char, c_int, EINVAL, EPERM};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::time::{Duration, Instant, SystemTime};
+use wasmer_runtime_core::vm::Ctx;
 
-#[cfg(target_os = "windows")]
-use libc::time_t;
+use time;
 
-#[cfg(target_os = "windows")]
-#[allow(non_camel_case_types)]
-type clockid_t = c_int;
+// The clocks of emscripten's libc.
+const CLOCK_REALTIME: c_int = 0;
+const CLOCK_MONOTONIC: c_int = 1;
+const CLOCK_PROCESS_CPUTIME_ID: c_int = 2;
+const CLOCK_THREAD_CPUTIME_ID: c_int = 3;
+const CLOCK_MONOTONIC_RAW: c_int = 4;
+const CLOCK_REALTIME_COARSE: c_int = 5;
+const CLOCK_MONOTONIC_COARSE: c_int = 6;
+const CLOCK_BOOTTIME: c_int = 7;
+
+const CLOCKS_PER_SEC: u128 = 1_000_000;
+
+/// Where the first read of a deterministic clock is, like in emscripten.
+const DETERMINISTIC_START: Duration = Duration::from_secs(10);
+
+/// Where an emscripten module gets the time from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clock {
+    /// The clocks and time zone of the host.
+    Host,
+    /// Like emscripten's `-s DETERMINISTIC=1`: every clock starts 10 seconds
+    /// after the epoch and moves a millisecond forward each time it's read,
+    /// whatever the host's clocks say, and the time zone is UTC. Runs of
+    /// a module see the same times, for tests to compare their output.
+    Deterministic,
+}
 
-#[cfg(target_os = "windows")]
-extern "C" {
-    #[link_name = "time"]
-    pub fn libc_time(s: *const time_t) -> time_t;
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::Host
+    }
 }
 
-use time;
+/// The clocks of an instance, and the memory time functions return.
+#[derive(Debug)]
+pub(crate) struct Clocks {
+    clock: Clock,
+    /// When the instance started, for `clock`.
+    start: Instant,
+    /// How many times a deterministic clock was read.
+    reads: u64,
+    /// The `struct tm` that `gmtime` and `localtime` return.
+    tm: u32,
+    /// The string that `asctime` and `ctime` return.
+    asctime: u32,
+    /// Time zone names in guest memory, for `tm_zone` and `tzname`.
+    zones: HashMap<String, u32>,
+}
 
-use super::env;
-use wasmer_runtime_core::vm::Ctx;
+impl Clocks {
+    pub(crate) fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            start: Instant::now(),
+            reads: 0,
+            tm: 0,
+            asctime: 0,
+            zones: HashMap::new(),
+        }
+    }
+}
 
-#[cfg(target_os = "linux")]
-use libc::{CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE, CLOCK_REALTIME};
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Source {
+    /// The time since the epoch.
+    Realtime,
+    /// The time since some point that doesn't move.
+    Monotonic,
+    /// The time the instance has been running.
+    Cpu,
+}
 
-#[cfg(target_os = "macos")]
-use libc::{CLOCK_MONOTONIC, CLOCK_REALTIME};
-#[cfg(target_os = "macos")]
-const CLOCK_MONOTONIC_COARSE: clockid_t = 6;
+fn read_clock(ctx: &mut Ctx, source: Source) -> Duration {
+    let clocks = &mut get_emscripten_data(ctx).clocks;
+    if clocks.clock == Clock::Deterministic {
+        clocks.reads += 1;
+        let elapsed = Duration::from_millis(clocks.reads);
+        return match source {
+            Source::Realtime | Source::Monotonic => DETERMINISTIC_START + elapsed,
+            Source::Cpu => elapsed,
+        };
+    }
+    match source {
+        Source::Realtime => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default(),
+        Source::Monotonic => Duration::from_nanos(time::precise_time_ns()),
+        Source::Cpu => clocks.start.elapsed(),
+    }
+}
 
-// some assumptions about the constants when targeting windows
-#[cfg(target_os = "windows")]
-const CLOCK_REALTIME: clockid_t = 0;
-#[cfg(target_os = "windows")]
-const CLOCK_MONOTONIC: clockid_t = 1;
-#[cfg(target_os = "windows")]
-const CLOCK_MONOTONIC_COARSE: clockid_t = 6;
+fn clock_source(clk_id: c_int) -> Option<Source> {
+    match clk_id {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => Some(Source::Realtime),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            Some(Source::Monotonic)
+        }
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => Some(Source::Cpu),
+        _ => None,
+    }
+}
 
-/// emscripten: _gettimeofday
-#[allow(clippy::cast_ptr_alignment)]
-pub fn _gettimeofday(ctx: &mut Ctx, tp: c_int, tz: c_int) -> c_int {
-    debug!("emscripten::_gettimeofday {} {}", tp, tz);
-    #[repr(C)]
-    struct GuestTimeVal {
-        tv_sec: i32,
-        tv_usec: i32,
+/// A broken-down time, as in `struct tm`, with the name of its zone.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Tm {
+    sec: i32,
+    min: i32,
+    hour: i32,
+    mday: i32,
+    mon: i32,
+    /// Years since 1900.
+    year: i32,
+    wday: i32,
+    yday: i32,
+    isdst: i32,
+    /// Seconds east of UTC.
+    gmtoff: i32,
+    zone: String,
+}
+
+/// The days between the epoch and a date of the proleptic Gregorian
+/// calendar, `month` going from 1 to 12.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` after the epoch, `month` going from 1 to 12.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// `a / b`, rounded down.
+fn div_floor(a: i64, b: i64) -> i64 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
     }
+}
 
-    assert!(
-        tz == 0,
-        "the timezone argument of `_gettimeofday` must be null"
-    );
-    unsafe {
-        let now = SystemTime::now();
-        let since_epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
-        let timeval_struct_ptr = emscripten_memory_pointer!(ctx.memory(0), tp) as *mut GuestTimeVal;
+/// `a % b`, with the sign of `b`.
+fn mod_floor(a: i64, b: i64) -> i64 {
+    a - div_floor(a, b) * b
+}
 
-        (*timeval_struct_ptr).tv_sec = since_epoch.as_secs() as _;
-        (*timeval_struct_ptr).tv_usec = since_epoch.subsec_nanos() as _;
+/// `t`, shifted by `gmtoff` seconds, broken down.
+fn break_down(t: i64, gmtoff: i32, isdst: i32, zone: String) -> Tm {
+    let t = t + i64::from(gmtoff);
+    let days = div_floor(t, 86_400);
+    let secs = mod_floor(t, 86_400);
+    let (year, month, day) = civil_from_days(days);
+    Tm {
+        sec: (secs % 60) as i32,
+        min: (secs / 60 % 60) as i32,
+        hour: (secs / 3600) as i32,
+        mday: day as i32,
+        mon: month as i32 - 1,
+        year: (year - 1900) as i32,
+        // The epoch was a Thursday.
+        wday: mod_floor(days + 4, 7) as i32,
+        yday: (days - days_from_civil(year, 1, 1)) as i32,
+        isdst,
+        gmtoff,
+        zone,
     }
-    0
 }
 
-/// emscripten: _clock_gettime
-#[allow(clippy::cast_ptr_alignment)]
-pub fn _clock_gettime(ctx: &mut Ctx, clk_id: clockid_t, tp: c_int) -> c_int {
-    debug!("emscripten::_clock_gettime {} {}", clk_id, tp);
-    // debug!("Memory {:?}", ctx.memory(0)[..]);
-    #[repr(C)]
-    struct GuestTimeSpec {
-        tv_sec: i32,
-        tv_nsec: i32,
-    }
-
-    #[allow(unreachable_patterns)]
-    let timespec = match clk_id {
-        CLOCK_REALTIME => time::get_time(),
-
-        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE => {
-            let precise_ns = time::precise_time_ns();
-            time::Timespec::new(
-                (precise_ns / 1000000000) as i64,
-                (precise_ns % 1000000000) as i32,
-            )
+/// The seconds since the epoch of `tm` taken as UTC. Fields out of
+/// their ranges carry over, like 32 January is 1 February.
+fn timegm(tm: &Tm) -> i64 {
+    let month = i64::from(tm.mon);
+    let year = i64::from(tm.year) + 1900 + div_floor(month, 12);
+    let days = days_from_civil(year, mod_floor(month, 12) + 1, 1) + i64::from(tm.mday) - 1;
+    days * 86_400 + i64::from(tm.hour) * 3600 + i64::from(tm.min) * 60 + i64::from(tm.sec)
+}
+
+/// The offset east of UTC, whether it's daylight saving time
+/// and the name of the local time zone at `t`.
+fn local_zone(clock: Clock, t: i64) -> (i32, i32, String) {
+    match clock {
+        Clock::Host => host_zone(t),
+        Clock::Deterministic => (0, 0, "UTC".to_string()),
+    }
+}
+
+#[cfg(unix)]
+fn host_zone(t: i64) -> (i32, i32, String) {
+    use std::ffi::CStr;
+    let t = t as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return (0, 0, "UTC".to_string());
+    }
+    let zone = if tm.tm_zone.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(tm.tm_zone) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    (tm.tm_gmtoff as i32, (tm.tm_isdst > 0) as i32, zone)
+}
+
+#[cfg(not(unix))]
+fn host_zone(t: i64) -> (i32, i32, String) {
+    let tm = time::at(time::Timespec::new(t, 0));
+    let offset = tm.tm_utcoff.abs() / 60;
+    let sign = if tm.tm_utcoff < 0 { '-' } else { '+' };
+    let zone = format!("UTC{}{:02}{:02}", sign, offset / 60, offset % 60);
+    (tm.tm_utcoff, (tm.tm_isdst > 0) as i32, zone)
+}
+
+fn local_time(clock: Clock, t: i64) -> Tm {
+    let (gmtoff, isdst, zone) = local_zone(clock, t);
+    break_down(t, gmtoff, isdst, zone)
+}
+
+/// The seconds since the epoch of `tm` taken as local time. `tm_isdst`
+/// says whether it's daylight saving time when that's ambiguous.
+fn mktime(clock: Clock, tm: &Tm) -> i64 {
+    let local = timegm(tm);
+    let (gmtoff, _, _) = local_zone(clock, local);
+    let mut t = local - i64::from(gmtoff);
+    // The offset may be different on the other side of a transition.
+    let (gmtoff, isdst, _) = local_zone(clock, t);
+    t = local - i64::from(gmtoff);
+    if tm.isdst > 0 && isdst == 0 {
+        t -= 3600;
+    } else if tm.isdst == 0 && isdst > 0 {
+        t += 3600;
+    }
+    t
+}
+
+// struct tm, in words: tm_sec, tm_min, tm_hour, tm_mday, tm_mon, tm_year,
+// tm_wday, tm_yday, tm_isdst, tm_gmtoff and tm_zone, a `const char *`.
+const TM_SIZE: u32 = 44;
+
+fn read_tm(ctx: &mut Ctx, ptr: u32) -> Tm {
+    let base = ptr as usize / 4;
+    let (fields, zone) = {
+        let view = ctx.memory(0).view::<i32>();
+        let mut fields = [0; 10];
+        for (i, field) in fields.iter_mut().enumerate() {
+            *field = view[base + i].get();
         }
-        _ => panic!("Clock with id \"{}\" is not supported.", clk_id),
+        (fields, view[base + 10].get() as u32)
     };
+    Tm {
+        sec: fields[0],
+        min: fields[1],
+        hour: fields[2],
+        mday: fields[3],
+        mon: fields[4],
+        year: fields[5],
+        wday: fields[6],
+        yday: fields[7],
+        isdst: fields[8],
+        gmtoff: fields[9],
+        zone: if zone == 0 {
+            String::new()
+        } else {
+            read_string_from_wasm(ctx.memory(0), zone)
+        },
+    }
+}
 
-    unsafe {
-        let timespec_struct_ptr =
-            emscripten_memory_pointer!(ctx.memory(0), tp) as *mut GuestTimeSpec;
-        (*timespec_struct_ptr).tv_sec = timespec.sec as _;
-        (*timespec_struct_ptr).tv_nsec = timespec.nsec as _;
+fn write_tm(ctx: &mut Ctx, ptr: u32, tm: &Tm) {
+    let zone = zone_name(ctx, &tm.zone);
+    let fields = [
+        tm.sec,
+        tm.min,
+        tm.hour,
+        tm.mday,
+        tm.mon,
+        tm.year,
+        tm.wday,
+        tm.yday,
+        tm.isdst,
+        tm.gmtoff,
+        zone as i32,
+    ];
+    let view = ctx.memory(0).view::<i32>();
+    for (i, &field) in fields.iter().enumerate() {
+        view[ptr as usize / 4 + i].set(field);
     }
-    0
+}
+
+/// The time zone name `name` in guest memory, to point at for good.
+fn zone_name(ctx: &mut Ctx, name: &str) -> u32 {
+    if let Some(&ptr) = get_emscripten_data(ctx).clocks.zones.get(name) {
+        return ptr;
+    }
+    let cstr = CString::new(name).unwrap_or_default();
+    let ptr = unsafe { copy_cstr_into_wasm(ctx, cstr.as_ptr()) };
+    get_emscripten_data(ctx)
+        .clocks
+        .zones
+        .insert(name.to_string(), ptr);
+    ptr
+}
+
+/// The guest `time_t` at `ptr`.
+fn read_time(ctx: &mut Ctx, ptr: u32) -> i64 {
+    i64::from(ctx.memory(0).view::<i32>()[ptr as usize / 4].get())
+}
+
+/// The `struct tm` that `gmtime` and `localtime` share.
+fn static_tm(ctx: &mut Ctx) -> u32 {
+    if get_emscripten_data(ctx).clocks.tm == 0 {
+        let tm = env::call_malloc(ctx, TM_SIZE);
+        get_emscripten_data(ctx).clocks.tm = tm;
+    }
+    get_emscripten_data(ctx).clocks.tm
+}
+
+/// emscripten: _gettimeofday
+pub fn _gettimeofday(ctx: &mut Ctx, tp: u32, tz: u32) -> Result<c_int, String> {
+    debug!("emscripten::_gettimeofday {} {}", tp, tz);
+    let now = read_clock(ctx, Source::Realtime);
+    if tp != 0 {
+        // struct timeval { time_t tv_sec; suseconds_t tv_usec; }
+        let view = ctx.memory(0).view::<i32>();
+        view[tp as usize / 4].set(now.as_secs() as i32);
+        view[tp as usize / 4 + 1].set(now.subsec_micros() as i32);
+    }
+    if tz != 0 {
+        // struct timezone { int tz_minuteswest; int tz_dsttime; }
+        let clock = get_emscripten_data(ctx).clocks.clock;
+        let (gmtoff, _, _) = local_zone(clock, now.as_secs() as i64);
+        let view = ctx.memory(0).view::<i32>();
+        view[tz as usize / 4].set(-gmtoff / 60);
+        view[tz as usize / 4 + 1].set(0);
+    }
+    signal::deliver(ctx)?;
+    Ok(0)
+}
+
+/// emscripten: _clock_gettime
+pub fn _clock_gettime(ctx: &mut Ctx, clk_id: c_int, tp: u32) -> Result<c_int, String> {
+    debug!("emscripten::_clock_gettime {} {}", clk_id, tp);
+    let source = match clock_source(clk_id) {
+        Some(source) => source,
+        None => {
+            set_errno(ctx, EINVAL);
+            return Ok(-1);
+        }
+    };
+    let now = read_clock(ctx, source);
+    // struct timespec { time_t tv_sec; long tv_nsec; }
+    let view = ctx.memory(0).view::<i32>();
+    view[tp as usize / 4].set(now.as_secs() as i32);
+    view[tp as usize / 4 + 1].set(now.subsec_nanos() as i32);
+    signal::deliver(ctx)?;
+    Ok(0)
 }
 
 /// emscripten: ___clock_gettime
-pub fn ___clock_gettime(ctx: &mut Ctx, clk_id: clockid_t, tp: c_int) -> c_int {
+pub fn ___clock_gettime(ctx: &mut Ctx, clk_id: c_int, tp: u32) -> Result<c_int, String> {
     debug!("emscripten::___clock_gettime {} {}", clk_id, tp);
     _clock_gettime(ctx, clk_id, tp)
 }
 
+/// emscripten: _clock_getres
+pub fn _clock_getres(ctx: &mut Ctx, clk_id: c_int, res: u32) -> c_int {
+    debug!("emscripten::_clock_getres {} {}", clk_id, res);
+    if clock_source(clk_id).is_none() {
+        set_errno(ctx, EINVAL);
+        return -1;
+    }
+    let nsec = match get_emscripten_data(ctx).clocks.clock {
+        Clock::Host => 1,
+        Clock::Deterministic => 1_000_000,
+    };
+    if res != 0 {
+        let view = ctx.memory(0).view::<i32>();
+        view[res as usize / 4].set(0);
+        view[res as usize / 4 + 1].set(nsec);
+    }
+    0
+}
+
+/// emscripten: _clock_settime
+///
+/// Modules can't set the host's clocks: the realtime clock refuses, like
+/// it does for unprivileged processes, and the others can't be set at all.
+pub fn _clock_settime(ctx: &mut Ctx, clk_id: c_int, _tp: u32) -> c_int {
+    debug!("emscripten::_clock_settime {}", clk_id);
+    let errno = match clock_source(clk_id) {
+        Some(Source::Realtime) => EPERM,
+        _ => EINVAL,
+    };
+    set_errno(ctx, errno);
+    -1
+}
+
 /// emscripten: _clock
-pub fn _clock(_ctx: &mut Ctx) -> c_int {
+pub fn _clock(ctx: &mut Ctx) -> c_int {
     debug!("emscripten::_clock");
-    0 // TODO: unimplemented
+    let elapsed = read_clock(ctx, Source::Cpu);
+    (elapsed.as_micros() * CLOCKS_PER_SEC / 1_000_000) as c_int
 }
 
 /// emscripten: _difftime
-pub fn _difftime(_ctx: &mut Ctx, t0: u32, t1: u32) -> f64 {
+pub fn _difftime(_ctx: &mut Ctx, t1: i32, t0: i32) -> f64 {
     debug!("emscripten::_difftime");
-    (t0 - t1) as _
+    f64::from(t1) - f64::from(t0)
 }
 
-pub fn _gmtime_r(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::_gmtime_r");
-    -1
+/// emscripten: _time
+pub fn _time(ctx: &mut Ctx, time_p: u32) -> Result<i32, String> {
+    debug!("emscripten::_time {}", time_p);
+    let now = read_clock(ctx, Source::Realtime).as_secs() as i32;
+    if time_p != 0 {
+        ctx.memory(0).view::<i32>()[time_p as usize / 4].set(now);
+    }
+    signal::deliver(ctx)?;
+    Ok(now)
 }
 
-pub fn _mktime(_ctx: &mut Ctx, _one: i32) -> i32 {
-    debug!("emscripten::_mktime");
-    -1
+/// emscripten: _gmtime_r
+pub fn _gmtime_r(ctx: &mut Ctx, time_p: u32, result: u32) -> u32 {
+    debug!("emscripten::_gmtime_r {} {}", time_p, result);
+    let t = read_time(ctx, time_p);
+    write_tm(ctx, result, &break_down(t, 0, 0, "UTC".to_string()));
+    result
 }
 
-pub fn _gmtime(_ctx: &mut Ctx, _one: i32) -> i32 {
-    debug!("emscripten::_gmtime");
-    -1
+/// emscripten: _gmtime
+pub fn _gmtime(ctx: &mut Ctx, time_p: u32) -> u32 {
+    debug!("emscripten::_gmtime {}", time_p);
+    let result = static_tm(ctx);
+    _gmtime_r(ctx, time_p, result)
 }
 
-#[repr(C)]
-struct guest_tm {
-    pub tm_sec: c_int,    // 0
-    pub tm_min: c_int,    // 4
-    pub tm_hour: c_int,   // 8
-    pub tm_mday: c_int,   // 12
-    pub tm_mon: c_int,    // 16
-    pub tm_year: c_int,   // 20
-    pub tm_wday: c_int,   // 24
-    pub tm_yday: c_int,   // 28
-    pub tm_isdst: c_int,  // 32
-    pub tm_gmtoff: c_int, // 36
-    pub tm_zone: c_int,   // 40
+/// emscripten: _localtime_r
+pub fn _localtime_r(ctx: &mut Ctx, time_p: u32, result: u32) -> u32 {
+    debug!("emscripten::_localtime_r {} {}", time_p, result);
+    // Like in emscripten, local times are what sets `tzname` and friends.
+    _tzset(ctx);
+    let t = read_time(ctx, time_p);
+    let clock = get_emscripten_data(ctx).clocks.clock;
+    write_tm(ctx, result, &local_time(clock, t));
+    result
 }
 
-/// emscripten: _tvset
-pub fn _tvset(_ctx: &mut Ctx) {
-    debug!("emscripten::_tvset UNIMPLEMENTED");
+/// emscripten: _localtime
+pub fn _localtime(ctx: &mut Ctx, time_p: u32) -> u32 {
+    debug!("emscripten::_localtime {}", time_p);
+    let result = static_tm(ctx);
+    _localtime_r(ctx, time_p, result)
 }
 
-/// formats time as a C string
-#[allow(clippy::cast_ptr_alignment)]
-unsafe fn fmt_time(ctx: &mut Ctx, time: u32) -> *const c_char {
-    let date = &*(emscripten_memory_pointer!(ctx.memory(0), time) as *mut guest_tm);
+/// The guest `time_t` for `t`, or -1 if it doesn't fit.
+fn to_time_t(t: i64) -> i32 {
+    if t >= i64::from(i32::min_value()) && t <= i64::from(i32::max_value()) {
+        t as i32
+    } else {
+        -1
+    }
+}
 
-    let days = vec!["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
-    let months = vec![
-        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
-    ];
-    let year = 1900 + date.tm_year;
-
-    let time_str = format!(
-        // NOTE: TODO: Hack! The 14 accompanying chars are needed for some reason
-        "{} {} {:2} {:02}:{:02}:{:02} {:4}\n\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        days[date.tm_wday as usize],
-        months[date.tm_mon as usize],
-        date.tm_mday,
-        date.tm_hour,
-        date.tm_min,
-        date.tm_sec,
-        year
-    );
+/// emscripten: _mktime
+///
+/// Like in libc, the fields of `tm` are brought back into their ranges
+/// and its day of the week and of the year are filled in.
+pub fn _mktime(ctx: &mut Ctx, tm_ptr: u32) -> i32 {
+    debug!("emscripten::_mktime {}", tm_ptr);
+    _tzset(ctx);
+    let tm = read_tm(ctx, tm_ptr);
+    let clock = get_emscripten_data(ctx).clocks.clock;
+    let t = to_time_t(mktime(clock, &tm));
+    if t != -1 {
+        write_tm(ctx, tm_ptr, &local_time(clock, i64::from(t)));
+    }
+    t
+}
 
-    time_str[0..26].as_ptr() as _
+/// emscripten: _timegm
+///
+/// Like in emscripten, `tm_isdst`, `tm_gmtoff` and `tm_zone` are left
+/// alone, so a local `tm` still prints its own zone afterwards.
+pub fn _timegm(ctx: &mut Ctx, tm_ptr: u32) -> i32 {
+    debug!("emscripten::_timegm {}", tm_ptr);
+    let tm = read_tm(ctx, tm_ptr);
+    let t = to_time_t(timegm(&tm));
+    if t != -1 {
+        let utc = Tm {
+            isdst: tm.isdst,
+            gmtoff: tm.gmtoff,
+            ..break_down(i64::from(t), 0, 0, tm.zone)
+        };
+        write_tm(ctx, tm_ptr, &utc);
+    }
+    t
 }
 
-/// emscripten: _asctime
-pub fn _asctime(ctx: &mut Ctx, time: u32) -> u32 {
-    debug!("emscripten::_asctime {}", time);
+/// emscripten: _tzset
+///
+/// Fills in `timezone`, `daylight` and `tzname` from the offsets of
+/// the local time zone in winter and in summer this year.
+pub fn _tzset(ctx: &mut Ctx) {
+    debug!("emscripten::_tzset");
+    let clock = get_emscripten_data(ctx).clocks.clock;
+    let now = read_clock(ctx, Source::Realtime).as_secs() as i64;
+    let year = i64::from(local_time(clock, now).year) + 1900;
+    let winter = local_zone(clock, days_from_civil(year, 1, 1) * 86_400);
+    let summer = local_zone(clock, days_from_civil(year, 7, 1) * 86_400);
+    // Standard time has the smaller offset, in either hemisphere.
+    let (standard, daylight) = if summer.0 < winter.0 {
+        (summer, winter)
+    } else {
+        (winter, summer)
+    };
 
-    unsafe {
-        let time_str_ptr = fmt_time(ctx, time);
-        copy_cstr_into_wasm(ctx, time_str_ptr)
+    let data = get_emscripten_data(ctx);
+    let addresses = match (&data.get_timezone, &data.get_daylight, &data.get_tzname) {
+        (Some(timezone), Some(daylight), Some(tzname)) => (
+            timezone.call().map_err(|_| ()),
+            daylight.call().map_err(|_| ()),
+            tzname.call().map_err(|_| ()),
+        ),
+        _ => return,
+    };
+    let (timezone_ptr, daylight_ptr, tzname_ptr) = match addresses {
+        (Ok(timezone), Ok(daylight), Ok(tzname)) => (timezone, daylight, tzname),
+        _ => return,
+    };
+    let standard_name = zone_name(ctx, &standard.2);
+    let daylight_name = zone_name(ctx, &daylight.2);
+    let view = ctx.memory(0).view::<i32>();
+    view[timezone_ptr as usize / 4].set(-standard.0);
+    view[daylight_ptr as usize / 4].set((standard.0 != daylight.0) as i32);
+    view[tzname_ptr as usize / 4].set(standard_name as i32);
+    view[tzname_ptr as usize / 4 + 1].set(daylight_name as i32);
+}
 
-        // let c_str = emscripten_memory_pointer!(ctx.memory(0), res) as *mut i8;
-        // use std::ffi::CStr;
-        // debug!("#### cstr = {:?}", CStr::from_ptr(c_str));
-    }
+const DAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn day_name(wday: i32) -> &'static str {
+    DAYS.get(wday as usize).cloned().unwrap_or("?")
 }
 
-/// emscripten: _asctime_r
-pub fn _asctime_r(ctx: &mut Ctx, time: u32, buf: u32) -> u32 {
-    debug!("emscripten::_asctime_r {}, {}", time, buf);
+fn month_name(mon: i32) -> &'static str {
+    MONTHS.get(mon as usize).cloned().unwrap_or("?")
+}
 
-    unsafe {
-        // NOTE: asctime_r is specced to behave in an undefined manner if the algorithm would attempt
-        //      to write out more than 26 bytes (including the null terminator).
-        //      See http://pubs.opengroup.org/onlinepubs/9699919799/functions/asctime.html
-        //      Our undefined behavior is to truncate the write to at most 26 bytes, including null terminator.
-        let time_str_ptr = fmt_time(ctx, time);
-        write_to_buf(ctx, time_str_ptr, buf, 26)
+/// `tm` as `asctime` formats it, like `Thu Feb 21 20:23:04 1974\n`.
+fn asctime(tm: &Tm) -> String {
+    format!(
+        "{:.3} {:.3}{:3} {:02}:{:02}:{:02} {}\n",
+        day_name(tm.wday),
+        month_name(tm.mon),
+        tm.mday,
+        tm.hour,
+        tm.min,
+        tm.sec,
+        i64::from(tm.year) + 1900
+    )
+}
 
-        // let c_str = emscripten_memory_pointer!(ctx.memory(0), res) as *mut i8;
-        // use std::ffi::CStr;
-        // debug!("#### cstr = {:?}", CStr::from_ptr(c_str));
+/// Write `s` and a null byte to `buf`, at most `size` bytes of them.
+fn write_cstr(ctx: &mut Ctx, buf: u32, s: &[u8], size: usize) {
+    let len = s.len().min(size.saturating_sub(1));
+    let view = ctx.memory(0).view::<u8>();
+    for (cell, &byte) in view[buf as usize..].iter().zip(&s[..len]) {
+        cell.set(byte);
     }
+    view[buf as usize + len].set(0);
 }
 
-/// emscripten: _localtime
-#[allow(clippy::cast_ptr_alignment)]
-pub fn _localtime(ctx: &mut Ctx, time_p: u32) -> c_int {
-    debug!("emscripten::_localtime {}", time_p);
-    // NOTE: emscripten seems to want tzset() called in this function
-    //      https://stackoverflow.com/questions/19170721/real-time-awareness-of-timezone-change-in-localtime-vs-localtime-r
-
-    let timespec = unsafe {
-        let time_p_addr = emscripten_memory_pointer!(ctx.memory(0), time_p) as *mut i64;
-        let seconds = *time_p_addr.clone();
-        time::Timespec::new(seconds, 0)
-    };
-    let result_tm = time::at(timespec);
-
-    unsafe {
-        let tm_struct_offset = env::call_malloc(ctx, mem::size_of::<guest_tm>() as _);
-        let tm_struct_ptr =
-            emscripten_memory_pointer!(ctx.memory(0), tm_struct_offset) as *mut guest_tm;
-        // debug!(
-        //     ">>>>>>> time = {}, {}, {}, {}, {}, {}, {}, {}",
-        //     result_tm.tm_sec, result_tm.tm_min, result_tm.tm_hour, result_tm.tm_mday,
-        //     result_tm.tm_mon, result_tm.tm_year, result_tm.tm_wday, result_tm.tm_yday,
-        // );
-        (*tm_struct_ptr).tm_sec = result_tm.tm_sec;
-        (*tm_struct_ptr).tm_min = result_tm.tm_min;
-        (*tm_struct_ptr).tm_hour = result_tm.tm_hour;
-        (*tm_struct_ptr).tm_mday = result_tm.tm_mday;
-        (*tm_struct_ptr).tm_mon = result_tm.tm_mon;
-        (*tm_struct_ptr).tm_year = result_tm.tm_year;
-        (*tm_struct_ptr).tm_wday = result_tm.tm_wday;
-        (*tm_struct_ptr).tm_yday = result_tm.tm_yday;
-        (*tm_struct_ptr).tm_isdst = result_tm.tm_isdst;
-        (*tm_struct_ptr).tm_gmtoff = 0;
-        (*tm_struct_ptr).tm_zone = 0;
-
-        tm_struct_offset as _
+/// The string that `asctime` and `ctime` share.
+fn static_asctime(ctx: &mut Ctx, tm: &Tm) -> u32 {
+    // Long enough for any year a `struct tm` can hold.
+    const SIZE: u32 = 64;
+    if get_emscripten_data(ctx).clocks.asctime == 0 {
+        let buf = env::call_malloc(ctx, SIZE);
+        get_emscripten_data(ctx).clocks.asctime = buf;
     }
+    let buf = get_emscripten_data(ctx).clocks.asctime;
+    write_cstr(ctx, buf, asctime(tm).as_bytes(), SIZE as usize);
+    buf
 }
-/// emscripten: _localtime_r
-#[allow(clippy::cast_ptr_alignment)]
-pub fn _localtime_r(ctx: &mut Ctx, time_p: u32, result: u32) -> c_int {
-    debug!("emscripten::_localtime_r {}", time_p);
 
-    // NOTE: emscripten seems to want tzset() called in this function
-    //      https://stackoverflow.com/questions/19170721/real-time-awareness-of-timezone-change-in-localtime-vs-localtime-r
+/// emscripten: _asctime
+pub fn _asctime(ctx: &mut Ctx, tm_ptr: u32) -> u32 {
+    debug!("emscripten::_asctime {}", tm_ptr);
+    let tm = read_tm(ctx, tm_ptr);
+    static_asctime(ctx, &tm)
+}
+
+/// Write `asctime` of `tm` to the 26 bytes at `buf`.
+fn write_asctime_r(ctx: &mut Ctx, buf: u32, tm: &Tm) -> u32 {
+    let mut bytes = asctime(tm).into_bytes();
+    bytes.truncate(25);
+    bytes.resize(26, 0);
+    unsafe { write_to_buf(ctx, bytes.as_ptr() as *const c_char, buf, 26) }
+}
 
-    unsafe {
-        let seconds = emscripten_memory_pointer!(ctx.memory(0), time_p) as *const i32;
-        let timespec = time::Timespec::new(*seconds as _, 0);
-        let result_tm = time::at(timespec);
+/// emscripten: _asctime_r
+pub fn _asctime_r(ctx: &mut Ctx, tm_ptr: u32, buf: u32) -> u32 {
+    debug!("emscripten::_asctime_r {}, {}", tm_ptr, buf);
+    // NOTE: asctime_r is specced to behave in an undefined manner if the algorithm would attempt
+    //      to write out more than 26 bytes (including the null terminator).
+    //      See http://pubs.opengroup.org/onlinepubs/9699919799/functions/asctime.html
+    //      Our undefined behavior is to truncate the write to at most 26 bytes, including null terminator.
+    let tm = read_tm(ctx, tm_ptr);
+    write_asctime_r(ctx, buf, &tm)
+}
 
-        // debug!(
-        //     ">>>>>>> time = {}, {}, {}, {}, {}, {}, {}, {}",
-        //     result_tm.tm_sec, result_tm.tm_min, result_tm.tm_hour, result_tm.tm_mday,
-        //     result_tm.tm_mon, result_tm.tm_year, result_tm.tm_wday, result_tm.tm_yday,
-        // );
+/// emscripten: _ctime
+pub fn _ctime(ctx: &mut Ctx, time_p: u32) -> u32 {
+    debug!("emscripten::_ctime {}", time_p);
+    let t = read_time(ctx, time_p);
+    let clock = get_emscripten_data(ctx).clocks.clock;
+    static_asctime(ctx, &local_time(clock, t))
+}
 
-        let result_addr = emscripten_memory_pointer!(ctx.memory(0), result) as *mut guest_tm;
+/// emscripten: _ctime_r
+pub fn _ctime_r(ctx: &mut Ctx, time_p: u32, buf: u32) -> u32 {
+    debug!("emscripten::_ctime_r {}, {}", time_p, buf);
+    let t = read_time(ctx, time_p);
+    let clock = get_emscripten_data(ctx).clocks.clock;
+    write_asctime_r(ctx, buf, &local_time(clock, t))
+}
 
-        (*result_addr).tm_sec = result_tm.tm_sec;
-        (*result_addr).tm_min = result_tm.tm_min;
-        (*result_addr).tm_hour = result_tm.tm_hour;
-        (*result_addr).tm_mday = result_tm.tm_mday;
-        (*result_addr).tm_mon = result_tm.tm_mon;
-        (*result_addr).tm_year = result_tm.tm_year;
-        (*result_addr).tm_wday = result_tm.tm_wday;
-        (*result_addr).tm_yday = result_tm.tm_yday;
-        (*result_addr).tm_isdst = result_tm.tm_isdst;
-        (*result_addr).tm_gmtoff = 0;
-        (*result_addr).tm_zone = 0;
+/// The day of the week of the `yday`th day of `year`.
+fn weekday(year: i64, yday: i64) -> i64 {
+    mod_floor(days_from_civil(year, 1, 1) + yday + 4, 7)
+}
 
-        result as _
+fn iso_weeks_in_year(year: i64) -> i64 {
+    let p = |y: i64| {
+        mod_floor(
+            y + div_floor(y, 4) - div_floor(y, 100) + div_floor(y, 400),
+            7,
+        )
+    };
+    if p(year) == 4 || p(year - 1) == 3 {
+        53
+    } else {
+        52
     }
 }
 
-/// emscripten: _time
-#[allow(clippy::cast_ptr_alignment)]
-pub fn _time(ctx: &mut Ctx, time_p: u32) -> i32 {
-    debug!("emscripten::_time {}", time_p);
+/// The ISO 8601 week-based year and week of the `yday`th day of `year`.
+fn iso_week(year: i64, yday: i64) -> (i64, i64) {
+    let monday_based = (weekday(year, yday) + 6) % 7;
+    let week = (yday - monday_based + 10) / 7;
+    if week < 1 {
+        (year - 1, iso_weeks_in_year(year - 1))
+    } else if week > iso_weeks_in_year(year) {
+        (year + 1, 1)
+    } else {
+        (year, week)
+    }
+}
 
-    unsafe {
-        let time_p_addr = emscripten_memory_pointer!(ctx.memory(0), time_p) as *mut i64;
-        libc_time(time_p_addr) as i32 // TODO review i64
+/// The week of the year of `tm`, weeks starting on `first_day`; days
+/// before the first of those are in week 0.
+fn week_of_year(tm: &Tm, first_day: i64) -> i64 {
+    // Like emscripten, from the date rather than `tm_yday` and `tm_wday`.
+    let year = i64::from(tm.year) + 1900;
+    let january_first = days_from_civil(year, 1, 1);
+    let day = days_from_civil(year, i64::from(tm.mon) + 1, i64::from(tm.mday));
+    let first_weekday = mod_floor(january_first + 4, 7);
+    let first_week = january_first + mod_floor(first_day - first_weekday, 7);
+    if day < first_week {
+        0
+    } else {
+        (day - first_week) / 7 + 1
     }
 }
 
+/// What a conversion of `strftime` expands to.
+enum Field {
+    /// A number, with the width and padding it has by default.
+    Number(i64, usize, u8),
+    Text(Vec<u8>),
+}
+
+/// `tm` formatted like `strftime` does in the C locale, with the
+/// padding flags and field widths of POSIX and glibc. Unknown
+/// conversions are kept as they are.
+fn strftime(format: &[u8], tm: &Tm) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < format.len() {
+        if format[i] != b'%' {
+            out.push(format[i]);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+
+        let mut pad = None;
+        let mut upper = false;
+        while let Some(&flag) = format.get(i) {
+            match flag {
+                b'_' | b'-' | b'0' | b'+' => pad = Some(flag),
+                b'^' => upper = true,
+                b'#' => {}
+                _ => break,
+            }
+            i += 1;
+        }
+        let mut width = None;
+        while let Some(digit) = format.get(i).filter(|byte| byte.is_ascii_digit()) {
+            width = Some(width.unwrap_or(0) * 10 + usize::from(digit - b'0'));
+            i += 1;
+        }
+        if let Some(b'E') | Some(b'O') = format.get(i) {
+            i += 1;
+        }
+        let conversion = match format.get(i) {
+            Some(&conversion) => conversion,
+            None => {
+                out.extend_from_slice(&format[start..]);
+                break;
+            }
+        };
+        i += 1;
+
+        let year = i64::from(tm.year) + 1900;
+        let hour12 = match tm.hour % 12 {
+            0 => 12,
+            hour => hour,
+        };
+        let field = match conversion {
+            b'a' => Field::Text(day_name(tm.wday)[..3].into()),
+            b'A' => Field::Text(day_name(tm.wday).into()),
+            b'b' | b'h' => Field::Text(month_name(tm.mon)[..3].into()),
+            b'B' => Field::Text(month_name(tm.mon).into()),
+            b'c' => Field::Text(strftime(b"%a %b %e %H:%M:%S %Y", tm)),
+            b'C' => Field::Number(div_floor(year, 100), 2, b'0'),
+            b'd' => Field::Number(tm.mday.into(), 2, b'0'),
+            b'D' => Field::Text(strftime(b"%m/%d/%y", tm)),
+            b'e' => Field::Number(tm.mday.into(), 2, b' '),
+            b'F' => Field::Text(strftime(b"%Y-%m-%d", tm)),
+            b'g' => Field::Number(mod_floor(iso_week(year, tm.yday.into()).0, 100), 2, b'0'),
+            b'G' => Field::Number(iso_week(year, tm.yday.into()).0, 1, b'0'),
+            b'H' => Field::Number(tm.hour.into(), 2, b'0'),
+            b'I' => Field::Number(hour12.into(), 2, b'0'),
+            b'j' => Field::Number(i64::from(tm.yday) + 1, 3, b'0'),
+            b'k' => Field::Number(tm.hour.into(), 2, b' '),
+            b'l' => Field::Number(hour12.into(), 2, b' '),
+            b'm' => Field::Number(i64::from(tm.mon) + 1, 2, b'0'),
+            b'M' => Field::Number(tm.min.into(), 2, b'0'),
+            b'n' => Field::Text(b"\n".to_vec()),
+            b'p' => Field::Text(if tm.hour < 12 { "AM" } else { "PM" }.into()),
+            b'P' => Field::Text(if tm.hour < 12 { "am" } else { "pm" }.into()),
+            b'r' => Field::Text(strftime(b"%I:%M:%S %p", tm)),
+            b'R' => Field::Text(strftime(b"%H:%M", tm)),
+            b's' => Field::Number(timegm(tm) - i64::from(tm.gmtoff), 1, b'0'),
+            b'S' => Field::Number(tm.sec.into(), 2, b'0'),
+            b't' => Field::Text(b"\t".to_vec()),
+            b'T' => Field::Text(strftime(b"%H:%M:%S", tm)),
+            b'u' => Field::Number(if tm.wday == 0 { 7 } else { tm.wday.into() }, 1, b'0'),
+            b'U' => Field::Number(week_of_year(tm, 0), 2, b'0'),
+            b'V' => Field::Number(iso_week(year, tm.yday.into()).1, 2, b'0'),
+            b'w' => Field::Number(tm.wday.into(), 1, b'0'),
+            b'W' => Field::Number(week_of_year(tm, 1), 2, b'0'),
+            b'x' => Field::Text(strftime(b"%m/%d/%y", tm)),
+            b'X' => Field::Text(strftime(b"%H:%M:%S", tm)),
+            b'y' => Field::Number(mod_floor(year, 100), 2, b'0'),
+            b'Y' => Field::Number(year, 1, b'0'),
+            b'z' => {
+                let minutes = tm.gmtoff.abs() / 60;
+                let sign = if tm.gmtoff < 0 { '-' } else { '+' };
+                Field::Text(format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60).into())
+            }
+            b'Z' => Field::Text(tm.zone.as_bytes().into()),
+            b'%' => Field::Text(b"%".to_vec()),
+            _ => Field::Text(format[start..i].into()),
+        };
+
+        match field {
+            Field::Number(value, default_width, default_pad) => {
+                let pad = match pad {
+                    Some(b'-') => None,
+                    Some(b'_') => Some(b' '),
+                    Some(b'0') | Some(b'+') => Some(b'0'),
+                    _ => Some(default_pad),
+                };
+                let width = width.unwrap_or(default_width);
+                let digits = value.abs().to_string().into_bytes();
+                let sign_len = (value < 0) as usize;
+                let fill = match pad {
+                    Some(_) => width.saturating_sub(digits.len() + sign_len),
+                    None => 0,
+                };
+                if pad == Some(b' ') {
+                    out.extend(std::iter::repeat(b' ').take(fill));
+                }
+                if value < 0 {
+                    out.push(b'-');
+                }
+                if pad == Some(b'0') {
+                    out.extend(std::iter::repeat(b'0').take(fill));
+                }
+                out.extend(digits);
+            }
+            Field::Text(mut text) => {
+                if upper {
+                    text.make_ascii_uppercase();
+                }
+                let fill = width.unwrap_or(0).saturating_sub(text.len());
+                let pad = if pad == Some(b'0') { b'0' } else { b' ' };
+                out.extend(std::iter::repeat(pad).take(fill));
+                out.extend(text);
+            }
+        }
+    }
+    out
+}
+
 /// emscripten: _strftime
-pub fn _strftime(
-    ctx: &mut Ctx,
-    s_ptr: c_int,
-    maxsize: u32,
-    format_ptr: c_int,
-    tm_ptr: c_int,
-) -> i32 {
+pub fn _strftime(ctx: &mut Ctx, s_ptr: u32, maxsize: u32, format_ptr: u32, tm_ptr: u32) -> u32 {
     debug!(
         "emscripten::_strftime {} {} {} {}",
         s_ptr, maxsize, format_ptr, tm_ptr
     );
-
-    #[allow(clippy::cast_ptr_alignment)]
-    let s = emscripten_memory_pointer!(ctx.memory(0), s_ptr) as *mut c_char;
-    #[allow(clippy::cast_ptr_alignment)]
-    let format = emscripten_memory_pointer!(ctx.memory(0), format_ptr) as *const c_char;
-    #[allow(clippy::cast_ptr_alignment)]
-    let tm = emscripten_memory_pointer!(ctx.memory(0), tm_ptr) as *const guest_tm;
-
-    let format_string = unsafe { std::ffi::CStr::from_ptr(format).to_str().unwrap() };
-
-    debug!("=> format_string: {:?}", format_string);
-
-    let tm = unsafe { &*tm };
-
-    let rust_tm = ::time::Tm {
-        tm_sec: tm.tm_sec,
-        tm_min: tm.tm_min,
-        tm_hour: tm.tm_hour,
-        tm_mday: tm.tm_mday,
-        tm_mon: tm.tm_mon,
-        tm_year: tm.tm_year,
-        tm_wday: tm.tm_wday,
-        tm_yday: tm.tm_yday,
-        tm_isdst: tm.tm_isdst,
-        tm_utcoff: tm.tm_gmtoff,
-        tm_nsec: 0,
-    };
-
-    let result_str = match ::time::strftime(format_string, &rust_tm) {
-        Ok(res_string) => res_string,
-        // TODO: maybe match on e in Err(e) and return different values if required
-        _ => return 0,
-    };
-
-    // pad for null?
-    let bytes = result_str.chars().count();
-    if bytes as u32 > maxsize {
+    let format: Vec<u8> = ctx.memory(0).view::<u8>()[format_ptr as usize..]
+        .iter()
+        .map(|cell| cell.get())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    let tm = read_tm(ctx, tm_ptr);
+    let result = strftime(&format, &tm);
+    debug!("=> {:?}", String::from_utf8_lossy(&result));
+
+    // There must be room for the null byte too.
+    if result.len() >= maxsize as usize {
         return 0;
-    } else {
-        // write output string
-        for (i, c) in result_str.chars().enumerate() {
-            unsafe { *s.add(i) = c as c_char };
-        }
-        // null terminate?
-        bytes as i32
     }
+    write_cstr(ctx, s_ptr, &result, maxsize as usize);
+    result.len() as u32
 }
 
 /// emscripten: _strftime_l
+///
+/// Only the C locale is supported, whatever `locale` is.
 pub fn _strftime_l(
     ctx: &mut Ctx,
-    s_ptr: c_int,
+    s_ptr: u32,
     maxsize: u32,
-    format_ptr: c_int,
-    tm_ptr: c_int,
-    _last: c_int,
-) -> i32 {
+    format_ptr: u32,
+    tm_ptr: u32,
+    _locale: u32,
+) -> u32 {
     debug!(
         "emscripten::_strftime_l {} {} {} {}",
         s_ptr, maxsize, format_ptr, tm_ptr
     );
-
     _strftime(ctx, s_ptr, maxsize, format_ptr, tm_ptr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{break_down, civil_from_days, days_from_civil, strftime, timegm, Tm};
+
+    fn tm(year: i32, mon: i32, mday: i32, wday: i32, yday: i32) -> Tm {
+        Tm {
+            sec: 4,
+            min: 23,
+            hour: 20,
+            mday,
+            mon,
+            year,
+            wday,
+            yday,
+            ..Tm::default()
+        }
+    }
+
+    fn format(format: &str, tm: &Tm) -> String {
+        String::from_utf8(strftime(format.as_bytes(), tm)).unwrap()
+    }
+
+    #[test]
+    fn days_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for &days in &[-800_000, -1, 0, 59, 10_957, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn times_break_down_and_back() {
+        // Thu Feb 21 20:23:04 1974
+        let t = 130_710_184;
+        let tm = break_down(t, 0, 0, "UTC".to_string());
+        assert_eq!(
+            (tm.year, tm.mon, tm.mday, tm.hour, tm.min, tm.sec),
+            (74, 1, 21, 20, 23, 4)
+        );
+        assert_eq!((tm.wday, tm.yday), (4, 51));
+        assert_eq!(timegm(&tm), t);
+
+        let before_epoch = break_down(-1, 0, 0, String::new());
+        assert_eq!((before_epoch.year, before_epoch.yday), (69, 364));
+        assert_eq!(before_epoch.wday, 3);
+
+        // Out of range fields carry over.
+        let mut tm = tm;
+        tm.mon = 13;
+        tm.mday = 0;
+        assert_eq!(break_down(timegm(&tm), 0, 0, String::new()).mon, 0);
+    }
+
+    #[test]
+    fn strftime_formats_like_emscripten() {
+        let mut tm = tm(74, 1, 21, 4, 51);
+        assert_eq!(format("%a %A %b %B", &tm), "Thu Thursday Feb February");
+        assert_eq!(
+            format("%d %H %I %j %m %M %p %S", &tm),
+            "21 20 08 052 02 23 PM 04"
+        );
+        assert_eq!(format("%U %w %W %y %Y %%", &tm), "07 4 07 74 1974 %");
+        assert_eq!(format("%c", &tm), "Thu Feb 21 20:23:04 1974");
+        assert_eq!(
+            format("%D %F %T %e", &tm),
+            "02/21/74 1974-02-21 20:23:04 21"
+        );
+        assert_eq!(format("%z %s", &tm), "+0000 130710184");
+        assert_eq!(format("%-d %_m %5Y %^a %q", &tm), "21  2 01974 THU %q");
+
+        tm.mon = 0;
+        tm.mday = 1;
+        assert_eq!(format("%U %W", &tm), "00 00");
+        tm.year = 73;
+        assert_eq!(format("%W", &tm), "01");
+        tm.year = 78;
+        assert_eq!(format("%U", &tm), "01");
+
+        tm.year = 99;
+        tm.yday = 1;
+        assert_eq!(format("%G (%V) %g", &tm), "1998 (53) 98");
+        tm.year = 97;
+        tm.yday = 363;
+        assert_eq!(format("%G (%V) %g", &tm), "1998 (01) 98");
+
+        tm.hour = 0;
+        tm.min = 1;
+        assert_eq!(format("%I %M %p", &tm), "12 01 AM");
+        tm.hour = 12;
+        assert_eq!(format("%I %M %p", &tm), "12 01 PM");
+    }
+}
@@ -22,13 +22,19 @@ pub fn is_emscripten_module(module: &Module) -> bool {
             .namespace_table
             .get(import_name.namespace_index);
         let field = module.info().name_table.get(import_name.name_index);
-        if field == "_emscripten_memcpy_big" && namespace == "env" {
+        if is_emscripten_import(namespace, field) {
             return true;
         }
     }
     false
 }
 
+/// Whether importing the function `field` of `namespace` makes a module
+/// an Emscripten generated one, for when it isn't compiled.
+pub fn is_emscripten_import(namespace: &str, field: &str) -> bool {
+    field == "_emscripten_memcpy_big" && namespace == "env"
+}
+
 pub fn get_emscripten_table_size(module: &Module) -> (u32, Option<u32>) {
     let (_, table) = &module.info().imported_tables[ImportedTableIndex::new(0)];
     (table.minimum, table.maximum)
@@ -156,7 +162,6 @@ pub unsafe fn copy_stat_into_wasm(ctx: &mut Ctx, buf: u32, stat: &stat) {
     (*stat_ptr).st_ino = stat.st_ino as _;
 }
 
-#[allow(dead_code)] // it's used in `env/windows/mod.rs`.
 pub fn read_string_from_wasm(memory: &Memory, offset: u32) -> String {
     let v: Vec<u8> = memory.view()[(offset as usize)..]
         .iter()
@@ -167,38 +172,61 @@ pub fn read_string_from_wasm(memory: &Memory, offset: u32) -> String {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::is_emscripten_module;
+    use crate::{generate_emscripten_env, EmscriptenConfig, EmscriptenData, EmscriptenGlobals};
+    use libc::c_void;
     use std::sync::Arc;
     use wabt::wat2wasm;
     use wasmer_runtime_core::backend::Compiler;
     use wasmer_runtime_core::compile_with;
+    use wasmer_runtime_core::vm::Ctx;
 
     #[cfg(feature = "clif")]
-    fn get_compiler() -> impl Compiler {
+    pub(crate) fn get_compiler() -> impl Compiler {
         use wasmer_clif_backend::CraneliftCompiler;
         CraneliftCompiler::new()
     }
 
     #[cfg(feature = "llvm")]
-    fn get_compiler() -> impl Compiler {
+    pub(crate) fn get_compiler() -> impl Compiler {
         use wasmer_llvm_backend::LLVMCompiler;
         LLVMCompiler::new()
     }
 
     #[cfg(feature = "singlepass")]
-    fn get_compiler() -> impl Compiler {
+    pub(crate) fn get_compiler() -> impl Compiler {
         use wasmer_singlepass_backend::SinglePassCompiler;
         SinglePassCompiler::new()
     }
 
     #[cfg(not(any(feature = "llvm", feature = "clif", feature = "singlepass")))]
-    fn get_compiler() -> impl Compiler {
+    pub(crate) fn get_compiler() -> impl Compiler {
         panic!("compiler not specified, activate a compiler via features");
         use wasmer_clif_backend::CraneliftCompiler;
         CraneliftCompiler::new()
     }
 
+    /// Run `f` with the context of an instance of a module that only has
+    /// the exports the runtime needs, set up as `run_emscripten_instance`
+    /// sets it up. The first page of memory is free for `f` to use, but for
+    /// its last word, where the function at table index 1 counts its calls,
+    /// and the one before. `fdopen` makes streams that are their fd plus
+    /// 1000, and `fclose` closes them.
+    pub(crate) fn with_runtime<F: FnOnce(&mut Ctx)>(f: F) {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/runtime.wast");
+        let wasm_binary = wat2wasm(WAST_BYTES.to_vec()).expect("Can't convert to wasm");
+        let module =
+            compile_with(&wasm_binary[..], &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module);
+        let import_object = generate_emscripten_env(&mut globals);
+        let instance = &mut module.instantiate(&import_object).unwrap();
+        let mut data = EmscriptenData::new(instance, EmscriptenConfig::default());
+        let data_ptr = &mut data as *mut _ as *mut c_void;
+        instance.context_mut().data = data_ptr;
+        f(instance.context_mut());
+    }
+
     #[test]
     fn should_detect_emscripten_files() {
         const WAST_BYTES: &[u8] = include_bytes!("tests/is_emscripten_true.wast");
@@ -1,5 +1,4 @@
 #[test]
-#[ignore]
 fn test_test_posixtime() {
     assert_emscripten_output!(
         "../../emtests/test_posixtime.wasm",
@@ -1,5 +1,4 @@
 #[test]
-#[ignore]
 fn test_test_time_c() {
     assert_emscripten_output!(
         "../../emtests/test_time_c.wasm",
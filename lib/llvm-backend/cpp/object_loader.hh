@@ -85,7 +85,7 @@ struct WasmTrap : UncatchableException
         Unknown,
     };
 
-    WasmTrap(Type type) : type(type) {}
+    WasmTrap(Type type, void *address) : type(type), address(address) {}
 
     virtual std::string description() const noexcept override
     {
@@ -98,6 +98,8 @@ struct WasmTrap : UncatchableException
     }
 
     Type type;
+    // The instruction that trapped, if known.
+    void *address;
 
   private:
     friend std::ostream &operator<<(std::ostream &out, const Type &ty)
@@ -158,6 +160,9 @@ struct WasmModule
     std::unique_ptr<llvm::RuntimeDyld> runtime_dyld;
 };
 
+// The instruction behind the last trap caught by `invoke_trampoline` on this thread.
+static thread_local void *last_trap_address = nullptr;
+
 extern "C"
 {
     result_t module_load(const uint8_t *mem_ptr, size_t mem_size, callbacks_t callbacks, WasmModule **module_out)
@@ -167,8 +172,20 @@ extern "C"
         return RESULT_OK;
     }
 
-    [[noreturn]] void throw_trap(WasmTrap::Type ty) {
-        throw WasmTrap(ty);
+    // Called from signal handlers, with the instruction that faulted.
+    [[noreturn]] void throw_trap_at(WasmTrap::Type ty, void *address) {
+        throw WasmTrap(ty, address);
+    }
+
+    // Called from compiled code. `throw_trap` never returns, so the call
+    // may be the last instruction of its function: step back into it.
+    [[noreturn]] __attribute__((noinline)) void throw_trap(WasmTrap::Type ty) {
+        throw WasmTrap(ty, (uint8_t *)__builtin_return_address(0) - 1);
+    }
+
+    void *get_trap_address()
+    {
+        return last_trap_address;
     }
 
     void module_delete(WasmModule *module)
@@ -191,6 +208,8 @@ extern "C"
         WasmTrap::Type *trap_out,
         void *invoke_env) throw()
     {
+        last_trap_address = nullptr;
+
         try
         {
             trampoline(ctx, func, params, results);
@@ -199,6 +218,7 @@ extern "C"
         catch (const WasmTrap &e)
         {
             *trap_out = e.type;
+            last_trap_address = e.address;
             return false;
         }
         catch (const WasmException &e)
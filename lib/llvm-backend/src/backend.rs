@@ -18,11 +18,12 @@ use std::{
     sync::Once,
 };
 use wasmer_runtime_core::{
-    backend::RunnableModule,
+    backend::{CpuFeature, OptLevel, RunnableModule},
+    jit_debug::{self, FunctionDebugInfo, JitDebugRegistration},
     module::ModuleInfo,
     structures::TypedIndex,
     typed_func::{Wasm, WasmTrapInfo},
-    types::{LocalFuncIndex, SigIndex},
+    types::{LocalFuncIndex, LocalOrImport, SigIndex},
     vm, vmcalls,
 };
 
@@ -73,6 +74,7 @@ extern "C" {
     fn get_func_symbol(module: *mut LLVMModule, name: *const c_char) -> *const vm::Func;
 
     fn throw_trap(ty: i32);
+    fn get_trap_address() -> *const c_void;
 
     /// This should be the same as spliting up the fat pointer into two arguments,
     /// but this is cleaner, I think?
@@ -203,41 +205,108 @@ fn get_callbacks() -> Callbacks {
     }
 }
 
+/// The features of `base`, with each of `CpuFeature` turned on or off
+/// depending on whether it's in `cpu_features`. Later entries win.
+fn target_features(base: &str, cpu_features: &[CpuFeature]) -> String {
+    let mut features = base.to_string();
+    for &feature in CpuFeature::all() {
+        let name = match feature {
+            CpuFeature::BMI1 => "bmi",
+            _ => feature.name(),
+        };
+        let sign = if cpu_features.contains(&feature) {
+            '+'
+        } else {
+            '-'
+        };
+        if !features.is_empty() {
+            features.push(',');
+        }
+        features.push(sign);
+        features.push_str(name);
+    }
+    features
+}
+
 unsafe impl Send for LLVMBackend {}
 unsafe impl Sync for LLVMBackend {}
 
 pub struct LLVMBackend {
     module: *mut LLVMModule,
-    #[allow(dead_code)]
     memory_buffer: MemoryBuffer,
+    debug_registration: Option<JitDebugRegistration>,
 }
 
 impl LLVMBackend {
-    pub fn new(module: Module, _intrinsics: Intrinsics) -> Self {
-        Target::initialize_x86(&InitializationConfig {
+    /// Compile `module` for `triple`, or the host if it's `None`. The
+    /// code is only loaded, so that it can be run, if it's for the host's
+    /// architecture.
+    pub fn new(
+        module: Module,
+        _intrinsics: Intrinsics,
+        triple: Option<&str>,
+        cpu_features: &[CpuFeature],
+        opt_level: OptLevel,
+    ) -> Result<Self, String> {
+        let config = InitializationConfig {
             asm_parser: true,
             asm_printer: true,
             base: true,
             disassembler: true,
             info: true,
             machine_code: true,
-        });
-        let triple = TargetMachine::get_default_triple().to_string();
-        let target = Target::from_triple(&triple).unwrap();
+        };
+        let (triple, cpu_name, features) = match triple {
+            // Other targets start from their defaults, like in the other backends.
+            Some(triple) => {
+                Target::initialize_all(&config);
+                (
+                    triple.to_string(),
+                    "generic".to_string(),
+                    target_features("", cpu_features),
+                )
+            }
+            None => {
+                Target::initialize_x86(&config);
+                (
+                    TargetMachine::get_default_triple().to_string(),
+                    TargetMachine::get_host_cpu_name().to_string(),
+                    target_features(
+                        &TargetMachine::get_host_cpu_features().to_string(),
+                        cpu_features,
+                    ),
+                )
+            }
+        };
+        let target = Target::from_triple(&triple)
+            .map_err(|e| format!("unsupported target {}: {:?}", triple, e))?;
         let target_machine = target
             .create_target_machine(
                 &triple,
-                &TargetMachine::get_host_cpu_name().to_string(),
-                &TargetMachine::get_host_cpu_features().to_string(),
-                OptimizationLevel::Aggressive,
+                &cpu_name,
+                &features,
+                match opt_level {
+                    OptLevel::None => OptimizationLevel::None,
+                    OptLevel::Speed => OptimizationLevel::Default,
+                    OptLevel::SpeedAndSize => OptimizationLevel::Aggressive,
+                },
                 RelocMode::PIC,
                 CodeModel::Default,
             )
-            .unwrap();
+            .ok_or_else(|| format!("unsupported target {}", triple))?;
 
         let memory_buffer = target_machine
             .write_to_memory_buffer(&module, FileType::Object)
-            .unwrap();
+            .map_err(|e| format!("{:?}", e))?;
+
+        if triple.split('-').next() != Some(std::env::consts::ARCH) {
+            return Ok(Self {
+                module: ptr::null_mut(),
+                memory_buffer,
+                debug_registration: None,
+            });
+        }
+
         let mem_buf_slice = memory_buffer.as_slice();
 
         let callbacks = get_callbacks();
@@ -259,19 +328,60 @@ impl LLVMBackend {
         });
 
         if res != LLVMResult::OK {
-            panic!("failed to load object")
+            return Err("failed to load object".to_string());
         }
 
-        Self {
+        Ok(Self {
             module,
             memory_buffer,
-        }
+            debug_registration: None,
+        })
+    }
+
+    /// The address and size of every local function, read from the
+    /// symbols of the object file. This is empty if the object file
+    /// isn't in the ELF format.
+    ///
+    /// The code generated by this backend doesn't carry source
+    /// locations, so the functions don't have any instructions.
+    pub fn function_debug_info(&self, info: &ModuleInfo) -> Vec<FunctionDebugInfo> {
+        let sizes = match jit_debug::symbol_sizes(self.memory_buffer.as_slice()) {
+            Some(sizes) => sizes,
+            None => return vec![],
+        };
+
+        let imported_func_count = info.imported_functions.len();
+        (0..info.func_assoc.len() - imported_func_count)
+            .filter_map(|index| {
+                let local_func_index = LocalFuncIndex::new(index);
+                let address = self.get_func(info, local_func_index)?.as_ptr() as usize;
+                let size = *sizes.get(&format!("fn{}", imported_func_count + index))?;
+
+                Some(FunctionDebugInfo {
+                    func_index: local_func_index.convert_up(info),
+                    address,
+                    size: size as usize,
+                    instructions: vec![],
+                })
+            })
+            .collect()
+    }
+
+    /// Register the compiled functions with native debuggers.
+    /// Only function symbols are registered, there are no line tables.
+    pub fn register_debug_info(&mut self, wasm: &[u8], info: &ModuleInfo) {
+        let functions = self.function_debug_info(info);
+        self.debug_registration = jit_debug::register(wasm, info, &functions);
     }
 }
 
 impl Drop for LLVMBackend {
     fn drop(&mut self) {
-        unsafe { module_delete(self.module) }
+        // Unregister the code from debuggers before freeing it.
+        self.debug_registration.take();
+        if !self.module.is_null() {
+            unsafe { module_delete(self.module) }
+        }
     }
 }
 
@@ -281,6 +391,11 @@ impl RunnableModule for LLVMBackend {
         info: &ModuleInfo,
         local_func_index: LocalFuncIndex,
     ) -> Option<NonNull<vm::Func>> {
+        // Code for another architecture isn't loaded.
+        if self.module.is_null() {
+            return None;
+        }
+
         let index = info.imported_functions.len() + local_func_index.index();
         let name = if cfg!(target_os = "macos") {
             format!("_fn{}", index)
@@ -295,6 +410,10 @@ impl RunnableModule for LLVMBackend {
     }
 
     fn get_trampoline(&self, _: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm> {
+        if self.module.is_null() {
+            return None;
+        }
+
         let trampoline: unsafe extern "C" fn(
             *mut vm::Ctx,
             NonNull<vm::Func>,
@@ -320,6 +439,23 @@ impl RunnableModule for LLVMBackend {
     unsafe fn do_early_trap(&self, data: Box<dyn Any>) -> ! {
         throw_any(Box::leak(data))
     }
+
+    fn get_trap_func(&self, info: &ModuleInfo) -> Option<LocalFuncIndex> {
+        let address = unsafe { get_trap_address() } as usize;
+        if address == 0 {
+            return None;
+        }
+
+        self.function_debug_info(info)
+            .into_iter()
+            .find(|function| {
+                function.address <= address && address < function.address + function.size
+            })
+            .and_then(|function| match function.func_index.local_or_import(info) {
+                LocalOrImport::Local(local_func_index) => Some(local_func_index),
+                LocalOrImport::Import(_) => None,
+            })
+    }
 }
 
 #[cfg(feature = "disasm")]
@@ -9,6 +9,7 @@ use inkwell::{
 };
 use smallvec::SmallVec;
 use wasmer_runtime_core::{
+    backend::OptLevel,
     memory::MemoryType,
     module::ModuleInfo,
     structures::{Map, SliceMap, TypedIndex},
@@ -61,6 +62,8 @@ fn type_to_llvm(intrinsics: &Intrinsics, ty: Type) -> BasicTypeEnum {
 pub fn parse_function_bodies(
     info: &ModuleInfo,
     code_reader: CodeSectionReader,
+    opt_level: OptLevel,
+    enable_verifier: bool,
 ) -> Result<(Module, Intrinsics), BinaryReaderError> {
     let context = Context::create();
     let module = context.create_module("module");
@@ -122,17 +125,21 @@ pub fn parse_function_bodies(
     generate_trampolines(info, &signatures, &module, &context, &builder, &intrinsics);
 
     let pass_manager = PassManager::create_for_module();
-    // pass_manager.add_verifier_pass();
-    pass_manager.add_function_inlining_pass();
-    pass_manager.add_promote_memory_to_register_pass();
-    pass_manager.add_cfg_simplification_pass();
-    // pass_manager.add_instruction_combining_pass();
-    pass_manager.add_aggressive_inst_combiner_pass();
-    pass_manager.add_merged_load_store_motion_pass();
-    // pass_manager.add_sccp_pass();
-    // pass_manager.add_gvn_pass();
-    pass_manager.add_new_gvn_pass();
-    pass_manager.add_aggressive_dce_pass();
+    if enable_verifier {
+        pass_manager.add_verifier_pass();
+    }
+    if opt_level != OptLevel::None {
+        pass_manager.add_function_inlining_pass();
+        pass_manager.add_promote_memory_to_register_pass();
+        pass_manager.add_cfg_simplification_pass();
+        // pass_manager.add_instruction_combining_pass();
+        pass_manager.add_aggressive_inst_combiner_pass();
+        pass_manager.add_merged_load_store_motion_pass();
+        // pass_manager.add_sccp_pass();
+        // pass_manager.add_gvn_pass();
+        pass_manager.add_new_gvn_pass();
+        pass_manager.add_aggressive_dce_pass();
+    }
     pass_manager.run_on_module(&module);
 
     // module.print_to_stderr();
@@ -1,10 +1,11 @@
 #![cfg_attr(nightly, feature(unwind_attributes))]
 
 use wasmer_runtime_core::{
-    backend::{Compiler, CompilerConfig, Token},
+    backend::{Backend, Compiler, CompilerConfig, Token},
     cache::{Artifact, Error as CacheError},
     error::CompileError,
     module::ModuleInner,
+    profiling,
 };
 use wasmparser::{self, WasmDecoder};
 
@@ -35,10 +36,39 @@ impl Compiler for LLVMCompiler {
     ) -> Result<ModuleInner, CompileError> {
         validate(wasm)?;
 
-        let (info, code_reader) = read_info::read_module(wasm, compiler_config).unwrap();
-        let (module, intrinsics) = code::parse_function_bodies(&info, code_reader).unwrap();
-
-        let backend = backend::LLVMBackend::new(module, intrinsics);
+        let triple = compiler_config.triple.clone();
+        let generate_debug_info = compiler_config.generate_debug_info;
+        let profiling_strategy = compiler_config.profiling;
+        let opt_level = compiler_config.opt_level;
+        let enable_verifier = compiler_config.enable_verifier;
+        let (mut info, code_reader) = read_info::read_module(wasm, compiler_config).unwrap();
+        let (module, intrinsics) =
+            code::parse_function_bodies(&info, code_reader, opt_level, enable_verifier).unwrap();
+
+        let mut backend = backend::LLVMBackend::new(
+            module,
+            intrinsics,
+            triple.as_ref().map(|triple| triple.as_str()),
+            &info.cpu_features,
+            opt_level,
+        )
+        .map_err(|msg| CompileError::InternalError { msg })?;
+
+        if generate_debug_info || profiling_strategy.is_some() {
+            // Function names come from the `name` section, which is
+            // otherwise only read once the module is compiled.
+            info.import_custom_sections(wasm)
+                .map_err(|e| CompileError::InternalError {
+                    msg: format!("{:?}", e),
+                })?;
+        }
+        if let Some(strategy) = profiling_strategy {
+            // Failing to write profiling data shouldn't fail compilation.
+            let _ = profiling::register(strategy, &info, &backend.function_debug_info(&info));
+        }
+        if generate_debug_info {
+            backend.register_debug_info(wasm, &info);
+        }
 
         // Create placeholder values here.
         let cache_gen = {
@@ -68,8 +98,17 @@ impl Compiler for LLVMCompiler {
         })
     }
 
-    unsafe fn from_cache(&self, _artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
-        unimplemented!("the llvm backend doesn't support caching yet")
+    unsafe fn from_cache(&self, artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+        if artifact.info().backend != Backend::LLVM {
+            return Err(CacheError::IncompatibleBackend(artifact.info().backend));
+        }
+        Err(CacheError::Unknown(
+            "the llvm backend doesn't support caching yet".to_string(),
+        ))
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::LLVM
     }
 }
 
@@ -121,9 +160,18 @@ fn test_read_module() {
 
     let (info, code_reader) = read_info::read_module(&wasm, Default::default()).unwrap();
 
-    let (module, intrinsics) = code::parse_function_bodies(&info, code_reader).unwrap();
-
-    let backend = backend::LLVMBackend::new(module, intrinsics);
+    let config = CompilerConfig::default();
+    let (module, intrinsics) =
+        code::parse_function_bodies(&info, code_reader, config.opt_level, true).unwrap();
+
+    let backend = backend::LLVMBackend::new(
+        module,
+        intrinsics,
+        None,
+        &info.cpu_features,
+        config.opt_level,
+    )
+    .unwrap();
 
     let func_ptr = backend.get_func(&info, LocalFuncIndex::new(0)).unwrap();
 
@@ -40,7 +40,7 @@ pub unsafe fn visit_fde(addr: *mut u8, size: usize, visitor: extern "C" fn(*mut
 
 extern "C" {
     #[cfg_attr(nightly, unwind(allowed))]
-    fn throw_trap(ty: i32) -> !;
+    fn throw_trap_at(ty: i32, address: *const c_void) -> !;
 }
 
 pub unsafe fn install_signal_handler() {
@@ -57,7 +57,7 @@ pub unsafe fn install_signal_handler() {
 extern "C" fn signal_trap_handler(
     _signum: ::nix::libc::c_int,
     _siginfo: *mut siginfo_t,
-    _ucontext: *mut c_void,
+    ucontext: *mut c_void,
 ) {
     unsafe {
         // Apparently, we can unwind from arbitary instructions, as long
@@ -65,6 +65,26 @@ extern "C" fn signal_trap_handler(
         // was interrupted.
         //
         // This works on macos, not sure about linux.
-        throw_trap(2);
+        throw_trap_at(2, get_ip(ucontext));
     }
 }
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn get_ip(ucontext: *mut c_void) -> *const c_void {
+    let ucontext = ucontext as *const libc::ucontext_t;
+    (*ucontext).uc_mcontext.gregs[libc::REG_RIP as usize] as _
+}
+
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+unsafe fn get_ip(ucontext: *mut c_void) -> *const c_void {
+    let ucontext = ucontext as *const libc::ucontext_t;
+    (*(*ucontext).uc_mcontext).__ss.__rip as _
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "macos", target_arch = "x86_64"),
+)))]
+unsafe fn get_ip(_ucontext: *mut c_void) -> *const c_void {
+    std::ptr::null()
+}
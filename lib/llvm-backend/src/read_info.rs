@@ -44,6 +44,8 @@ pub fn read_module(
         func_assoc: Map::new(),
         signatures: Map::new(),
         backend: Backend::LLVM,
+        triple: compiler_config.triple.clone(),
+        cpu_features: compiler_config.cpu_features(),
 
         namespace_table: StringTable::new(),
         name_table: StringTable::new(),
@@ -51,6 +53,8 @@ pub fn read_module(
         em_symbol_map: compiler_config.symbol_map.clone(),
 
         custom_sections: HashMap::new(),
+
+        names: Default::default(),
     };
 
     let mut reader = ModuleReader::new(wasm)?;
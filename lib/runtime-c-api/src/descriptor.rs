@@ -0,0 +1,163 @@
+//! Helpers shared by the import and export descriptor functions.
+
+use crate::{
+    error::{update_last_error, CApiError},
+    export::wasmer_import_export_kind,
+    global::wasmer_global_descriptor_t,
+    value::wasmer_value_tag,
+    wasmer_limit_option_t, wasmer_limits_t, wasmer_result_t,
+};
+use libc::{c_int, uint32_t};
+use std::slice;
+use wasmer_runtime_core::{module::ExternDescriptor, types::FuncSig};
+
+impl From<&ExternDescriptor> for wasmer_import_export_kind {
+    fn from(ty: &ExternDescriptor) -> Self {
+        match ty {
+            ExternDescriptor::Function(_) => wasmer_import_export_kind::WASM_FUNCTION,
+            ExternDescriptor::Memory(_) => wasmer_import_export_kind::WASM_MEMORY,
+            ExternDescriptor::Table(_) => wasmer_import_export_kind::WASM_TABLE,
+            ExternDescriptor::Global(_) => wasmer_import_export_kind::WASM_GLOBAL,
+        }
+    }
+}
+
+fn kind_error(fn_name: &str, expected: &str, ty: &ExternDescriptor) -> wasmer_result_t {
+    update_last_error(CApiError {
+        msg: format!(
+            "{}: expected a {} descriptor, found a {} descriptor",
+            fn_name,
+            expected,
+            ty.kind_name()
+        ),
+    });
+    wasmer_result_t::WASMER_ERROR
+}
+
+fn signature<'a>(ty: &'a ExternDescriptor, fn_name: &str) -> Result<&'a FuncSig, wasmer_result_t> {
+    match ty {
+        ExternDescriptor::Function(signature) => Ok(signature),
+        _ => Err(kind_error(fn_name, "function", ty)),
+    }
+}
+
+fn to_limits(minimum: u32, maximum: Option<u32>) -> wasmer_limits_t {
+    wasmer_limits_t {
+        min: minimum,
+        max: wasmer_limit_option_t {
+            has_some: maximum.is_some(),
+            some: maximum.unwrap_or(0),
+        },
+    }
+}
+
+pub(crate) unsafe fn func_params_arity(
+    ty: &ExternDescriptor,
+    result: *mut uint32_t,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match signature(ty, fn_name) {
+        Ok(signature) => {
+            *result = signature.params().len() as uint32_t;
+            wasmer_result_t::WASMER_OK
+        }
+        Err(error) => error,
+    }
+}
+
+pub(crate) unsafe fn func_params(
+    ty: &ExternDescriptor,
+    params: *mut wasmer_value_tag,
+    params_len: c_int,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match signature(ty, fn_name) {
+        Ok(signature) => {
+            let params: &mut [wasmer_value_tag] =
+                slice::from_raw_parts_mut(params, params_len as usize);
+            for (param, item) in params.iter_mut().zip(signature.params().iter()) {
+                *param = item.into();
+            }
+            wasmer_result_t::WASMER_OK
+        }
+        Err(error) => error,
+    }
+}
+
+pub(crate) unsafe fn func_returns_arity(
+    ty: &ExternDescriptor,
+    result: *mut uint32_t,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match signature(ty, fn_name) {
+        Ok(signature) => {
+            *result = signature.returns().len() as uint32_t;
+            wasmer_result_t::WASMER_OK
+        }
+        Err(error) => error,
+    }
+}
+
+pub(crate) unsafe fn func_returns(
+    ty: &ExternDescriptor,
+    returns: *mut wasmer_value_tag,
+    returns_len: c_int,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match signature(ty, fn_name) {
+        Ok(signature) => {
+            let returns: &mut [wasmer_value_tag] =
+                slice::from_raw_parts_mut(returns, returns_len as usize);
+            for (ret, item) in returns.iter_mut().zip(signature.returns().iter()) {
+                *ret = item.into();
+            }
+            wasmer_result_t::WASMER_OK
+        }
+        Err(error) => error,
+    }
+}
+
+pub(crate) unsafe fn memory_limits(
+    ty: &ExternDescriptor,
+    limits: *mut wasmer_limits_t,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match ty {
+        ExternDescriptor::Memory(desc) => {
+            *limits = to_limits(desc.minimum.0, desc.maximum.map(|pages| pages.0));
+            wasmer_result_t::WASMER_OK
+        }
+        _ => kind_error(fn_name, "memory", ty),
+    }
+}
+
+pub(crate) unsafe fn table_limits(
+    ty: &ExternDescriptor,
+    limits: *mut wasmer_limits_t,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match ty {
+        ExternDescriptor::Table(desc) => {
+            *limits = to_limits(desc.minimum, desc.maximum);
+            wasmer_result_t::WASMER_OK
+        }
+        _ => kind_error(fn_name, "table", ty),
+    }
+}
+
+pub(crate) unsafe fn global_descriptor(
+    ty: &ExternDescriptor,
+    descriptor: *mut wasmer_global_descriptor_t,
+    fn_name: &str,
+) -> wasmer_result_t {
+    match ty {
+        ExternDescriptor::Global(desc) => {
+            *descriptor = wasmer_global_descriptor_t {
+                mutable: desc.mutable,
+                kind: desc.ty.into(),
+            };
+            wasmer_result_t::WASMER_OK
+        }
+        _ => kind_error(fn_name, "global", ty),
+    }
+}
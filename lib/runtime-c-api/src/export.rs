@@ -1,19 +1,23 @@
 //! Wasm exports.
 
 use crate::{
+    descriptor,
     error::{update_last_error, CApiError},
-    global::wasmer_global_t,
+    global::{wasmer_global_descriptor_t, wasmer_global_t},
     import::wasmer_import_func_t,
     memory::wasmer_memory_t,
     module::wasmer_module_t,
     table::wasmer_table_t,
     value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
-    wasmer_byte_array, wasmer_result_t,
+    wasmer_byte_array, wasmer_limits_t, wasmer_result_t,
 };
 use libc::{c_int, uint32_t};
 use std::{ptr, slice};
 use wasmer_runtime::{Instance, Memory, Module, Value};
-use wasmer_runtime_core::{export::Export, module::ExportIndex};
+use wasmer_runtime_core::{
+    export::Export,
+    module::{ExportDescriptor, ExternDescriptor},
+};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -66,7 +70,7 @@ pub unsafe extern "C" fn wasmer_export_descriptors(
     let module = &*(module as *const Module);
 
     let named_export_descriptors: Box<NamedExportDescriptors> = Box::new(NamedExportDescriptors(
-        module.info().exports.iter().map(|e| e.into()).collect(),
+        module.exports().into_iter().map(|e| e.into()).collect(),
     ));
     *export_descriptors =
         Box::into_raw(named_export_descriptors) as *mut wasmer_export_descriptors_t;
@@ -135,6 +139,142 @@ pub unsafe extern "C" fn wasmer_export_descriptor_kind(
     named_export_descriptor.kind.clone()
 }
 
+/// Sets the result parameter to the arity of the params of the function described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_func_params_arity(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    result: *mut uint32_t,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::func_params_arity(
+        &named.ty,
+        result,
+        "wasmer_export_descriptor_func_params_arity",
+    )
+}
+
+/// Sets the params buffer to the parameter types of the function described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_func_params(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    params: *mut wasmer_value_tag,
+    params_len: c_int,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::func_params(
+        &named.ty,
+        params,
+        params_len,
+        "wasmer_export_descriptor_func_params",
+    )
+}
+
+/// Sets the result parameter to the arity of the returns of the function described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_func_returns_arity(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    result: *mut uint32_t,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::func_returns_arity(
+        &named.ty,
+        result,
+        "wasmer_export_descriptor_func_returns_arity",
+    )
+}
+
+/// Sets the returns buffer to the return types of the function described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_func_returns(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    returns: *mut wasmer_value_tag,
+    returns_len: c_int,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::func_returns(
+        &named.ty,
+        returns,
+        returns_len,
+        "wasmer_export_descriptor_func_returns",
+    )
+}
+
+/// Sets the limits parameter to the limits of the memory described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_memory_limits(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    limits: *mut wasmer_limits_t,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::memory_limits(&named.ty, limits, "wasmer_export_descriptor_memory_limits")
+}
+
+/// Sets the limits parameter to the limits of the table described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_table_limits(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    limits: *mut wasmer_limits_t,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::table_limits(&named.ty, limits, "wasmer_export_descriptor_table_limits")
+}
+
+/// Sets the descriptor parameter to the type and mutability of the global described by the wasmer_export_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_descriptor_global_descriptor(
+    export_descriptor: *const wasmer_export_descriptor_t,
+    descriptor: *mut wasmer_global_descriptor_t,
+) -> wasmer_result_t {
+    let named = &*(export_descriptor as *const NamedExportDescriptor);
+    descriptor::global_descriptor(
+        &named.ty,
+        descriptor,
+        "wasmer_export_descriptor_global_descriptor",
+    )
+}
+
 pub(crate) struct NamedExports(pub Vec<NamedExport>);
 
 /// Frees the memory for the given exports
@@ -413,17 +553,12 @@ pub unsafe extern "C" fn wasmer_export_func_call(
     }
 }
 
-impl From<(&std::string::String, &ExportIndex)> for NamedExportDescriptor {
-    fn from((name, export_index): (&String, &ExportIndex)) -> Self {
-        let kind = match *export_index {
-            ExportIndex::Memory(_) => wasmer_import_export_kind::WASM_MEMORY,
-            ExportIndex::Global(_) => wasmer_import_export_kind::WASM_GLOBAL,
-            ExportIndex::Table(_) => wasmer_import_export_kind::WASM_TABLE,
-            ExportIndex::Func(_) => wasmer_import_export_kind::WASM_FUNCTION,
-        };
+impl From<ExportDescriptor> for NamedExportDescriptor {
+    fn from(export: ExportDescriptor) -> Self {
         NamedExportDescriptor {
-            name: name.clone(),
-            kind,
+            kind: (&export.ty).into(),
+            name: export.name,
+            ty: export.ty,
         }
     }
 }
@@ -437,4 +572,5 @@ pub(crate) struct NamedExport {
 pub(crate) struct NamedExportDescriptor {
     name: String,
     kind: wasmer_import_export_kind,
+    ty: ExternDescriptor,
 }
@@ -6,8 +6,8 @@ use wasmer_runtime::Global;
 #[repr(C)]
 #[derive(Clone)]
 pub struct wasmer_global_descriptor_t {
-    mutable: bool,
-    kind: wasmer_value_tag,
+    pub(crate) mutable: bool,
+    pub(crate) kind: wasmer_value_tag,
 }
 
 #[repr(C)]
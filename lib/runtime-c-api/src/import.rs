@@ -1,18 +1,20 @@
 //! Wasm imports.
 
 use crate::{
+    descriptor,
     error::{update_last_error, CApiError},
     export::{wasmer_import_export_kind, wasmer_import_export_value},
+    global::wasmer_global_descriptor_t,
     module::wasmer_module_t,
     value::wasmer_value_tag,
-    wasmer_byte_array, wasmer_result_t,
+    wasmer_byte_array, wasmer_limits_t, wasmer_result_t,
 };
 use libc::{c_int, uint32_t};
 use std::{ffi::c_void, ptr, slice, sync::Arc};
 use wasmer_runtime::Module;
 use wasmer_runtime_core::{
     export::{Context, Export, FuncPointer},
-    module::ImportName,
+    module::ExternDescriptor,
     types::{FuncSig, Type},
 };
 
@@ -46,88 +48,24 @@ pub unsafe extern "C" fn wasmer_import_descriptors(
     import_descriptors: *mut *mut wasmer_import_descriptors_t,
 ) {
     let module = &*(module as *const Module);
-    let total_imports = module.info().imported_functions.len()
-        + module.info().imported_tables.len()
-        + module.info().imported_globals.len()
-        + module.info().imported_memories.len();
-    let mut descriptors: Vec<NamedImportDescriptor> = Vec::with_capacity(total_imports);
-
-    for (
-        _index,
-        ImportName {
-            namespace_index,
-            name_index,
-        },
-    ) in &module.info().imported_functions
-    {
-        let namespace = module.info().namespace_table.get(*namespace_index);
-        let name = module.info().name_table.get(*name_index);
-        descriptors.push(NamedImportDescriptor {
-            module: namespace.to_string(),
-            name: name.to_string(),
-            kind: wasmer_import_export_kind::WASM_FUNCTION,
-        });
-    }
-
-    for (
-        _index,
-        (
-            ImportName {
-                namespace_index,
-                name_index,
-            },
-            _,
-        ),
-    ) in &module.info().imported_tables
-    {
-        let namespace = module.info().namespace_table.get(*namespace_index);
-        let name = module.info().name_table.get(*name_index);
-        descriptors.push(NamedImportDescriptor {
-            module: namespace.to_string(),
-            name: name.to_string(),
-            kind: wasmer_import_export_kind::WASM_TABLE,
-        });
-    }
-
-    for (
-        _index,
-        (
-            ImportName {
-                namespace_index,
-                name_index,
-            },
-            _,
-        ),
-    ) in &module.info().imported_globals
-    {
-        let namespace = module.info().namespace_table.get(*namespace_index);
-        let name = module.info().name_table.get(*name_index);
-        descriptors.push(NamedImportDescriptor {
-            module: namespace.to_string(),
-            name: name.to_string(),
-            kind: wasmer_import_export_kind::WASM_GLOBAL,
-        });
-    }
-
-    for (
-        _index,
-        (
-            ImportName {
-                namespace_index,
-                name_index,
-            },
-            _,
-        ),
-    ) in &module.info().imported_memories
-    {
-        let namespace = module.info().namespace_table.get(*namespace_index);
-        let name = module.info().name_table.get(*name_index);
-        descriptors.push(NamedImportDescriptor {
-            module: namespace.to_string(),
-            name: name.to_string(),
-            kind: wasmer_import_export_kind::WASM_MEMORY,
-        });
-    }
+    let mut descriptors: Vec<NamedImportDescriptor> = module
+        .imports()
+        .into_iter()
+        .map(|import| NamedImportDescriptor {
+            kind: (&import.ty).into(),
+            module: import.namespace,
+            name: import.name,
+            ty: import.ty,
+        })
+        .collect();
+    // Imports have always been listed functions first, then tables,
+    // globals and memories, each in the order of the module.
+    descriptors.sort_by_key(|descriptor| match descriptor.kind {
+        wasmer_import_export_kind::WASM_FUNCTION => 0,
+        wasmer_import_export_kind::WASM_TABLE => 1,
+        wasmer_import_export_kind::WASM_GLOBAL => 2,
+        wasmer_import_export_kind::WASM_MEMORY => 3,
+    });
 
     let named_import_descriptors: Box<NamedImportDescriptors> =
         Box::new(NamedImportDescriptors(descriptors));
@@ -211,6 +149,142 @@ pub unsafe extern "C" fn wasmer_import_descriptor_kind(
     named_import_descriptor.kind.clone()
 }
 
+/// Sets the result parameter to the arity of the params of the function described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_func_params_arity(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    result: *mut uint32_t,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::func_params_arity(
+        &named.ty,
+        result,
+        "wasmer_import_descriptor_func_params_arity",
+    )
+}
+
+/// Sets the params buffer to the parameter types of the function described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_func_params(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    params: *mut wasmer_value_tag,
+    params_len: c_int,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::func_params(
+        &named.ty,
+        params,
+        params_len,
+        "wasmer_import_descriptor_func_params",
+    )
+}
+
+/// Sets the result parameter to the arity of the returns of the function described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_func_returns_arity(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    result: *mut uint32_t,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::func_returns_arity(
+        &named.ty,
+        result,
+        "wasmer_import_descriptor_func_returns_arity",
+    )
+}
+
+/// Sets the returns buffer to the return types of the function described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_func_returns(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    returns: *mut wasmer_value_tag,
+    returns_len: c_int,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::func_returns(
+        &named.ty,
+        returns,
+        returns_len,
+        "wasmer_import_descriptor_func_returns",
+    )
+}
+
+/// Sets the limits parameter to the limits of the memory described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_memory_limits(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    limits: *mut wasmer_limits_t,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::memory_limits(&named.ty, limits, "wasmer_import_descriptor_memory_limits")
+}
+
+/// Sets the limits parameter to the limits of the table described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_table_limits(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    limits: *mut wasmer_limits_t,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::table_limits(&named.ty, limits, "wasmer_import_descriptor_table_limits")
+}
+
+/// Sets the descriptor parameter to the type and mutability of the global described by the wasmer_import_descriptor_t
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_import_descriptor_global_descriptor(
+    import_descriptor: *const wasmer_import_descriptor_t,
+    descriptor: *mut wasmer_global_descriptor_t,
+) -> wasmer_result_t {
+    let named = &*(import_descriptor as *const NamedImportDescriptor);
+    descriptor::global_descriptor(
+        &named.ty,
+        descriptor,
+        "wasmer_import_descriptor_global_descriptor",
+    )
+}
+
 /// Sets the result parameter to the arity of the params of the wasmer_import_func_t
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -355,4 +429,5 @@ struct NamedImportDescriptor {
     module: String,
     name: String,
     kind: wasmer_import_export_kind,
+    ty: ExternDescriptor,
 }
@@ -3,6 +3,7 @@ extern crate wasmer_runtime_core;
 
 use libc::{uint32_t, uint8_t};
 
+mod descriptor;
 pub mod error;
 pub mod export;
 pub mod global;
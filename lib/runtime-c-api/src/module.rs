@@ -141,6 +141,74 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
     wasmer_result_t::WASMER_OK
 }
 
+/// Gets the number of custom sections named `name` in the given module.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_custom_sections_len(
+    module: *const wasmer_module_t,
+    name: wasmer_byte_array,
+) -> c_int {
+    let module = &*(module as *const Module);
+    let name = slice::from_raw_parts(name.bytes, name.bytes_len as usize);
+    let name = if let Ok(s) = std::str::from_utf8(name) {
+        s
+    } else {
+        return 0;
+    };
+
+    module
+        .custom_sections(name)
+        .map(|sections| sections.len() as c_int)
+        .unwrap_or(0)
+}
+
+/// Gets the contents of the custom section named `name` at the given index.
+///
+/// The returned bytes are owned by the module and are valid as long as it is.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_custom_section(
+    module: *const wasmer_module_t,
+    name: wasmer_byte_array,
+    idx: c_int,
+    section: *mut wasmer_byte_array,
+) -> wasmer_result_t {
+    let module = &*(module as *const Module);
+    let name = slice::from_raw_parts(name.bytes, name.bytes_len as usize);
+    let name = if let Ok(s) = std::str::from_utf8(name) {
+        s
+    } else {
+        update_last_error(CApiError {
+            msg: "error converting custom section name to string".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    };
+
+    match module
+        .custom_sections(name)
+        .and_then(|sections| sections.get(idx as usize))
+    {
+        Some(bytes) => {
+            *section = wasmer_byte_array {
+                bytes: bytes.as_ptr(),
+                bytes_len: bytes.len() as u32,
+            };
+            wasmer_result_t::WASMER_OK
+        }
+        None => {
+            update_last_error(CApiError {
+                msg: format!("custom section `{}` at index {} not found", name, idx),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
 /// Serialize the given Module.
 ///
 /// The caller owns the object and should call `wasmer_serialized_module_destroy` to free it.
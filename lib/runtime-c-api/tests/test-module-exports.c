@@ -45,6 +45,37 @@ int main()
         assert(name_bytes.bytes[idx] == expected[idx]);
     }
 
+    uint32_t params_arity;
+    wasmer_result_t params_arity_result = wasmer_export_descriptor_func_params_arity(export, &params_arity);
+    assert(params_arity_result == WASMER_OK);
+    assert(params_arity == 2);
+
+    wasmer_value_tag params_sig[2];
+    wasmer_result_t params_result = wasmer_export_descriptor_func_params(export, params_sig, params_arity);
+    assert(params_result == WASMER_OK);
+    assert(params_sig[0] == WASM_I32);
+    assert(params_sig[1] == WASM_I32);
+
+    uint32_t returns_arity;
+    wasmer_result_t returns_arity_result = wasmer_export_descriptor_func_returns_arity(export, &returns_arity);
+    assert(returns_arity_result == WASMER_OK);
+    assert(returns_arity == 1);
+
+    wasmer_value_tag returns_sig[1];
+    wasmer_result_t returns_result = wasmer_export_descriptor_func_returns(export, returns_sig, returns_arity);
+    assert(returns_result == WASMER_OK);
+    assert(returns_sig[0] == WASM_I32);
+
+    char section_name[] = {'n', 'a', 'm', 'e'};
+    wasmer_byte_array section_name_bytes = { .bytes = (const uint8_t *) section_name, .bytes_len = 4 };
+    int sections_len = wasmer_module_custom_sections_len(module, section_name_bytes);
+    assert(sections_len == 1);
+
+    wasmer_byte_array section;
+    wasmer_result_t section_result = wasmer_module_custom_section(module, section_name_bytes, 0, &section);
+    assert(section_result == WASMER_OK);
+    assert(section.bytes_len > 0);
+
     printf("Destroy module\n");
     wasmer_module_destroy(module);
     printf("Destroy exports\n");
@@ -48,6 +48,26 @@ int main()
         assert(module_name_bytes.bytes[idx] == module_expected[idx]);
     }
 
+    uint32_t params_arity;
+    wasmer_result_t params_arity_result = wasmer_import_descriptor_func_params_arity(import, &params_arity);
+    assert(params_arity_result == WASMER_OK);
+    assert(params_arity == 2);
+
+    wasmer_value_tag params_sig[2];
+    wasmer_result_t params_result = wasmer_import_descriptor_func_params(import, params_sig, params_arity);
+    assert(params_result == WASMER_OK);
+    assert(params_sig[0] == WASM_I32);
+    assert(params_sig[1] == WASM_I32);
+
+    uint32_t returns_arity;
+    wasmer_result_t returns_arity_result = wasmer_import_descriptor_func_returns_arity(import, &returns_arity);
+    assert(returns_arity_result == WASMER_OK);
+    assert(returns_arity == 0);
+
+    wasmer_limits_t limits;
+    wasmer_result_t limits_result = wasmer_import_descriptor_memory_limits(import, &limits);
+    assert(limits_result == WASMER_ERROR);
+
     printf("Destroy module\n");
     wasmer_module_destroy(module);
     printf("Destroy imports\n");
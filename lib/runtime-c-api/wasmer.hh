@@ -140,12 +140,63 @@ wasmer_result_t wasmer_compile(wasmer_module_t **module,
                                uint8_t *wasm_bytes,
                                uint32_t wasm_bytes_len);
 
+/// Sets the params buffer to the parameter types of the function described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_func_params(const wasmer_export_descriptor_t *export_descriptor,
+                                                     wasmer_value_tag *params,
+                                                     int params_len);
+
+/// Sets the result parameter to the arity of the params of the function described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_func_params_arity(const wasmer_export_descriptor_t *export_descriptor,
+                                                           uint32_t *result);
+
+/// Sets the returns buffer to the return types of the function described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_func_returns(const wasmer_export_descriptor_t *export_descriptor,
+                                                      wasmer_value_tag *returns,
+                                                      int returns_len);
+
+/// Sets the result parameter to the arity of the returns of the function described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_func_returns_arity(const wasmer_export_descriptor_t *export_descriptor,
+                                                            uint32_t *result);
+
+/// Sets the descriptor parameter to the type and mutability of the global described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_global_descriptor(const wasmer_export_descriptor_t *export_descriptor,
+                                                           wasmer_global_descriptor_t *descriptor);
+
 /// Gets export descriptor kind
 wasmer_import_export_kind wasmer_export_descriptor_kind(wasmer_export_descriptor_t *export_);
 
+/// Sets the limits parameter to the limits of the memory described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_memory_limits(const wasmer_export_descriptor_t *export_descriptor,
+                                                       wasmer_limits_t *limits);
+
 /// Gets name for the export descriptor
 wasmer_byte_array wasmer_export_descriptor_name(wasmer_export_descriptor_t *export_descriptor);
 
+/// Sets the limits parameter to the limits of the table described by the wasmer_export_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_descriptor_table_limits(const wasmer_export_descriptor_t *export_descriptor,
+                                                      wasmer_limits_t *limits);
+
 /// Gets export descriptors for the given module
 /// The caller owns the object and should call `wasmer_export_descriptors_destroy` to free it.
 void wasmer_export_descriptors(const wasmer_module_t *module,
@@ -241,15 +292,66 @@ wasmer_global_t *wasmer_global_new(wasmer_value_t value, bool mutable_);
 /// Sets the value stored by the given Global
 void wasmer_global_set(wasmer_global_t *global, wasmer_value_t value);
 
+/// Sets the params buffer to the parameter types of the function described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_func_params(const wasmer_import_descriptor_t *import_descriptor,
+                                                     wasmer_value_tag *params,
+                                                     int params_len);
+
+/// Sets the result parameter to the arity of the params of the function described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_func_params_arity(const wasmer_import_descriptor_t *import_descriptor,
+                                                           uint32_t *result);
+
+/// Sets the returns buffer to the return types of the function described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_func_returns(const wasmer_import_descriptor_t *import_descriptor,
+                                                      wasmer_value_tag *returns,
+                                                      int returns_len);
+
+/// Sets the result parameter to the arity of the returns of the function described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_func_returns_arity(const wasmer_import_descriptor_t *import_descriptor,
+                                                            uint32_t *result);
+
+/// Sets the descriptor parameter to the type and mutability of the global described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_global_descriptor(const wasmer_import_descriptor_t *import_descriptor,
+                                                           wasmer_global_descriptor_t *descriptor);
+
 /// Gets export descriptor kind
 wasmer_import_export_kind wasmer_import_descriptor_kind(wasmer_import_descriptor_t *export_);
 
+/// Sets the limits parameter to the limits of the memory described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_memory_limits(const wasmer_import_descriptor_t *import_descriptor,
+                                                       wasmer_limits_t *limits);
+
 /// Gets module name for the import descriptor
 wasmer_byte_array wasmer_import_descriptor_module_name(wasmer_import_descriptor_t *import_descriptor);
 
 /// Gets name for the import descriptor
 wasmer_byte_array wasmer_import_descriptor_name(wasmer_import_descriptor_t *import_descriptor);
 
+/// Sets the limits parameter to the limits of the table described by the wasmer_import_descriptor_t
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_import_descriptor_table_limits(const wasmer_import_descriptor_t *import_descriptor,
+                                                      wasmer_limits_t *limits);
+
 /// Gets import descriptors for the given module
 /// The caller owns the object and should call `wasmer_import_descriptors_destroy` to free it.
 void wasmer_import_descriptors(const wasmer_module_t *module,
@@ -395,6 +497,19 @@ uint32_t wasmer_memory_length(const wasmer_memory_t *memory);
 /// and `wasmer_last_error_message` to get an error message.
 wasmer_result_t wasmer_memory_new(wasmer_memory_t **memory, wasmer_limits_t limits);
 
+/// Gets the contents of the custom section named `name` at the given index.
+/// The returned bytes are owned by the module and are valid as long as it is.
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_module_custom_section(const wasmer_module_t *module,
+                                             wasmer_byte_array name,
+                                             int idx,
+                                             wasmer_byte_array *section);
+
+/// Gets the number of custom sections named `name` in the given module.
+int wasmer_module_custom_sections_len(const wasmer_module_t *module, wasmer_byte_array name);
+
 /// Deserialize the given serialized module.
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
@@ -0,0 +1,81 @@
+//! Ahead-of-time compiled modules.
+//!
+//! A compiled module can be written out as a relocatable ELF object
+//! that holds its serialized [`Artifact`] in a `.wasmer_artifact`
+//! section, under a global symbol. The object can be linked into a
+//! program, or into a shared object with the system linker.
+//!
+//! Loading it back goes through the backend's `from_cache`, which only
+//! has to relocate the precompiled code, so the program doesn't need a
+//! compiler. Only backends that can produce artifacts can compile
+//! modules ahead of time.
+//!
+//! [`Artifact`]: ../cache/struct.Artifact.html
+
+use crate::{
+    cache::{Artifact, Error, InvalidFileType, ARTIFACT_HEADER_SIZE},
+    jit_debug::elf,
+};
+use std::slice;
+
+/// The section holding the serialized artifact.
+pub const ARTIFACT_SECTION: &str = ".wasmer_artifact";
+
+/// The symbol of the serialized artifact, unless another is chosen.
+pub const DEFAULT_SYMBOL: &str = "WASMER_MODULE";
+
+/// Write `artifact` to a relocatable object for the architecture it was
+/// compiled for, defining `symbol` at the start of the serialized artifact.
+pub fn write_object(artifact: &Artifact, symbol: &str) -> Result<Vec<u8>, Error> {
+    let arch = match &artifact.info().triple {
+        Some(triple) => triple.split('-').next().unwrap_or(""),
+        None => std::env::consts::ARCH,
+    };
+    let machine = elf::machine(arch)
+        .ok_or_else(|| Error::Unknown(format!("object files can't be written for {}", arch)))?;
+
+    let data = artifact.serialize()?;
+    let size = data.len() as u64;
+
+    let mut section = elf::Section::new(ARTIFACT_SECTION, elf::SHT_PROGBITS, data);
+    section.flags = elf::SHF_ALLOC;
+    section.align = 16;
+
+    let symbols = [elf::Symbol {
+        name: symbol,
+        ty: elf::STT_OBJECT,
+        section: 0,
+        value: 0,
+        size,
+    }];
+
+    Ok(elf::write(machine, vec![section], &symbols))
+}
+
+/// Read the artifact stored in an object file or shared object
+/// written by `write_object` or linked from one.
+pub fn read_object(object: &[u8]) -> Result<Artifact, Error> {
+    let data = elf::section_data(object, ARTIFACT_SECTION)
+        .ok_or(Error::InvalidFile(InvalidFileType::MissingSection))?;
+    Artifact::deserialize(data)
+}
+
+/// Read the artifact at the address of a symbol defined by an object
+/// from `write_object` that was linked into this program.
+///
+/// ```ignore
+/// extern "C" {
+///     static WASMER_MODULE: u8;
+/// }
+///
+/// let artifact = unsafe { read_linked(&WASMER_MODULE) }?;
+/// ```
+///
+/// # Safety
+///
+/// `start` must point to the beginning of a serialized artifact.
+pub unsafe fn read_linked(start: *const u8) -> Result<Artifact, Error> {
+    let header = slice::from_raw_parts(start, ARTIFACT_HEADER_SIZE);
+    let len = Artifact::serialized_len(header)?;
+    Artifact::deserialize(slice::from_raw_parts(start, len))
+}
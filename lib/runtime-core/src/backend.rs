@@ -9,6 +9,7 @@ use crate::{
 use crate::{
     cache::{Artifact, Error as CacheError},
     module::ModuleInfo,
+    profiling::ProfilingStrategy,
     sys::Memory,
 };
 use std::{any::Any, ptr::NonNull};
@@ -27,6 +28,120 @@ pub enum Backend {
     LLVM,
 }
 
+/// How hard the compiler tries to optimize the code it generates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OptLevel {
+    /// Compile as quickly as possible.
+    None,
+    /// Make the code fast.
+    Speed,
+    /// Make the code fast and small.
+    SpeedAndSize,
+}
+
+impl Default for OptLevel {
+    fn default() -> Self {
+        OptLevel::SpeedAndSize
+    }
+}
+
+impl std::str::FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(OptLevel::None),
+            "speed" => Ok(OptLevel::Speed),
+            "speed_and_size" => Ok(OptLevel::SpeedAndSize),
+            _ => Err(format!("The optimization level {} doesn't exist", s)),
+        }
+    }
+}
+
+/// An optional x86 instruction set extension that
+/// compiled code can use.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CpuFeature {
+    SSE3,
+    SSSE3,
+    SSE41,
+    SSE42,
+    POPCNT,
+    AVX,
+    AVX2,
+    BMI1,
+    BMI2,
+    LZCNT,
+}
+
+impl CpuFeature {
+    pub fn all() -> &'static [CpuFeature] {
+        &[
+            CpuFeature::SSE3,
+            CpuFeature::SSSE3,
+            CpuFeature::SSE41,
+            CpuFeature::SSE42,
+            CpuFeature::POPCNT,
+            CpuFeature::AVX,
+            CpuFeature::AVX2,
+            CpuFeature::BMI1,
+            CpuFeature::BMI2,
+            CpuFeature::LZCNT,
+        ]
+    }
+
+    /// The name of the feature, as used by `rustc`'s `target_feature`.
+    pub fn name(self) -> &'static str {
+        match self {
+            CpuFeature::SSE3 => "sse3",
+            CpuFeature::SSSE3 => "ssse3",
+            CpuFeature::SSE41 => "sse4.1",
+            CpuFeature::SSE42 => "sse4.2",
+            CpuFeature::POPCNT => "popcnt",
+            CpuFeature::AVX => "avx",
+            CpuFeature::AVX2 => "avx2",
+            CpuFeature::BMI1 => "bmi1",
+            CpuFeature::BMI2 => "bmi2",
+            CpuFeature::LZCNT => "lzcnt",
+        }
+    }
+
+    /// Whether the CPU this is running on supports the feature.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub fn is_supported_by_host(self) -> bool {
+        match self {
+            CpuFeature::SSE3 => is_x86_feature_detected!("sse3"),
+            CpuFeature::SSSE3 => is_x86_feature_detected!("ssse3"),
+            CpuFeature::SSE41 => is_x86_feature_detected!("sse4.1"),
+            CpuFeature::SSE42 => is_x86_feature_detected!("sse4.2"),
+            CpuFeature::POPCNT => is_x86_feature_detected!("popcnt"),
+            CpuFeature::AVX => is_x86_feature_detected!("avx"),
+            CpuFeature::AVX2 => is_x86_feature_detected!("avx2"),
+            CpuFeature::BMI1 => is_x86_feature_detected!("bmi1"),
+            CpuFeature::BMI2 => is_x86_feature_detected!("bmi2"),
+            CpuFeature::LZCNT => is_x86_feature_detected!("lzcnt"),
+        }
+    }
+
+    /// Whether the CPU this is running on supports the feature.
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    pub fn is_supported_by_host(self) -> bool {
+        false
+    }
+}
+
+impl std::str::FromStr for CpuFeature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        CpuFeature::all()
+            .iter()
+            .cloned()
+            .find(|feature| feature.name() == s.to_lowercase())
+            .ok_or_else(|| format!("The CPU feature {} doesn't exist", s))
+    }
+}
+
 /// This type cannot be constructed from
 /// outside the runtime crate.
 pub struct Token {
@@ -43,11 +158,62 @@ impl Token {
 pub struct CompilerConfig {
     /// Symbol information generated from emscripten; used for more detailed debug messages
     pub symbol_map: Option<HashMap<u32, String>>,
+    /// Register the compiled code with native debuggers such as gdb and lldb,
+    /// translating the DWARF sections of the module when it has them (in
+    /// the Cranelift backend only). See the [`jit_debug`] module.
+    ///
+    /// [`jit_debug`]: ../jit_debug/index.html
+    pub generate_debug_info: bool,
+    /// Report the compiled functions to `perf` with the given strategy.
+    /// See the [`profiling`] module.
+    ///
+    /// [`profiling`]: ../profiling/index.html
+    pub profiling: Option<ProfilingStrategy>,
+    /// The target triple to compile for, such as `x86_64-unknown-linux-gnu`,
+    /// or the host if this is `None`. Code compiled for another target can't
+    /// be run, only cached or compiled ahead of time. The LLVM backend
+    /// can't cache its code yet, so there it's only of use for triples of
+    /// the host's architecture.
+    pub triple: Option<String>,
+    /// CPU features to enable on top of the target's defaults. When
+    /// compiling for the host, those are the features it supports.
+    pub enable_cpu_features: Vec<CpuFeature>,
+    /// CPU features to disable. This takes precedence over `enable_cpu_features`.
+    pub disable_cpu_features: Vec<CpuFeature>,
+    /// How hard to optimize the compiled code.
+    pub opt_level: OptLevel,
+    /// Check the compiler's intermediate representation for errors
+    /// while compiling. This is slow, and meant for debugging the compiler.
+    pub enable_verifier: bool,
+}
+
+impl CompilerConfig {
+    /// The CPU features compiled code is allowed to use.
+    pub fn cpu_features(&self) -> Vec<CpuFeature> {
+        CpuFeature::all()
+            .iter()
+            .cloned()
+            .filter(|feature| {
+                let default = self.triple.is_none() && feature.is_supported_by_host();
+                (default || self.enable_cpu_features.contains(feature))
+                    && !self.disable_cpu_features.contains(feature)
+            })
+            .collect()
+    }
 }
 
 impl Default for CompilerConfig {
     fn default() -> CompilerConfig {
-        CompilerConfig { symbol_map: None }
+        CompilerConfig {
+            symbol_map: None,
+            generate_debug_info: false,
+            profiling: None,
+            triple: None,
+            enable_cpu_features: vec![],
+            disable_cpu_features: vec![],
+            opt_level: OptLevel::default(),
+            enable_verifier: false,
+        }
     }
 }
 
@@ -63,6 +229,15 @@ pub trait Compiler {
     ) -> CompileResult<ModuleInner>;
 
     unsafe fn from_cache(&self, cache: Artifact, _: Token) -> Result<ModuleInner, CacheError>;
+
+    /// The backend this compiler generates code with. Cache artifacts
+    /// of other backends are rejected.
+    ///
+    /// Defaults to Cranelift, the default backend, so compilers written
+    /// before this existed keep building; others should override it.
+    fn backend(&self) -> Backend {
+        Backend::Cranelift
+    }
 }
 
 pub trait RunnableModule: Send + Sync {
@@ -80,6 +255,13 @@ pub trait RunnableModule: Send + Sync {
     fn get_trampoline(&self, info: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm>;
 
     unsafe fn do_early_trap(&self, data: Box<dyn Any>) -> !;
+
+    /// Returns the local function containing the instruction that caused
+    /// the most recent trap on the current thread, if it belongs to this
+    /// module. This is used to name the function in trap messages.
+    fn get_trap_func(&self, _info: &ModuleInfo) -> Option<LocalFuncIndex> {
+        None
+    }
 }
 
 pub trait CacheGen: Send + Sync {
@@ -88,3 +270,45 @@ pub trait CacheGen: Send + Sync {
         module: &ModuleInner,
     ) -> Result<(Box<ModuleInfo>, Box<[u8]>, Memory), CacheError>;
 }
+
+#[cfg(test)]
+mod test {
+    use super::{CompilerConfig, CpuFeature, OptLevel};
+
+    #[test]
+    fn cpu_features_of_other_targets_start_empty() {
+        let config = CompilerConfig {
+            triple: Some("x86_64-unknown-linux-gnu".to_string()),
+            enable_cpu_features: vec![CpuFeature::SSE41, CpuFeature::BMI2],
+            disable_cpu_features: vec![CpuFeature::BMI2],
+            ..Default::default()
+        };
+        assert_eq!(config.cpu_features(), vec![CpuFeature::SSE41]);
+    }
+
+    #[test]
+    fn disabled_cpu_features_are_never_used() {
+        let config = CompilerConfig {
+            disable_cpu_features: CpuFeature::all().to_vec(),
+            ..Default::default()
+        };
+        assert!(config.cpu_features().is_empty());
+    }
+
+    #[test]
+    fn opt_levels_parse_from_their_names() {
+        assert_eq!("none".parse(), Ok(OptLevel::None));
+        assert_eq!("speed".parse(), Ok(OptLevel::Speed));
+        assert_eq!("Speed_And_Size".parse(), Ok(OptLevel::SpeedAndSize));
+        assert!("fastest".parse::<OptLevel>().is_err());
+    }
+
+    #[test]
+    fn cpu_features_parse_from_their_names() {
+        for &feature in CpuFeature::all() {
+            assert_eq!(feature.name().parse(), Ok(feature));
+        }
+        assert_eq!("SSE4.1".parse(), Ok(CpuFeature::SSE41));
+        assert!("sse5".parse::<CpuFeature>().is_err());
+    }
+}
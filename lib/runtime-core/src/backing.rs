@@ -15,7 +15,8 @@ use crate::{
     },
     vm,
 };
-use std::slice;
+use hashbrown::HashSet;
+use std::{any::Any, rc::Rc, slice};
 
 #[derive(Debug)]
 pub struct LocalBacking {
@@ -330,6 +331,11 @@ pub struct ImportBacking {
     pub(crate) vm_memories: BoxedMap<ImportedMemoryIndex, *mut vm::LocalMemory>,
     pub(crate) vm_tables: BoxedMap<ImportedTableIndex, *mut vm::LocalTable>,
     pub(crate) vm_globals: BoxedMap<ImportedGlobalIndex, *mut vm::LocalGlobal>,
+
+    /// What the imported functions that run in another context need
+    /// for that context to stay valid. They're only held, never read.
+    #[allow(dead_code)]
+    pub(crate) keepalives: Vec<Rc<dyn Any>>,
 }
 
 impl ImportBacking {
@@ -341,11 +347,12 @@ impl ImportBacking {
         let mut failed = false;
         let mut link_errors = vec![];
 
-        let vm_functions = import_functions(module, imports, vmctx).unwrap_or_else(|le| {
-            failed = true;
-            link_errors.extend(le);
-            Map::new().into_boxed_map()
-        });
+        let (vm_functions, keepalives) =
+            import_functions(module, imports, vmctx).unwrap_or_else(|le| {
+                failed = true;
+                link_errors.extend(le);
+                (Map::new().into_boxed_map(), vec![])
+            });
 
         let (memories, vm_memories) = import_memories(module, imports).unwrap_or_else(|le| {
             failed = true;
@@ -377,6 +384,8 @@ impl ImportBacking {
                 vm_memories,
                 vm_tables,
                 vm_globals,
+
+                keepalives,
             })
         }
     }
@@ -390,9 +399,14 @@ fn import_functions(
     module: &ModuleInner,
     imports: &ImportObject,
     vmctx: *mut vm::Ctx,
-) -> LinkResult<BoxedMap<ImportedFuncIndex, vm::ImportedFunc>> {
+) -> LinkResult<(
+    BoxedMap<ImportedFuncIndex, vm::ImportedFunc>,
+    Vec<Rc<dyn Any>>,
+)> {
     let mut link_errors = vec![];
     let mut functions = Map::with_capacity(module.info.imported_functions.len());
+    let mut keepalives = vec![];
+    let mut kept_namespaces = HashSet::new();
     for (
         index,
         ImportName {
@@ -417,6 +431,15 @@ fn import_functions(
                 signature,
             }) => {
                 if *expected_sig == *signature {
+                    if let Context::External(_) = ctx {
+                        if kept_namespaces.insert(*namespace_index) {
+                            keepalives.extend(
+                                imports
+                                    .get_namespace(namespace)
+                                    .and_then(|namespace| namespace.keepalive()),
+                            );
+                        }
+                    }
                     functions.push(vm::ImportedFunc {
                         func: func.inner(),
                         vmctx: match ctx {
@@ -460,7 +483,7 @@ fn import_functions(
     if link_errors.len() > 0 {
         Err(link_errors)
     } else {
-        Ok(functions.into_boxed_map())
+        Ok((functions.into_boxed_map(), keepalives))
     }
 }
 
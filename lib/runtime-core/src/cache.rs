@@ -1,14 +1,18 @@
 use crate::{
+    backend::{Backend, CompilerConfig},
     module::{Module, ModuleInfo},
     sys::Memory,
 };
 use blake2b_simd::blake2bp;
+use ed25519_dalek::{Signer, Verifier};
 use std::{fmt, io, mem, slice};
 
 #[derive(Debug)]
 pub enum InvalidFileType {
     InvalidSize,
     InvalidMagic,
+    /// An object file doesn't contain a compiled module.
+    MissingSection,
 }
 
 #[derive(Debug)]
@@ -19,6 +23,15 @@ pub enum Error {
     Unknown(String),
     InvalidFile(InvalidFileType),
     InvalidatedCache,
+    /// The artifact doesn't match its content hash: it's been corrupted.
+    InvalidChecksum,
+    /// The artifact isn't signed with the expected key.
+    InvalidSignature,
+    /// The artifact was compiled by another backend than the one loading it.
+    IncompatibleBackend(Backend),
+    /// The artifact was compiled for another architecture, or uses
+    /// CPU features the host doesn't support.
+    IncompatibleTarget(String),
 }
 
 impl From<io::Error> for Error {
@@ -27,9 +40,11 @@ impl From<io::Error> for Error {
     }
 }
 
-/// The hash of a wasm module.
+/// The hash of a wasm module, and optionally of the way it's compiled.
 ///
 /// Used as a key when loading and storing modules in a [`Cache`].
+/// Caches shared by several backends or compiler configurations
+/// should use keys from `generate_with_config`.
 ///
 /// [`Cache`]: trait.Cache.html
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -56,6 +71,42 @@ impl WasmHash {
         WasmHash(first_part, second_part)
     }
 
+    /// Hash a wasm module along with the backend and the compiler settings
+    /// that change the code it's compiled to, so that a cache never returns
+    /// a module compiled differently than asked.
+    ///
+    /// Settings that only affect how compiled code is reported, like
+    /// `generate_debug_info` and `profiling`, aren't part of the key.
+    pub fn generate_with_config(wasm: &[u8], backend: Backend, config: &CompilerConfig) -> Self {
+        let mut symbol_map: Vec<_> = config.symbol_map.iter().flatten().collect();
+        symbol_map.sort();
+
+        let cpu_features: Vec<_> = config.cpu_features().iter().map(|f| f.name()).collect();
+        let settings = format!(
+            "backend={:?};triple={:?};cpu_features={};opt_level={:?};verifier={};symbol_map={:?}",
+            backend,
+            config.triple,
+            cpu_features.join(","),
+            config.opt_level,
+            config.enable_verifier,
+            symbol_map,
+        );
+
+        let mut state = blake2bp::State::new();
+        state.update(wasm);
+        state.update(settings.as_bytes());
+        // The length keeps different splits between the module
+        // and the settings from hashing alike.
+        state.update(&(settings.len() as u64).to_le_bytes());
+
+        let hash = state.finalize();
+        let mut first_part = [0u8; 32];
+        let mut second_part = [0u8; 32];
+        first_part.copy_from_slice(&hash.as_bytes()[0..32]);
+        second_part.copy_from_slice(&hash.as_bytes()[32..64]);
+        WasmHash(first_part, second_part)
+    }
+
     /// Create the hexadecimal representation of the
     /// stored hash.
     pub fn encode(self) -> String {
@@ -70,28 +121,62 @@ impl WasmHash {
     }
 }
 
-const CURRENT_CACHE_VERSION: u64 = 0;
+const CURRENT_CACHE_VERSION: u64 = 1;
 static WASMER_CACHE_MAGIC: [u8; 8] = *b"WASMER\0\0";
 
+/// The size of the header that starts every serialized artifact.
+pub(crate) const ARTIFACT_HEADER_SIZE: usize = mem::size_of::<ArtifactHeader>();
+
+// Values of `ArtifactHeader::signature_kind`.
+const UNSIGNED: u64 = 0;
+const SIGNED_HMAC: u64 = 1;
+const SIGNED_ED25519: u64 = 2;
+
 /// The header of a cache file.
 #[repr(C, packed)]
 struct ArtifactHeader {
     magic: [u8; 8], // [W, A, S, M, E, R, \0, \0]
     version: u64,
     data_len: u64,
+    /// The decoded `WASMER_VERSION_HASH` of the wasmer that wrote the file.
+    wasmer_version: [u8; 64],
+    /// The BLAKE2b hash of the header, with this field and the
+    /// signature zeroed, followed by the body.
+    content_hash: [u8; 64],
+    signature_kind: u64,
+    /// The signature of `content_hash`, if it's signed.
+    signature: [u8; 64],
 }
 
 impl ArtifactHeader {
+    fn new(data_len: u64) -> Self {
+        Self {
+            magic: WASMER_CACHE_MAGIC,
+            version: CURRENT_CACHE_VERSION,
+            data_len,
+            wasmer_version: wasmer_version(),
+            content_hash: [0; 64],
+            signature_kind: UNSIGNED,
+            signature: [0; 64],
+        }
+    }
+
+    /// Split a serialized artifact into its header and body, making
+    /// sure it was written in this format, by this version of wasmer.
     pub fn read_from_slice(buffer: &[u8]) -> Result<(&Self, &[u8]), Error> {
         if buffer.len() >= mem::size_of::<ArtifactHeader>() {
             if &buffer[..8] == &WASMER_CACHE_MAGIC {
                 let (header_slice, body_slice) = buffer.split_at(mem::size_of::<ArtifactHeader>());
                 let header = unsafe { &*(header_slice.as_ptr() as *const ArtifactHeader) };
 
-                if header.version == CURRENT_CACHE_VERSION {
-                    Ok((header, body_slice))
-                } else {
+                if header.version != CURRENT_CACHE_VERSION
+                    || header.wasmer_version[..] != wasmer_version()[..]
+                {
                     Err(Error::InvalidatedCache)
+                } else if body_slice.len() < header.data_len as usize {
+                    Err(Error::InvalidFile(InvalidFileType::InvalidSize))
+                } else {
+                    Ok((header, &body_slice[..header.data_len as usize]))
                 }
             } else {
                 Err(Error::InvalidFile(InvalidFileType::InvalidMagic))
@@ -101,30 +186,143 @@ impl ArtifactHeader {
         }
     }
 
-    pub fn read_from_slice_mut(buffer: &mut [u8]) -> Result<(&mut Self, &mut [u8]), Error> {
-        if buffer.len() >= mem::size_of::<ArtifactHeader>() {
-            if &buffer[..8] == &WASMER_CACHE_MAGIC {
-                let (header_slice, body_slice) =
-                    buffer.split_at_mut(mem::size_of::<ArtifactHeader>());
-                let header = unsafe { &mut *(header_slice.as_ptr() as *mut ArtifactHeader) };
+    pub fn as_slice(&self) -> &[u8] {
+        let ptr = self as *const ArtifactHeader as *const u8;
+        unsafe { slice::from_raw_parts(ptr, mem::size_of::<ArtifactHeader>()) }
+    }
 
-                if header.version == CURRENT_CACHE_VERSION {
-                    Ok((header, body_slice))
-                } else {
-                    Err(Error::InvalidatedCache)
-                }
-            } else {
-                Err(Error::InvalidFile(InvalidFileType::InvalidMagic))
+    fn compute_content_hash(&self, body: &[u8]) -> [u8; 64] {
+        let unsealed = ArtifactHeader {
+            content_hash: [0; 64],
+            signature_kind: UNSIGNED,
+            signature: [0; 64],
+            ..*self
+        };
+
+        let mut state = blake2b_simd::State::new();
+        state.update(unsealed.as_slice());
+        state.update(body);
+
+        let mut hash = [0; 64];
+        hash.copy_from_slice(state.finalize().as_bytes());
+        hash
+    }
+
+    /// Fill in the content hash, and the signature if there's a key.
+    fn seal(&mut self, body: &[u8], key: Option<&SigningKey>) {
+        self.content_hash = self.compute_content_hash(body);
+        if let Some(key) = key {
+            let (signature_kind, signature) = key.sign(&self.content_hash);
+            self.signature_kind = signature_kind;
+            self.signature = signature;
+        }
+    }
+
+    /// Check the content hash, and the signature if there's a key.
+    fn verify(&self, body: &[u8], key: Option<&VerifyingKey>) -> Result<(), Error> {
+        let content_hash = self.content_hash;
+        if !constant_time_eq(&self.compute_content_hash(body), &content_hash) {
+            return Err(Error::InvalidChecksum);
+        }
+        match key {
+            Some(key) if !key.verify(&content_hash, self.signature_kind, &self.signature) => {
+                Err(Error::InvalidSignature)
             }
-        } else {
-            Err(Error::InvalidFile(InvalidFileType::InvalidSize))
+            _ => Ok(()),
         }
     }
+}
 
-    pub fn as_slice(&self) -> &[u8] {
-        let ptr = self as *const ArtifactHeader as *const u8;
-        unsafe { slice::from_raw_parts(ptr, mem::size_of::<ArtifactHeader>()) }
+fn wasmer_version() -> [u8; 64] {
+    let mut version = [0; 64];
+    if let Ok(decoded) = hex::decode(WASMER_VERSION_HASH) {
+        let len = decoded.len().min(64);
+        version[..len].copy_from_slice(&decoded[..len]);
     }
+    version
+}
+
+/// A key that signs serialized artifacts, so that holders of
+/// the matching [`VerifyingKey`] can load them safely.
+///
+/// [`VerifyingKey`]: enum.VerifyingKey.html
+pub enum SigningKey {
+    /// HMAC-BLAKE2b, with a secret shared with the verifiers.
+    Hmac(Vec<u8>),
+    /// An ed25519 key pair. Verifiers only need its public key.
+    Ed25519(ed25519_dalek::Keypair),
+}
+
+impl SigningKey {
+    /// The key that verifies artifacts signed with this one.
+    pub fn verifying_key(&self) -> VerifyingKey {
+        match self {
+            SigningKey::Hmac(secret) => VerifyingKey::Hmac(secret.clone()),
+            SigningKey::Ed25519(keypair) => VerifyingKey::Ed25519(keypair.public),
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> (u64, [u8; 64]) {
+        match self {
+            SigningKey::Hmac(secret) => (SIGNED_HMAC, hmac_blake2b(secret, message)),
+            SigningKey::Ed25519(keypair) => (SIGNED_ED25519, keypair.sign(message).to_bytes()),
+        }
+    }
+}
+
+/// A key that checks the signature of serialized artifacts.
+pub enum VerifyingKey {
+    /// HMAC-BLAKE2b, with the secret the artifacts were signed with.
+    Hmac(Vec<u8>),
+    /// The public half of the ed25519 key pair the artifacts were signed with.
+    Ed25519(ed25519_dalek::PublicKey),
+}
+
+impl VerifyingKey {
+    fn verify(&self, message: &[u8], signature_kind: u64, signature: &[u8; 64]) -> bool {
+        match self {
+            VerifyingKey::Hmac(secret) => {
+                signature_kind == SIGNED_HMAC
+                    && constant_time_eq(&hmac_blake2b(secret, message), signature)
+            }
+            VerifyingKey::Ed25519(public_key) => {
+                signature_kind == SIGNED_ED25519
+                    && ed25519_dalek::Signature::from_bytes(signature)
+                        .map(|signature| public_key.verify(message, &signature).is_ok())
+                        .unwrap_or(false)
+            }
+        }
+    }
+}
+
+/// HMAC (RFC 2104) with BLAKE2b-512, whose block size is 128 bytes.
+fn hmac_blake2b(key: &[u8], message: &[u8]) -> [u8; 64] {
+    const BLOCK_SIZE: usize = 128;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..64].copy_from_slice(blake2b_simd::blake2b(key).as_bytes());
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| -> Vec<u8> { block.iter().map(|b| b ^ byte).collect() };
+
+    let mut inner = blake2b_simd::State::new();
+    inner.update(&pad(0x36));
+    inner.update(message);
+
+    let mut outer = blake2b_simd::State::new();
+    outer.update(&pad(0x5c));
+    outer.update(inner.finalize().as_bytes());
+
+    let mut mac = [0; 64];
+    mac.copy_from_slice(outer.finalize().as_bytes());
+    mac
+}
+
+/// Compare two digests without leaking where they differ through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
 #[derive(Serialize, Deserialize)]
@@ -154,15 +352,40 @@ impl Artifact {
         }
     }
 
+    /// Deserialize an artifact, checking that it's intact and was
+    /// written by this version of wasmer.
+    ///
+    /// Anyone could have written it, so it's unsafe to load. Use
+    /// `deserialize_verified` to check where it comes from.
     pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
-        let (_, body_slice) = ArtifactHeader::read_from_slice(bytes)?;
+        Self::read(bytes, None)
+    }
 
-        let inner = serde_bench::deserialize(body_slice)
+    /// Deserialize an artifact like `deserialize`, and check that it
+    /// was signed with the counterpart of `key`.
+    pub fn deserialize_verified(bytes: &[u8], key: &VerifyingKey) -> Result<Self, Error> {
+        Self::read(bytes, Some(key))
+    }
+
+    fn read(bytes: &[u8], key: Option<&VerifyingKey>) -> Result<Self, Error> {
+        let (header, body_slice) = ArtifactHeader::read_from_slice(bytes)?;
+        header.verify(body_slice, key)?;
+
+        let inner: ArtifactInner = serde_bench::deserialize(body_slice)
             .map_err(|e| Error::DeserializeError(format!("{:#?}", e)))?;
 
+        check_target(&inner.info)?;
+
         Ok(Artifact { inner })
     }
 
+    /// The total size of a serialized artifact, read from the
+    /// header at the start of `bytes`.
+    pub(crate) fn serialized_len(bytes: &[u8]) -> Result<usize, Error> {
+        let (header, _) = ArtifactHeader::read_from_slice(bytes)?;
+        Ok(mem::size_of::<ArtifactHeader>() + header.data_len as usize)
+    }
+
     pub fn info(&self) -> &ModuleInfo {
         &self.inner.info
     }
@@ -176,38 +399,210 @@ impl Artifact {
         )
     }
 
+    /// Serialize the artifact, with a hash of its contents.
     pub fn serialize(&self) -> Result<Vec<u8>, Error> {
-        let cache_header = ArtifactHeader {
-            magic: WASMER_CACHE_MAGIC,
-            version: CURRENT_CACHE_VERSION,
-            data_len: 0,
-        };
+        self.write(None)
+    }
+
+    /// Serialize the artifact like `serialize`, and sign it with `key`.
+    pub fn serialize_signed(&self, key: &SigningKey) -> Result<Vec<u8>, Error> {
+        self.write(Some(key))
+    }
 
-        let mut buffer = cache_header.as_slice().to_vec();
+    fn write(&self, key: Option<&SigningKey>) -> Result<Vec<u8>, Error> {
+        let mut buffer = vec![0; mem::size_of::<ArtifactHeader>()];
 
         serde_bench::serialize(&mut buffer, &self.inner)
             .map_err(|e| Error::SerializeError(e.to_string()))?;
 
-        let data_len = (buffer.len() - mem::size_of::<ArtifactHeader>()) as u64;
+        let body = &buffer[mem::size_of::<ArtifactHeader>()..];
+        let mut header = ArtifactHeader::new(body.len() as u64);
+        header.seal(body, key);
 
-        let (header, _) = ArtifactHeader::read_from_slice_mut(&mut buffer)?;
-        header.data_len = data_len;
+        let header_bytes = header.as_slice().to_vec();
+        buffer[..header_bytes.len()].copy_from_slice(&header_bytes);
 
         Ok(buffer)
     }
 }
 
+/// Make sure the code of a module can run on this machine.
+fn check_target(info: &ModuleInfo) -> Result<(), Error> {
+    if !info.is_for_host_arch() {
+        return Err(Error::IncompatibleTarget(format!(
+            "compiled for {}, but this is {}",
+            info.triple.as_ref().unwrap(),
+            std::env::consts::ARCH
+        )));
+    }
+
+    let missing: Vec<_> = info
+        .cpu_features
+        .iter()
+        .filter(|feature| !feature.is_supported_by_host())
+        .map(|feature| feature.name())
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::IncompatibleTarget(format!(
+            "compiled for CPU features this host doesn't support: {}",
+            missing.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
 /// A generic cache for storing and loading compiled wasm modules.
 ///
-/// The `wasmer-runtime` supplies a naive `FileSystemCache` api.
+/// Both methods take `&self`, so a cache can be shared between threads,
+/// behind an `Arc` for example; implementations that need to mutate
+/// state use interior mutability.
+///
+/// The `wasmer-runtime` supplies `FileSystemCache`, `MemoryCache` and
+/// `KeyValueCache` implementations.
 pub trait Cache {
     type LoadError: fmt::Debug;
     type StoreError: fmt::Debug;
 
     fn load(&self, key: WasmHash) -> Result<Module, Self::LoadError>;
-    fn store(&mut self, key: WasmHash, module: Module) -> Result<(), Self::StoreError>;
+    fn store(&self, key: WasmHash, module: Module) -> Result<(), Self::StoreError>;
 }
 
 /// A unique ID generated from the version of Wasmer for use with cache versioning
 pub const WASMER_VERSION_HASH: &'static str =
     include_str!(concat!(env!("OUT_DIR"), "/wasmer_version_hash.txt"));
+
+#[cfg(test)]
+mod test {
+    use super::{hmac_blake2b, ArtifactHeader, Error, SigningKey, WasmHash, SIGNED_HMAC};
+    use crate::backend::{Backend, CompilerConfig, CpuFeature, OptLevel};
+
+    fn sealed_header(body: &[u8], key: Option<&SigningKey>) -> ArtifactHeader {
+        let mut header = ArtifactHeader::new(body.len() as u64);
+        header.seal(body, key);
+        header
+    }
+
+    fn to_bytes(header: &ArtifactHeader, body: &[u8]) -> Vec<u8> {
+        let mut bytes = header.as_slice().to_vec();
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    fn sealed(body: &[u8], key: Option<&SigningKey>) -> Vec<u8> {
+        to_bytes(&sealed_header(body, key), body)
+    }
+
+    fn verify(bytes: &[u8], key: Option<&SigningKey>) -> Result<(), Error> {
+        let (header, body) = ArtifactHeader::read_from_slice(bytes)?;
+        header.verify(body, key.map(SigningKey::verifying_key).as_ref())
+    }
+
+    #[test]
+    fn sealed_artifacts_verify() {
+        let key = SigningKey::Hmac(b"secret".to_vec());
+        assert!(verify(&sealed(b"code", None), None).is_ok());
+        assert!(verify(&sealed(b"code", Some(&key)), None).is_ok());
+        assert!(verify(&sealed(b"code", Some(&key)), Some(&key)).is_ok());
+    }
+
+    #[test]
+    fn tampered_artifacts_are_rejected() {
+        let key = SigningKey::Hmac(b"secret".to_vec());
+        let mut bytes = sealed(b"code", Some(&key));
+        *bytes.last_mut().unwrap() ^= 1;
+        match verify(&bytes, Some(&key)) {
+            Err(Error::InvalidChecksum) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Rehashing the tampered body doesn't help without the key.
+        let mut forged = sealed_header(b"cods", None);
+        forged.signature_kind = SIGNED_HMAC;
+        forged.signature = hmac_blake2b(b"guess", &[0; 64]);
+        match verify(&to_bytes(&forged, b"cods"), Some(&key)) {
+            Err(Error::InvalidSignature) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn artifacts_need_the_right_key() {
+        let key = SigningKey::Hmac(b"secret".to_vec());
+        let other_key = SigningKey::Hmac(b"other secret".to_vec());
+        match verify(&sealed(b"code", Some(&key)), Some(&other_key)) {
+            Err(Error::InvalidSignature) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match verify(&sealed(b"code", None), Some(&key)) {
+            Err(Error::InvalidSignature) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn artifacts_from_other_versions_are_invalidated() {
+        let mut header = sealed_header(b"code", None);
+        header.wasmer_version[0] ^= 1;
+        match verify(&to_bytes(&header, b"code"), None) {
+            Err(Error::InvalidatedCache) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keys_depend_on_how_modules_are_compiled() {
+        let wasm = b"\0asm\x01\0\0\0";
+        let key = |backend, config: &CompilerConfig| {
+            WasmHash::generate_with_config(wasm, backend, config)
+        };
+        let default = CompilerConfig::default();
+
+        assert_eq!(
+            key(Backend::Cranelift, &default),
+            key(Backend::Cranelift, &CompilerConfig::default())
+        );
+        assert_ne!(key(Backend::Cranelift, &default), WasmHash::generate(wasm));
+        assert_ne!(
+            key(Backend::Cranelift, &default),
+            key(Backend::Singlepass, &default)
+        );
+        assert_ne!(
+            key(Backend::Cranelift, &default),
+            key(
+                Backend::Cranelift,
+                &CompilerConfig {
+                    opt_level: OptLevel::None,
+                    ..Default::default()
+                }
+            )
+        );
+        assert_ne!(
+            key(
+                Backend::Cranelift,
+                &CompilerConfig {
+                    triple: Some("x86_64-unknown-linux-gnu".to_string()),
+                    ..Default::default()
+                }
+            ),
+            key(
+                Backend::Cranelift,
+                &CompilerConfig {
+                    triple: Some("x86_64-unknown-linux-gnu".to_string()),
+                    enable_cpu_features: vec![CpuFeature::SSE41],
+                    ..Default::default()
+                }
+            )
+        );
+        assert_eq!(
+            key(Backend::Cranelift, &default),
+            key(
+                Backend::Cranelift,
+                &CompilerConfig {
+                    generate_debug_info: true,
+                    ..Default::default()
+                }
+            )
+        );
+    }
+}
@@ -91,22 +91,51 @@ impl PartialEq for LinkError {
 impl std::fmt::Display for LinkError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            LinkError::ImportNotFound {namespace, name} => write!(f, "Import not found, namespace: {}, name: {}", namespace, name),
-            LinkError::IncorrectGlobalDescriptor {namespace, name,expected,found} => {
+            LinkError::ImportNotFound { namespace, name } => write!(
+                f,
+                "Import not found, namespace: {}, name: {}",
+                namespace, name
+            ),
+            LinkError::IncorrectGlobalDescriptor {
+                namespace,
+                name,
+                expected,
+                found,
+            } => {
                 write!(f, "Incorrect global descriptor, namespace: {}, name: {}, expected global descriptor: {:?}, found global descriptor: {:?}", namespace, name, expected, found)
-            },
-            LinkError::IncorrectImportSignature{namespace, name,expected,found} => {
+            }
+            LinkError::IncorrectImportSignature {
+                namespace,
+                name,
+                expected,
+                found,
+            } => {
                 write!(f, "Incorrect import signature, namespace: {}, name: {}, expected signature: {}, found signature: {}", namespace, name, expected, found)
             }
-            LinkError::IncorrectImportType{namespace, name,expected,found} => {
+            LinkError::IncorrectImportType {
+                namespace,
+                name,
+                expected,
+                found,
+            } => {
                 write!(f, "Incorrect import type, namespace: {}, name: {}, expected type: {}, found type: {}", namespace, name, expected, found)
             }
-            LinkError::IncorrectMemoryDescriptor{namespace, name,expected,found} => {
+            LinkError::IncorrectMemoryDescriptor {
+                namespace,
+                name,
+                expected,
+                found,
+            } => {
                 write!(f, "Incorrect memory descriptor, namespace: {}, name: {}, expected memory descriptor: {:?}, found memory descriptor: {:?}", namespace, name, expected, found)
-            },
-            LinkError::IncorrectTableDescriptor{namespace, name,expected,found} => {
+            }
+            LinkError::IncorrectTableDescriptor {
+                namespace,
+                name,
+                expected,
+                found,
+            } => {
                 write!(f, "Incorrect table descriptor, namespace: {}, name: {}, expected table descriptor: {:?}, found table descriptor: {:?}", namespace, name, expected, found)
-            },
+            }
         }
     }
 }
@@ -250,6 +279,8 @@ pub enum CreationError {
     UnableToCreateMemory,
     UnableToCreateTable,
     InvalidDescriptor(String),
+    /// The module was compiled for another architecture.
+    IncompatibleTarget(String),
 }
 
 impl PartialEq for CreationError {
@@ -268,6 +299,11 @@ impl std::fmt::Display for CreationError {
                 "Unable to create because the supplied descriptor is invalid: \"{}\"",
                 msg
             ),
+            CreationError::IncompatibleTarget(triple) => write!(
+                f,
+                "Unable to create because the module was compiled for {}",
+                triple
+            ),
         }
     }
 }
@@ -2,6 +2,7 @@ use crate::export::Export;
 use hashbrown::{hash_map::Entry, HashMap};
 use std::collections::VecDeque;
 use std::{
+    any::Any,
     cell::{Ref, RefCell},
     ffi::c_void,
     rc::Rc,
@@ -11,6 +12,14 @@ pub trait LikeNamespace {
     fn get_export(&self, name: &str) -> Option<Export>;
     fn get_exports(&self) -> Vec<(String, Export)>;
     fn maybe_insert(&mut self, name: &str, export: Export) -> Option<()>;
+
+    /// Whatever the functions of this namespace need to stay valid,
+    /// if they run in the context of something that could be dropped,
+    /// like another instance. Instances importing those functions
+    /// hold on to it for as long as they live.
+    fn keepalive(&self) -> Option<Rc<dyn Any>> {
+        None
+    }
 }
 
 pub trait IsExport {
@@ -1,13 +1,16 @@
 use crate::{
     backend::RunnableModule,
     backing::{ImportBacking, LocalBacking},
-    error::{CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError},
+    error::{
+        CallError, CallResult, CreationError, ResolveError, ResolveResult, Result, RuntimeError,
+    },
     export::{Context, Export, ExportIter, FuncPointer},
     global::Global,
     import::{ImportObject, LikeNamespace},
     memory::Memory,
     module::{ExportIndex, Module, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
+    structures::TypedIndex,
     table::Table,
     typed_func::{Func, Wasm, WasmTrapInfo, WasmTypeList},
     types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Type, Value},
@@ -40,12 +43,17 @@ impl Drop for InstanceInner {
 pub struct Instance {
     module: Arc<ModuleInner>,
     inner: Box<InstanceInner>,
-    #[allow(dead_code)]
     import_object: ImportObject,
 }
 
 impl Instance {
     pub(crate) fn new(module: Arc<ModuleInner>, imports: &ImportObject) -> Result<Instance> {
+        // Code compiled for another architecture can only be cached.
+        if !module.info.is_for_host_arch() {
+            let triple = module.info.triple.clone().unwrap_or_default();
+            return Err(CreationError::IncompatibleTarget(triple).into());
+        }
+
         // We need the backing and import_backing to create a vm::Ctx, but we need
         // a vm::Ctx to create a backing and an import_backing. The solution is to create an
         // uninitialized vm::Ctx and then initialize it in-place.
@@ -344,6 +352,11 @@ impl Instance {
     pub fn module(&self) -> Module {
         Module::new(Arc::clone(&self.module))
     }
+
+    /// The imports this Instance was instantiated with.
+    pub fn import_object(&self) -> &ImportObject {
+        &self.import_object
+    }
 }
 
 impl InstanceInner {
@@ -456,7 +469,7 @@ impl LikeNamespace for Instance {
     }
 
     fn get_exports(&self) -> Vec<(String, Export)> {
-        unimplemented!("Use the exports method instead");
+        self.exports().collect()
     }
 
     fn maybe_insert(&mut self, _name: &str, _export: Export) -> Option<()> {
@@ -542,9 +555,18 @@ fn call_func_with_index(
         if success {
             Ok(())
         } else {
-            Err(RuntimeError::Trap {
-                msg: trap_info.to_string().into(),
-            })
+            let msg = match runnable.get_trap_func(info) {
+                Some(local_func_index) => {
+                    let func_index = local_func_index.convert_up(info);
+                    match info.function_name(func_index) {
+                        Some(name) => format!("{} in function `{}`", trap_info, name),
+                        None => format!("{} in function {}", trap_info, func_index.index()),
+                    }
+                }
+                None => trap_info.to_string(),
+            };
+
+            Err(RuntimeError::Trap { msg: msg.into() })
         }
     };
 
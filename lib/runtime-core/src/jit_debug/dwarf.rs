@@ -0,0 +1,817 @@
+//! Translation of wasm DWARF into DWARF describing the native code.
+//!
+//! Only what debuggers need to map native code back to source is
+//! translated: a compile unit with its line table for every wasm line
+//! program, and a subprogram for every function. Addresses in wasm
+//! DWARF are offsets into the code section; they are mapped to native
+//! addresses through the source locations recorded by the backend.
+//! Only 32-bit DWARF versions 2 to 4 are understood.
+
+use super::{FunctionDebugInfo, Reader, WriteBytes};
+use hashbrown::HashMap;
+
+const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_STMT_LIST: u64 = 0x10;
+const DW_AT_LOW_PC: u64 = 0x11;
+const DW_AT_HIGH_PC: u64 = 0x12;
+const DW_AT_COMP_DIR: u64 = 0x1b;
+
+const DW_FORM_ADDR: u64 = 0x01;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_SEC_OFFSET: u64 = 0x17;
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_SET_COLUMN: u8 = 5;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+/// Abbreviation codes of the `.debug_abbrev` section we write.
+const ABBREV_UNIT_WITH_LINES: u64 = 1;
+const ABBREV_UNIT: u64 = 2;
+const ABBREV_SUBPROGRAM: u64 = 3;
+
+/// The line program header we write, after `header_length`.
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+/// The DWARF sections of a wasm module.
+pub struct WasmDwarf<'a> {
+    pub debug_info: Option<&'a [u8]>,
+    pub debug_abbrev: Option<&'a [u8]>,
+    pub debug_line: Option<&'a [u8]>,
+    pub debug_str: Option<&'a [u8]>,
+}
+
+/// Build the `.debug_abbrev`, `.debug_info` and `.debug_line` sections
+/// describing `functions`. `names` holds the name of each function.
+///
+/// Without wasm DWARF (or without the offset of the code section it is
+/// relative to) the result only describes the functions, not their lines.
+pub fn translate(
+    dwarf: WasmDwarf,
+    code_section_offset: Option<usize>,
+    module_name: Option<&str>,
+    functions: &[FunctionDebugInfo],
+    names: &[String],
+) -> Vec<(&'static str, Vec<u8>)> {
+    let units = match (dwarf.debug_info, dwarf.debug_abbrev) {
+        (Some(debug_info), Some(debug_abbrev)) => {
+            read_compile_units(debug_info, debug_abbrev, dwarf.debug_str)
+        }
+        _ => HashMap::new(),
+    };
+    let programs = match (dwarf.debug_line, code_section_offset) {
+        (Some(debug_line), Some(_)) => read_line_programs(debug_line),
+        _ => Vec::new(),
+    };
+
+    let mut ranges: Vec<&LineRange> = programs
+        .iter()
+        .flat_map(|program| program.ranges.iter())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+
+    // The rows of every function, grouped by the line program they come from.
+    let mut sequences: Vec<Vec<(&FunctionDebugInfo, &str, Vec<Row>)>> =
+        programs.iter().map(|_| Vec::new()).collect();
+    let mut without_lines = Vec::new();
+
+    for (function, name) in functions.iter().zip(names) {
+        let mut program = None;
+        let mut rows: Vec<Row> = Vec::new();
+
+        for &(address, offset) in function.instructions.iter() {
+            let wasm_address = match offset.checked_sub(code_section_offset.unwrap_or(0)) {
+                Some(wasm_address) => wasm_address as u64,
+                None => continue,
+            };
+            let range = match lookup(&ranges, wasm_address) {
+                Some(range) => range,
+                None => continue,
+            };
+            if *program.get_or_insert(range.program) != range.program {
+                continue;
+            }
+
+            let row = Row {
+                address: address as u64,
+                file: range.file,
+                line: range.line,
+                column: range.column,
+            };
+            if rows.last().map_or(true, |last| !last.same_location(&row)) {
+                rows.push(row);
+            }
+        }
+
+        match program {
+            Some(program) => sequences[program].push((function, name.as_str(), rows)),
+            None => without_lines.push((function, name.as_str())),
+        }
+    }
+
+    let mut debug_info = Vec::new();
+    let mut debug_line = Vec::new();
+
+    for (program, sequences) in programs.iter().zip(sequences.iter()) {
+        if sequences.is_empty() {
+            continue;
+        }
+
+        let stmt_list = debug_line.len() as u32;
+        write_line_program(&mut debug_line, program, sequences);
+
+        let unit = units.get(&program.offset);
+        let name = unit
+            .and_then(|unit| unit.name.as_ref())
+            .map(|name| name.as_str())
+            .or(module_name)
+            .unwrap_or("wasm");
+        let comp_dir = unit
+            .and_then(|unit| unit.comp_dir.as_ref())
+            .map(|comp_dir| comp_dir.as_str())
+            .unwrap_or("");
+        let functions: Vec<_> = sequences
+            .iter()
+            .map(|&(function, name, _)| (function, name))
+            .collect();
+
+        write_compile_unit(
+            &mut debug_info,
+            name,
+            Some((comp_dir, stmt_list)),
+            &functions,
+        );
+    }
+
+    if !without_lines.is_empty() {
+        write_compile_unit(
+            &mut debug_info,
+            module_name.unwrap_or("wasm"),
+            None,
+            &without_lines,
+        );
+    }
+
+    let mut sections = vec![
+        (".debug_abbrev", abbreviations()),
+        (".debug_info", debug_info),
+    ];
+    if !debug_line.is_empty() {
+        sections.push((".debug_line", debug_line));
+    }
+    sections
+}
+
+/// A row of a line table, with the registers debuggers care about.
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    address: u64,
+    file: u64,
+    line: u64,
+    column: u64,
+}
+
+impl Row {
+    fn same_location(&self, other: &Row) -> bool {
+        self.file == other.file && self.line == other.line && self.column == other.column
+    }
+}
+
+/// The source location of the wasm code in `start..end`.
+#[derive(Debug, Clone, PartialEq)]
+struct LineRange {
+    start: u64,
+    end: u64,
+    /// Index of the line program this range belongs to.
+    program: usize,
+    file: u64,
+    line: u64,
+    column: u64,
+}
+
+fn lookup<'a>(ranges: &[&'a LineRange], address: u64) -> Option<&'a LineRange> {
+    let index = match ranges.binary_search_by_key(&address, |range| range.start) {
+        Ok(index) => index,
+        Err(0) => return None,
+        Err(index) => index - 1,
+    };
+    let range = ranges[index];
+    if address < range.end {
+        Some(range)
+    } else {
+        None
+    }
+}
+
+struct LineProgram<'a> {
+    /// Offset of the program in the wasm `.debug_line` section,
+    /// which is what `DW_AT_stmt_list` refers to.
+    offset: u64,
+    /// The directory and file tables, including their terminators.
+    include_directories: &'a [u8],
+    file_names: &'a [u8],
+    ranges: Vec<LineRange>,
+}
+
+fn read_line_programs(debug_line: &[u8]) -> Vec<LineProgram> {
+    let mut programs = Vec::new();
+    let mut reader = Reader::new(debug_line);
+
+    while !reader.eof() {
+        let offset = reader.position;
+        let unit_length = match reader.read_u32() {
+            // 64-bit DWARF isn't produced for wasm32.
+            Some(0xffff_ffff) | None => break,
+            Some(unit_length) => unit_length as usize,
+        };
+        let unit = match reader.bytes(unit_length) {
+            Some(unit) => unit,
+            None => break,
+        };
+
+        let index = programs.len();
+        if let Some(program) = read_line_program(unit, offset as u64, index) {
+            programs.push(program);
+        }
+    }
+
+    programs
+}
+
+fn read_line_program(unit: &[u8], offset: u64, index: usize) -> Option<LineProgram> {
+    let mut reader = Reader::new(unit);
+    let version = reader.read_u16()?;
+    if version < 2 || version > 4 {
+        return None;
+    }
+
+    let header_length = reader.read_u32()? as usize;
+    let program_start = reader.position.checked_add(header_length)?;
+    let minimum_instruction_length = u64::from(reader.read_u8()?);
+    if version >= 4 {
+        let _maximum_operations_per_instruction = reader.read_u8()?;
+    }
+    let _default_is_stmt = reader.read_u8()?;
+    let line_base = reader.read_u8()? as i8;
+    let line_range = reader.read_u8()?;
+    let opcode_base = reader.read_u8()?;
+    if line_range == 0 || opcode_base == 0 {
+        return None;
+    }
+    let standard_opcode_lengths = reader.bytes(opcode_base as usize - 1)?;
+
+    let start = reader.position;
+    while !reader.read_cstr()?.is_empty() {}
+    let include_directories = &unit[start..reader.position];
+
+    let start = reader.position;
+    while !reader.read_cstr()?.is_empty() {
+        for _ in 0..3 {
+            reader.read_uleb128()?;
+        }
+    }
+    let file_names = &unit[start..reader.position];
+
+    let mut reader = Reader::new(unit.get(program_start..)?);
+    let mut ranges = Vec::new();
+    let mut sequence: Vec<(u64, u64, u64, u64)> = Vec::new();
+
+    let mut address = 0u64;
+    let mut file = 1;
+    let mut line = 1u64;
+    let mut column = 0;
+
+    while !reader.eof() {
+        let opcode = reader.read_u8()?;
+        let mut emit = false;
+
+        if opcode >= opcode_base {
+            let adjusted = opcode - opcode_base;
+            address += u64::from(adjusted / line_range) * minimum_instruction_length;
+            line = add_line(
+                line,
+                i64::from(line_base) + i64::from(adjusted % line_range),
+            );
+            emit = true;
+        } else if opcode == 0 {
+            let len = reader.read_uleb128()? as usize;
+            let mut extended = Reader::new(reader.bytes(len)?);
+            match extended.read_u8()? {
+                DW_LNE_END_SEQUENCE => {
+                    sequence.push((address, file, line, column));
+                    push_ranges(&mut ranges, &sequence, index);
+                    sequence.clear();
+
+                    address = 0;
+                    file = 1;
+                    line = 1;
+                    column = 0;
+                }
+                DW_LNE_SET_ADDRESS => {
+                    address = match len - 1 {
+                        4 => u64::from(extended.read_u32()?),
+                        8 => extended.read_u64()?,
+                        _ => return None,
+                    };
+                }
+                _ => {}
+            }
+        } else {
+            match opcode {
+                DW_LNS_COPY => emit = true,
+                DW_LNS_ADVANCE_PC => {
+                    address += reader.read_uleb128()? * minimum_instruction_length;
+                }
+                DW_LNS_ADVANCE_LINE => line = add_line(line, reader.read_sleb128()?),
+                DW_LNS_SET_FILE => file = reader.read_uleb128()?,
+                DW_LNS_SET_COLUMN => column = reader.read_uleb128()?,
+                DW_LNS_CONST_ADD_PC => {
+                    let adjusted = 255 - opcode_base;
+                    address += u64::from(adjusted / line_range) * minimum_instruction_length;
+                }
+                DW_LNS_FIXED_ADVANCE_PC => address += u64::from(reader.read_u16()?),
+                _ => {
+                    // Skip the operands of opcodes we don't care about.
+                    for _ in 0..standard_opcode_lengths[opcode as usize - 1] {
+                        reader.read_uleb128()?;
+                    }
+                }
+            }
+        }
+
+        if emit {
+            sequence.push((address, file, line, column));
+        }
+    }
+
+    Some(LineProgram {
+        offset,
+        include_directories,
+        file_names,
+        ranges,
+    })
+}
+
+fn add_line(line: u64, delta: i64) -> u64 {
+    (line as i64).wrapping_add(delta) as u64
+}
+
+/// Turn the rows of a sequence, the last of which ends it,
+/// into address ranges.
+fn push_ranges(ranges: &mut Vec<LineRange>, sequence: &[(u64, u64, u64, u64)], program: usize) {
+    for rows in sequence.windows(2) {
+        let (start, file, line, column) = rows[0];
+        let end = rows[1].0;
+        if start < end {
+            ranges.push(LineRange {
+                start,
+                end,
+                program,
+                file,
+                line,
+                column,
+            });
+        }
+    }
+}
+
+fn write_line_program(
+    debug_line: &mut Vec<u8>,
+    program: &LineProgram,
+    sequences: &[(&FunctionDebugInfo, &str, Vec<Row>)],
+) {
+    let mut header = Vec::new();
+    header.write_u8(1); // minimum_instruction_length
+    header.write_u8(1); // maximum_operations_per_instruction
+    header.write_u8(1); // default_is_stmt
+    header.write_u8(LINE_BASE as u8);
+    header.write_u8(LINE_RANGE);
+    header.write_u8(OPCODE_BASE);
+    header.extend_from_slice(&STANDARD_OPCODE_LENGTHS);
+    header.extend_from_slice(program.include_directories);
+    header.extend_from_slice(program.file_names);
+
+    let mut body = Vec::new();
+    for (function, _, rows) in sequences {
+        if rows.is_empty() {
+            continue;
+        }
+
+        body.write_u8(0);
+        body.write_uleb128(9);
+        body.write_u8(DW_LNE_SET_ADDRESS);
+        body.write_u64(rows[0].address);
+
+        let mut previous = Row {
+            address: rows[0].address,
+            file: 1,
+            line: 1,
+            column: 0,
+        };
+        for row in rows {
+            if row.address != previous.address {
+                body.write_u8(DW_LNS_ADVANCE_PC);
+                body.write_uleb128(row.address - previous.address);
+            }
+            if row.line != previous.line {
+                body.write_u8(DW_LNS_ADVANCE_LINE);
+                body.write_sleb128(row.line.wrapping_sub(previous.line) as i64);
+            }
+            if row.file != previous.file {
+                body.write_u8(DW_LNS_SET_FILE);
+                body.write_uleb128(row.file);
+            }
+            if row.column != previous.column {
+                body.write_u8(DW_LNS_SET_COLUMN);
+                body.write_uleb128(row.column);
+            }
+            body.write_u8(DW_LNS_COPY);
+            previous = row.clone();
+        }
+
+        let end = (function.address + function.size) as u64;
+        if end > previous.address {
+            body.write_u8(DW_LNS_ADVANCE_PC);
+            body.write_uleb128(end - previous.address);
+        }
+        body.write_u8(0);
+        body.write_uleb128(1);
+        body.write_u8(DW_LNE_END_SEQUENCE);
+    }
+
+    // version + header_length + header + body
+    let unit_length = 2 + 4 + header.len() + body.len();
+    debug_line.write_u32(unit_length as u32);
+    debug_line.write_u16(4);
+    debug_line.write_u32(header.len() as u32);
+    debug_line.extend_from_slice(&header);
+    debug_line.extend_from_slice(&body);
+}
+
+fn abbreviations() -> Vec<u8> {
+    let declarations: [(u64, u64, bool, &[(u64, u64)]); 3] = [
+        (
+            ABBREV_UNIT_WITH_LINES,
+            DW_TAG_COMPILE_UNIT,
+            true,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_COMP_DIR, DW_FORM_STRING),
+                (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            ],
+        ),
+        (
+            ABBREV_UNIT,
+            DW_TAG_COMPILE_UNIT,
+            true,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            ],
+        ),
+        (
+            ABBREV_SUBPROGRAM,
+            DW_TAG_SUBPROGRAM,
+            false,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA8),
+            ],
+        ),
+    ];
+
+    let mut debug_abbrev = Vec::new();
+    for &(code, tag, has_children, attributes) in declarations.iter() {
+        debug_abbrev.write_uleb128(code);
+        debug_abbrev.write_uleb128(tag);
+        debug_abbrev.write_u8(has_children as u8);
+        for &(name, form) in attributes {
+            debug_abbrev.write_uleb128(name);
+            debug_abbrev.write_uleb128(form);
+        }
+        debug_abbrev.write_uleb128(0);
+        debug_abbrev.write_uleb128(0);
+    }
+    debug_abbrev.write_uleb128(0);
+    debug_abbrev
+}
+
+/// Write a compile unit containing a subprogram for each function.
+/// `lines` holds the compilation directory and the offset of the
+/// unit's line program, if it has one.
+fn write_compile_unit(
+    debug_info: &mut Vec<u8>,
+    name: &str,
+    lines: Option<(&str, u32)>,
+    functions: &[(&FunctionDebugInfo, &str)],
+) {
+    let low_pc = functions.iter().map(|(f, _)| f.address).min().unwrap_or(0) as u64;
+    let high_pc = functions
+        .iter()
+        .map(|(f, _)| f.address + f.size)
+        .max()
+        .unwrap_or(0) as u64;
+
+    let mut unit = Vec::new();
+    unit.write_u16(4); // version
+    unit.write_u32(0); // debug_abbrev_offset
+    unit.write_u8(8); // address_size
+
+    match lines {
+        Some((comp_dir, stmt_list)) => {
+            unit.write_uleb128(ABBREV_UNIT_WITH_LINES);
+            unit.write_cstr(name);
+            unit.write_cstr(comp_dir);
+            unit.write_u32(stmt_list);
+        }
+        None => {
+            unit.write_uleb128(ABBREV_UNIT);
+            unit.write_cstr(name);
+        }
+    }
+    unit.write_u64(low_pc);
+    unit.write_u64(high_pc - low_pc);
+
+    for &(function, name) in functions {
+        unit.write_uleb128(ABBREV_SUBPROGRAM);
+        unit.write_cstr(name);
+        unit.write_u64(function.address as u64);
+        unit.write_u64(function.size as u64);
+    }
+    unit.write_uleb128(0);
+
+    debug_info.write_u32(unit.len() as u32);
+    debug_info.extend_from_slice(&unit);
+}
+
+/// The attributes of a wasm compile unit that we carry over.
+#[derive(Debug, Default)]
+struct CompileUnit {
+    name: Option<String>,
+    comp_dir: Option<String>,
+}
+
+/// Read the name and compilation directory of every compile unit,
+/// keyed by the offset of its line program.
+fn read_compile_units(
+    debug_info: &[u8],
+    debug_abbrev: &[u8],
+    debug_str: Option<&[u8]>,
+) -> HashMap<u64, CompileUnit> {
+    let mut units = HashMap::new();
+    let mut reader = Reader::new(debug_info);
+
+    while !reader.eof() {
+        let unit_length = match reader.read_u32() {
+            Some(0xffff_ffff) | None => break,
+            Some(unit_length) => unit_length as usize,
+        };
+        let unit = match reader.bytes(unit_length) {
+            Some(unit) => unit,
+            None => break,
+        };
+
+        if let Some((stmt_list, unit)) = read_compile_unit(unit, debug_abbrev, debug_str) {
+            units.insert(stmt_list, unit);
+        }
+    }
+
+    units
+}
+
+fn read_compile_unit(
+    unit: &[u8],
+    debug_abbrev: &[u8],
+    debug_str: Option<&[u8]>,
+) -> Option<(u64, CompileUnit)> {
+    let mut reader = Reader::new(unit);
+    let version = reader.read_u16()?;
+    if version < 2 || version > 4 {
+        return None;
+    }
+    let abbrev_offset = reader.read_u32()? as usize;
+    let address_size = reader.read_u8()? as usize;
+
+    let code = reader.read_uleb128()?;
+    let attributes = find_abbreviation(debug_abbrev.get(abbrev_offset..)?, code)?;
+
+    let mut stmt_list = None;
+    let mut compile_unit = CompileUnit::default();
+
+    for (name, form) in attributes {
+        match (name, form) {
+            (DW_AT_NAME, DW_FORM_STRING) | (DW_AT_NAME, DW_FORM_STRP) => {
+                compile_unit.name = read_string(&mut reader, form, debug_str);
+            }
+            (DW_AT_COMP_DIR, DW_FORM_STRING) | (DW_AT_COMP_DIR, DW_FORM_STRP) => {
+                compile_unit.comp_dir = read_string(&mut reader, form, debug_str);
+            }
+            (DW_AT_STMT_LIST, DW_FORM_DATA4) | (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET) => {
+                stmt_list = Some(u64::from(reader.read_u32()?));
+            }
+            _ => skip_attribute(&mut reader, form, version, address_size)?,
+        }
+    }
+
+    Some((stmt_list?, compile_unit))
+}
+
+fn read_string(reader: &mut Reader, form: u64, debug_str: Option<&[u8]>) -> Option<String> {
+    let bytes = match form {
+        DW_FORM_STRING => reader.read_cstr()?,
+        DW_FORM_STRP => {
+            let offset = reader.read_u32()? as usize;
+            Reader::new(debug_str?.get(offset..)?).read_cstr()?
+        }
+        _ => return None,
+    };
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn find_abbreviation(debug_abbrev: &[u8], code: u64) -> Option<Vec<(u64, u64)>> {
+    let mut reader = Reader::new(debug_abbrev);
+    loop {
+        let current = reader.read_uleb128()?;
+        if current == 0 {
+            return None;
+        }
+        let _tag = reader.read_uleb128()?;
+        let _has_children = reader.read_u8()?;
+
+        let mut attributes = Vec::new();
+        loop {
+            let name = reader.read_uleb128()?;
+            let form = reader.read_uleb128()?;
+            if name == 0 && form == 0 {
+                break;
+            }
+            attributes.push((name, form));
+        }
+
+        if current == code {
+            return Some(attributes);
+        }
+    }
+}
+
+fn skip_attribute(reader: &mut Reader, form: u64, version: u16, address_size: usize) -> Option<()> {
+    let len = match form {
+        DW_FORM_ADDR => address_size,
+        0x03 => reader.read_u16()? as usize, // DW_FORM_block2
+        0x04 => reader.read_u32()? as usize, // DW_FORM_block4
+        0x05 | 0x12 => 2,                    // DW_FORM_data2, DW_FORM_ref2
+        0x06 | 0x13 | 0x0e | 0x17 => 4,      // data4, ref4, strp, sec_offset
+        0x07 | 0x14 | 0x20 => 8,             // data8, ref8, ref_sig8
+        0x08 => {
+            reader.read_cstr()?;
+            0
+        }
+        0x09 | 0x18 => reader.read_uleb128()? as usize, // DW_FORM_block, DW_FORM_exprloc
+        0x0a => reader.read_u8()? as usize,             // DW_FORM_block1
+        0x0b | 0x0c | 0x11 => 1,                        // data1, flag, ref1
+        0x0d => {
+            reader.read_sleb128()?;
+            0
+        }
+        0x0f | 0x15 => {
+            reader.read_uleb128()?;
+            0
+        }
+        0x10 if version == 2 => address_size, // DW_FORM_ref_addr
+        0x10 => 4,
+        0x19 => 0, // DW_FORM_flag_present
+        _ => return None,
+    };
+    reader.skip(len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lookup, read_line_programs, translate, LineRange, WasmDwarf};
+    use crate::jit_debug::{FunctionDebugInfo, WriteBytes};
+    use crate::{structures::TypedIndex, types::FuncIndex};
+
+    /// A version 4 line program for wasm32 with a single file and a
+    /// sequence covering code section offsets 0x10 to 0x20.
+    fn wasm_line_program() -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&[1, 1, 1, (-5i8) as u8, 14, 13]);
+        header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+        header.write_u8(0); // no include directories
+        header.write_cstr("main.c");
+        header.extend_from_slice(&[0, 0, 0]);
+        header.write_u8(0);
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&[0, 5, 2]);
+        body.write_u32(0x10);
+        body.write_u8(3); // advance_line
+        body.write_sleb128(9);
+        body.write_u8(1); // copy: line 10 at 0x10
+        body.write_u8(2); // advance_pc
+        body.write_uleb128(8);
+        body.write_u8(3);
+        body.write_sleb128(2);
+        body.write_u8(1); // copy: line 12 at 0x18
+        body.write_u8(2);
+        body.write_uleb128(8);
+        body.extend_from_slice(&[0, 1, 1]); // end_sequence at 0x20
+
+        let mut unit = Vec::new();
+        unit.write_u32((2 + 4 + header.len() + body.len()) as u32);
+        unit.write_u16(4);
+        unit.write_u32(header.len() as u32);
+        unit.extend_from_slice(&header);
+        unit.extend_from_slice(&body);
+        unit
+    }
+
+    #[test]
+    fn read_wasm_line_program() {
+        let debug_line = wasm_line_program();
+        let programs = read_line_programs(&debug_line);
+
+        assert_eq!(programs.len(), 1);
+        let ranges: Vec<_> = programs[0].ranges.iter().collect();
+        assert_eq!(
+            ranges,
+            vec![
+                &LineRange {
+                    start: 0x10,
+                    end: 0x18,
+                    program: 0,
+                    file: 1,
+                    line: 10,
+                    column: 0,
+                },
+                &LineRange {
+                    start: 0x18,
+                    end: 0x20,
+                    program: 0,
+                    file: 1,
+                    line: 12,
+                    column: 0,
+                },
+            ]
+        );
+
+        assert_eq!(lookup(&ranges, 0x0f), None);
+        assert_eq!(lookup(&ranges, 0x1a).map(|range| range.line), Some(12));
+        assert_eq!(lookup(&ranges, 0x20), None);
+    }
+
+    #[test]
+    fn translate_line_program() {
+        let debug_line = wasm_line_program();
+        let function = FunctionDebugInfo {
+            func_index: FuncIndex::new(0),
+            address: 0x7000,
+            size: 0x40,
+            // The code section starts at offset 0x100 of the module.
+            instructions: vec![(0x7000, 0x110), (0x7008, 0x112), (0x7020, 0x118)],
+        };
+
+        let sections = translate(
+            WasmDwarf {
+                debug_info: None,
+                debug_abbrev: None,
+                debug_line: Some(&debug_line),
+                debug_str: None,
+            },
+            Some(0x100),
+            None,
+            &[function],
+            &["main".to_string()],
+        );
+        let names: Vec<_> = sections.iter().map(|&(name, _)| name).collect();
+        assert_eq!(names, vec![".debug_abbrev", ".debug_info", ".debug_line"]);
+
+        // Translating the translated program gives native ranges.
+        let programs = read_line_programs(&sections[2].1);
+        let ranges: Vec<_> = programs[0]
+            .ranges
+            .iter()
+            .map(|range| (range.start, range.end, range.line))
+            .collect();
+        assert_eq!(ranges, vec![(0x7000, 0x7020, 10), (0x7020, 0x7040, 12)]);
+    }
+}
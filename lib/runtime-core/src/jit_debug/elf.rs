@@ -0,0 +1,344 @@
+//! A minimal ELF64 writer for relocatable objects, and readers for the
+//! few things the runtime needs from object files.
+
+use super::{Reader, WriteBytes};
+use hashbrown::HashMap;
+
+/// The `e_machine` of an architecture, named as in target triples,
+/// if objects can be written for it.
+pub fn machine(arch: &str) -> Option<u16> {
+    match arch {
+        "x86_64" => Some(62),   // EM_X86_64
+        "aarch64" => Some(183), // EM_AARCH64
+        _ => None,
+    }
+}
+
+const ET_REL: u16 = 1;
+
+pub const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+pub const SHT_NOBITS: u32 = 8;
+
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+
+const STB_GLOBAL: u8 = 1;
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+
+const HEADER_SIZE: usize = 64;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+pub struct Section {
+    pub name: &'static str,
+    pub ty: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub data: Vec<u8>,
+    /// Only differs from the length of `data` for `SHT_NOBITS`,
+    /// which has no data in the file.
+    pub size: u64,
+    pub align: u64,
+    pub link: u32,
+    pub info: u32,
+    pub entsize: u64,
+}
+
+impl Section {
+    pub fn new(name: &'static str, ty: u32, data: Vec<u8>) -> Self {
+        Self {
+            name,
+            ty,
+            flags: 0,
+            addr: 0,
+            size: data.len() as u64,
+            data,
+            align: 1,
+            link: 0,
+            info: 0,
+            entsize: 0,
+        }
+    }
+}
+
+/// A global symbol defined in one of the sections passed to `write`.
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub ty: u8,
+    /// The index of the section in the list passed to `write`.
+    pub section: usize,
+    /// The offset of the symbol in its section.
+    pub value: u64,
+    pub size: u64,
+}
+
+/// Write a relocatable object made of `sections` and `symbols`.
+///
+/// The symbol and string tables are generated and appended
+/// after the given sections.
+pub fn write(machine: u16, sections: Vec<Section>, symbols: &[Symbol]) -> Vec<u8> {
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; SYMBOL_SIZE];
+    for symbol in symbols {
+        symtab.write_u32(strtab.len() as u32);
+        strtab.write_cstr(symbol.name);
+        symtab.write_u8(STB_GLOBAL << 4 | symbol.ty);
+        symtab.write_u8(0);
+        // Section 0 is the null section.
+        symtab.write_u16(symbol.section as u16 + 1);
+        symtab.write_u64(symbol.value);
+        symtab.write_u64(symbol.size);
+    }
+
+    let mut sections: Vec<Section> = Some(Section::new("", 0, vec![]))
+        .into_iter()
+        .chain(sections)
+        .collect();
+
+    let strtab_index = sections.len() as u32 + 1;
+    let mut symtab = Section::new(".symtab", SHT_SYMTAB, symtab);
+    symtab.link = strtab_index;
+    // Index of the first non-local symbol.
+    symtab.info = 1;
+    symtab.align = 8;
+    symtab.entsize = SYMBOL_SIZE as u64;
+    sections.push(symtab);
+    sections.push(Section::new(".strtab", SHT_STRTAB, strtab));
+
+    let mut shstrtab = vec![0];
+    let mut section_names = Vec::with_capacity(sections.len() + 1);
+    for section in sections.iter() {
+        if section.name.is_empty() {
+            section_names.push(0);
+        } else {
+            section_names.push(shstrtab.len() as u32);
+            shstrtab.write_cstr(section.name);
+        }
+    }
+    section_names.push(shstrtab.len() as u32);
+    shstrtab.write_cstr(".shstrtab");
+    sections.push(Section::new(".shstrtab", SHT_STRTAB, shstrtab));
+
+    // Section contents follow the header; section headers come last.
+    let mut offsets = Vec::with_capacity(sections.len());
+    let mut offset = HEADER_SIZE;
+    for section in sections.iter() {
+        offset = align(offset, section.align.max(8) as usize);
+        offsets.push(offset);
+        offset += section.data.len();
+    }
+    let section_headers_offset = align(offset, 8);
+
+    let mut image =
+        Vec::with_capacity(section_headers_offset + sections.len() * SECTION_HEADER_SIZE);
+    image.extend_from_slice(&[0x7f, b'E', b'L', b'F']);
+    image.write_u8(2); // ELFCLASS64
+    image.write_u8(1); // ELFDATA2LSB
+    image.write_u8(1); // EV_CURRENT
+    image.resize(16, 0);
+    image.write_u16(ET_REL);
+    image.write_u16(machine);
+    image.write_u32(1); // EV_CURRENT
+    image.write_u64(0); // e_entry
+    image.write_u64(0); // e_phoff
+    image.write_u64(section_headers_offset as u64);
+    image.write_u32(0); // e_flags
+    image.write_u16(HEADER_SIZE as u16);
+    image.write_u16(0); // e_phentsize
+    image.write_u16(0); // e_phnum
+    image.write_u16(SECTION_HEADER_SIZE as u16);
+    image.write_u16(sections.len() as u16);
+    image.write_u16(sections.len() as u16 - 1); // e_shstrndx
+
+    for (section, &offset) in sections.iter().zip(offsets.iter()) {
+        image.resize(offset, 0);
+        image.extend_from_slice(&section.data);
+    }
+    image.resize(section_headers_offset, 0);
+
+    for ((section, &offset), &name) in sections
+        .iter()
+        .zip(offsets.iter())
+        .zip(section_names.iter())
+    {
+        image.write_u32(name);
+        image.write_u32(section.ty);
+        image.write_u64(section.flags);
+        image.write_u64(section.addr);
+        image.write_u64(if section.ty == 0 { 0 } else { offset as u64 });
+        image.write_u64(section.size);
+        image.write_u32(section.link);
+        image.write_u32(section.info);
+        image.write_u64(section.align);
+        image.write_u64(section.entsize);
+    }
+
+    image
+}
+
+fn align(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+struct SectionHeader {
+    name: usize,
+    ty: u32,
+    offset: usize,
+    size: usize,
+    link: usize,
+}
+
+/// Read the section headers of a little-endian ELF64 file, and
+/// the index of the section holding the section names.
+fn section_headers(object: &[u8]) -> Option<(Vec<SectionHeader>, usize)> {
+    if object.get(..6)? != &[0x7f, b'E', b'L', b'F', 2, 1][..] {
+        return None;
+    }
+
+    let mut header = Reader::new(object);
+    header.skip(0x28)?;
+    let section_headers_offset = header.read_u64()? as usize;
+    header.skip(0x3a - 0x30)?;
+    let section_header_size = header.read_u16()? as usize;
+    let section_count = header.read_u16()? as usize;
+    let names_index = header.read_u16()? as usize;
+
+    let headers = (0..section_count)
+        .map(|index| {
+            let mut reader = Reader::new(object);
+            reader.skip(section_headers_offset.checked_add(index * section_header_size)?)?;
+            let name = reader.read_u32()? as usize;
+            let ty = reader.read_u32()?;
+            reader.skip(16)?;
+            let offset = reader.read_u64()? as usize;
+            let size = reader.read_u64()? as usize;
+            let link = reader.read_u32()? as usize;
+            Some(SectionHeader {
+                name,
+                ty,
+                offset,
+                size,
+                link,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((headers, names_index))
+}
+
+fn contents<'a>(object: &'a [u8], section: &SectionHeader) -> Option<&'a [u8]> {
+    object.get(section.offset..section.offset.checked_add(section.size)?)
+}
+
+fn string(strtab: &[u8], offset: usize) -> Option<&[u8]> {
+    Reader::new(strtab.get(offset..)?).read_cstr()
+}
+
+/// Read the size of every symbol defined in a little-endian ELF64
+/// object file, keyed by symbol name.
+///
+/// This is used to find the extent of functions in object files
+/// produced by other compilers. Returns `None` if `object` isn't such a file.
+pub fn symbol_sizes(object: &[u8]) -> Option<HashMap<String, u64>> {
+    let (sections, _) = section_headers(object)?;
+
+    let mut sizes = HashMap::new();
+    for section in sections.iter().filter(|section| section.ty == SHT_SYMTAB) {
+        let strtab = contents(object, sections.get(section.link)?)?;
+        let symbols = contents(object, section)?;
+
+        for symbol in symbols.chunks(SYMBOL_SIZE) {
+            let mut reader = Reader::new(symbol);
+            let name_offset = reader.read_u32()? as usize;
+            reader.skip(4)?;
+            reader.skip(8)?;
+            let symbol_size = reader.read_u64()?;
+
+            let name = string(strtab, name_offset)?;
+            if !name.is_empty() {
+                sizes.insert(String::from_utf8_lossy(name).into_owned(), symbol_size);
+            }
+        }
+    }
+
+    Some(sizes)
+}
+
+/// Find the contents of the section called `name` in a little-endian
+/// ELF64 file, either relocatable or linked.
+pub fn section_data<'a>(object: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let (sections, names_index) = section_headers(object)?;
+    let names = contents(object, sections.get(names_index)?)?;
+
+    sections
+        .iter()
+        .filter(|section| section.ty != SHT_NOBITS)
+        .find(|section| string(names, section.name) == Some(name.as_bytes()))
+        .and_then(|section| contents(object, section))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        section_data, symbol_sizes, write, Section, Symbol, SHF_ALLOC, SHT_PROGBITS, STT_FUNC,
+        STT_OBJECT,
+    };
+
+    #[test]
+    fn written_objects_can_be_read_back() {
+        let mut data = Section::new(".data.test", SHT_PROGBITS, vec![1, 2, 3, 4, 5]);
+        data.flags = SHF_ALLOC;
+        data.align = 16;
+        let sections = vec![Section::new(".text", SHT_PROGBITS, vec![0xc3; 0x68]), data];
+        let symbols = [
+            Symbol {
+                name: "add",
+                ty: STT_FUNC,
+                section: 0,
+                value: 0,
+                size: 0x20,
+            },
+            Symbol {
+                name: "wasm-function[1]",
+                ty: STT_FUNC,
+                section: 0,
+                value: 0x20,
+                size: 0x48,
+            },
+            Symbol {
+                name: "DATA",
+                ty: STT_OBJECT,
+                section: 1,
+                value: 0,
+                size: 5,
+            },
+        ];
+
+        let image = write(62, sections, &symbols);
+
+        let sizes = symbol_sizes(&image).unwrap();
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes["add"], 0x20);
+        assert_eq!(sizes["wasm-function[1]"], 0x48);
+        assert_eq!(sizes["DATA"], 5);
+
+        assert_eq!(
+            section_data(&image, ".data.test"),
+            Some(&[1, 2, 3, 4, 5][..])
+        );
+        assert_eq!(
+            section_data(&image, ".text").map(|text| text.len()),
+            Some(0x68)
+        );
+        assert_eq!(section_data(&image, ".data"), None);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(symbol_sizes(b"\0asm\x01\0\0\0").is_none());
+        assert!(section_data(b"\x7fELF", ".text").is_none());
+    }
+}
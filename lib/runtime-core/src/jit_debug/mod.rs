@@ -0,0 +1,455 @@
+//! Registration of compiled code with native debuggers.
+//!
+//! gdb and lldb both implement the [GDB JIT interface]: they put a
+//! breakpoint on `__jit_debug_register_code` and, when it is hit, read
+//! the in-memory object files linked from `__jit_debug_descriptor`.
+//!
+//! Backends describe the functions they compiled with [`FunctionDebugInfo`]
+//! and hand them to [`register`], which builds such an object file. It
+//! contains a symbol for every function (named after the `name` section
+//! when possible) and, if the module was built with DWARF debug info, a
+//! line table translated from wasm code offsets to native addresses, so
+//! breakpoints can be set on the original source lines.
+//!
+//! Line tables need the wasm offset of every generated instruction, which
+//! only the Cranelift backend keeps track of. The LLVM and single-pass
+//! backends register symbols only, so their code can be found by name in
+//! a debugger but not mapped back to source lines. Doing so would take
+//! LLVM debug locations carrying wasm offsets through code generation,
+//! and the single-pass backend recording the offset of every operator it
+//! emits; neither is done yet.
+//!
+//! [GDB JIT interface]: https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html
+//! [`FunctionDebugInfo`]: struct.FunctionDebugInfo.html
+//! [`register`]: fn.register.html
+
+mod dwarf;
+pub(crate) mod elf;
+
+pub use self::elf::symbol_sizes;
+
+use crate::{module::ModuleInfo, structures::TypedIndex, types::FuncIndex};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::ptr;
+
+/// Debug information about a single compiled function.
+#[derive(Debug, Clone)]
+pub struct FunctionDebugInfo {
+    /// The index of the function in the module.
+    pub func_index: FuncIndex,
+    /// The address of the first byte of native code.
+    pub address: usize,
+    /// The size of the native code, in bytes.
+    pub size: usize,
+    /// Native addresses and the offset in the wasm binary of the
+    /// operator they were generated for, sorted by native address.
+    /// This may be empty if the backend doesn't track source locations.
+    pub instructions: Vec<(usize, usize)>,
+}
+
+impl FunctionDebugInfo {
+    /// The name of the function, from the `name` section if it has one.
+    pub fn name(&self, info: &ModuleInfo) -> String {
+        match info.function_name(self.func_index) {
+            Some(name) => name.to_string(),
+            None => format!("wasm-function[{}]", self.func_index.index()),
+        }
+    }
+}
+
+/// Keeps compiled code registered with the debugger. The code is
+/// unregistered when this is dropped, so it must not outlive the code.
+pub struct JitDebugRegistration {
+    entry: *mut JitCodeEntry,
+    _image: Box<[u8]>,
+}
+
+unsafe impl Send for JitDebugRegistration {}
+unsafe impl Sync for JitDebugRegistration {}
+
+/// Register the functions of a module with any attached debugger.
+///
+/// `wasm` is the binary the functions were compiled from; its DWARF
+/// custom sections are read from `info.custom_sections`. Returns `None`
+/// if there's nothing to register or the target architecture isn't
+/// supported.
+pub fn register(
+    wasm: &[u8],
+    info: &ModuleInfo,
+    functions: &[FunctionDebugInfo],
+) -> Option<JitDebugRegistration> {
+    if functions.is_empty() {
+        return None;
+    }
+
+    let names: Vec<String> = functions
+        .iter()
+        .map(|function| function.name(info))
+        .collect();
+
+    let code_section_offset = code_section_offset(wasm);
+    let debug_section = |name: &str| {
+        info.custom_sections
+            .get(name)
+            .map(|sections| &sections[0][..])
+    };
+    let sections = dwarf::translate(
+        dwarf::WasmDwarf {
+            debug_info: debug_section(".debug_info"),
+            debug_abbrev: debug_section(".debug_abbrev"),
+            debug_line: debug_section(".debug_line"),
+            debug_str: debug_section(".debug_str"),
+        },
+        code_section_offset,
+        info.names.module.as_ref().map(|name| name.as_str()),
+        functions,
+        &names,
+    );
+
+    let image = write_image(functions, &names, sections)?;
+    Some(JitDebugRegistration::new(image))
+}
+
+/// Write a relocatable object describing `functions`.
+///
+/// `.text` is a `SHT_NOBITS` section whose address is the start of
+/// the compiled code, so the debugger reads instructions from memory.
+/// Symbol values are relative to it, while addresses in the DWARF
+/// `sections` are absolute. Returns `None` on unsupported architectures.
+fn write_image(
+    functions: &[FunctionDebugInfo],
+    names: &[String],
+    dwarf_sections: Vec<(&'static str, Vec<u8>)>,
+) -> Option<Vec<u8>> {
+    let machine = elf::machine(std::env::consts::ARCH)?;
+
+    let text_start = functions.iter().map(|f| f.address).min()?;
+    let text_end = functions.iter().map(|f| f.address + f.size).max()?;
+
+    let mut text = elf::Section::new(".text", elf::SHT_NOBITS, vec![]);
+    text.flags = elf::SHF_ALLOC | elf::SHF_EXECINSTR;
+    text.addr = text_start as u64;
+    text.size = (text_end - text_start) as u64;
+    text.align = 16;
+
+    let sections = Some(text)
+        .into_iter()
+        .chain(
+            dwarf_sections
+                .into_iter()
+                .map(|(name, data)| elf::Section::new(name, elf::SHT_PROGBITS, data)),
+        )
+        .collect();
+
+    let symbols: Vec<_> = functions
+        .iter()
+        .zip(names)
+        .map(|(function, name)| elf::Symbol {
+            name: name.as_str(),
+            ty: elf::STT_FUNC,
+            section: 0,
+            value: (function.address - text_start) as u64,
+            size: function.size as u64,
+        })
+        .collect();
+
+    Some(elf::write(machine, sections, &symbols))
+}
+
+/// The offset in `wasm` of the contents of the code section, which is
+/// what wasm DWARF uses as address zero.
+fn code_section_offset(wasm: &[u8]) -> Option<usize> {
+    let mut reader = wasmparser::ModuleReader::new(wasm).ok()?;
+    while !reader.eof() {
+        let section = reader.read().ok()?;
+        if let wasmparser::SectionCode::Code = section.code {
+            return Some(section.range().start);
+        }
+    }
+    None
+}
+
+#[repr(u32)]
+#[allow(dead_code)]
+enum JitAction {
+    NoAction = 0,
+    RegisterFn = 1,
+    UnregisterFn = 2,
+}
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[doc(hidden)]
+#[repr(C)]
+pub struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+/// Read by the debugger; the name and layout are fixed by the interface.
+#[doc(hidden)]
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JitAction::NoAction as u32,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The debugger puts a breakpoint on this function, so it must
+/// not be inlined or optimized away.
+#[doc(hidden)]
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    unsafe {
+        ptr::read_volatile(&__jit_debug_descriptor.action_flag);
+    }
+}
+
+lazy_static! {
+    /// Serializes updates of `__jit_debug_descriptor`.
+    static ref JIT_DEBUG_LOCK: Mutex<()> = Mutex::new(());
+}
+
+impl JitDebugRegistration {
+    fn new(image: Vec<u8>) -> Self {
+        let image = image.into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        }));
+
+        let _guard = JIT_DEBUG_LOCK.lock();
+        unsafe {
+            let descriptor = &mut __jit_debug_descriptor;
+            (*entry).next_entry = descriptor.first_entry;
+            if !descriptor.first_entry.is_null() {
+                (*descriptor.first_entry).prev_entry = entry;
+            }
+            descriptor.first_entry = entry;
+            descriptor.relevant_entry = entry;
+            descriptor.action_flag = JitAction::RegisterFn as u32;
+            __jit_debug_register_code();
+        }
+
+        Self {
+            entry,
+            _image: image,
+        }
+    }
+}
+
+impl Drop for JitDebugRegistration {
+    fn drop(&mut self) {
+        let _guard = JIT_DEBUG_LOCK.lock();
+        unsafe {
+            let descriptor = &mut __jit_debug_descriptor;
+            let entry = &mut *self.entry;
+            if entry.prev_entry.is_null() {
+                descriptor.first_entry = entry.next_entry;
+            } else {
+                (*entry.prev_entry).next_entry = entry.next_entry;
+            }
+            if !entry.next_entry.is_null() {
+                (*entry.next_entry).prev_entry = entry.prev_entry;
+            }
+            descriptor.relevant_entry = self.entry;
+            descriptor.action_flag = JitAction::UnregisterFn as u32;
+            __jit_debug_register_code();
+
+            descriptor.relevant_entry = ptr::null_mut();
+            descriptor.action_flag = JitAction::NoAction as u32;
+            drop(Box::from_raw(self.entry));
+        }
+    }
+}
+
+/// Little-endian encoding helpers shared by the ELF and DWARF writers.
+trait WriteBytes {
+    fn write_u8(&mut self, value: u8);
+    fn write_u16(&mut self, value: u16);
+    fn write_u32(&mut self, value: u32);
+    fn write_u64(&mut self, value: u64);
+    fn write_uleb128(&mut self, value: u64);
+    fn write_sleb128(&mut self, value: i64);
+    fn write_cstr(&mut self, value: &str);
+}
+
+impl WriteBytes for Vec<u8> {
+    fn write_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_uleb128(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.push(byte);
+                break;
+            }
+            self.push(byte | 0x80);
+        }
+    }
+
+    fn write_sleb128(&mut self, mut value: i64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+            if done {
+                self.push(byte);
+                break;
+            }
+            self.push(byte | 0x80);
+        }
+    }
+
+    fn write_cstr(&mut self, value: &str) {
+        self.extend_from_slice(value.as_bytes());
+        self.push(0);
+    }
+}
+
+/// A little-endian cursor over a byte slice. Every read returns
+/// `None` when the data runs out.
+#[derive(Clone)]
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn eof(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(len)?;
+        let bytes = self.data.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn read_u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from(bytes[0]) | u16::from(bytes[1]) << 8)
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.bytes(4)?;
+        Some((0..4).fold(0, |value, i| value | u32::from(bytes[i]) << (8 * i)))
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        let bytes = self.bytes(8)?;
+        Some((0..8).fold(0, |value, i| value | u64::from(bytes[i]) << (8 * i)))
+    }
+
+    fn read_uleb128(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift < 64 {
+                value |= u64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    }
+
+    fn read_sleb128(&mut self) -> Option<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift < 64 {
+                value |= i64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Some(value);
+            }
+        }
+    }
+
+    /// Reads a NUL-terminated string, without the terminator.
+    fn read_cstr(&mut self) -> Option<&'a [u8]> {
+        let rest = self.data.get(self.position..)?;
+        let len = rest.iter().position(|&byte| byte == 0)?;
+        self.position += len + 1;
+        Some(&rest[..len])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Reader, WriteBytes};
+
+    #[test]
+    fn leb128_round_trip() {
+        let unsigned = [0, 1, 127, 128, 624_485, u64::from(u32::max_value())];
+        let signed = [0, 1, -1, 63, -64, 64, -65, -123_456];
+
+        let mut buf = Vec::new();
+        for &value in unsigned.iter() {
+            buf.write_uleb128(value);
+        }
+        for &value in signed.iter() {
+            buf.write_sleb128(value);
+        }
+
+        let mut reader = Reader::new(&buf);
+        for &value in unsigned.iter() {
+            assert_eq!(reader.read_uleb128(), Some(value));
+        }
+        for &value in signed.iter() {
+            assert_eq!(reader.read_sleb128(), Some(value));
+        }
+        assert!(reader.eof());
+        assert_eq!(reader.read_uleb128(), None);
+    }
+}
@@ -9,6 +9,7 @@ extern crate serde_derive;
 
 #[macro_use]
 mod macros;
+pub mod aot;
 #[doc(hidden)]
 pub mod backend;
 mod backing;
@@ -19,8 +20,11 @@ pub mod export;
 pub mod global;
 pub mod import;
 pub mod instance;
+pub mod jit_debug;
+pub mod linker;
 pub mod memory;
 pub mod module;
+pub mod profiling;
 mod sig_registry;
 pub mod structures;
 mod sys;
@@ -32,9 +36,9 @@ pub mod vm;
 #[doc(hidden)]
 pub mod vmcalls;
 
-use self::error::CompileResult;
 #[doc(inline)]
 pub use self::error::Result;
+use self::error::{CompileError, CompileResult};
 #[doc(inline)]
 pub use self::import::IsExport;
 #[doc(inline)]
@@ -71,11 +75,7 @@ pub fn compile_with(
     let token = backend::Token::generate();
     compiler
         .compile(wasm, Default::default(), token)
-        .map(|mut inner| {
-            let inner_info: &mut crate::module::ModuleInfo = &mut inner.info;
-            inner_info.import_custom_sections(wasm).unwrap();
-            module::Module::new(Arc::new(inner))
-        })
+        .and_then(|inner| with_custom_sections(wasm, inner))
 }
 
 /// The same as `compile_with` but changes the compiler behavior
@@ -88,7 +88,21 @@ pub fn compile_with_config(
     let token = backend::Token::generate();
     compiler
         .compile(wasm, compiler_config, token)
-        .map(|inner| module::Module::new(Arc::new(inner)))
+        .and_then(|inner| with_custom_sections(wasm, inner))
+}
+
+/// The module compiled from `wasm`, with the custom sections of `wasm`.
+fn with_custom_sections(
+    wasm: &[u8],
+    mut inner: module::ModuleInner,
+) -> CompileResult<module::Module> {
+    inner
+        .info
+        .import_custom_sections(wasm)
+        .map_err(|e| CompileError::ValidationError {
+            msg: format!("malformed custom section: {:?}", e),
+        })?;
+    Ok(module::Module::new(Arc::new(inner)))
 }
 
 /// Perform validation as defined by the
@@ -122,5 +136,23 @@ pub unsafe fn load_cache_with(
         .map(|inner| module::Module::new(Arc::new(inner)))
 }
 
+/// Load a serialized module after making sure that it was signed
+/// with the counterpart of `key`, by this version of wasmer, and that
+/// it was compiled by `compiler`'s backend.
+///
+/// Unlike `load_cache_with`, this is safe: only whoever holds the
+/// signing key can produce code that gets loaded.
+pub fn load_verified_cache_with(
+    bytes: &[u8],
+    key: &cache::VerifyingKey,
+    compiler: &dyn backend::Compiler,
+) -> std::result::Result<module::Module, CacheError> {
+    let artifact = Artifact::deserialize_verified(bytes, key)?;
+    if artifact.info().backend != compiler.backend() {
+        return Err(CacheError::IncompatibleBackend(artifact.info().backend));
+    }
+    unsafe { load_cache_with(artifact, compiler) }
+}
+
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
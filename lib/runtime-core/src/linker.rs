@@ -0,0 +1,138 @@
+use crate::{
+    error::Result,
+    export::Export,
+    import::{ImportObject, LikeNamespace},
+    module::Module,
+    Instance,
+};
+use hashbrown::HashMap;
+use std::{any::Any, rc::Rc};
+
+/// A namespace that resolves imports against the exports
+/// of a live [`Instance`].
+///
+/// Every instance importing functions from the namespace holds on
+/// to the instance, so those functions stay valid even once the
+/// namespace, and the `ImportObject` or `Linker` it was in, are gone.
+///
+/// [`Instance`]: struct.Instance.html
+#[derive(Clone)]
+pub struct InstanceNamespace {
+    instance: Rc<Instance>,
+}
+
+impl InstanceNamespace {
+    pub fn new(instance: Rc<Instance>) -> Self {
+        Self { instance }
+    }
+
+    /// The instance this namespace resolves against.
+    pub fn instance(&self) -> &Rc<Instance> {
+        &self.instance
+    }
+}
+
+impl LikeNamespace for InstanceNamespace {
+    fn get_export(&self, name: &str) -> Option<Export> {
+        self.instance.get_export(name)
+    }
+
+    fn get_exports(&self) -> Vec<(String, Export)> {
+        self.instance.exports().collect()
+    }
+
+    fn maybe_insert(&mut self, _name: &str, _export: Export) -> Option<()> {
+        None
+    }
+
+    fn keepalive(&self) -> Option<Rc<dyn Any>> {
+        Some(Rc::clone(&self.instance) as Rc<dyn Any>)
+    }
+}
+
+/// Links modules together by registering instances under a module name.
+///
+/// Every registered instance becomes a namespace in the linker's
+/// [`ImportObject`], so a module instantiated afterwards can import
+/// anything the instance exports. Signature and descriptor mismatches
+/// are reported as [`LinkError`]s when instantiating.
+///
+/// [`ImportObject`]: struct.ImportObject.html
+/// [`LinkError`]: enum.LinkError.html
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::error::Result;
+/// # use wasmer_runtime_core::Module;
+/// # use wasmer_runtime_core::linker::Linker;
+/// fn link(lib: &Module, app: &Module) -> Result<()> {
+///     let mut linker = Linker::new();
+///
+///     let lib_instance = linker.instantiate(lib)?;
+///     linker.register("lib", lib_instance);
+///
+///     // `app` may now import anything that `lib` exports
+///     // from the "lib" namespace.
+///     let app_instance = linker.instantiate(app)?;
+///     // ...
+/// #   let _ = app_instance;
+/// #   Ok(())
+/// }
+/// ```
+pub struct Linker {
+    instances: HashMap<String, Rc<Instance>>,
+    import_object: ImportObject,
+}
+
+impl Linker {
+    /// Create a new `Linker` with no namespaces.
+    pub fn new() -> Self {
+        Self::with_imports(ImportObject::new())
+    }
+
+    /// Create a new `Linker` on top of an existing `ImportObject`,
+    /// e.g. one that already contains host functions.
+    pub fn with_imports(import_object: ImportObject) -> Self {
+        Self {
+            instances: HashMap::new(),
+            import_object,
+        }
+    }
+
+    /// Register `instance` under the module name `name`.
+    ///
+    /// This replaces any namespace previously registered under
+    /// the same name for modules instantiated afterwards; instances
+    /// already linked against the previous one keep using it.
+    /// A shared handle to the instance is returned so that it
+    /// can still be called by the embedder.
+    pub fn register<S>(&mut self, name: S, instance: Instance) -> Rc<Instance>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let instance = Rc::new(instance);
+
+        self.import_object
+            .register(name.clone(), InstanceNamespace::new(Rc::clone(&instance)));
+        self.instances.insert(name, Rc::clone(&instance));
+
+        instance
+    }
+
+    /// Get the instance registered under `name`, if any.
+    pub fn instance(&self, name: &str) -> Option<Rc<Instance>> {
+        self.instances.get(name).cloned()
+    }
+
+    /// The `ImportObject` containing every registered namespace.
+    pub fn import_object(&self) -> &ImportObject {
+        &self.import_object
+    }
+
+    /// Instantiate `module`, resolving its imports against
+    /// the registered instances.
+    pub fn instantiate(&self, module: &Module) -> Result<Instance> {
+        module.instantiate(&self.import_object)
+    }
+}
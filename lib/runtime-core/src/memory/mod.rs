@@ -323,5 +323,4 @@ mod memory_tests {
         .unwrap();
         assert_eq!(unshared_memory.size(), Pages(10));
     }
-
 }
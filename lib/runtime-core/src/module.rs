@@ -1,5 +1,5 @@
 use crate::{
-    backend::{Backend, RunnableModule},
+    backend::{Backend, CpuFeature, RunnableModule},
     cache::{Artifact, Error as CacheError},
     error,
     import::ImportObject,
@@ -7,8 +7,8 @@ use crate::{
     types::{
         FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit, ImportedFuncIndex,
         ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex, Initializer,
-        LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryDescriptor, MemoryIndex,
-        SigIndex, TableDescriptor, TableIndex,
+        LocalGlobalIndex, LocalMemoryIndex, LocalOrImport, LocalTableIndex, MemoryDescriptor,
+        MemoryIndex, SigIndex, TableDescriptor, TableIndex,
     },
     Instance,
 };
@@ -50,6 +50,10 @@ pub struct ModuleInfo {
     pub func_assoc: Map<FuncIndex, SigIndex>,
     pub signatures: Map<SigIndex, FuncSig>,
     pub backend: Backend,
+    /// The target triple the code was compiled for, if not the host.
+    pub triple: Option<String>,
+    /// The CPU features the compiled code may use.
+    pub cpu_features: Vec<CpuFeature>,
 
     pub namespace_table: StringTable<NamespaceIndex>,
     pub name_table: StringTable<NameIndex>,
@@ -57,11 +61,28 @@ pub struct ModuleInfo {
     /// Symbol information from emscripten
     pub em_symbol_map: Option<HashMap<u32, String>>,
 
-    pub custom_sections: HashMap<String, Vec<u8>>,
+    /// The contents of every custom section, grouped by section name
+    /// in the order they appear in the module.
+    pub custom_sections: HashMap<String, Vec<Vec<u8>>>,
+
+    /// Names parsed from the `name` custom section.
+    pub names: Names,
 }
 
 impl ModuleInfo {
+    /// Whether the compiled code is for the architecture of this machine,
+    /// so that it can be run here.
+    pub fn is_for_host_arch(&self) -> bool {
+        match &self.triple {
+            Some(triple) => triple.split('-').next() == Some(std::env::consts::ARCH),
+            None => true,
+        }
+    }
+
+    /// Reads every custom section of `wasm` into `custom_sections`
+    /// and parses the `name` section, if there is one.
     pub fn import_custom_sections(&mut self, wasm: &[u8]) -> crate::error::ParseResult<()> {
+        self.custom_sections.clear();
         let mut parser = wasmparser::ModuleReader::new(wasm)?;
         while !parser.eof() {
             let section = parser.read()?;
@@ -71,11 +92,74 @@ impl ModuleInfo {
                 let bytes = reader.read_bytes(len)?;
                 let data = bytes.to_vec();
                 let name = name.to_string();
-                self.custom_sections.insert(name, data);
+                self.custom_sections
+                    .entry(name)
+                    .or_insert_with(Vec::new)
+                    .push(data);
             }
         }
+
+        // A malformed `name` section doesn't invalidate the module,
+        // so it is ignored rather than reported.
+        self.names = self
+            .custom_sections
+            .get("name")
+            .and_then(|sections| Names::parse(&sections[0]).ok())
+            .unwrap_or_default();
+
         Ok(())
     }
+
+    /// The name of a function, taken from the `name` section or,
+    /// failing that, from the emscripten symbol map.
+    pub fn function_name(&self, func_index: FuncIndex) -> Option<&str> {
+        self.names
+            .functions
+            .get(&func_index)
+            .or_else(|| {
+                self.em_symbol_map
+                    .as_ref()
+                    .and_then(|symbol_map| symbol_map.get(&(func_index.index() as u32)))
+            })
+            .map(|name| name.as_str())
+    }
+
+    fn import_name(&self, import_name: &ImportName) -> (String, String) {
+        let namespace = self.namespace_table.get(import_name.namespace_index);
+        let name = self.name_table.get(import_name.name_index);
+        (namespace.to_string(), name.to_string())
+    }
+
+    fn func_signature(&self, func_index: FuncIndex) -> FuncSig {
+        let sig_index = self.func_assoc[func_index];
+        self.signatures[sig_index].clone()
+    }
+
+    fn extern_descriptor(&self, export_index: ExportIndex) -> ExternDescriptor {
+        match export_index {
+            ExportIndex::Func(func_index) => {
+                ExternDescriptor::Function(self.func_signature(func_index))
+            }
+            ExportIndex::Memory(memory_index) => {
+                ExternDescriptor::Memory(match memory_index.local_or_import(self) {
+                    LocalOrImport::Local(local_index) => self.memories[local_index],
+                    LocalOrImport::Import(import_index) => self.imported_memories[import_index].1,
+                })
+            }
+            ExportIndex::Table(table_index) => {
+                ExternDescriptor::Table(match table_index.local_or_import(self) {
+                    LocalOrImport::Local(local_index) => self.tables[local_index],
+                    LocalOrImport::Import(import_index) => self.imported_tables[import_index].1,
+                })
+            }
+            ExportIndex::Global(global_index) => {
+                ExternDescriptor::Global(match global_index.local_or_import(self) {
+                    LocalOrImport::Local(local_index) => self.globals[local_index].desc,
+                    LocalOrImport::Import(import_index) => self.imported_globals[import_index].1,
+                })
+            }
+        }
+    }
 }
 
 /// A compiled WebAssembly module.
@@ -128,6 +212,121 @@ impl Module {
     pub fn info(&self) -> &ModuleInfo {
         &self.inner.info
     }
+
+    /// The name of this module, as given by the `name` section.
+    pub fn name(&self) -> Option<&str> {
+        self.inner
+            .info
+            .names
+            .module
+            .as_ref()
+            .map(|name| name.as_str())
+    }
+
+    /// The name of the function at `func_index`, as given by the
+    /// `name` section or the emscripten symbol map.
+    pub fn function_name(&self, func_index: FuncIndex) -> Option<&str> {
+        self.inner.info.function_name(func_index)
+    }
+
+    /// The name of local `local_index` of the function at `func_index`,
+    /// as given by the `name` section. Parameters count as locals.
+    pub fn local_name(&self, func_index: FuncIndex, local_index: u32) -> Option<&str> {
+        self.inner
+            .info
+            .names
+            .locals
+            .get(&func_index)
+            .and_then(|locals| locals.get(&local_index))
+            .map(|name| name.as_str())
+    }
+
+    /// Returns a descriptor for every import of this module.
+    ///
+    /// Imports are listed by kind (functions, memories, tables, then globals)
+    /// and, within a kind, in the order they are declared in the module.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::module::ExternDescriptor;
+    /// fn print_imports(module: &Module) {
+    ///     for import in module.imports() {
+    ///         if let ExternDescriptor::Function(signature) = import.ty {
+    ///             println!("{}.{}: {}", import.namespace, import.name, signature);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn imports(&self) -> Vec<ImportDescriptor> {
+        let info = &self.inner.info;
+        let mut imports = Vec::with_capacity(
+            info.imported_functions.len()
+                + info.imported_memories.len()
+                + info.imported_tables.len()
+                + info.imported_globals.len(),
+        );
+
+        for (index, import_name) in &info.imported_functions {
+            let (namespace, name) = info.import_name(import_name);
+            imports.push(ImportDescriptor {
+                namespace,
+                name,
+                ty: ExternDescriptor::Function(info.func_signature(index.convert_up(info))),
+            });
+        }
+        for (_, (import_name, desc)) in &info.imported_memories {
+            let (namespace, name) = info.import_name(import_name);
+            imports.push(ImportDescriptor {
+                namespace,
+                name,
+                ty: ExternDescriptor::Memory(*desc),
+            });
+        }
+        for (_, (import_name, desc)) in &info.imported_tables {
+            let (namespace, name) = info.import_name(import_name);
+            imports.push(ImportDescriptor {
+                namespace,
+                name,
+                ty: ExternDescriptor::Table(*desc),
+            });
+        }
+        for (_, (import_name, desc)) in &info.imported_globals {
+            let (namespace, name) = info.import_name(import_name);
+            imports.push(ImportDescriptor {
+                namespace,
+                name,
+                ty: ExternDescriptor::Global(*desc),
+            });
+        }
+
+        imports
+    }
+
+    /// Returns a descriptor for every export of this module, sorted by name.
+    pub fn exports(&self) -> Vec<ExportDescriptor> {
+        let info = &self.inner.info;
+        let mut exports: Vec<_> = info
+            .exports
+            .iter()
+            .map(|(name, export_index)| ExportDescriptor {
+                name: name.clone(),
+                ty: info.extern_descriptor(*export_index),
+            })
+            .collect();
+        exports.sort_by(|a, b| a.name.cmp(&b.name));
+        exports
+    }
+
+    /// Returns the contents of every custom section named `name`,
+    /// in the order they appear in the module.
+    pub fn custom_sections(&self, name: &str) -> Option<&[Vec<u8>]> {
+        self.inner
+            .info
+            .custom_sections
+            .get(name)
+            .map(|sections| sections.as_slice())
+    }
 }
 
 impl Clone for Module {
@@ -155,6 +354,117 @@ pub enum ExportIndex {
     Table(TableIndex),
 }
 
+/// Module, function and local names from the `name` custom section.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Names {
+    /// The name of the module itself.
+    pub module: Option<String>,
+    /// Function names, indexed by function index.
+    pub functions: HashMap<FuncIndex, String>,
+    /// Local names, indexed by function index and then local index.
+    pub locals: HashMap<FuncIndex, HashMap<u32, String>>,
+}
+
+impl Names {
+    const MODULE_SUBSECTION: u32 = 0;
+    const FUNCTION_SUBSECTION: u32 = 1;
+    const LOCAL_SUBSECTION: u32 = 2;
+
+    /// Parse the contents of a `name` custom section.
+    ///
+    /// Unknown subsections are skipped, as the spec requires.
+    pub fn parse(data: &[u8]) -> crate::error::ParseResult<Self> {
+        let mut names = Names::default();
+        let mut reader = wasmparser::BinaryReader::new(data);
+
+        while !reader.eof() {
+            let id = reader.read_u8()?;
+            let size = reader.read_var_u32()? as usize;
+            let payload = reader.read_bytes(size)?;
+            let mut subsection = wasmparser::BinaryReader::new(payload);
+
+            match id {
+                Self::MODULE_SUBSECTION => {
+                    names.module = Some(subsection.read_string()?.to_string());
+                }
+                Self::FUNCTION_SUBSECTION => {
+                    for _ in 0..subsection.read_var_u32()? {
+                        let index = FuncIndex::new(subsection.read_var_u32()? as usize);
+                        let name = subsection.read_string()?.to_string();
+                        names.functions.insert(index, name);
+                    }
+                }
+                Self::LOCAL_SUBSECTION => {
+                    for _ in 0..subsection.read_var_u32()? {
+                        let index = FuncIndex::new(subsection.read_var_u32()? as usize);
+                        let mut locals = HashMap::new();
+                        for _ in 0..subsection.read_var_u32()? {
+                            let local_index = subsection.read_var_u32()?;
+                            let name = subsection.read_string()?.to_string();
+                            locals.insert(local_index, name);
+                        }
+                        names.locals.insert(index, locals);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+/// The kind of an import or export, along with the
+/// signature or limits that describe it.
+#[derive(Debug, Clone)]
+pub enum ExternDescriptor {
+    /// A function and its signature.
+    Function(FuncSig),
+    /// A linear memory and its limits.
+    Memory(MemoryDescriptor),
+    /// A table and its limits.
+    Table(TableDescriptor),
+    /// A global, its type and mutability.
+    Global(GlobalDescriptor),
+}
+
+impl ExternDescriptor {
+    /// The name of this kind of import or export,
+    /// e.g. `"function"` or `"memory"`.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ExternDescriptor::Function(_) => "function",
+            ExternDescriptor::Memory(_) => "memory",
+            ExternDescriptor::Table(_) => "table",
+            ExternDescriptor::Global(_) => "global",
+        }
+    }
+}
+
+/// Describes a single import of a [`Module`].
+///
+/// [`Module`]: struct.Module.html
+#[derive(Debug, Clone)]
+pub struct ImportDescriptor {
+    /// The namespace (module name) the import is resolved from.
+    pub namespace: String,
+    /// The name of the import within its namespace.
+    pub name: String,
+    /// What is imported.
+    pub ty: ExternDescriptor,
+}
+
+/// Describes a single export of a [`Module`].
+///
+/// [`Module`]: struct.Module.html
+#[derive(Debug, Clone)]
+pub struct ExportDescriptor {
+    /// The name the item is exported under.
+    pub name: String,
+    /// What is exported.
+    pub ty: ExternDescriptor,
+}
+
 /// A data initializer for linear memory.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DataInitializer {
@@ -281,3 +591,38 @@ impl TypedIndex for NameIndex {
         self.0 as usize
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Names;
+    use crate::structures::TypedIndex;
+    use crate::types::FuncIndex;
+
+    #[test]
+    fn parse_name_section() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            // module name: "m"
+            0x00, 0x02, 0x01, b'm',
+            // function names: 1 => "add"
+            0x01, 0x06, 0x01, 0x01, 0x03, b'a', b'd', b'd',
+            // local names: function 1, local 0 => "lhs"
+            0x02, 0x08, 0x01, 0x01, 0x01, 0x00, 0x03, b'l', b'h', b's',
+            // unknown subsection, skipped
+            0x07, 0x01, 0xff,
+        ];
+
+        let names = Names::parse(data).unwrap();
+        let add = FuncIndex::new(1);
+
+        assert_eq!(names.module.as_ref().map(|s| s.as_str()), Some("m"));
+        assert_eq!(names.functions[&add], "add");
+        assert_eq!(names.locals[&add][&0], "lhs");
+    }
+
+    #[test]
+    fn parse_truncated_name_section() {
+        let data: &[u8] = &[0x01, 0x06, 0x01, 0x01, 0x03, b'a'];
+        assert!(Names::parse(data).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! Reporting compiled functions to `perf`.
+//!
+//! `perf` can't symbolize code that isn't backed by a file, so JIT
+//! frames show up as raw addresses. It supports two ways for a JIT to
+//! describe its code:
+//!
+//! - A perf map, `/tmp/perf-PID.map`, with one `START SIZE NAME` line
+//!   per function. `perf report` reads it directly.
+//! - A [jitdump] file, `/tmp/jit-PID.dump`, which also holds a copy of
+//!   the code so that `perf annotate` works. Record with `perf record -k
+//!   mono` and run `perf inject --jit` on the result before reporting.
+//!
+//! Functions are named after the `name` section when it's present.
+//!
+//! [jitdump]: https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jitdump-specification.txt
+
+use crate::{jit_debug::FunctionDebugInfo, module::ModuleInfo};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    process, slice,
+};
+
+/// How compiled functions are reported to `perf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfilingStrategy {
+    /// Append to `/tmp/perf-PID.map`.
+    PerfMap,
+    /// Write records to `/tmp/jit-PID.dump`.
+    JitDump,
+}
+
+impl std::str::FromStr for ProfilingStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "perfmap" => Ok(ProfilingStrategy::PerfMap),
+            "jitdump" => Ok(ProfilingStrategy::JitDump),
+            _ => Err(format!("The profiling strategy {} doesn't exist", s)),
+        }
+    }
+}
+
+/// Report `functions`, compiled from the module described by
+/// `info`, with the given strategy.
+///
+/// The code of every function must be readable, since jitdump
+/// records contain a copy of it.
+pub fn register(
+    strategy: ProfilingStrategy,
+    info: &ModuleInfo,
+    functions: &[FunctionDebugInfo],
+) -> io::Result<()> {
+    match strategy {
+        ProfilingStrategy::PerfMap => {
+            let mut perf_map = PERF_MAP.lock();
+            if perf_map.is_none() {
+                let path = format!("/tmp/perf-{}.map", process::id());
+                *perf_map = Some(OpenOptions::new().create(true).append(true).open(path)?);
+            }
+            let file = perf_map.as_mut().unwrap();
+
+            let map: String = functions
+                .iter()
+                .map(|function| perf_map_line(function, &function.name(info)))
+                .collect();
+            file.write_all(map.as_bytes())
+        }
+        ProfilingStrategy::JitDump => {
+            let mut jitdump = JITDUMP.lock();
+            if jitdump.is_none() {
+                *jitdump = Some(JitDumpFile::create()?);
+            }
+            let jitdump = jitdump.as_mut().unwrap();
+
+            for function in functions {
+                jitdump.write_code_load(function, &function.name(info))?;
+            }
+            jitdump.file.flush()
+        }
+    }
+}
+
+lazy_static! {
+    static ref PERF_MAP: Mutex<Option<File>> = Mutex::new(None);
+    static ref JITDUMP: Mutex<Option<JitDumpFile>> = Mutex::new(None);
+}
+
+const JITDUMP_MAGIC: u32 = 0x4A69_5444;
+const JITDUMP_VERSION: u32 = 1;
+const JITDUMP_HEADER_SIZE: u32 = 40;
+const JIT_CODE_LOAD: u32 = 0;
+
+#[cfg(target_arch = "x86_64")]
+const ELF_MACHINE: u32 = 62; // EM_X86_64
+#[cfg(target_arch = "aarch64")]
+const ELF_MACHINE: u32 = 183; // EM_AARCH64
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const ELF_MACHINE: u32 = 0; // EM_NONE
+
+fn perf_map_line(function: &FunctionDebugInfo, name: &str) -> String {
+    format!("{:x} {:x} {}\n", function.address, function.size, name)
+}
+
+struct JitDumpFile {
+    file: File,
+    code_index: u64,
+}
+
+impl JitDumpFile {
+    fn create() -> io::Result<Self> {
+        let path = format!("/tmp/jit-{}.dump", process::id());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        // `perf record` notices jitdump files through an executable mapping.
+        // The mapping has to stay alive until the process exits, so it's leaked.
+        #[cfg(unix)]
+        unsafe {
+            use std::os::unix::io::AsRawFd;
+
+            let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+            let marker = libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            );
+            if marker == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        file.write_all(&jitdump_header())?;
+
+        Ok(Self {
+            file,
+            code_index: 0,
+        })
+    }
+
+    fn write_code_load(&mut self, function: &FunctionDebugInfo, name: &str) -> io::Result<()> {
+        let record = code_load_record(function, name, self.code_index);
+        self.code_index += 1;
+        self.file.write_all(&record)
+    }
+}
+
+fn jitdump_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(JITDUMP_HEADER_SIZE as usize);
+    header.extend_from_slice(&JITDUMP_MAGIC.to_le_bytes());
+    header.extend_from_slice(&JITDUMP_VERSION.to_le_bytes());
+    header.extend_from_slice(&JITDUMP_HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&ELF_MACHINE.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes()); // pad1
+    header.extend_from_slice(&process::id().to_le_bytes());
+    header.extend_from_slice(&timestamp().to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes()); // flags
+    header
+}
+
+fn code_load_record(function: &FunctionDebugInfo, name: &str, code_index: u64) -> Vec<u8> {
+    let code = unsafe { slice::from_raw_parts(function.address as *const u8, function.size) };
+
+    // record header + pid, tid, vma, code_addr, code_size, code_index
+    let fixed_size = 16 + 4 + 4 + 8 * 4;
+    let total_size = fixed_size + name.len() + 1 + code.len();
+
+    let mut record = Vec::with_capacity(total_size);
+    record.extend_from_slice(&JIT_CODE_LOAD.to_le_bytes());
+    record.extend_from_slice(&(total_size as u32).to_le_bytes());
+    record.extend_from_slice(&timestamp().to_le_bytes());
+    record.extend_from_slice(&process::id().to_le_bytes());
+    record.extend_from_slice(&thread_id().to_le_bytes());
+    record.extend_from_slice(&(function.address as u64).to_le_bytes());
+    record.extend_from_slice(&(function.address as u64).to_le_bytes());
+    record.extend_from_slice(&(function.size as u64).to_le_bytes());
+    record.extend_from_slice(&code_index.to_le_bytes());
+    record.extend_from_slice(name.as_bytes());
+    record.push(0);
+    record.extend_from_slice(code);
+    record
+}
+
+/// The timestamp of jitdump records, which perf expects
+/// to come from the monotonic clock.
+#[cfg(unix)]
+fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(not(unix))]
+fn timestamp() -> u64 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+fn thread_id() -> u32 {
+    unsafe { libc::syscall(libc::SYS_gettid) as u32 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_id() -> u32 {
+    process::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{structures::TypedIndex, types::FuncIndex};
+    use std::convert::TryInto;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn function(code: &[u8]) -> FunctionDebugInfo {
+        FunctionDebugInfo {
+            func_index: FuncIndex::new(3),
+            address: code.as_ptr() as usize,
+            size: code.len(),
+            instructions: vec![],
+        }
+    }
+
+    #[test]
+    fn jitdump_header_layout() {
+        let header = jitdump_header();
+        assert_eq!(header.len(), JITDUMP_HEADER_SIZE as usize);
+        assert_eq!(&header[0..4], b"DTiJ");
+        assert_eq!(u32_at(&header, 4), JITDUMP_VERSION);
+        assert_eq!(u32_at(&header, 8), JITDUMP_HEADER_SIZE);
+        assert_eq!(u32_at(&header, 12), ELF_MACHINE);
+        assert_eq!(u32_at(&header, 16), 0);
+        assert_eq!(u32_at(&header, 20), process::id());
+        assert_eq!(u64_at(&header, 32), 0);
+    }
+
+    #[test]
+    fn code_load_record_layout() {
+        let code = [0x55, 0x48, 0x89, 0xe5, 0xc3];
+        let function = function(&code);
+        let record = code_load_record(&function, "add", 7);
+
+        assert_eq!(record.len(), 56 + 4 + code.len());
+        assert_eq!(u32_at(&record, 0), JIT_CODE_LOAD);
+        assert_eq!(u32_at(&record, 4), record.len() as u32);
+        assert_eq!(u32_at(&record, 16), process::id());
+        assert_eq!(u64_at(&record, 24), function.address as u64);
+        assert_eq!(u64_at(&record, 32), function.address as u64);
+        assert_eq!(u64_at(&record, 40), code.len() as u64);
+        assert_eq!(u64_at(&record, 48), 7);
+        assert_eq!(&record[56..60], b"add\0");
+        assert_eq!(&record[60..], &code);
+    }
+
+    #[test]
+    fn perf_map_line_format() {
+        let code = [0xc3; 0x2a];
+        let function = function(&code);
+        assert_eq!(
+            perf_map_line(&function, "add"),
+            format!("{:x} 2a add\n", function.address)
+        );
+    }
+}
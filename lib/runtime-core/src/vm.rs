@@ -3,7 +3,7 @@ use crate::{
     memory::Memory,
     module::ModuleInner,
     structures::TypedIndex,
-    types::{LocalOrImport, MemoryIndex},
+    types::{FuncIndex, LocalOrImport, MemoryIndex},
 };
 use std::{ffi::c_void, mem, ptr};
 
@@ -163,6 +163,14 @@ impl Ctx {
     pub unsafe fn borrow_symbol_map(&self) -> &Option<HashMap<u32, String>> {
         &(*self.module).info.em_symbol_map
     }
+
+    /// Gives access to the name of a function, taken from the `name` section
+    /// or the emscripten symbol map, used for debugging
+    pub unsafe fn borrow_function_name(&self, func_index: u32) -> Option<&str> {
+        (*self.module)
+            .info
+            .function_name(FuncIndex::new(func_index as usize))
+    }
 }
 
 #[doc(hidden)]
@@ -518,6 +526,8 @@ mod vm_ctx_tests {
             vm_memories: Map::new().into_boxed_map(),
             vm_tables: Map::new().into_boxed_map(),
             vm_globals: Map::new().into_boxed_map(),
+
+            keepalives: Vec::new(),
         };
         let module = generate_module();
         let data = &mut data as *mut _ as *mut c_void;
@@ -537,6 +547,16 @@ mod vm_ctx_tests {
         drop(ctx);
     }
 
+    #[test]
+    fn code_for_other_architectures_isnt_instantiated() {
+        use crate::{import::ImportObject, instance::Instance};
+        use std::sync::Arc;
+
+        let mut module = generate_module();
+        module.info.triple = Some("wasm32-unknown-unknown".to_string());
+        assert!(Instance::new(Arc::new(module), &ImportObject::new()).is_err());
+    }
+
     fn cast_test_data(data: *mut c_void) -> &'static mut TestData {
         let test_data: &mut TestData = unsafe { &mut *(data as *mut TestData) };
         test_data
@@ -601,6 +621,8 @@ mod vm_ctx_tests {
                 func_assoc: Map::new(),
                 signatures: Map::new(),
                 backend: Backend::Cranelift,
+                triple: None,
+                cpu_features: vec![],
 
                 namespace_table: StringTable::new(),
                 name_table: StringTable::new(),
@@ -608,6 +630,8 @@ mod vm_ctx_tests {
                 em_symbol_map: None,
 
                 custom_sections: HashMap::new(),
+
+                names: Default::default(),
             },
         }
     }
@@ -32,7 +32,7 @@ fn compile_benchmark(c: &mut Criterion) {
 fn load_benchmark(c: &mut Criterion) {
     c.bench_function("nginx load", |b| {
         let tempdir = tempdir().unwrap();
-        let mut cache = unsafe {
+        let cache = unsafe {
             FileSystemCache::new(tempdir.path()).expect("unable to create file system cache")
         };
         let module = compile(NGINX_WASM).unwrap();
@@ -0,0 +1,169 @@
+//! Compile modules ahead of time with Cranelift, and load them
+//! without a compiler.
+//!
+//! Only the Cranelift backend can compile ahead of time: the LLVM and
+//! single-pass backends can't serialize what they compile.
+//!
+//! [`compile_object`] turns a wasm module into a relocatable ELF
+//! object. It isn't native code the system linker can call into: the
+//! object holds the serialized Cranelift artifact, precompiled code
+//! and all, in a `.wasmer_artifact` section, under a global symbol.
+//! It can be written to disk and loaded with [`load_object`], linked
+//! into a shared object with [`link_shared_object`], which runs
+//! `$CC -shared`, or linked straight into a program and loaded with
+//! [`load_linked`].
+//!
+//! Loading relocates the code with the Cranelift backend's loader, so
+//! the `wasmer-clif-backend` crate is always linked in. Programs that
+//! never compile can be built without the `default-compiler` feature
+//! and with `aot-loader` instead, which leaves Cranelift's compiler,
+//! but not the loader, out:
+//!
+//! ```toml
+//! [dependencies]
+//! wasmer-runtime = { version = "0.3", default-features = false, features = ["aot-loader"] }
+//! ```
+//!
+//! Objects are only loadable on the architecture and with the version
+//! of wasmer that produced them.
+//!
+//! [`compile_object`]: fn.compile_object.html
+//! [`load_object`]: fn.load_object.html
+//! [`link_shared_object`]: fn.link_shared_object.html
+//! [`load_linked`]: fn.load_linked.html
+
+use crate::Module;
+use std::{env, fmt, io, path::Path, process::Command};
+use wasmer_runtime_core::{
+    aot,
+    backend::{Backend, Compiler, CompilerConfig},
+    cache::{Artifact, Error as CacheError},
+    error::CompileError,
+};
+
+pub use wasmer_runtime_core::aot::{ARTIFACT_SECTION, DEFAULT_SYMBOL};
+
+/// An error that occurs while compiling a module ahead of time.
+#[derive(Debug)]
+pub enum AotError {
+    CompileError(CompileError),
+    CacheError(CacheError),
+    IoError(io::Error),
+    LinkerError(String),
+}
+
+impl From<CompileError> for AotError {
+    fn from(compile_err: CompileError) -> Self {
+        AotError::CompileError(compile_err)
+    }
+}
+
+impl From<CacheError> for AotError {
+    fn from(cache_err: CacheError) -> Self {
+        AotError::CacheError(cache_err)
+    }
+}
+
+impl From<io::Error> for AotError {
+    fn from(io_err: io::Error) -> Self {
+        AotError::IoError(io_err)
+    }
+}
+
+impl fmt::Display for AotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AotError::CompileError(err) => write!(f, "compile error: {}", err),
+            AotError::CacheError(err) => write!(f, "serialization error: {:?}", err),
+            AotError::IoError(err) => write!(f, "io error: {}", err),
+            AotError::LinkerError(msg) => write!(f, "linker error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AotError {}
+
+/// Compile `wasm` with Cranelift into a relocatable object for the
+/// target of `compiler_config`, with the serialized module at `symbol`.
+///
+/// This fails if the default compiler isn't Cranelift, as when the
+/// `llvm` or `singlepass` feature is enabled.
+pub fn compile_object(
+    wasm: &[u8],
+    compiler_config: CompilerConfig,
+    symbol: &str,
+) -> Result<Vec<u8>, AotError> {
+    let module = crate::compile_with_config(wasm, compiler_config)?;
+    let artifact = module.cache()?;
+    Ok(aot::write_object(&artifact, symbol)?)
+}
+
+/// Link an object from `compile_object` into a shared object at
+/// `output`, with the C compiler from `$CC`, or `cc`.
+pub fn link_shared_object(object: &Path, output: &Path) -> Result<(), AotError> {
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let result = Command::new(&cc)
+        .arg("-shared")
+        .arg("-o")
+        .arg(output)
+        .arg(object)
+        .output()?;
+
+    if result.status.success() {
+        Ok(())
+    } else {
+        Err(AotError::LinkerError(format!(
+            "`{}` failed with {}: {}",
+            cc,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )))
+    }
+}
+
+/// Load a module from the contents of an object or shared
+/// object built with `compile_object`.
+///
+/// # Safety
+///
+/// The compiled code in `object` is run as is, so it
+/// must come from a trusted source.
+pub unsafe fn load_object(object: &[u8]) -> Result<Module, CacheError> {
+    load(aot::read_object(object)?)
+}
+
+/// Load a module from an object built with `compile_object`
+/// and linked into this program.
+///
+/// ```ignore
+/// extern "C" {
+///     static WASMER_MODULE: u8;
+/// }
+///
+/// let module = unsafe { wasmer_runtime::aot::load_linked(&WASMER_MODULE)? };
+/// ```
+///
+/// # Safety
+///
+/// `start` must be the address of the symbol passed to `compile_object`.
+pub unsafe fn load_linked(start: *const u8) -> Result<Module, CacheError> {
+    load(aot::read_linked(start)?)
+}
+
+unsafe fn load(artifact: Artifact) -> Result<Module, CacheError> {
+    use lazy_static::lazy_static;
+    use wasmer_clif_backend::CraneliftCompiler;
+
+    lazy_static! {
+        static ref LOADER: CraneliftCompiler = { CraneliftCompiler::new() };
+    }
+
+    if artifact.info().backend != Backend::Cranelift {
+        return Err(CacheError::Unknown(format!(
+            "modules compiled with {:?} can't be loaded ahead of time",
+            artifact.info().backend
+        )));
+    }
+
+    wasmer_runtime_core::load_cache_with(artifact, &*LOADER as &dyn Compiler)
+}
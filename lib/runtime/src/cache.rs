@@ -1,13 +1,27 @@
 use crate::Module;
+use filetime::FileTime;
+use fs2::FileExt;
 use memmap::Mmap;
 use std::{
-    fs::{create_dir_all, File},
+    collections::HashMap,
+    fs::{self, create_dir_all, File, OpenOptions},
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
 };
 
-use wasmer_runtime_core::cache::Error as CacheError;
-pub use wasmer_runtime_core::cache::{Artifact, Cache, WasmHash, WASMER_VERSION_HASH};
+pub use wasmer_runtime_core::cache::{
+    Artifact, Cache, SigningKey, VerifyingKey, WasmHash, WASMER_VERSION_HASH,
+};
+use wasmer_runtime_core::{
+    backend::{Backend, Compiler},
+    cache::Error as CacheError,
+};
 
 /// Representation of a directory that contains compiled wasm artifacts.
 ///
@@ -26,7 +40,7 @@ pub use wasmer_runtime_core::cache::{Artifact, Cache, WasmHash, WASMER_VERSION_H
 ///     // Create a new file system cache.
 ///     // This is unsafe because we can't ensure that the artifact wasn't
 ///     // corrupted or tampered with.
-///     let mut fs_cache = unsafe { FileSystemCache::new("some/directory/goes/here")? };
+///     let fs_cache = unsafe { FileSystemCache::new("some/directory/goes/here")? };
 ///     // Compute a key for a given WebAssembly binary
 ///     let key = WasmHash::generate(&[]);
 ///     // Store a module into the cache given a key
@@ -34,10 +48,43 @@ pub use wasmer_runtime_core::cache::{Artifact, Cache, WasmHash, WASMER_VERSION_H
 ///     Ok(module)
 /// }
 /// ```
+///
+/// Several processes can share a cache directory: artifacts are
+/// written to a temporary file and renamed into place, and a lock file
+/// keeps evictions from racing with other stores. Unless it's given a
+/// maximum size with [`with_max_size`], the cache grows forever.
+///
+/// [`with_max_size`]: struct.FileSystemCache.html#method.with_max_size
 pub struct FileSystemCache {
     path: PathBuf,
+    /// Signs stored artifacts, and is required to verify loaded ones.
+    key: Option<SigningKey>,
+    /// The size, in bytes, the artifacts are pruned to after each store.
+    max_size: Option<u64>,
 }
 
+/// An artifact stored in a `FileSystemCache`.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// The encoded key the artifact is stored under.
+    pub key: String,
+    /// The size of the artifact, in bytes.
+    pub size: u64,
+    /// The backend that compiled the artifact, or `None` if this
+    /// version of wasmer can't read it. Entries returned by `prune`
+    /// aren't read, and don't have a backend.
+    pub backend: Option<Backend>,
+    /// When the artifact was last stored or loaded.
+    pub last_used: SystemTime,
+}
+
+/// The file that processes sharing a cache directory lock.
+const LOCK_FILE: &str = ".lock";
+
+/// Distinguishes the temporary files of stores running concurrently
+/// in this process.
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 impl FileSystemCache {
     /// Construct a new `FileSystemCache` around the specified directory.
     /// The contents of the cache are stored in sub-versioned directories.
@@ -46,8 +93,67 @@ impl FileSystemCache {
     /// This method is unsafe because there's no way to ensure the artifacts
     /// stored in this cache haven't been corrupted or tampered with.
     pub unsafe fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        Self::open(path.into(), None)
+    }
+
+    /// Construct a new `FileSystemCache` around the specified directory,
+    /// signing the artifacts it stores with `key`.
+    ///
+    /// Artifacts are only loaded if they were signed with `key`, so this
+    /// is safe as long as the key stays secret, or for ed25519 keys, as
+    /// long as the private half does.
+    pub fn new_verified<P: Into<PathBuf>>(path: P, key: SigningKey) -> io::Result<Self> {
+        Self::open(path.into(), Some(key))
+    }
+
+    /// Evict the least recently used artifacts whenever storing one makes
+    /// the cache grow past `max_size` bytes.
+    pub fn with_max_size(self, max_size: u64) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+
+    /// List the artifacts in the cache, most recently used first.
+    pub fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let _lock = self.lock(false)?;
+        let mut entries = self.read_entries()?;
+        for entry in &mut entries {
+            entry.backend = read_backend(&self.path.join(&entry.key));
+        }
+        Ok(entries)
+    }
+
+    /// Load the module stored under `key`, which must have been compiled
+    /// by `compiler`'s backend. `load` uses the default compiler.
+    pub fn load_with(&self, key: WasmHash, compiler: &dyn Compiler) -> Result<Module, CacheError> {
+        let filename = key.encode();
+        let mut new_path_buf = self.path.clone();
+        new_path_buf.push(filename);
+        let mmap = {
+            let _lock = self.lock(false)?;
+            let file = File::open(&new_path_buf)?;
+            // The modification time tracks when artifacts were last used.
+            // Failing to update it only makes eviction less accurate.
+            let _ = filetime::set_file_mtime(&new_path_buf, FileTime::now());
+            // The mapping stays valid even if the file is evicted.
+            unsafe { Mmap::map(&file)? }
+        };
+
+        load_serialized(&mmap[..], self.key.as_ref(), compiler)
+    }
+
+    /// Evict the least recently used artifacts until the cache takes up
+    /// at most `max_size` bytes, returning the evicted entries.
+    pub fn prune(&self, max_size: u64) -> io::Result<Vec<CacheEntry>> {
+        let _lock = self.lock(true)?;
+        self.prune_locked(max_size)
+    }
+
+    fn open(path: PathBuf, key: Option<SigningKey>) -> io::Result<Self> {
         let path: PathBuf = {
-            let mut path = path.into();
+            let mut path = path;
             path.push(WASMER_VERSION_HASH);
             path
         };
@@ -56,7 +162,11 @@ impl FileSystemCache {
             let metadata = path.metadata()?;
             if metadata.is_dir() {
                 if !metadata.permissions().readonly() {
-                    Ok(Self { path })
+                    Ok(Self {
+                        path,
+                        key,
+                        max_size: None,
+                    })
                 } else {
                     // This directory is readonly.
                     Err(io::Error::new(
@@ -77,37 +187,620 @@ impl FileSystemCache {
         } else {
             // Create the directory and any parent directories if they don't yet exist.
             create_dir_all(&path)?;
-            Ok(Self { path })
+            Ok(Self {
+                path,
+                key,
+                max_size: None,
+            })
         }
     }
 }
 
+impl FileSystemCache {
+    /// Lock the cache directory, shared or exclusively, until the
+    /// returned file is dropped.
+    fn lock(&self, exclusive: bool) -> io::Result<File> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.path.join(LOCK_FILE))?;
+        if exclusive {
+            file.lock_exclusive()?;
+        } else {
+            file.lock_shared()?;
+        }
+        Ok(file)
+    }
+
+    /// List the artifacts from their metadata alone, without a backend.
+    fn read_entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = vec![];
+        for dir_entry in fs::read_dir(&self.path)? {
+            let dir_entry = dir_entry?;
+            let key = dir_entry.file_name().to_string_lossy().into_owned();
+            // Skip the lock file and artifacts that are being written.
+            if key.starts_with('.') {
+                continue;
+            }
+            let metadata = dir_entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            entries.push(CacheEntry {
+                key,
+                size: metadata.len(),
+                backend: None,
+                last_used: metadata.modified()?,
+            });
+        }
+        entries.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+        Ok(entries)
+    }
+
+    /// Evict artifacts while holding the exclusive lock.
+    fn prune_locked(&self, max_size: u64) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = self.read_entries()?;
+        let mut size: u64 = entries.iter().map(|entry| entry.size).sum();
+
+        let mut evicted = vec![];
+        while size > max_size {
+            let entry = match entries.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            match fs::remove_file(self.path.join(&entry.key)) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            size -= entry.size;
+            evicted.push(entry);
+        }
+        Ok(evicted)
+    }
+}
+
+/// Load a module serialized by `serialize` with `compiler`,
+/// verifying its signature if there's a key.
+fn load_serialized(
+    bytes: &[u8],
+    key: Option<&SigningKey>,
+    compiler: &dyn Compiler,
+) -> Result<Module, CacheError> {
+    match key {
+        Some(key) => {
+            wasmer_runtime_core::load_verified_cache_with(bytes, &key.verifying_key(), compiler)
+        }
+        None => {
+            let serialized_cache = Artifact::deserialize(bytes)?;
+            unsafe { wasmer_runtime_core::load_cache_with(serialized_cache, compiler) }
+        }
+    }
+}
+
+/// Serialize a module, signing it if there's a key.
+fn serialize(module: &Module, key: Option<&SigningKey>) -> Result<Vec<u8>, CacheError> {
+    let serialized_cache = module.cache()?;
+    match key {
+        Some(key) => serialized_cache.serialize_signed(key),
+        None => serialized_cache.serialize(),
+    }
+}
+
+/// The backend an artifact was compiled with, if it can be read.
+fn read_backend(path: &Path) -> Option<Backend> {
+    let file = File::open(path).ok()?;
+    let mmap = unsafe { Mmap::map(&file).ok()? };
+    let artifact = Artifact::deserialize(&mmap[..]).ok()?;
+    Some(artifact.info().backend)
+}
+
 impl Cache for FileSystemCache {
     type LoadError = CacheError;
     type StoreError = CacheError;
 
     fn load(&self, key: WasmHash) -> Result<Module, CacheError> {
+        self.load_with(key, super::default_compiler())
+    }
+
+    fn store(&self, key: WasmHash, module: Module) -> Result<(), CacheError> {
         let filename = key.encode();
         let mut new_path_buf = self.path.clone();
         new_path_buf.push(filename);
-        let file = File::open(new_path_buf)?;
-        let mmap = unsafe { Mmap::map(&file)? };
 
-        let serialized_cache = Artifact::deserialize(&mmap[..])?;
-        unsafe { wasmer_runtime_core::load_cache_with(serialized_cache, super::default_compiler()) }
+        let buffer = serialize(&module, self.key.as_ref())?;
+
+        // Write to a temporary file first, so that other processes
+        // never see a partially written artifact.
+        let temp_path = self.path.join(format!(
+            ".{}.{}.{}.tmp",
+            key.encode(),
+            process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = File::create(&temp_path).and_then(|mut file| {
+            file.write_all(&buffer)?;
+            file.sync_all()
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+
+        let _lock = self.lock(true)?;
+        fs::rename(&temp_path, new_path_buf)?;
+        if let Some(max_size) = self.max_size {
+            self.prune_locked(max_size)?;
+        }
+
+        Ok(())
     }
+}
 
-    fn store(&mut self, key: WasmHash, module: Module) -> Result<(), CacheError> {
-        let filename = key.encode();
-        let mut new_path_buf = self.path.clone();
-        new_path_buf.push(filename);
+/// A bounded, in-memory cache of compiled modules.
+///
+/// Modules are kept as is rather than serialized, so loading one is as
+/// cheap as cloning it. Once the cache holds `capacity` modules, storing
+/// another evicts the least recently used one.
+///
+/// # Usage:
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer_runtime::cache::{Cache, MemoryCache, WasmHash};
+///
+/// # use wasmer_runtime::Module;
+/// fn share_module(module: Module) -> Arc<MemoryCache> {
+///     let cache = Arc::new(MemoryCache::new(64));
+///     cache.store(WasmHash::generate(&[]), module).unwrap();
+///     // `cache` can now be cloned into worker threads.
+///     cache
+/// }
+/// ```
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<MemoryCacheState>,
+}
+
+struct MemoryCacheState {
+    /// The modules, with the tick at which they were last used.
+    modules: HashMap<WasmHash, (Module, u64)>,
+    tick: u64,
+}
+
+impl MemoryCache {
+    /// Create a cache that holds at most `capacity` modules.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(MemoryCacheState {
+                modules: HashMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    /// The number of modules in the cache.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Cache for MemoryCache {
+    type LoadError = CacheError;
+    type StoreError = CacheError;
+
+    fn load(&self, key: WasmHash) -> Result<Module, CacheError> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        match state.modules.get_mut(&key) {
+            Some((module, last_used)) => {
+                *last_used = tick;
+                Ok(module.clone())
+            }
+            None => Err(CacheError::IoError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the module {} isn't cached", key.encode()),
+            ))),
+        }
+    }
+
+    fn store(&self, key: WasmHash, module: Module) -> Result<(), CacheError> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        if !state.modules.contains_key(&key) && state.modules.len() >= self.capacity {
+            let least_recently_used = state
+                .modules
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+            if let Some(evicted) = least_recently_used {
+                state.modules.remove(&evicted);
+            }
+        }
+        state.modules.insert(key, (module, tick));
+        Ok(())
+    }
+}
+
+/// A key-value store that serialized modules can be kept in, such
+/// as a client of a redis server or of a shared object store.
+///
+/// Implementations are shared between threads, so they have to
+/// synchronize access to their connection themselves.
+pub trait KeyValueStore: Send + Sync {
+    /// Get the value stored under `key`, if any.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Store `value` under `key`, replacing any previous value.
+    fn set(&self, key: &str, value: &[u8]) -> io::Result<()>;
+}
+
+/// A `KeyValueStore` backed by a `HashMap` in this process.
+///
+/// This is mostly a stand-in for remote stores, in tests for example.
+#[derive(Default)]
+pub struct InMemoryStore {
+    values: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyValueStore for InMemoryStore {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+}
+
+/// A cache of serialized modules kept in a `KeyValueStore`, so that
+/// workers sharing the store can share compiled modules.
+///
+/// Keys are prefixed with `WASMER_VERSION_HASH`, so that different
+/// versions of wasmer can share a store without clashing.
+pub struct KeyValueCache<S: KeyValueStore> {
+    store: S,
+    /// Signs stored artifacts, and is required to verify loaded ones.
+    key: Option<SigningKey>,
+}
+
+impl<S: KeyValueStore> KeyValueCache<S> {
+    /// Cache modules in `store`.
+    ///
+    /// # Note:
+    /// This method is unsafe because anyone who can write to the store
+    /// can make this cache load arbitrary code. Prefer `new_verified`.
+    pub unsafe fn new(store: S) -> Self {
+        Self { store, key: None }
+    }
+
+    /// Cache modules in `store`, signing them with `key`. Only modules
+    /// signed with `key` are loaded.
+    pub fn new_verified(store: S, key: SigningKey) -> Self {
+        Self {
+            store,
+            key: Some(key),
+        }
+    }
 
-        let serialized_cache = module.cache()?;
-        let buffer = serialized_cache.serialize()?;
+    /// The store the modules are kept in.
+    pub fn backing_store(&self) -> &S {
+        &self.store
+    }
 
-        let mut file = File::create(new_path_buf)?;
-        file.write_all(&buffer)?;
+    /// Load the module stored under `key`, which must have been compiled
+    /// by `compiler`'s backend. `load` uses the default compiler.
+    pub fn load_with(&self, key: WasmHash, compiler: &dyn Compiler) -> Result<Module, CacheError> {
+        let bytes = self.store.get(&Self::store_key(key))?.ok_or_else(|| {
+            CacheError::IoError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the module {} isn't cached", key.encode()),
+            ))
+        })?;
+        load_serialized(&bytes, self.key.as_ref(), compiler)
+    }
+
+    fn store_key(key: WasmHash) -> String {
+        format!("wasmer:{}:{}", WASMER_VERSION_HASH, key.encode())
+    }
+}
+
+impl<S: KeyValueStore> Cache for KeyValueCache<S> {
+    type LoadError = CacheError;
+    type StoreError = CacheError;
+
+    fn load(&self, key: WasmHash) -> Result<Module, CacheError> {
+        self.load_with(key, super::default_compiler())
+    }
 
+    fn store(&self, key: WasmHash, module: Module) -> Result<(), CacheError> {
+        let buffer = serialize(&module, self.key.as_ref())?;
+        self.store.set(&Self::store_key(key), &buffer)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Cache, FileSystemCache, InMemoryStore, KeyValueCache, KeyValueStore, MemoryCache,
+        SigningKey, WasmHash, WASMER_VERSION_HASH,
+    };
+    use crate::{compile, default_compiler, imports, Func, Module};
+    use filetime::FileTime;
+    use std::{fs, sync::Arc, thread, time::Duration};
+    use tempfile::tempdir;
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{
+        backend::{Backend, Compiler, CompilerConfig, Token},
+        cache::{Artifact, Error as CacheError},
+        error::CompileResult,
+        module::ModuleInner,
+    };
+
+    static WAT: &str = r#"
+        (module
+          (func (export "answer") (result i32)
+            i32.const 42))
+    "#;
+
+    fn module() -> Module {
+        compile(&wat2wasm(WAT).unwrap()).unwrap()
+    }
+
+    fn answer(module: Module) -> i32 {
+        let instance = module.instantiate(&imports! {}).unwrap();
+        let answer: Func<(), i32> = instance.func("answer").unwrap();
+        answer.call().unwrap()
+    }
+
+    /// A compiler of another backend than the default one.
+    struct LLVMCompiler;
+
+    impl Compiler for LLVMCompiler {
+        fn compile(&self, _: &[u8], _: CompilerConfig, _: Token) -> CompileResult<ModuleInner> {
+            unimplemented!()
+        }
+
+        unsafe fn from_cache(&self, _: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+            unimplemented!()
+        }
+
+        fn backend(&self) -> Backend {
+            Backend::LLVM
+        }
+    }
+
+    fn key(n: u8) -> WasmHash {
+        WasmHash::generate(&[n])
+    }
+
+    /// Mark an artifact as last used `seconds_ago`.
+    fn set_last_used(cache: &FileSystemCache, key: WasmHash, seconds_ago: i64) {
+        let now = FileTime::now();
+        let mtime = FileTime::from_unix_time(now.unix_seconds() - seconds_ago, 0);
+        filetime::set_file_mtime(cache.path.join(key.encode()), mtime).unwrap();
+    }
+
+    fn keys(cache: &FileSystemCache) -> Vec<String> {
+        cache
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect()
+    }
+
+    #[test]
+    fn prune_evicts_the_least_recently_used_artifacts() {
+        let dir = tempdir().unwrap();
+        let cache = unsafe { FileSystemCache::new(dir.path()).unwrap() };
+        let module = module();
+        for n in 0..3 {
+            cache.store(key(n), module.clone()).unwrap();
+            set_last_used(&cache, key(n), 300 - 100 * i64::from(n));
+        }
+        // Loading the oldest artifact makes it the most recently used.
+        cache.load(key(0)).unwrap();
+
+        let size = cache.entries().unwrap()[0].size;
+        let evicted = cache.prune(2 * size).unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].key, key(1).encode());
+        assert_eq!(keys(&cache), vec![key(0).encode(), key(2).encode()]);
+        assert!(cache.load(key(1)).is_err());
+    }
+
+    #[test]
+    fn stores_keep_the_cache_within_its_max_size() {
+        let dir = tempdir().unwrap();
+        let module = module();
+        let size = {
+            let cache = unsafe { FileSystemCache::new(dir.path().join("sizing")).unwrap() };
+            cache.store(key(0), module.clone()).unwrap();
+            cache.entries().unwrap()[0].size
+        };
+
+        let cache = unsafe { FileSystemCache::new(dir.path()).unwrap() }.with_max_size(2 * size);
+        for n in 0..3 {
+            cache.store(key(n), module.clone()).unwrap();
+            set_last_used(&cache, key(n), 300 - 100 * i64::from(n));
+        }
+        assert_eq!(keys(&cache), vec![key(2).encode(), key(1).encode()]);
+        let total: u64 = cache
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.size)
+            .sum();
+        assert!(total <= 2 * size);
+
+        assert!(cache
+            .prune(0)
+            .unwrap()
+            .iter()
+            .all(|entry| entry.backend.is_none()));
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stores_wait_for_the_lock() {
+        let dir = tempdir().unwrap();
+        let cache = Arc::new(unsafe { FileSystemCache::new(dir.path()).unwrap() });
+        let module = module();
+
+        let lock = cache.lock(true).unwrap();
+        let store = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || cache.store(key(0), module).unwrap())
+        };
+        thread::sleep(Duration::from_millis(50));
+        // Listing the entries would wait for the lock too.
+        assert!(!cache.path.join(key(0).encode()).exists());
+        drop(lock);
+        store.join().unwrap();
+        assert_eq!(keys(&cache), vec![key(0).encode()]);
+    }
+
+    #[test]
+    fn concurrent_stores_of_the_same_key_succeed() {
+        let dir = tempdir().unwrap();
+        let cache = Arc::new(unsafe { FileSystemCache::new(dir.path()).unwrap() });
+        let module = module();
+
+        let stores: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                let module = module.clone();
+                thread::spawn(move || cache.store(key(0), module).unwrap())
+            })
+            .collect();
+        for store in stores {
+            store.join().unwrap();
+        }
+
+        // No temporary files are left behind.
+        let files: Vec<_> = fs::read_dir(&cache.path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect();
+        assert!(files.is_empty());
+        assert_eq!(keys(&cache), vec![key(0).encode()]);
+        assert_eq!(answer(cache.load(key(0)).unwrap()), 42);
+    }
+
+    #[test]
+    fn memory_cache_round_trips_and_misses() {
+        let cache = MemoryCache::new(2);
+        let module = module();
+        assert!(cache.load(key(0)).is_err());
+
+        cache.store(key(0), module.clone()).unwrap();
+        assert_eq!(answer(cache.load(key(0)).unwrap()), 42);
+        assert!(cache.load(key(1)).is_err());
+    }
+
+    #[test]
+    fn memory_cache_evicts_the_least_recently_used_module() {
+        let cache = MemoryCache::new(2);
+        let module = module();
+        cache.store(key(0), module.clone()).unwrap();
+        cache.store(key(1), module.clone()).unwrap();
+        cache.load(key(0)).unwrap();
+        cache.store(key(2), module.clone()).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.load(key(0)).is_ok());
+        assert!(cache.load(key(1)).is_err());
+        assert!(cache.load(key(2)).is_ok());
+
+        let empty = MemoryCache::new(0);
+        empty.store(key(0), module).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn key_value_cache_round_trips_and_misses() {
+        let cache = unsafe { KeyValueCache::new(InMemoryStore::new()) };
+        assert!(cache.load(key(0)).is_err());
+
+        cache.store(key(0), module()).unwrap();
+        assert_eq!(answer(cache.load(key(0)).unwrap()), 42);
+        assert!(cache.load(key(1)).is_err());
+
+        let store_key = format!("wasmer:{}:{}", WASMER_VERSION_HASH, key(0).encode());
+        assert!(cache.backing_store().get(&store_key).unwrap().is_some());
+    }
+
+    #[test]
+    fn verified_key_value_cache_rejects_foreign_artifacts() {
+        let signing_key = || SigningKey::Hmac(b"secret".to_vec());
+        let cache = KeyValueCache::new_verified(InMemoryStore::new(), signing_key());
+        cache.store(key(0), module()).unwrap();
+        assert_eq!(answer(cache.load(key(0)).unwrap()), 42);
+
+        let store_key = format!("wasmer:{}:{}", WASMER_VERSION_HASH, key(0).encode());
+        let signed = cache.backing_store().get(&store_key).unwrap().unwrap();
+
+        // Artifacts signed with another key, or not at all, aren't loaded.
+        let other = KeyValueCache::new_verified(
+            InMemoryStore::new(),
+            SigningKey::Hmac(b"another secret".to_vec()),
+        );
+        other.backing_store().set(&store_key, &signed).unwrap();
+        assert!(other.load(key(0)).is_err());
+
+        let unsigned = unsafe { KeyValueCache::new(InMemoryStore::new()) };
+        unsigned.store(key(0), module()).unwrap();
+        let bytes = unsigned.backing_store().get(&store_key).unwrap().unwrap();
+        let verified = KeyValueCache::new_verified(InMemoryStore::new(), signing_key());
+        verified.backing_store().set(&store_key, &bytes).unwrap();
+        assert!(verified.load(key(0)).is_err());
+    }
+
+    #[test]
+    fn artifacts_are_loaded_with_the_compiler_of_their_backend() {
+        let signing_key = || SigningKey::Hmac(b"secret".to_vec());
+        let dir = tempdir().unwrap();
+        let cache = FileSystemCache::new_verified(dir.path(), signing_key()).unwrap();
+        cache.store(key(0), module()).unwrap();
+        let kv_cache = KeyValueCache::new_verified(InMemoryStore::new(), signing_key());
+        kv_cache.store(key(0), module()).unwrap();
+
+        assert_eq!(
+            answer(cache.load_with(key(0), default_compiler()).unwrap()),
+            42
+        );
+        match cache.load_with(key(0), &LLVMCompiler) {
+            Err(CacheError::IncompatibleBackend(Backend::Cranelift)) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        match kv_cache.load_with(key(0), &LLVMCompiler) {
+            Err(CacheError::IncompatibleBackend(Backend::Cranelift)) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+}
@@ -78,6 +78,7 @@ pub use wasmer_runtime_core::export::Export;
 pub use wasmer_runtime_core::global::Global;
 pub use wasmer_runtime_core::import::ImportObject;
 pub use wasmer_runtime_core::instance::{DynFunc, Instance};
+pub use wasmer_runtime_core::linker::{InstanceNamespace, Linker};
 pub use wasmer_runtime_core::memory::Memory;
 pub use wasmer_runtime_core::module::Module;
 pub use wasmer_runtime_core::table::Table;
@@ -95,6 +96,7 @@ pub mod memory {
 pub mod wasm {
     //! Various types exposed by the Wasmer Runtime.
     pub use wasmer_runtime_core::global::Global;
+    pub use wasmer_runtime_core::module::{ExportDescriptor, ExternDescriptor, ImportDescriptor};
     pub use wasmer_runtime_core::table::Table;
     pub use wasmer_runtime_core::types::{
         FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type, Value,
@@ -111,6 +113,8 @@ pub mod units {
     pub use wasmer_runtime_core::units::{Bytes, Pages};
 }
 
+#[cfg(feature = "aot-loader")]
+pub mod aot;
 pub mod cache;
 
 use wasmer_runtime_core::backend::{Compiler, CompilerConfig};
@@ -196,3 +200,159 @@ pub fn default_compiler() -> &'static dyn Compiler {
 
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg(test)]
+mod tests {
+    use super::{compile, compile_with_config, imports, Func, Linker};
+    use std::rc::Rc;
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{
+        backend::{CompilerConfig, CpuFeature},
+        module::ExternDescriptor,
+        types::{FuncSig, Type},
+        units::Pages,
+    };
+
+    static LIB: &str = r#"
+        (module
+          (global $answer i32 (i32.const 42))
+          (func (export "answer") (result i32)
+            get_global $answer))
+    "#;
+
+    static APP: &str = r#"
+        (module
+          (import "lib" "answer" (func $answer (result i32)))
+          (func (export "run") (result i32)
+            call $answer))
+    "#;
+
+    #[test]
+    fn linked_instances_outlive_the_linker() {
+        let lib = compile(&wat2wasm(LIB).unwrap()).unwrap();
+        let app = compile(&wat2wasm(APP).unwrap()).unwrap();
+
+        let mut linker = Linker::new();
+        let lib_instance = linker.register("lib", lib.instantiate(&imports! {}).unwrap());
+        let app_instance = linker.instantiate(&app).unwrap();
+        // A later registration under the same name doesn't
+        // affect instances linked against the first one.
+        linker.register("lib", lib.instantiate(&imports! {}).unwrap());
+
+        let lib_weak = Rc::downgrade(&lib_instance);
+        drop(lib_instance);
+        drop(linker);
+        assert!(lib_weak.upgrade().is_some());
+
+        let run: Func<(), i32> = app_instance.func("run").unwrap();
+        assert_eq!(run.call().unwrap(), 42);
+
+        drop(run);
+        drop(app_instance);
+        assert!(lib_weak.upgrade().is_none());
+    }
+
+    static DESCRIBED: &str = r#"
+        (module
+          (import "env" "print" (func $print (param i32 i64) (result f32)))
+          (import "env" "memory" (memory 1 2))
+          (import "env" "table" (table 3 anyfunc))
+          (import "env" "base" (global i32))
+          (global $zero f64 (f64.const 0))
+          (func $run (result f64) get_global $zero)
+          (export "zero" (global $zero))
+          (export "print" (func $print))
+          (export "run" (func $run))
+          (export "memory" (memory 0)))
+    "#;
+
+    #[test]
+    fn imports_are_described_by_kind() {
+        let module = compile(&wat2wasm(DESCRIBED).unwrap()).unwrap();
+        let imports: Vec<_> = module
+            .imports()
+            .into_iter()
+            .map(|import| (import.namespace, import.name, import.ty))
+            .collect();
+        assert_eq!(imports.len(), 4);
+
+        match &imports[0] {
+            (namespace, name, ExternDescriptor::Function(sig)) => {
+                assert_eq!((namespace.as_str(), name.as_str()), ("env", "print"));
+                assert_eq!(
+                    *sig,
+                    FuncSig::new(vec![Type::I32, Type::I64], vec![Type::F32])
+                );
+            }
+            import => panic!("unexpected import {:?}", import),
+        }
+        match &imports[1] {
+            (_, name, ExternDescriptor::Memory(desc)) => {
+                assert_eq!(name, "memory");
+                assert_eq!((desc.minimum, desc.maximum), (Pages(1), Some(Pages(2))));
+                assert!(!desc.shared);
+            }
+            import => panic!("unexpected import {:?}", import),
+        }
+        match &imports[2] {
+            (_, name, ExternDescriptor::Table(desc)) => {
+                assert_eq!(name, "table");
+                assert_eq!((desc.minimum, desc.maximum), (3, None));
+            }
+            import => panic!("unexpected import {:?}", import),
+        }
+        match &imports[3] {
+            (_, name, ExternDescriptor::Global(desc)) => {
+                assert_eq!(name, "base");
+                assert_eq!((desc.ty, desc.mutable), (Type::I32, false));
+            }
+            import => panic!("unexpected import {:?}", import),
+        }
+    }
+
+    #[test]
+    fn exports_are_described_by_name() {
+        let module = compile(&wat2wasm(DESCRIBED).unwrap()).unwrap();
+        let exports: Vec<_> = module
+            .exports()
+            .into_iter()
+            .map(|export| (export.name, export.ty.kind_name()))
+            .collect();
+        assert_eq!(
+            exports,
+            vec![
+                ("memory".to_string(), "memory"),
+                ("print".to_string(), "function"),
+                ("run".to_string(), "function"),
+                ("zero".to_string(), "global"),
+            ]
+        );
+
+        // Exported imports are described like the imports.
+        let print = module.exports().remove(1);
+        match print.ty {
+            ExternDescriptor::Function(sig) => assert_eq!(sig.returns(), &[Type::F32]),
+            ty => panic!("unexpected export {:?}", ty),
+        }
+        assert!(module.custom_sections("name").is_none());
+    }
+
+    #[test]
+    fn malformed_custom_sections_are_compile_errors() {
+        // A custom section whose name is longer than the section.
+        let wasm = [0, b'a', b's', b'm', 1, 0, 0, 0, 0, 2, 5, b'n'];
+        assert!(compile(&wasm).is_err());
+    }
+
+    #[cfg(not(any(feature = "llvm", feature = "singlepass")))]
+    #[test]
+    fn cpu_features_cranelift_cant_use_are_rejected() {
+        let wasm = wat2wasm(LIB).unwrap();
+        let config = |features| CompilerConfig {
+            enable_cpu_features: features,
+            ..Default::default()
+        };
+        assert!(compile_with_config(&wasm, config(vec![CpuFeature::AVX])).is_ok());
+        assert!(compile_with_config(&wasm, config(vec![CpuFeature::AVX2])).is_err());
+    }
+}
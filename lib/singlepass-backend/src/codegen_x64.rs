@@ -12,6 +12,7 @@ use std::ptr::NonNull;
 use std::{any::Any, collections::HashMap, sync::Arc};
 use wasmer_runtime_core::{
     backend::RunnableModule,
+    jit_debug::{self, FunctionDebugInfo, JitDebugRegistration},
     memory::MemoryType,
     module::ModuleInfo,
     structures::{Map, TypedIndex},
@@ -153,7 +154,9 @@ unsafe impl Send for FuncPtr {}
 unsafe impl Sync for FuncPtr {}
 
 pub struct X64ExecutionContext {
-    #[allow(dead_code)]
+    // Declared before `code` so the code is unregistered from
+    // debuggers before it's freed.
+    debug_registration: Option<JitDebugRegistration>,
     code: ExecutableBuffer,
     #[allow(dead_code)]
     functions: Vec<X64FunctionCode>,
@@ -179,6 +182,52 @@ pub enum IfElseState {
     Else,
 }
 
+impl X64ExecutionContext {
+    /// The address and size of the code of every local function.
+    ///
+    /// Functions are laid out one after the other, so each one
+    /// extends up to the next function or the end of the code.
+    pub fn function_debug_info(&self, info: &ModuleInfo) -> Vec<FunctionDebugInfo> {
+        let code_end = self.code.as_ptr() as usize + self.code.len();
+        let mut starts: Vec<usize> = self
+            .function_pointers
+            .iter()
+            .map(|ptr| ptr.0 as usize)
+            .collect();
+        starts.sort();
+
+        self.function_pointers[self.func_import_count..]
+            .iter()
+            .enumerate()
+            .map(|(index, ptr)| {
+                let address = ptr.0 as usize;
+                let end = starts
+                    .iter()
+                    .cloned()
+                    .find(|&start| start > address)
+                    .unwrap_or(code_end);
+
+                FunctionDebugInfo {
+                    func_index: LocalFuncIndex::new(index).convert_up(info),
+                    address,
+                    size: end - address,
+                    instructions: vec![],
+                }
+            })
+            .collect()
+    }
+
+    /// Register the compiled functions with native debuggers.
+    ///
+    /// Only function symbols are registered: this backend doesn't keep
+    /// track of the wasm offset of the instructions it generates, so
+    /// there are no line tables.
+    pub fn register_debug_info(&mut self, wasm: &[u8], info: &ModuleInfo) {
+        let functions = self.function_debug_info(info);
+        self.debug_registration = jit_debug::register(wasm, info, &functions);
+    }
+}
+
 impl RunnableModule for X64ExecutionContext {
     fn get_func(
         &self,
@@ -253,6 +302,23 @@ impl RunnableModule for X64ExecutionContext {
         protect_unix::TRAP_EARLY_DATA.with(|x| x.set(Some(data)));
         protect_unix::trigger_trap();
     }
+
+    fn get_trap_func(&self, _: &ModuleInfo) -> Option<LocalFuncIndex> {
+        let (_, inst_ptr) = protect_unix::CAUGHT_ADDRESSES.with(|cell| cell.get());
+        let ip = inst_ptr as usize;
+        let start = self.code.as_ptr() as usize;
+
+        if ip < start || ip >= start + self.code.len() {
+            return None;
+        }
+
+        self.function_pointers[self.func_import_count..]
+            .iter()
+            .enumerate()
+            .filter(|(_, ptr)| ptr.0 as usize <= ip)
+            .max_by_key(|(_, ptr)| ptr.0 as usize)
+            .map(|(index, _)| LocalFuncIndex::new(index))
+    }
 }
 
 impl X64ModuleCodeGenerator {
@@ -364,6 +430,7 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext> for X64ModuleCode
         }
 
         Ok(X64ExecutionContext {
+            debug_registration: None,
             code: output,
             functions: self.functions,
             signatures: self.signatures.as_ref().unwrap().clone(),
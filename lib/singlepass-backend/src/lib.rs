@@ -28,12 +28,18 @@ mod protect_unix;
 use crate::codegen::{CodegenError, ModuleCodeGenerator};
 use crate::parse::LoadError;
 use wasmer_runtime_core::{
-    backend::{sys::Memory, Backend, CacheGen, Compiler, CompilerConfig, Token},
+    backend::{sys::Memory, Backend, CacheGen, Compiler, CompilerConfig, CpuFeature, Token},
     cache::{Artifact, Error as CacheError},
     error::{CompileError, CompileResult},
     module::{ModuleInfo, ModuleInner},
+    profiling,
 };
 
+/// The CPU features of the bit counting instructions, which are emitted
+/// unconditionally.
+pub(crate) const REQUIRED_CPU_FEATURES: &[CpuFeature] =
+    &[CpuFeature::POPCNT, CpuFeature::LZCNT, CpuFeature::BMI1];
+
 struct Placeholder;
 impl CacheGen for Placeholder {
     fn generate_cache(
@@ -60,9 +66,28 @@ impl Compiler for SinglePassCompiler {
         compiler_config: CompilerConfig,
         _: Token,
     ) -> CompileResult<ModuleInner> {
+        check_target(&compiler_config)?;
+
         let mut mcg = codegen_x64::X64ModuleCodeGenerator::new();
-        let info = parse::read_module(wasm, Backend::Singlepass, &mut mcg, &compiler_config)?;
-        let exec_context = mcg.finalize(&info)?;
+        let mut info = parse::read_module(wasm, Backend::Singlepass, &mut mcg, &compiler_config)?;
+        let mut exec_context = mcg.finalize(&info)?;
+
+        if compiler_config.generate_debug_info || compiler_config.profiling.is_some() {
+            // Function names come from the `name` section, which is
+            // otherwise only read once the module is compiled.
+            info.import_custom_sections(wasm)
+                .map_err(|e| CompileError::InternalError {
+                    msg: format!("{:?}", e),
+                })?;
+        }
+        if let Some(strategy) = compiler_config.profiling {
+            // Failing to write profiling data shouldn't fail compilation.
+            let _ = profiling::register(strategy, &info, &exec_context.function_debug_info(&info));
+        }
+        if compiler_config.generate_debug_info {
+            exec_context.register_debug_info(wasm, &info);
+        }
+
         Ok(ModuleInner {
             cache_gen: Box::new(Placeholder),
             runnable_module: Box::new(exec_context),
@@ -70,11 +95,50 @@ impl Compiler for SinglePassCompiler {
         })
     }
 
-    unsafe fn from_cache(&self, _artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+    unsafe fn from_cache(&self, artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+        if artifact.info().backend != Backend::Singlepass {
+            return Err(CacheError::IncompatibleBackend(artifact.info().backend));
+        }
         Err(CacheError::Unknown(
             "the singlepass backend doesn't support caching yet".to_string(),
         ))
     }
+
+    fn backend(&self) -> Backend {
+        Backend::Singlepass
+    }
+}
+
+/// Make sure `compiler_config` allows for the code this backend generates:
+/// x86-64 code, using the features of `REQUIRED_CPU_FEATURES`.
+fn check_target(compiler_config: &CompilerConfig) -> CompileResult<()> {
+    if let Some(triple) = &compiler_config.triple {
+        if triple.split('-').next() != Some("x86_64") {
+            return Err(CompileError::InternalError {
+                msg: format!(
+                    "the singlepass backend can only compile for x86_64, not {}",
+                    triple
+                ),
+            });
+        }
+    }
+
+    let cpu_features = compiler_config.cpu_features();
+    let missing: Vec<_> = REQUIRED_CPU_FEATURES
+        .iter()
+        .filter(|feature| !cpu_features.contains(feature))
+        .map(|feature| feature.name())
+        .collect();
+    if !missing.is_empty() {
+        return Err(CompileError::InternalError {
+            msg: format!(
+                "the singlepass backend needs these CPU features: {}",
+                missing.join(", ")
+            ),
+        });
+    }
+
+    Ok(())
 }
 
 impl From<CodegenError> for CompileError {
@@ -68,6 +68,8 @@ pub fn read_module<
         func_assoc: Map::new(),
         signatures: Map::new(),
         backend: backend,
+        triple: compiler_config.triple.clone(),
+        cpu_features: crate::REQUIRED_CPU_FEATURES.to_vec(),
 
         namespace_table: StringTable::new(),
         name_table: StringTable::new(),
@@ -75,6 +77,8 @@ pub fn read_module<
         em_symbol_map: compiler_config.symbol_map.clone(),
 
         custom_sections: HashMap::new(),
+
+        names: Default::default(),
     };
 
     let mut parser = wasmparser::ValidatingParser::new(
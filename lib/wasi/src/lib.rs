@@ -13,7 +13,7 @@ use self::syscalls::*;
 
 use std::ffi::c_void;
 
-pub use self::utils::is_wasi_module;
+pub use self::utils::{is_wasi_import, is_wasi_module};
 
 use wasmer_runtime_core::{func, import::ImportObject, imports};
 
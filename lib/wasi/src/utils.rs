@@ -7,9 +7,15 @@ pub fn is_wasi_module(module: &Module) -> bool {
             .info()
             .namespace_table
             .get(import_name.namespace_index);
-        if namespace == "wasi_unstable" {
+        if is_wasi_import(namespace) {
             return true;
         }
     }
     false
 }
+
+/// Whether importing a function from `namespace` makes a module a WASI
+/// one, for when it isn't compiled.
+pub fn is_wasi_import(namespace: &str) -> bool {
+    namespace == "wasi_unstable"
+}
@@ -1,12 +1,13 @@
 extern crate structopt;
 
 use std::env;
-use std::fs::{read_to_string, File};
+use std::fs::{self, read_to_string, File};
 use std::io;
 use std::io::Read;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use hashbrown::HashMap;
 use structopt::StructOpt;
@@ -19,7 +20,9 @@ use wasmer_llvm_backend::LLVMCompiler;
 use wasmer_runtime::cache::{Cache as BaseCache, FileSystemCache, WasmHash, WASMER_VERSION_HASH};
 use wasmer_runtime_core::{
     self,
-    backend::{Compiler, CompilerConfig},
+    backend::{Compiler, CompilerConfig, CpuFeature, OptLevel},
+    profiling::ProfilingStrategy,
+    types::{FuncSig, Type, Value},
 };
 #[cfg(feature = "backend:singlepass")]
 use wasmer_singlepass_backend::SinglePassCompiler;
@@ -35,6 +38,10 @@ mod wasmer_wasi {
         false
     }
 
+    pub fn is_wasi_import(_namespace: &str) -> bool {
+        false
+    }
+
     pub fn generate_import_object(_args: Vec<Vec<u8>>, _envs: Vec<Vec<u8>>) -> ImportObject {
         unimplemented!()
     }
@@ -56,6 +63,21 @@ enum CLIOptions {
     #[structopt(name = "validate")]
     Validate(Validate),
 
+    /// Compile a WebAssembly file ahead of time with Cranelift into an
+    /// object file that `wasmer run` can load without compiling it
+    #[structopt(name = "compile")]
+    Compile(Compile),
+
+    /// Describe the imports, exports, memories, tables, globals
+    /// and sections of a WebAssembly file
+    #[structopt(name = "inspect")]
+    Inspect(Inspect),
+
+    /// Run the directives of a spec-style .wast test script,
+    /// reporting whether each assertion passed
+    #[structopt(name = "wast")]
+    Wast(Wast),
+
     /// Update wasmer to the latest version
     #[structopt(name = "self-update")]
     SelfUpdate,
@@ -83,9 +105,55 @@ struct Run {
     #[structopt(long = "em-symbol-map", parse(from_os_str))]
     em_symbol_map: Option<PathBuf>,
 
+    /// Register the compiled code with gdb and lldb, with line tables from the
+    /// module's DWARF (cranelift only). Implies --disable-cache
+    #[structopt(short = "g", long = "debug-info")]
+    debug_info: bool,
+
+    /// Report compiled functions to `perf`, either in /tmp/perf-PID.map
+    /// or as jitdump records in /tmp/jit-PID.dump. Implies --disable-cache
+    #[structopt(long = "profile")]
+    profile: Option<ProfilingStrategy>,
+
+    /// How hard to optimize the compiled code: none, speed or speed_and_size
+    #[structopt(long = "opt-level")]
+    opt_level: Option<OptLevel>,
+
+    /// Check the compiler's intermediate representation for errors while
+    /// compiling. This is slow, and meant for debugging the compiler
+    #[structopt(long = "enable-verifier")]
+    enable_verifier: bool,
+
     #[structopt(long = "command-name", hidden = true)]
     command_name: Option<String>,
 
+    /// Call this exported function instead of the module's entry point,
+    /// passing it the application arguments, and print what it returns
+    #[structopt(long = "invoke", short = "i")]
+    invoke: Option<String>,
+
+    /// Make a host directory available to emscripten modules, as
+    /// `GUEST_DIR:HOST_DIR`. Without any, they only see the current
+    /// directory, at `/`
+    #[structopt(
+        long = "mapdir",
+        parse(try_from_str = "parse_mapdir"),
+        raw(number_of_values = "1")
+    )]
+    mapdirs: Vec<(String, PathBuf)>,
+
+    /// Let emscripten modules run this host program with `system`, `popen`
+    /// or `exec`. It runs outside of the sandbox. Command lines with pipes,
+    /// redirections or variables need `sh`, and allowing it allows anything
+    #[structopt(long = "allow-command", raw(number_of_values = "1"))]
+    allowed_commands: Vec<String>,
+
+    /// Give emscripten modules clocks that start 10 seconds after the epoch
+    /// and tick a millisecond per read, in UTC, like emscripten's
+    /// `-s DETERMINISTIC=1`, so that runs can be compared
+    #[structopt(long = "deterministic-clock")]
+    deterministic_clock: bool,
+
     /// Application arguments
     #[structopt(name = "--", raw(multiple = "true"))]
     args: Vec<String>,
@@ -130,6 +198,47 @@ enum Cache {
     /// Display the location of the cache
     #[structopt(name = "dir")]
     Dir,
+
+    /// List the cached modules, with their size, backend and last use
+    #[structopt(name = "ls")]
+    Ls,
+
+    /// Evict the least recently used modules until the cache fits in a size
+    #[structopt(name = "prune")]
+    Prune {
+        /// The size to shrink the cache to, in bytes, or with a K, M or G suffix
+        #[structopt(long = "max-size", parse(try_from_str = "parse_size"))]
+        max_size: u64,
+    },
+}
+
+/// Parse a size in bytes, like `1024`, `512K`, `100M` or `2G`
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, unit) = match s.char_indices().last() {
+        Some((index, 'k')) | Some((index, 'K')) => (&s[..index], 1 << 10),
+        Some((index, 'm')) | Some((index, 'M')) => (&s[..index], 1 << 20),
+        Some((index, 'g')) | Some((index, 'G')) => (&s[..index], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(unit))
+        .ok_or_else(|| format!("The size {} isn't valid", s))
+}
+
+/// Parse a directory mapping, like `/data:./data`
+fn parse_mapdir(s: &str) -> Result<(String, PathBuf), String> {
+    match s.find(':') {
+        Some(index) if s[..index].starts_with('/') => {
+            Ok((s[..index].to_string(), PathBuf::from(&s[index + 1..])))
+        }
+        _ => Err(format!(
+            "The mapping {} isn't valid, it should look like /guest/dir:host/dir",
+            s
+        )),
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -139,6 +248,76 @@ struct Validate {
     path: PathBuf,
 }
 
+#[derive(Debug, StructOpt)]
+struct Wast {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    #[structopt(
+        long = "backend",
+        default_value = "cranelift",
+        raw(possible_values = "Backend::variants()", case_insensitive = "true")
+    )]
+    backend: Backend,
+}
+
+#[derive(Debug, StructOpt)]
+struct Inspect {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Print the description as JSON
+    #[structopt(long = "json")]
+    json: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct Compile {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Output file. A relocatable ELF object holding the compiled module in
+    /// its `.wasmer_artifact` section is written, unless the name ends in
+    /// `.so`, in which case it's linked into a shared object with
+    /// `$CC -shared`. Either is loaded by wasmer, not called into
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+
+    /// The symbol at which the compiled module is defined in the object
+    #[structopt(long = "symbol", default_value = "WASMER_MODULE")]
+    symbol: String,
+
+    /// Emscripten symbol map
+    #[structopt(long = "em-symbol-map", parse(from_os_str))]
+    em_symbol_map: Option<PathBuf>,
+
+    /// The target triple to compile for, such as `x86_64-unknown-linux-gnu`.
+    /// Defaults to the host
+    #[structopt(long = "target")]
+    target: Option<String>,
+
+    /// A CPU feature to enable, such as `sse4.1` or `bmi2`. When compiling
+    /// for the host, the features it supports are enabled by default
+    #[structopt(long = "enable-cpu-feature", number_of_values = 1)]
+    enable_cpu_features: Vec<CpuFeature>,
+
+    /// A CPU feature to disable
+    #[structopt(long = "disable-cpu-feature", number_of_values = 1)]
+    disable_cpu_features: Vec<CpuFeature>,
+
+    /// How hard to optimize the compiled code: none, speed or speed_and_size
+    #[structopt(long = "opt-level")]
+    opt_level: Option<OptLevel>,
+
+    /// Check the compiler's intermediate representation for errors while
+    /// compiling. This is slow, and meant for debugging the compiler
+    #[structopt(long = "enable-verifier")]
+    enable_verifier: bool,
+}
+
 /// Read the contents of a file
 fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     let mut buffer: Vec<u8> = Vec::new();
@@ -149,6 +328,30 @@ fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     Ok(buffer)
 }
 
+/// Open the cache used by `wasmer run`, bounded by `$WASMER_CACHE_MAX_SIZE`
+/// if it's set
+///
+/// This is unsafe because the cached artifacts aren't signed
+unsafe fn open_cache() -> Result<FileSystemCache, String> {
+    let cache =
+        FileSystemCache::new(get_cache_dir()).map_err(|e| format!("Cache error: {:?}", e))?;
+    match env::var("WASMER_CACHE_MAX_SIZE") {
+        Ok(max_size) => Ok(cache.with_max_size(parse_size(&max_size)?)),
+        Err(_) => Ok(cache),
+    }
+}
+
+/// How long ago `time` was, roughly
+fn format_age(time: SystemTime) -> String {
+    let seconds = time.elapsed().map(|age| age.as_secs()).unwrap_or(0);
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
 fn get_cache_dir() -> PathBuf {
     match env::var("WASMER_CACHE_DIR") {
         Ok(dir) => PathBuf::from(dir),
@@ -162,25 +365,11 @@ fn get_cache_dir() -> PathBuf {
     }
 }
 
-/// Execute a wasm/wat file
-fn execute_wasm(options: &Run) -> Result<(), String> {
-    // force disable caching on windows
-    #[cfg(target_os = "windows")]
-    let disable_cache = true;
-    #[cfg(not(target_os = "windows"))]
-    let disable_cache = options.disable_cache;
-
-    let wasm_path = &options.path;
-
-    let mut wasm_binary: Vec<u8> = read_file_contents(wasm_path).map_err(|err| {
-        format!(
-            "Can't read the file {}: {}",
-            wasm_path.as_os_str().to_string_lossy(),
-            err
-        )
-    })?;
-
-    let em_symbol_map = if let Some(em_symbol_map_path) = options.em_symbol_map.clone() {
+/// Read an Emscripten symbol map, made of `0:func_name` lines
+fn read_em_symbol_map(
+    em_symbol_map_path: &Option<PathBuf>,
+) -> Result<Option<HashMap<u32, String>>, String> {
+    if let Some(em_symbol_map_path) = em_symbol_map_path {
         let em_symbol_map_content: String = read_to_string(&em_symbol_map_path)
             .map_err(|err| {
                 format!(
@@ -217,17 +406,14 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
 
             em_symbol_map.insert(num, name_str);
         }
-        Some(em_symbol_map)
+        Ok(Some(em_symbol_map))
     } else {
-        None
-    };
-
-    if !utils::is_wasm_binary(&wasm_binary) {
-        wasm_binary = wabt::wat2wasm(wasm_binary)
-            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+        Ok(None)
     }
+}
 
-    let compiler: Box<dyn Compiler> = match options.backend {
+fn get_compiler(backend: &Backend) -> Result<Box<dyn Compiler>, String> {
+    Ok(match backend {
         #[cfg(feature = "backend:singlepass")]
         Backend::Singlepass => Box::new(SinglePassCompiler::new()),
         #[cfg(not(feature = "backend:singlepass"))]
@@ -237,28 +423,67 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         Backend::LLVM => Box::new(LLVMCompiler::new()),
         #[cfg(not(feature = "backend:llvm"))]
         Backend::LLVM => return Err("the llvm backend is not enabled".to_string()),
+    })
+}
+
+/// Execute a wasm/wat file
+fn execute_wasm(options: &Run) -> Result<(), String> {
+    // force disable caching on windows
+    #[cfg(target_os = "windows")]
+    let disable_cache = true;
+    // code loaded from the cache isn't registered with debuggers or profilers
+    #[cfg(not(target_os = "windows"))]
+    let disable_cache = options.disable_cache || options.debug_info || options.profile.is_some();
+
+    let wasm_path = &options.path;
+
+    let mut wasm_binary: Vec<u8> = read_file_contents(wasm_path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            wasm_path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+
+    let em_symbol_map = read_em_symbol_map(&options.em_symbol_map)?;
+
+    if !utils::is_wasm_binary(&wasm_binary) && !utils::is_object_file(&wasm_binary) {
+        wasm_binary = wabt::wat2wasm(wasm_binary)
+            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+    }
+
+    let compiler = get_compiler(&options.backend)?;
+
+    let compiler_config = CompilerConfig {
+        symbol_map: em_symbol_map,
+        generate_debug_info: options.debug_info,
+        profiling: options.profile,
+        opt_level: options.opt_level.unwrap_or_default(),
+        enable_verifier: options.enable_verifier,
+        ..Default::default()
     };
 
-    let module = if !disable_cache {
+    let module = if utils::is_object_file(&wasm_binary) {
+        // Compiled ahead of time by `wasmer compile`
+        unsafe { wasmer_runtime::aot::load_object(&wasm_binary) }
+            .map_err(|e| format!("Can't load compiled module: {:?}", e))?
+    } else if !disable_cache {
         // If we have cache enabled
 
-        // We generate a hash for the given binary, so we can use it as key
-        // for the Filesystem cache
-        let hash = WasmHash::generate(&wasm_binary);
-
-        let wasmer_cache_dir = get_cache_dir();
+        // We generate a hash for the given binary and the way it's compiled,
+        // so we can use it as key for the Filesystem cache
+        let hash =
+            WasmHash::generate_with_config(&wasm_binary, compiler.backend(), &compiler_config);
 
         // We create a new cache instance.
         // It could be possible to use any other kinds of caching, as long as they
         // implement the Cache trait (with save and load functions)
-        let mut cache = unsafe {
-            FileSystemCache::new(wasmer_cache_dir).map_err(|e| format!("Cache error: {:?}", e))?
-        };
+        let cache = unsafe { open_cache()? };
 
         // cache.load will return the Module if it's able to deserialize it properly, and an error if:
         // * The file is not found
         // * The file exists, but it's corrupted or can't be converted to a module
-        let module = match cache.load(hash) {
+        let module = match cache.load_with(hash, &*compiler) {
             Ok(module) => {
                 // We are able to load the module from cache
                 module
@@ -266,9 +491,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             Err(_) => {
                 let module = webassembly::compile_with_config_with(
                     &wasm_binary[..],
-                    CompilerConfig {
-                        symbol_map: em_symbol_map,
-                    },
+                    compiler_config,
                     &*compiler,
                 )
                 .map_err(|e| format!("Can't compile module: {:?}", e))?;
@@ -280,14 +503,8 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         };
         module
     } else {
-        webassembly::compile_with_config_with(
-            &wasm_binary[..],
-            CompilerConfig {
-                symbol_map: em_symbol_map,
-            },
-            &*compiler,
-        )
-        .map_err(|e| format!("Can't compile module: {:?}", e))?
+        webassembly::compile_with_config_with(&wasm_binary[..], compiler_config, &*compiler)
+            .map_err(|e| format!("Can't compile module: {:?}", e))?
     };
 
     // TODO: refactor this
@@ -332,22 +549,204 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         .instantiate(&import_object)
         .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
 
-    webassembly::run_instance(
-        &module,
-        &mut instance,
-        abi,
-        if let Some(cn) = &options.command_name {
-            cn
+    if let Some(invoke) = &options.invoke {
+        let params = {
+            let func = instance
+                .dyn_func(invoke)
+                .map_err(|e| format!("Can't find the function {}: {:?}", invoke, e))?;
+            parse_params(func.signature(), &options.args)?
+        };
+        let results = if abi == InstanceABI::Emscripten {
+            wasmer_emscripten::invoke_emscripten_function_with_config(
+                &mut instance,
+                invoke,
+                &params,
+                emscripten_config(options)?,
+            )
         } else {
-            options.path.to_str().unwrap()
-        },
-        options.args.iter().map(|arg| arg.as_str()).collect(),
-    )
+            instance.call(invoke, &params)
+        }
+        .map_err(|e| format!("Calling {} failed: {:?}", invoke, e))?;
+        for result in results {
+            match result {
+                Value::I32(x) => println!("{}", x),
+                Value::I64(x) => println!("{}", x),
+                Value::F32(x) => println!("{}", x),
+                Value::F64(x) => println!("{}", x),
+            }
+        }
+        return Ok(());
+    }
+
+    let path = if let Some(cn) = &options.command_name {
+        cn
+    } else {
+        options.path.to_str().unwrap()
+    };
+    let args = options.args.iter().map(|arg| arg.as_str()).collect();
+    if abi == InstanceABI::Emscripten {
+        wasmer_emscripten::run_emscripten_instance_with_config(
+            &module,
+            &mut instance,
+            path,
+            args,
+            emscripten_config(options)?,
+        )
+    } else {
+        webassembly::run_instance(&module, &mut instance, abi, path, args)
+    }
     .map_err(|e| format!("{:?}", e))?;
 
     Ok(())
 }
 
+/// The emscripten runtime configuration given by the options of `wasmer run`
+fn emscripten_config(options: &Run) -> Result<wasmer_emscripten::EmscriptenConfig, String> {
+    let mut config = wasmer_emscripten::EmscriptenConfig::default();
+    if !options.mapdirs.is_empty() {
+        config.mounts = wasmer_emscripten::Mounts::new();
+        for (guest, host) in &options.mapdirs {
+            config
+                .mounts
+                .mount(guest, host)
+                .map_err(|e| format!("Can't map {} to {}: {}", guest, host.display(), e))?;
+        }
+    }
+    for program in &options.allowed_commands {
+        config.commands.allow(program);
+    }
+    if options.deterministic_clock {
+        config.clock = wasmer_emscripten::Clock::Deterministic;
+    }
+    // Side modules are compiled like the main module.
+    config.compiler = get_compiler(&options.backend)?.into();
+    Ok(config)
+}
+
+/// Parse the arguments of `wasmer run --invoke` as the parameters of a function
+fn parse_params(signature: &FuncSig, args: &[String]) -> Result<Vec<Value>, String> {
+    if signature.params().len() != args.len() {
+        return Err(format!(
+            "The function takes {} arguments ({:?}), but {} were given",
+            signature.params().len(),
+            signature.params(),
+            args.len()
+        ));
+    }
+
+    signature
+        .params()
+        .iter()
+        .zip(args)
+        .map(|(ty, arg)| {
+            let value = match ty {
+                // Unsigned values are accepted too, and reinterpreted.
+                Type::I32 => arg
+                    .parse::<i32>()
+                    .or_else(|_| arg.parse::<u32>().map(|x| x as i32))
+                    .map(Value::I32)
+                    .ok(),
+                Type::I64 => arg
+                    .parse::<i64>()
+                    .or_else(|_| arg.parse::<u64>().map(|x| x as i64))
+                    .map(Value::I64)
+                    .ok(),
+                Type::F32 => arg.parse::<f32>().map(Value::F32).ok(),
+                Type::F64 => arg.parse::<f64>().map(Value::F64).ok(),
+            };
+            value.ok_or_else(|| format!("Can't parse {} as {:?}", arg, ty))
+        })
+        .collect()
+}
+
+fn inspect_wasm(options: &Inspect) -> Result<(), String> {
+    let mut wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    if !utils::is_wasm_binary(&wasm_binary) {
+        wasm_binary = wabt::wat2wasm(wasm_binary)
+            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+    }
+
+    let inspection = wasmer::inspect::inspect(&wasm_binary, |imports| {
+        let functions = || imports.iter().filter(|import| import.kind == "function");
+        if functions()
+            .any(|import| wasmer_emscripten::is_emscripten_import(&import.module, &import.name))
+        {
+            "emscripten"
+        } else if cfg!(feature = "wasi")
+            && functions().any(|import| wasmer_wasi::is_wasi_import(&import.module))
+        {
+            "wasi"
+        } else {
+            "none"
+        }
+    })?;
+    if options.json {
+        let json = serde_json::to_string_pretty(&inspection)
+            .map_err(|e| format!("Can't write JSON: {}", e))?;
+        println!("{}", json);
+    } else {
+        print!("{}", inspection);
+    }
+    Ok(())
+}
+
+/// Run a .wast script, returning whether every directive passed
+fn run_wast(options: &Wast) -> Result<bool, String> {
+    let source = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    let filename = options.path.to_string_lossy();
+    let compiler = get_compiler(&options.backend)?;
+
+    let outcomes = wasmer::wast::run_script(&source, &filename, &*compiler)?;
+    for outcome in &outcomes {
+        match &outcome.failure {
+            None => println!("{}:{}: {} ok", filename, outcome.line, outcome.directive),
+            Some(failure) => println!(
+                "{}:{}: {} FAILED: {}",
+                filename, outcome.line, outcome.directive, failure
+            ),
+        }
+    }
+
+    let failed = outcomes.iter().filter(|outcome| !outcome.passed()).count();
+    println!(
+        "\n{} directives, {} passed, {} failed",
+        outcomes.len(),
+        outcomes.len() - failed,
+        failed
+    );
+    Ok(failed == 0)
+}
+
+fn wast(options: Wast) {
+    match run_wast(&options) {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            exit(1);
+        }
+    }
+}
+
+fn inspect(options: Inspect) {
+    if let Err(message) = inspect_wasm(&options) {
+        eprintln!("Error: {}", message);
+        exit(1);
+    }
+}
+
 fn run(options: Run) {
     match execute_wasm(&options) {
         Ok(()) => {}
@@ -383,6 +782,55 @@ fn validate_wasm(validate: Validate) -> Result<(), String> {
     Ok(())
 }
 
+fn compile_wasm(options: &Compile) -> Result<(), String> {
+    let mut wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+
+    if !utils::is_wasm_binary(&wasm_binary) {
+        wasm_binary = wabt::wat2wasm(wasm_binary)
+            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+    }
+
+    let compiler_config = CompilerConfig {
+        symbol_map: read_em_symbol_map(&options.em_symbol_map)?,
+        triple: options.target.clone(),
+        enable_cpu_features: options.enable_cpu_features.clone(),
+        disable_cpu_features: options.disable_cpu_features.clone(),
+        opt_level: options.opt_level.unwrap_or_default(),
+        enable_verifier: options.enable_verifier,
+        ..Default::default()
+    };
+    let object =
+        wasmer_runtime::aot::compile_object(&wasm_binary, compiler_config, &options.symbol)
+            .map_err(|e| format!("Can't compile module: {}", e))?;
+
+    let is_shared_object = options.output.extension().map_or(false, |ext| ext == "so");
+    if is_shared_object {
+        let object_path = options.output.with_extension("o");
+        fs::write(&object_path, &object).map_err(|e| format!("Can't write object: {}", e))?;
+        let linked = wasmer_runtime::aot::link_shared_object(&object_path, &options.output);
+        let _ = fs::remove_file(&object_path);
+        linked.map_err(|e| format!("Can't link shared object: {}", e))?;
+    } else {
+        fs::write(&options.output, &object).map_err(|e| format!("Can't write object: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Runs logic for the `compile` subcommand
+fn compile(options: Compile) {
+    if let Err(message) = compile_wasm(&options) {
+        eprintln!("Error: {}", message);
+        exit(1);
+    }
+}
+
 /// Runs logic for the `validate` subcommand
 fn validate(validate: Validate) {
     match validate_wasm(validate) {
@@ -417,10 +865,56 @@ fn main() {
             Cache::Dir => {
                 println!("{}", get_cache_dir().to_string_lossy());
             }
+            Cache::Ls => {
+                // Entries are only listed, never loaded.
+                let entries = unsafe { open_cache() }
+                    .and_then(|cache| cache.entries().map_err(|e| format!("Cache error: {}", e)));
+                match entries {
+                    Ok(entries) => {
+                        for entry in entries {
+                            let backend = entry
+                                .backend
+                                .map(|backend| format!("{:?}", backend))
+                                .unwrap_or_else(|| "unreadable".to_string());
+                            println!(
+                                "{}  {:>10}  {:<10}  {}",
+                                entry.key,
+                                entry.size,
+                                backend,
+                                format_age(entry.last_used)
+                            );
+                        }
+                    }
+                    Err(message) => {
+                        eprintln!("{:?}", message);
+                        exit(1);
+                    }
+                }
+            }
+            Cache::Prune { max_size } => {
+                let evicted = unsafe { open_cache() }.and_then(|cache| {
+                    cache
+                        .prune(max_size)
+                        .map_err(|e| format!("Cache error: {}", e))
+                });
+                match evicted {
+                    Ok(evicted) => {
+                        let freed: u64 = evicted.iter().map(|entry| entry.size).sum();
+                        println!("Evicted {} modules, freeing {} bytes", evicted.len(), freed);
+                    }
+                    Err(message) => {
+                        eprintln!("{:?}", message);
+                        exit(1);
+                    }
+                }
+            }
         },
         CLIOptions::Validate(validate_options) => {
             validate(validate_options);
         }
+        CLIOptions::Compile(compile_options) => compile(compile_options),
+        CLIOptions::Inspect(inspect_options) => inspect(inspect_options),
+        CLIOptions::Wast(wast_options) => wast(wast_options),
         #[cfg(target_os = "windows")]
         CLIOptions::Cache(_) => {
             println!("Caching is disabled for Windows.");
@@ -0,0 +1,602 @@
+//! Describe the contents of a wasm module, for `wasmer inspect`.
+//!
+//! The module is validated and read, but not compiled, so that any
+//! module can be described quickly, whatever the backend.
+
+use serde_derive::Serialize;
+use std::fmt;
+use wasmer_runtime_core::{
+    module::{ExternDescriptor, Names},
+    structures::TypedIndex,
+    types::{
+        ElementType, FuncIndex, FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type,
+    },
+    units::Pages,
+};
+use wasmparser::{BinaryReaderError, ExternalKind, ImportSectionEntryType, SectionCode};
+
+/// What a module imports, exports and defines.
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    /// The ABI the module was built for: `emscripten`, `wasi` or `none`.
+    pub abi: String,
+    pub imports: Vec<Import>,
+    pub exports: Vec<Export>,
+    /// The memories defined by the module, not counting imported ones.
+    pub memories: Vec<Memory>,
+    /// The tables defined by the module, not counting imported ones.
+    pub tables: Vec<Table>,
+    /// The globals defined by the module, not counting imported ones.
+    pub globals: Vec<Global>,
+    pub start_function: Option<String>,
+    /// Every section of the binary, in order.
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub kind: &'static str,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Export {
+    pub name: String,
+    pub kind: &'static str,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// Limits of a memory, in 64 KiB pages.
+#[derive(Debug, Serialize)]
+pub struct Memory {
+    pub minimum: u32,
+    pub maximum: Option<u32>,
+    pub shared: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Table {
+    pub element: String,
+    pub minimum: u32,
+    pub maximum: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Global {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub mutable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Section {
+    /// The name of a custom section, or the kind of a known one.
+    pub name: String,
+    pub custom: bool,
+    /// The size of the contents of the section, in bytes.
+    pub size: usize,
+}
+
+/// The parts of a module that are described, read from its binary.
+/// Imported functions, tables, memories and globals come first in their
+/// index spaces, as in the module.
+#[derive(Default)]
+struct Parts {
+    signatures: Vec<FuncSig>,
+    imports: Vec<(String, String, ExternDescriptor)>,
+    /// The signature index of every function.
+    functions: Vec<u32>,
+    tables: Vec<TableDescriptor>,
+    memories: Vec<MemoryDescriptor>,
+    globals: Vec<GlobalDescriptor>,
+    imported_tables: usize,
+    imported_memories: usize,
+    imported_globals: usize,
+    exports: Vec<(String, ExternalKind, u32)>,
+    start_func: Option<u32>,
+    names: Names,
+}
+
+/// Describe the module `wasm`. `abi` names the ABI the module was built
+/// for, `emscripten`, `wasi` or `none`, given its imports.
+pub fn inspect<F>(wasm: &[u8], abi: F) -> Result<Inspection, String>
+where
+    F: FnOnce(&[Import]) -> &'static str,
+{
+    wasmer_runtime_core::validate_and_report_errors(wasm)
+        .map_err(|e| format!("Invalid module: {}", e))?;
+    let parts = read_parts(wasm).map_err(|e| format!("Can't read the module: {:?}", e))?;
+
+    let imports: Vec<_> = parts
+        .imports
+        .iter()
+        .map(|(namespace, name, desc)| Import {
+            module: namespace.clone(),
+            name: name.clone(),
+            kind: desc.kind_name(),
+            ty: extern_type(desc),
+        })
+        .collect();
+
+    let mut exports: Vec<_> = parts
+        .exports
+        .iter()
+        .map(|(name, kind, index)| {
+            let desc = parts.extern_descriptor(*kind, *index as usize);
+            Export {
+                name: name.clone(),
+                kind: desc.kind_name(),
+                ty: extern_type(&desc),
+            }
+        })
+        .collect();
+    exports.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let memories = parts.memories[parts.imported_memories..]
+        .iter()
+        .map(|desc| Memory {
+            minimum: desc.minimum.0,
+            maximum: desc.maximum.map(|pages| pages.0),
+            shared: desc.shared,
+        })
+        .collect();
+
+    let tables = parts.tables[parts.imported_tables..]
+        .iter()
+        .map(|desc| Table {
+            element: "anyfunc".to_string(),
+            minimum: desc.minimum,
+            maximum: desc.maximum,
+        })
+        .collect();
+
+    let globals = parts.globals[parts.imported_globals..]
+        .iter()
+        .map(|desc| Global {
+            ty: desc.ty.to_string(),
+            mutable: desc.mutable,
+        })
+        .collect();
+
+    let start_function = parts.start_func.map(|index| {
+        match parts.names.functions.get(&FuncIndex::new(index as usize)) {
+            Some(name) => format!("{} ({})", index, name),
+            None => index.to_string(),
+        }
+    });
+
+    Ok(Inspection {
+        abi: abi(&imports).to_string(),
+        imports,
+        exports,
+        memories,
+        tables,
+        globals,
+        start_function,
+        sections: sections(wasm).map_err(|e| format!("Can't read the sections: {:?}", e))?,
+    })
+}
+
+impl Parts {
+    fn extern_descriptor(&self, kind: ExternalKind, index: usize) -> ExternDescriptor {
+        match kind {
+            ExternalKind::Function => {
+                let sig_index = self.functions[index] as usize;
+                ExternDescriptor::Function(self.signatures[sig_index].clone())
+            }
+            ExternalKind::Table => ExternDescriptor::Table(self.tables[index]),
+            ExternalKind::Memory => ExternDescriptor::Memory(self.memories[index]),
+            ExternalKind::Global => ExternDescriptor::Global(self.globals[index]),
+        }
+    }
+}
+
+/// Read the parts of a module that has already been validated.
+fn read_parts(wasm: &[u8]) -> Result<Parts, BinaryReaderError> {
+    let mut parts = Parts::default();
+    let mut reader = wasmparser::ModuleReader::new(wasm)?;
+    while !reader.eof() {
+        let section = reader.read()?;
+        match section.code {
+            SectionCode::Type => {
+                for ty in section.get_type_section_reader()? {
+                    let ty = ty?;
+                    let params: Vec<_> = ty.params.iter().map(|&ty| value_type(ty)).collect();
+                    let returns: Vec<_> = ty.returns.iter().map(|&ty| value_type(ty)).collect();
+                    parts.signatures.push(FuncSig::new(params, returns));
+                }
+            }
+            SectionCode::Import => {
+                for import in section.get_import_section_reader()? {
+                    let import = import?;
+                    let desc = match import.ty {
+                        ImportSectionEntryType::Function(sig_index) => {
+                            parts.functions.push(sig_index);
+                            let sig = parts.signatures[sig_index as usize].clone();
+                            ExternDescriptor::Function(sig)
+                        }
+                        ImportSectionEntryType::Table(ty) => {
+                            parts.tables.push(table_descriptor(ty));
+                            parts.imported_tables += 1;
+                            ExternDescriptor::Table(table_descriptor(ty))
+                        }
+                        ImportSectionEntryType::Memory(ty) => {
+                            parts.memories.push(memory_descriptor(ty));
+                            parts.imported_memories += 1;
+                            ExternDescriptor::Memory(memory_descriptor(ty))
+                        }
+                        ImportSectionEntryType::Global(ty) => {
+                            parts.globals.push(global_descriptor(ty));
+                            parts.imported_globals += 1;
+                            ExternDescriptor::Global(global_descriptor(ty))
+                        }
+                    };
+                    let namespace = import.module.to_string();
+                    parts
+                        .imports
+                        .push((namespace, import.field.to_string(), desc));
+                }
+            }
+            SectionCode::Function => {
+                for sig_index in section.get_function_section_reader()? {
+                    parts.functions.push(sig_index?);
+                }
+            }
+            SectionCode::Table => {
+                for ty in section.get_table_section_reader()? {
+                    parts.tables.push(table_descriptor(ty?));
+                }
+            }
+            SectionCode::Memory => {
+                for ty in section.get_memory_section_reader()? {
+                    parts.memories.push(memory_descriptor(ty?));
+                }
+            }
+            SectionCode::Global => {
+                for global in section.get_global_section_reader()? {
+                    parts.globals.push(global_descriptor(global?.ty));
+                }
+            }
+            SectionCode::Export => {
+                for export in section.get_export_section_reader()? {
+                    let export = export?;
+                    parts
+                        .exports
+                        .push((export.field.to_string(), export.kind, export.index));
+                }
+            }
+            SectionCode::Start => {
+                parts.start_func = Some(section.get_start_section_content()?);
+            }
+            // A malformed `name` section doesn't invalidate the module.
+            SectionCode::Custom { name: "name", .. } => {
+                let mut reader = section.get_binary_reader();
+                let len = reader.bytes_remaining();
+                parts.names = Names::parse(reader.read_bytes(len)?).unwrap_or_default();
+            }
+            _ => {}
+        }
+    }
+    Ok(parts)
+}
+
+/// The type of a value of a validated module, which can't be a SIMD one.
+fn value_type(ty: wasmparser::Type) -> Type {
+    match ty {
+        wasmparser::Type::I32 => Type::I32,
+        wasmparser::Type::I64 => Type::I64,
+        wasmparser::Type::F32 => Type::F32,
+        wasmparser::Type::F64 => Type::F64,
+        ty => unreachable!("unexpected value type {:?}", ty),
+    }
+}
+
+fn table_descriptor(ty: wasmparser::TableType) -> TableDescriptor {
+    TableDescriptor {
+        element: ElementType::Anyfunc,
+        minimum: ty.limits.initial,
+        maximum: ty.limits.maximum,
+    }
+}
+
+fn memory_descriptor(ty: wasmparser::MemoryType) -> MemoryDescriptor {
+    MemoryDescriptor {
+        minimum: Pages(ty.limits.initial),
+        maximum: ty.limits.maximum.map(Pages),
+        shared: ty.shared,
+    }
+}
+
+fn global_descriptor(ty: wasmparser::GlobalType) -> GlobalDescriptor {
+    GlobalDescriptor {
+        mutable: ty.mutable,
+        ty: value_type(ty.content_type),
+    }
+}
+
+fn sections(wasm: &[u8]) -> Result<Vec<Section>, BinaryReaderError> {
+    let mut sections = vec![];
+    let mut reader = wasmparser::ModuleReader::new(wasm)?;
+    while !reader.eof() {
+        let section = reader.read()?;
+        let range = section.range();
+        let (name, custom) = match section.code {
+            SectionCode::Custom { name, .. } => (name.to_string(), true),
+            code => (format!("{:?}", code).to_lowercase(), false),
+        };
+        sections.push(Section {
+            name,
+            custom,
+            size: range.end - range.start,
+        });
+    }
+    Ok(sections)
+}
+
+fn extern_type(desc: &ExternDescriptor) -> String {
+    match desc {
+        ExternDescriptor::Function(sig) => sig.to_string(),
+        ExternDescriptor::Memory(desc) => memory_type(desc),
+        ExternDescriptor::Table(desc) => table_type(desc),
+        ExternDescriptor::Global(desc) => global_type(desc),
+    }
+}
+
+fn limits(minimum: u32, maximum: Option<u32>) -> String {
+    match maximum {
+        Some(maximum) => format!("{}..{}", minimum, maximum),
+        None => format!("{}..", minimum),
+    }
+}
+
+fn table_type(desc: &TableDescriptor) -> String {
+    format!("anyfunc {}", limits(desc.minimum, desc.maximum))
+}
+
+fn memory_type(desc: &MemoryDescriptor) -> String {
+    format!(
+        "{} pages{}",
+        limits(desc.minimum.0, desc.maximum.map(|pages| pages.0)),
+        if desc.shared { ", shared" } else { "" }
+    )
+}
+
+fn global_type(desc: &GlobalDescriptor) -> String {
+    if desc.mutable {
+        format!("mut {}", desc.ty)
+    } else {
+        desc.ty.to_string()
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ABI: {}", self.abi)?;
+
+        writeln!(f, "Imports:")?;
+        for import in &self.imports {
+            writeln!(
+                f,
+                "  {}.{}: {} {}",
+                import.module, import.name, import.kind, import.ty
+            )?;
+        }
+
+        writeln!(f, "Exports:")?;
+        for export in &self.exports {
+            writeln!(f, "  {}: {} {}", export.name, export.kind, export.ty)?;
+        }
+
+        writeln!(f, "Memories:")?;
+        for memory in &self.memories {
+            writeln!(
+                f,
+                "  {} pages{}",
+                limits(memory.minimum, memory.maximum),
+                if memory.shared { ", shared" } else { "" }
+            )?;
+        }
+
+        writeln!(f, "Tables:")?;
+        for table in &self.tables {
+            writeln!(
+                f,
+                "  {} {}",
+                table.element,
+                limits(table.minimum, table.maximum)
+            )?;
+        }
+
+        writeln!(f, "Globals:")?;
+        for global in &self.globals {
+            let mutability = if global.mutable { "mut " } else { "" };
+            writeln!(f, "  {}{}", mutability, global.ty)?;
+        }
+
+        match &self.start_function {
+            Some(start_function) => writeln!(f, "Start function: {}", start_function)?,
+            None => writeln!(f, "Start function: none")?,
+        }
+
+        writeln!(f, "Sections:")?;
+        for section in &self.sections {
+            if section.custom {
+                writeln!(f, "  custom \"{}\": {} bytes", section.name, section.size)?;
+            } else {
+                writeln!(f, "  {}: {} bytes", section.name, section.size)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inspect;
+    use wabt::Wat2Wasm;
+
+    static TEXT: &str = r#"ABI: none
+Imports:
+  env.print: function [I32] -> []
+  env.memory: memory 1..16 pages
+Exports:
+  run: function [I32, I64] -> [I32]
+  scale: global F64
+  table: table anyfunc 2..
+Memories:
+Tables:
+  anyfunc 2..
+Globals:
+  mut I32
+  F64
+Start function: 1 (init)
+Sections:
+  type: 14 bytes
+  import: 28 bytes
+  function: 3 bytes
+  table: 4 bytes
+  global: 18 bytes
+  export: 23 bytes
+  start: 1 bytes
+  code: 17 bytes
+  custom "name": 36 bytes
+"#;
+
+    static JSON: &str = r#"{
+  "abi": "emscripten",
+  "imports": [
+    {
+      "module": "env",
+      "name": "print",
+      "kind": "function",
+      "type": "[I32] -> []"
+    },
+    {
+      "module": "env",
+      "name": "memory",
+      "kind": "memory",
+      "type": "1..16 pages"
+    }
+  ],
+  "exports": [
+    {
+      "name": "run",
+      "kind": "function",
+      "type": "[I32, I64] -> [I32]"
+    },
+    {
+      "name": "scale",
+      "kind": "global",
+      "type": "F64"
+    },
+    {
+      "name": "table",
+      "kind": "table",
+      "type": "anyfunc 2.."
+    }
+  ],
+  "memories": [],
+  "tables": [
+    {
+      "element": "anyfunc",
+      "minimum": 2,
+      "maximum": null
+    }
+  ],
+  "globals": [
+    {
+      "type": "I32",
+      "mutable": true
+    },
+    {
+      "type": "F64",
+      "mutable": false
+    }
+  ],
+  "start_function": "1 (init)",
+  "sections": [
+    {
+      "name": "type",
+      "custom": false,
+      "size": 14
+    },
+    {
+      "name": "import",
+      "custom": false,
+      "size": 28
+    },
+    {
+      "name": "function",
+      "custom": false,
+      "size": 3
+    },
+    {
+      "name": "table",
+      "custom": false,
+      "size": 4
+    },
+    {
+      "name": "global",
+      "custom": false,
+      "size": 18
+    },
+    {
+      "name": "export",
+      "custom": false,
+      "size": 23
+    },
+    {
+      "name": "start",
+      "custom": false,
+      "size": 1
+    },
+    {
+      "name": "code",
+      "custom": false,
+      "size": 17
+    },
+    {
+      "name": "name",
+      "custom": true,
+      "size": 36
+    }
+  ]
+}"#;
+
+    fn fixture() -> Vec<u8> {
+        let source = include_bytes!("tests/inspect.wast");
+        Wat2Wasm::new()
+            .write_debug_names(true)
+            .convert(&source[..])
+            .unwrap()
+            .as_ref()
+            .to_vec()
+    }
+
+    #[test]
+    fn modules_are_described() {
+        let inspection = inspect(&fixture(), |imports| {
+            assert_eq!(imports.len(), 2);
+            "none"
+        })
+        .unwrap();
+        assert_eq!(inspection.to_string(), TEXT);
+    }
+
+    #[test]
+    fn modules_are_described_in_json() {
+        let inspection = inspect(&fixture(), |_| "emscripten").unwrap();
+        assert_eq!(serde_json::to_string_pretty(&inspection).unwrap(), JSON);
+    }
+
+    #[test]
+    fn invalid_modules_are_rejected() {
+        let mut wasm = fixture();
+        wasm.truncate(wasm.len() - 1);
+        assert!(inspect(&wasm, |_| "none").is_err());
+    }
+}
@@ -2,7 +2,9 @@
 extern crate wasmer_runtime_core;
 // extern crate wasmer_emscripten;
 
+pub mod inspect;
 #[macro_use]
 pub mod update;
 pub mod utils;
+pub mod wast;
 pub mod webassembly;
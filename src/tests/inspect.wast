@@ -0,0 +1,17 @@
+(module
+  (import "env" "print" (func $print (param i32)))
+  (import "env" "memory" (memory 1 16))
+  (table 2 anyfunc)
+  (global $counter (mut i32) (i32.const 0))
+  (global $scale f64 (f64.const 1))
+  (func $init
+    i32.const 1
+    set_global $counter)
+  (func $run (param i32 i64) (result i32)
+    get_global $counter
+    call $print
+    get_local 0)
+  (export "run" (func $run))
+  (export "scale" (global $scale))
+  (export "table" (table 0))
+  (start $init))
@@ -0,0 +1,22 @@
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.add)
+  (func (export "unreachable")
+    unreachable)
+  (func $recurse (export "recurse")
+    call $recurse)
+  (global (export "answer") i32 (i32.const 42)))
+
+;; These pass.
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))
+(assert_return (get "answer") (i32.const 42))
+(assert_trap (invoke "unreachable") "unreachable")
+(assert_exhaustion (invoke "recurse") "call stack exhausted")
+(assert_malformed (module quote "(func") "unexpected end")
+
+;; These fail.
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 4))
+(assert_trap (invoke "add" (i32.const 1) (i32.const 2)) "unreachable")
+(assert_return (invoke "unreachable"))
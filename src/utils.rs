@@ -4,3 +4,8 @@
 pub fn is_wasm_binary(binary: &[u8]) -> bool {
     binary.starts_with(&[b'\0', b'a', b's', b'm'])
 }
+
+/// Detect if a provided binary is an ELF object, as written by `wasmer compile`
+pub fn is_object_file(binary: &[u8]) -> bool {
+    binary.starts_with(&[0x7f, b'E', b'L', b'F'])
+}
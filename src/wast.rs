@@ -0,0 +1,395 @@
+//! Run spec-style `.wast` scripts, for `wasmer wast`.
+//!
+//! Unlike the spectests crate, which turns scripts into Rust tests at
+//! build time, this interprets the directives as it reads them, so any
+//! script can be checked against any backend.
+//!
+//! Modules can import the usual `spectest` module: `print`, `print_i32`,
+//! `global_i32`, `table` and `memory`.
+
+use hashbrown::HashMap;
+use std::rc::Rc;
+use wabt::script::{self, Action, Command, CommandKind, ScriptParser};
+use wasmer_runtime_core::{
+    backend::Compiler,
+    error::{CallError, Error, RuntimeError},
+    export::Export,
+    import::ImportObject,
+    linker::InstanceNamespace,
+    types::Value,
+    Instance,
+};
+
+static SPECTEST_MODULE: &str = r#"
+(module
+  (func (export "print"))
+  (func (export "print_i32") (param i32))
+  (func (export "print_i64") (param i64))
+  (func (export "print_f32") (param f32))
+  (func (export "print_f64") (param f64))
+  (func (export "print_i32_f32") (param i32 f32))
+  (func (export "print_f64_f64") (param f64 f64))
+  (table (export "table") 10 20 anyfunc)
+  (memory (export "memory") 1 2)
+  (global (export "global_i32") i32 (i32.const 666))
+  (global (export "global_i64") i64 (i64.const 666))
+  (global (export "global_f32") f32 (f32.const 666))
+  (global (export "global_f64") f64 (f64.const 666)))
+"#;
+
+/// The outcome of a directive of a script.
+#[derive(Debug)]
+pub struct Outcome {
+    /// The line the directive starts at.
+    pub line: u64,
+    /// The name of the directive, such as `assert_return`.
+    pub directive: &'static str,
+    /// Why the directive failed, if it did.
+    pub failure: Option<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Why an action didn't return.
+#[derive(Debug)]
+enum ActionError {
+    /// The function trapped.
+    Trap(String),
+    /// The action refers to something that doesn't exist, or the
+    /// function failed some other way, like a host function panicking.
+    Other(String),
+}
+
+impl From<CallError> for ActionError {
+    fn from(error: CallError) -> Self {
+        match error {
+            CallError::Runtime(RuntimeError::Trap { msg }) => ActionError::Trap(msg.to_string()),
+            error => ActionError::Other(format!("{:?}", error)),
+        }
+    }
+}
+
+/// Runs the directives of a script in order, keeping
+/// the instances they create alive.
+struct Runner<'a> {
+    compiler: &'a dyn Compiler,
+    import_object: ImportObject,
+    /// Instances of modules that were given a name, like `(module $M ...)`.
+    named: HashMap<String, Rc<Instance>>,
+    /// The instance of the last module, which actions apply to by default.
+    current: Option<Rc<Instance>>,
+}
+
+/// Run every directive of the script in `source`, and report the
+/// outcome of each one. Fails if the script can't be parsed.
+pub fn run_script(
+    source: &[u8],
+    filename: &str,
+    compiler: &dyn Compiler,
+) -> Result<Vec<Outcome>, String> {
+    let mut parser: ScriptParser = ScriptParser::from_source_and_name(source, filename)
+        .map_err(|e| format!("Can't parse {}: {:?}", filename, e))?;
+
+    let mut runner = Runner::new(compiler)?;
+    let mut outcomes = vec![];
+    while let Some(Command { line, kind }) = parser
+        .next()
+        .map_err(|e| format!("Can't parse {}: {:?}", filename, e))?
+    {
+        let (directive, result) = runner.run(kind);
+        outcomes.push(Outcome {
+            line,
+            directive,
+            failure: result.err(),
+        });
+    }
+    Ok(outcomes)
+}
+
+impl<'a> Runner<'a> {
+    fn new(compiler: &'a dyn Compiler) -> Result<Self, String> {
+        let wasm = wabt::wat2wasm(SPECTEST_MODULE).map_err(|e| format!("{:?}", e))?;
+        let spectest = wasmer_runtime_core::compile_with(&wasm, compiler)
+            .map_err(Error::from)
+            .and_then(|module| module.instantiate(&ImportObject::new()))
+            .map_err(|e| format!("Can't instantiate the spectest module: {:?}", e))?;
+
+        let mut import_object = ImportObject::new();
+        import_object.register("spectest", InstanceNamespace::new(Rc::new(spectest)));
+
+        Ok(Self {
+            compiler,
+            import_object,
+            named: HashMap::new(),
+            current: None,
+        })
+    }
+
+    fn run(&mut self, kind: CommandKind) -> (&'static str, Result<(), String>) {
+        match kind {
+            CommandKind::Module { module, name } => ("module", self.define(module, name)),
+            CommandKind::Register { name, as_name } => ("register", self.register(name, as_name)),
+            CommandKind::PerformAction(action) => (
+                "invoke",
+                self.perform(&action).map(|_| ()).map_err(|e| match e {
+                    ActionError::Trap(msg) => format!("trapped: {}", msg),
+                    ActionError::Other(msg) => msg,
+                }),
+            ),
+            CommandKind::AssertReturn { action, expected } => {
+                ("assert_return", self.assert_return(&action, &expected))
+            }
+            CommandKind::AssertReturnCanonicalNan { action } => (
+                "assert_return_canonical_nan",
+                self.assert_nan(&action, is_canonical_nan),
+            ),
+            CommandKind::AssertReturnArithmeticNan { action } => (
+                "assert_return_arithmetic_nan",
+                self.assert_nan(&action, is_arithmetic_nan),
+            ),
+            CommandKind::AssertTrap { action, message } => {
+                ("assert_trap", self.assert_trap(&action, &message))
+            }
+            CommandKind::AssertExhaustion { action } => (
+                "assert_exhaustion",
+                self.assert_trap(&action, "call stack exhausted"),
+            ),
+            CommandKind::AssertInvalid { module, .. } => {
+                ("assert_invalid", self.assert_not_compiling(module))
+            }
+            CommandKind::AssertMalformed { module, .. } => {
+                ("assert_malformed", self.assert_not_compiling(module))
+            }
+            CommandKind::AssertUnlinkable { module, .. } => (
+                "assert_unlinkable",
+                self.assert_not_instantiating(module, |e| match e {
+                    Error::LinkError(_) => true,
+                    _ => false,
+                }),
+            ),
+            CommandKind::AssertUninstantiable { module, .. } => (
+                "assert_uninstantiable",
+                self.assert_not_instantiating(module, |e| match e {
+                    Error::RuntimeError(_) | Error::CallError(_) => true,
+                    _ => false,
+                }),
+            ),
+        }
+    }
+
+    fn instantiate(&self, module: script::ModuleBinary) -> Result<Instance, Error> {
+        let module = wasmer_runtime_core::compile_with(&module.into_vec(), self.compiler)?;
+        module.instantiate(&self.import_object)
+    }
+
+    fn define(&mut self, module: script::ModuleBinary, name: Option<String>) -> Result<(), String> {
+        // Actions must not silently apply to an older module
+        // if this one fails.
+        self.current = None;
+        let instance = Rc::new(
+            self.instantiate(module)
+                .map_err(|e| format!("Can't instantiate the module: {:?}", e))?,
+        );
+        if let Some(name) = name {
+            self.named.insert(name, Rc::clone(&instance));
+        }
+        self.current = Some(instance);
+        Ok(())
+    }
+
+    fn register(&mut self, name: Option<String>, as_name: String) -> Result<(), String> {
+        let instance = self.instance(&name)?;
+        self.import_object
+            .register(as_name, InstanceNamespace::new(instance));
+        Ok(())
+    }
+
+    fn instance(&self, name: &Option<String>) -> Result<Rc<Instance>, String> {
+        match name {
+            Some(name) => self
+                .named
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("There's no module named {}", name)),
+            None => self
+                .current
+                .clone()
+                .ok_or_else(|| "There's no module to act on".to_string()),
+        }
+    }
+
+    fn perform(&self, action: &Action) -> Result<Vec<Value>, ActionError> {
+        match action {
+            Action::Invoke {
+                module,
+                field,
+                args,
+            } => {
+                let instance = self.instance(module).map_err(ActionError::Other)?;
+                let args: Vec<Value> = args.iter().map(to_value).collect();
+                let func = instance
+                    .dyn_func(field)
+                    .map_err(|e| ActionError::Other(format!("Can't find {}: {:?}", field, e)))?;
+                func.call(&args).map_err(ActionError::from)
+            }
+            Action::Get { module, field } => {
+                let instance = self.instance(module).map_err(ActionError::Other)?;
+                match instance.exports().find(|(name, _)| name == field) {
+                    Some((_, Export::Global(global))) => Ok(vec![global.get()]),
+                    _ => Err(ActionError::Other(format!("There's no global {}", field))),
+                }
+            }
+        }
+    }
+
+    fn assert_return(&self, action: &Action, expected: &[script::Value]) -> Result<(), String> {
+        let expected: Vec<Value> = expected.iter().map(to_value).collect();
+        match self.perform(action) {
+            Ok(ref results)
+                if results.len() == expected.len()
+                    && results.iter().zip(&expected).all(|(a, b)| same_value(a, b)) =>
+            {
+                Ok(())
+            }
+            Ok(results) => Err(format!("expected {:?}, got {:?}", expected, results)),
+            Err(ActionError::Trap(msg)) => {
+                Err(format!("expected {:?}, trapped: {}", expected, msg))
+            }
+            Err(ActionError::Other(msg)) => Err(msg),
+        }
+    }
+
+    fn assert_nan(&self, action: &Action, is_nan: fn(&Value) -> bool) -> Result<(), String> {
+        match self.perform(action) {
+            Ok(ref results) if results.len() == 1 && is_nan(&results[0]) => Ok(()),
+            Ok(results) => Err(format!("expected a NaN, got {:?}", results)),
+            Err(ActionError::Trap(msg)) => Err(format!("expected a NaN, trapped: {}", msg)),
+            Err(ActionError::Other(msg)) => Err(msg),
+        }
+    }
+
+    fn assert_trap(&self, action: &Action, message: &str) -> Result<(), String> {
+        match self.perform(action) {
+            Err(ActionError::Trap(_)) => Ok(()),
+            Ok(results) => Err(format!("expected a trap ({}), got {:?}", message, results)),
+            Err(ActionError::Other(msg)) => Err(msg),
+        }
+    }
+
+    fn assert_not_compiling(&self, module: script::ModuleBinary) -> Result<(), String> {
+        match wasmer_runtime_core::compile_with(&module.into_vec(), self.compiler) {
+            Ok(_) => Err("expected the module not to compile".to_string()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn assert_not_instantiating(
+        &self,
+        module: script::ModuleBinary,
+        expected: fn(&Error) -> bool,
+    ) -> Result<(), String> {
+        match self.instantiate(module) {
+            Ok(_) => Err("expected the module not to instantiate".to_string()),
+            Err(ref e) if expected(e) => Ok(()),
+            Err(e) => Err(format!("failed for another reason: {:?}", e)),
+        }
+    }
+}
+
+fn to_value(value: &script::Value) -> Value {
+    match *value {
+        script::Value::I32(x) => Value::I32(x),
+        script::Value::I64(x) => Value::I64(x),
+        script::Value::F32(x) => Value::F32(x),
+        script::Value::F64(x) => Value::F64(x),
+    }
+}
+
+/// Compare values bit for bit, so that NaN payloads are checked too.
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::I32(a), Value::I32(b)) => a == b,
+        (Value::I64(a), Value::I64(b)) => a == b,
+        (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+        (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+        _ => false,
+    }
+}
+
+/// A NaN whose payload only has its most significant bit set.
+fn is_canonical_nan(value: &Value) -> bool {
+    match value {
+        Value::F32(x) => x.to_bits() & 0x7fff_ffff == 0x7fc0_0000,
+        Value::F64(x) => x.to_bits() & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000,
+        _ => false,
+    }
+}
+
+/// A NaN with the most significant bit of its payload set.
+fn is_arithmetic_nan(value: &Value) -> bool {
+    match value {
+        Value::F32(x) => x.is_nan() && x.to_bits() & 0x0040_0000 != 0,
+        Value::F64(x) => x.is_nan() && x.to_bits() & 0x0008_0000_0000_0000 != 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_script, ActionError};
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::error::{CallError, ResolveError, RuntimeError};
+
+    #[test]
+    fn directives_pass_and_fail() {
+        let source = include_bytes!("tests/wast_script.wast");
+        let outcomes = run_script(source, "wast_script.wast", &CraneliftCompiler::new()).unwrap();
+        let summary: Vec<_> = outcomes
+            .iter()
+            .map(|outcome| (outcome.directive, outcome.passed()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("module", true),
+                ("assert_return", true),
+                ("assert_return", true),
+                ("assert_trap", true),
+                ("assert_exhaustion", true),
+                ("assert_malformed", true),
+                ("assert_return", false),
+                ("assert_trap", false),
+                ("assert_return", false),
+            ]
+        );
+        assert_eq!(outcomes[6].line, 20);
+    }
+
+    #[test]
+    fn only_traps_are_traps() {
+        let trap = CallError::Runtime(RuntimeError::Trap {
+            msg: "unreachable".into(),
+        });
+        match ActionError::from(trap) {
+            ActionError::Trap(msg) => assert_eq!(msg, "unreachable"),
+            e => panic!("expected a trap, got {:?}", e),
+        }
+
+        let panic = CallError::Runtime(RuntimeError::Panic {
+            data: Box::new("host function failed"),
+        });
+        let missing = CallError::Resolve(ResolveError::ExportNotFound {
+            name: "f".to_string(),
+        });
+        for error in vec![panic, missing] {
+            match ActionError::from(error) {
+                ActionError::Other(_) => {}
+                e => panic!("expected another error, got {:?}", e),
+            }
+        }
+    }
+}